@@ -0,0 +1,70 @@
+#import bevy_pbr::mesh_view_bindings::view
+#import bevy_pbr::forward_io::VertexOutput
+
+// Mirrors `CirrusUniform` in `src/cirrus.rs`.
+struct CirrusUniform {
+    tint: vec4<f32>,
+    ambient: vec4<f32>,
+    sun_direction: vec4<f32>,
+    sun_color: vec4<f32>,
+    lighting: vec4<f32>,
+    shape: vec4<f32>,
+    stretch: vec4<f32>,
+    scroll: vec4<f32>,
+}
+
+@group(2) @binding(0) var<uniform> cirrus: CirrusUniform;
+@group(2) @binding(1) var noise_texture: texture_2d<f32>;
+@group(2) @binding(2) var noise_sampler: sampler;
+
+const PI: f32 = 3.14159265358979;
+
+fn henyey_greenstein(cos_theta: f32, g_in: f32) -> f32 {
+    let g = clamp(g_in, -0.99, 0.99);
+    let g2 = g * g;
+    return (1.0 - g2) / (4.0 * PI * pow(1.0 + g2 - 2.0 * g * cos_theta, 1.5));
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Noise in world XZ, squashed across the streak direction.
+    let along_dir = cirrus.stretch.zw;
+    let p = in.world_position.xz - cirrus.scroll.xy;
+    let uv = vec2<f32>(dot(p, along_dir) / cirrus.stretch.y, dot(p, vec2<f32>(-along_dir.y, along_dir.x))) / cirrus.stretch.x;
+    let base = textureSample(noise_texture, noise_sampler, uv).r;
+    // A finer, offset octave breaks up the tile.
+    let detail = textureSample(noise_texture, noise_sampler, uv * 3.7 + vec2<f32>(0.37, 0.61)).r;
+    let noise = mix(base, detail, 0.3);
+
+    let threshold = 1.0 - cirrus.shape.x;
+    var alpha = smoothstep(threshold, threshold + cirrus.shape.y, noise) * cirrus.shape.z;
+    // Fade towards the rim so the sheet's outline never shows.
+    alpha *= 1.0 - smoothstep(0.6, 1.0, length(in.uv * 2.0 - 1.0));
+    // And where the sheet is seen edge-on.
+    let view_dir = normalize(in.world_position.xyz - view.world_position);
+    alpha *= smoothstep(0.0, cirrus.shape.w, abs(dot(view_dir, normalize(in.world_normal))));
+    if (alpha <= 0.001) {
+        discard;
+    }
+
+    var color = cirrus.ambient.rgb;
+    let sun_intensity = cirrus.lighting.x;
+    if (sun_intensity > 0.0 && cirrus.sun_direction.w > 0.0) {
+        let cos_theta = dot(view_dir, normalize(cirrus.sun_direction.xyz));
+        // As `CloudMaterial`: the artistic sun uses only the hue and scales
+        // the phase relative to isotropic, the physical one goes through the
+        // camera exposure.
+        var light_scale = vec3<f32>(view.exposure);
+        var phase_scale = 1.0;
+        if (cirrus.lighting.y <= 0.0) {
+            let sun = cirrus.sun_color.rgb;
+            light_scale = vec3<f32>(1.0 / max(max(sun.r, max(sun.g, sun.b)), 1e-4));
+            phase_scale = 4.0 * PI;
+        }
+        // Ice crystals scatter strongly forward, lighting the wisps up
+        // around the sun.
+        let phase = henyey_greenstein(cos_theta, cirrus.lighting.z) + cirrus.lighting.w * henyey_greenstein(cos_theta, 0.9);
+        color += cirrus.sun_color.rgb * light_scale * sun_intensity * phase * phase_scale;
+    }
+    return vec4<f32>(color * cirrus.tint.rgb, alpha);
+}
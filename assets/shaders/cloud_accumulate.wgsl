@@ -0,0 +1,31 @@
+// Running average of a still view's frames, for `AccumulationPlugin`. Reads
+// the frame just drawn and the average so far, and writes the new average to
+// both the view and the other history texture.
+
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+// Mirrors `AccumulationUniform` in `src/accumulation.rs`.
+struct Accumulation {
+    weight: vec4<f32>, // x: weight of this frame against the history
+}
+
+@group(0) @binding(0) var frame_texture: texture_2d<f32>;
+@group(0) @binding(1) var history_texture: texture_2d<f32>;
+@group(0) @binding(2) var<uniform> accumulation: Accumulation;
+
+struct Output {
+    @location(0) view: vec4<f32>,
+    @location(1) history: vec4<f32>,
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> Output {
+    let pixel = vec2<i32>(in.position.xy);
+    let frame = textureLoad(frame_texture, pixel, 0);
+    let history = textureLoad(history_texture, pixel, 0);
+    // A weight of 1 starts over from this frame, whatever the history holds;
+    // 0 holds the average.
+    let weight = accumulation.weight.x;
+    let average = select(mix(history, frame, weight), frame, weight >= 1.0);
+    return Output(average, average);
+}
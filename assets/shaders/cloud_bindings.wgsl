@@ -0,0 +1,90 @@
+#define_import_path bevy_clouds::bindings
+
+struct CloudMaterial {
+    color: vec4<f32>,
+    extinction: vec4<f32>, // rgb: per-channel extinction, scaled by settings.z
+    settings: vec4<f32>, // x: density, y: threshold, z: absorption, w: steps
+    box_min: vec4<f32>, // xyz: world bounds of the unrotated box; volume space is relative to its center
+    box_max: vec4<f32>,
+    rotation: vec4<f32>, // world-from-volume rotation quaternion
+    wind_offset: vec4<f32>, // xyz: wind scroll in volume space at the lower wind altitude, w: that world height
+    wind_offset_upper: vec4<f32>, // xyz: wind scroll at the upper wind altitude, w: that world height
+    shape: vec4<f32>, // x: coverage, yzw: noise stretch
+    lighting: vec4<f32>, // x: phase g, y: sun intensity, z: light steps, w: 1 for physical sun
+    silver: vec4<f32>, // x: silver lining intensity, y: spread
+    ambient_top: vec4<f32>,
+    ambient_bottom: vec4<f32>,
+    profile: vec4<f32>, // x: cloud type, y: threshold softness, z: softness erosion
+    anvil: vec4<f32>, // x: amount, y: start as a fraction of the box height
+    sampling: vec4<f32>, // x: per-step jitter as a fraction of the step, y: 1 to sample the light volume, z: 1 for the step heatmap, w: alpha cutoff
+    occlusion: vec4<f32>, // x: ambient occlusion strength
+    weather_map: vec4<f32>, // xy: world XZ of the map's min corner, zw: reciprocal size; zw 0 disables
+    sun_direction: vec4<f32>, // xyz: world direction towards the sun, w: 1 when there is one
+    sun_color: vec4<f32>, // rgb: linear color times illuminance
+    fill_direction: vec4<f32>, // xyz: world direction towards the fill light, w: 1 when enabled
+    fill_color: vec4<f32>,
+    ground_fog: vec4<f32>, // x: volume-space base height, y: scale height (0 off), z: noise blend, w: noise tile size
+    fog_color: vec4<f32>, // rgb: distance color, a: fog distance
+    bounds: vec4<f32>, // x: march start inset, y: density outset past the box, z: contact fade distance, all in world units
+    noise_offset: vec4<f32>, // xyz: offset of the sampling domain in noise tiles
+    noise_domain: vec4<f32>, // xy: cos and sin of the domain's turn about Y, z: -1 to mirror X, else 1
+    fbm_scale: vec4<f32>, // per-tap frequency relative to the bake
+    fbm_weight: vec4<f32>, // per-tap weight, summing to 1
+    mask: vec4<f32>, // x: 1 when the portal mask applies, yzw: world position of the view it is drawn for
+    merge_count: vec4<f32>, // x: boxes in use below
+    merge_min: array<vec4<f32>, 4>, // overlapping volumes that march these regions instead
+    merge_max: array<vec4<f32>, 4>,
+    trail_info: vec4<f32>, // x: path points in use, y: world radius
+    trail: array<vec4<f32>, 16>, // xyz: world position along a `CloudTrail`, newest first, w: coverage there
+    opacity: f32, // crossfade with the LOD impostor
+};
+
+// `CloudExtension` starts its bindings after those of `StandardMaterial`.
+#ifdef CLOUD_EXTENSION
+@group(2) @binding(100)
+var<uniform> material: CloudMaterial;
+@group(2) @binding(101)
+var noise_texture: texture_3d<f32>;
+@group(2) @binding(102)
+var noise_sampler: sampler;
+@group(2) @binding(103)
+var profile_lut: texture_2d<f32>;
+@group(2) @binding(104)
+var profile_sampler: sampler;
+@group(2) @binding(105)
+var mask_texture: texture_2d<f32>;
+@group(2) @binding(106)
+var mask_sampler: sampler;
+@group(2) @binding(107)
+var occupancy: texture_3d<f32>;
+@group(2) @binding(108)
+var weather_map: texture_2d<f32>;
+@group(2) @binding(109)
+var weather_map_sampler: sampler;
+#else
+@group(2) @binding(0)
+var<uniform> material: CloudMaterial;
+@group(2) @binding(1)
+var noise_texture: texture_3d<f32>;
+@group(2) @binding(2)
+var noise_sampler: sampler;
+@group(2) @binding(3)
+var profile_lut: texture_2d<f32>;
+@group(2) @binding(4)
+var profile_sampler: sampler;
+@group(2) @binding(5)
+var mask_texture: texture_2d<f32>;
+@group(2) @binding(6)
+var mask_sampler: sampler;
+// Only `CloudMaterial` has a baked light volume.
+@group(2) @binding(7)
+var light_volume: texture_3d<f32>;
+@group(2) @binding(8)
+var light_volume_sampler: sampler;
+@group(2) @binding(9)
+var occupancy: texture_3d<f32>;
+@group(2) @binding(10)
+var weather_map: texture_2d<f32>;
+@group(2) @binding(11)
+var weather_map_sampler: sampler;
+#endif
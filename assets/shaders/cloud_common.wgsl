@@ -0,0 +1,96 @@
+#define_import_path bevy_clouds::common
+
+#import bevy_pbr::mesh_view_bindings as view_bindings
+#import bevy_clouds::bindings::material
+
+const PI: f32 = 3.14159265;
+
+// Rotates `v` by the unit quaternion `q`.
+fn rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    let t = 2.0 * cross(q.xyz, v);
+    return v + q.w * t + cross(q.xyz, t);
+}
+
+// The march runs in volume space: world space relative to the box center,
+// rotated so that the box is axis-aligned and its local up is +Y. The box
+// spans -box_half_extents()..box_half_extents(), so positions along the ray
+// stay as small as the box even kilometres from the world origin, where
+// world positions would lose the step to f32 rounding.
+fn box_center() -> vec3<f32> {
+    return (material.box_min.xyz + material.box_max.xyz) * 0.5;
+}
+
+fn box_half_extents() -> vec3<f32> {
+    return (material.box_max.xyz - material.box_min.xyz) * 0.5;
+}
+
+fn to_volume_dir(dir: vec3<f32>) -> vec3<f32> {
+    return rotate(vec4<f32>(-material.rotation.xyz, material.rotation.w), dir);
+}
+
+fn to_volume(p: vec3<f32>) -> vec3<f32> {
+    return to_volume_dir(p - box_center());
+}
+
+fn to_world(p: vec3<f32>) -> vec3<f32> {
+    return box_center() + rotate(material.rotation, p);
+}
+
+// How far a world height sits from the lower wind altitude towards the upper
+// one, clamped so the wind outside them stays at the nearer.
+fn wind_weight(height: f32) -> f32 {
+    let span = material.wind_offset_upper.w - material.wind_offset.w;
+    return clamp((height - material.wind_offset.w) / span, 0.0, 1.0);
+}
+
+// Wind scroll at volume-space position `p`. The weight does not change over
+// time, so lerping the scrolls of the two altitudes gives the scroll of the
+// wind interpolated between them, and tall volumes shear.
+fn wind_scroll(p: vec3<f32>) -> vec3<f32> {
+    return mix(material.wind_offset.xyz, material.wind_offset_upper.xyz, wind_weight(to_world(p).y));
+}
+
+// Extinction coefficient per channel.
+fn extinction() -> vec3<f32> {
+    return max(material.extinction.rgb * material.settings.z, vec3<f32>(0.0));
+}
+
+// The portal mask is drawn for the main camera only, so other views (a
+// second window, an offscreen capture) stay unmasked. The view position is
+// the one per-view value that tells them apart when their viewports match.
+fn portal_mask_applies() -> bool {
+    let offset = view_bindings::view.world_position - material.mask.yzw;
+    let scale = max(dot(material.mask.yzw, material.mask.yzw), 1.0);
+    return material.mask.x > 0.0 && dot(offset, offset) <= 1e-8 * scale;
+}
+
+// Inside a region an overlapping volume marches instead, so overlaps are
+// only integrated once. `p` is in world space.
+fn merged_away(p: vec3<f32>) -> bool {
+    let count = min(u32(material.merge_count.x), 4u);
+    for (var i = 0u; i < count; i = i + 1u) {
+        if (all(p >= material.merge_min[i].xyz) && all(p <= material.merge_max[i].xyz)) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// Offset along the ray, in steps, of sample `index` under `pixel`. The hash
+// depends only on those, so the noise holds still with the camera.
+// Without CLOUD_STEP_JITTER every sample sits at the start of its step.
+fn step_jitter(pixel: vec2<f32>, index: i32) -> f32 {
+#ifdef CLOUD_STEP_JITTER
+    if (material.sampling.x <= 0.0) {
+        return 0.0;
+    }
+    var h = (u32(pixel.x) * 73856093u) ^ (u32(pixel.y) * 19349663u) ^ (u32(index) * 83492791u);
+    // PCG output permutation.
+    h = h * 747796405u + 2891336453u;
+    h = ((h >> ((h >> 28u) + 4u)) ^ h) * 277803737u;
+    h = (h >> 22u) ^ h;
+    return (f32(h) / 4294967295.0 - 0.5) * material.sampling.x;
+#else
+    return 0.0;
+#endif
+}
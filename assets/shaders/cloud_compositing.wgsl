@@ -0,0 +1,17 @@
+#define_import_path bevy_clouds::compositing
+
+#import bevy_clouds::bindings::material
+
+// The fragment color for the material's blend mode, from the scattered light
+// and the coverage of the march. The march already weights the scattered
+// light by coverage, which is the premultiplied convention; straight
+// blending applies alpha again.
+fn composite(color: vec3<f32>, coverage: f32) -> vec4<f32> {
+#ifdef CLOUD_ADDITIVE
+    return vec4<f32>(color * material.opacity, 0.0);
+#else ifdef CLOUD_PREMULTIPLIED
+    return vec4<f32>(color * material.opacity, coverage * material.opacity);
+#else
+    return vec4<f32>(color, coverage * material.opacity);
+#endif
+}
@@ -0,0 +1,16 @@
+#define_import_path bevy_clouds::debug
+
+// Debug views, compiled in with CLOUD_DEBUG_VIEWS while one is enabled.
+
+#import bevy_clouds::bindings::material
+
+// Whether the fragment shows a debug view instead of the cloud.
+fn debug_view_active() -> bool {
+    return material.sampling.z > 0.0;
+}
+
+// Step heatmap: blue for no density fetches through red for one per step.
+fn step_heatmap(samples: i32, steps: i32) -> vec4<f32> {
+    let load = clamp(f32(samples) / f32(steps), 0.0, 1.0);
+    return vec4<f32>(mix(vec3<f32>(0.0, 0.1, 1.0), vec3<f32>(1.0, 0.1, 0.0), load), material.opacity);
+}
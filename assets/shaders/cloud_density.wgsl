@@ -0,0 +1,261 @@
+#define_import_path bevy_clouds::density
+
+#import bevy_clouds::bindings::{material, noise_texture, noise_sampler, profile_lut, profile_sampler, occupancy, weather_map, weather_map_sampler}
+#import bevy_clouds::common::{extinction, to_world, box_center, box_half_extents, wind_scroll}
+#import bevy_clouds::intersection::{ray_box_intersection, clamp_to_box}
+#ifdef CLOUD_VIEW_BINDING
+#import bevy_clouds::view::view_detail
+#endif
+
+// Frequency of the erosion resample relative to the base noise.
+const EROSION_SCALE: f32 = 4.0;
+// Most an anvil widens the top, as a factor on the distance from the axis.
+const ANVIL_SPREAD: f32 = 1.5;
+
+// Mirrors and turns a volume-space vector into the volume's own sampling
+// domain, so volumes sharing one bake look different.
+fn to_domain(v: vec3<f32>) -> vec3<f32> {
+    let d = material.noise_domain;
+    let x = v.x * d.z;
+    return vec3<f32>(d.x * x - d.y * v.z, v.y, d.y * x + d.x * v.z);
+}
+
+// Map volume-space position to texture UV [0, 1]; the sampler repeats, so the wind
+// scroll wraps around the tileable bake. Stretching an axis scales its
+// features up without squashing the volume. The domain turns about the box
+// center after the scroll, so the wind still blows along its own direction,
+// and the volume's offset adds to the scroll. The scroll follows the height
+// of `p`; see `wind_scroll`.
+fn noise_uv(p: vec3<f32>) -> vec3<f32> {
+    let local = to_domain(p - wind_scroll(p));
+    let size = material.box_max.xyz - material.box_min.xyz;
+    return (local / size + 0.5) / material.shape.yzw + material.noise_offset.xyz;
+}
+
+// Whether the FBM mix is the bake alone, sampled as baked.
+fn single_tap() -> bool {
+    return material.fbm_weight.x >= 1.0 && material.fbm_scale.x == 1.0;
+}
+
+// The baked noise recombined by the FBM mix: up to four taps at their own
+// frequencies, summed by normalized weights. Taps without weight are skipped.
+fn mixed_noise(uv: vec3<f32>) -> f32 {
+    if (single_tap()) {
+        return textureSampleLevel(noise_texture, noise_sampler, uv, 0.0).r;
+    }
+    var value = 0.0;
+    for (var i = 0; i < 4; i = i + 1) {
+        if (material.fbm_weight[i] > 0.0) {
+            value += textureSampleLevel(noise_texture, noise_sampler, uv * material.fbm_scale[i], 0.0).r * material.fbm_weight[i];
+        }
+    }
+    return value;
+}
+
+// Height of volume-space `p` within the box, 0 at the bottom and 1 at the top.
+fn box_height(p: vec3<f32>) -> f32 {
+    return clamp(p.y / (box_half_extents().y * 2.0) + 0.5, 0.0, 1.0);
+}
+
+// How far the anvil has taken over at box height `height`: 0 below its
+// start, reaching the full amount halfway from there to the top.
+fn anvil_weight(height: f32) -> f32 {
+    let start = material.anvil.y;
+    return material.anvil.x * smoothstep(start, mix(start, 1.0, 0.5), height);
+}
+
+// Where `p` samples the noise under an anvil of `weight`: pulled towards the
+// vertical axis, so the shapes of the body spread outwards over it. How far
+// varies with the direction from the axis, read from the noise along a ring,
+// which breaks up what would otherwise be perfectly radial streaks.
+fn anvil_position(p: vec3<f32>, weight: f32) -> vec3<f32> {
+    let radial = p.xz;
+    let distance = length(radial);
+    if (weight <= 0.0 || distance < 1e-4) {
+        return p;
+    }
+    let ring = vec3<f32>(radial / distance * 0.25 + 0.5, 0.5);
+    let variation = textureSampleLevel(noise_texture, noise_sampler, ring, 0.0).r;
+    let spread = 1.0 + weight * ANVIL_SPREAD * mix(0.5, 1.5, variation);
+    let pulled = radial / spread;
+    return vec3<f32>(pulled.x, p.y, pulled.y);
+}
+
+// Coverage painted into the weather map over the world XZ position of `p`;
+// 1 without a map, which leaves CLOUD_WEATHER_MAP unset. The sampler clamps,
+// so the edge texels extend outwards.
+fn weather_coverage(p: vec3<f32>) -> f32 {
+#ifdef CLOUD_WEATHER_MAP
+    if (material.weather_map.z <= 0.0) {
+        return 1.0;
+    }
+    let uv = (to_world(p).xz - material.weather_map.xy) * material.weather_map.zw;
+    return textureSampleLevel(weather_map, weather_map_sampler, uv, 0.0).r;
+#else
+    return 1.0;
+#endif
+}
+
+// Coverage of a `CloudTrail` at `p`: the strongest of the capsules between
+// consecutive path points, each fading out over the radius and blending the
+// coverage of its ends along its length. 1 without a trail, which leaves
+// CLOUD_TRAIL unset.
+fn trail_coverage(p: vec3<f32>) -> f32 {
+#ifdef CLOUD_TRAIL
+    let count = min(i32(material.trail_info.x), 16);
+    if (count <= 0) {
+        return 1.0;
+    }
+    let world = to_world(p);
+    var coverage = 0.0;
+    for (var i = 0; i < count; i = i + 1) {
+        let a = material.trail[i];
+        let b = material.trail[min(i + 1, count - 1)];
+        let ab = b.xyz - a.xyz;
+        let t = clamp(dot(world - a.xyz, ab) / max(dot(ab, ab), 1e-8), 0.0, 1.0);
+        let distance = length(world - a.xyz - ab * t);
+        let fade = 1.0 - smoothstep(0.0, material.trail_info.y, distance);
+        coverage = max(coverage, mix(a.w, b.w, t) * fade);
+    }
+    return coverage;
+#else
+    return 1.0;
+#endif
+}
+
+#ifdef CLOUD_GROUND_FOG
+// Ground fog: an exponential falloff above the base height, varied by the
+// noise at a fixed world scale so the layer looks the same however large the
+// box is. Noise at 0.5 leaves the falloff unchanged; it is sampled about
+// the world origin, so the pattern stays put when the box moves.
+fn fog_density(p: vec3<f32>) -> f32 {
+    let falloff = exp(-max(p.y - material.ground_fog.x, 0.0) / material.ground_fog.y);
+    let uv = to_domain(p + box_center() - wind_scroll(p)) / (material.ground_fog.w * material.shape.yzw) + material.noise_offset.xyz;
+    let noise_val = mixed_noise(uv);
+    let variation = mix(1.0, 2.0 * noise_val, material.ground_fog.z);
+    return falloff * variation * material.shape.x * weather_coverage(p) * material.settings.x;
+}
+#endif
+
+// Density at volume-space position `p` (see `to_volume`). A replacement can
+// be supplied as a shader with `#define_import_path
+// bevy_clouds::density_override` that defines a function with the same
+// signature taking world-space positions; see
+// `assets/shaders/density_spheres.wgsl`.
+fn sample_density(p_in: vec3<f32>) -> f32 {
+#ifdef CLOUD_GROUND_FOG
+    return fog_density(p_in);
+#else
+    // Samples in the outset repeat the faces, ramp included, so the
+    // softened edge carries on past the box instead of ending at it.
+    let p = clamp_to_box(p_in);
+
+    let height = box_height(p);
+    let anvil = anvil_weight(height);
+    let uv = noise_uv(anvil_position(p, anvil));
+
+    // Sample the pre-baked 3D texture
+    let noise_val = mixed_noise(uv);
+
+    // Height profile of the volume's cloud type; texel centers span 0..1.
+    let lut_size = vec2<f32>(textureDimensions(profile_lut));
+    let lut_uv = (vec2<f32>(height, clamp(material.profile.x, 0.0, 1.0)) * (lut_size - 1.0) + 0.5) / lut_size;
+    // The anvil holds the profile up into a flat top, only fading it out
+    // just below the box top; it never rises above 1.
+    let profile = mix(
+        textureSampleLevel(profile_lut, profile_sampler, lut_uv, 0.0).r,
+        1.0 - smoothstep(0.9, 1.0, height),
+        anvil,
+    );
+
+    // Lower coverage raises the effective threshold towards 1.
+    let threshold = mix(1.0, material.settings.y, material.shape.x * weather_coverage(p) * trail_coverage(p));
+    let value = noise_val * profile;
+
+    // Ramp the density in over `softness` above the threshold instead of
+    // starting at full slope. Erosion scales the ramp by a finer resample of
+    // the same noise, so the edge is uneven; CLOUD_DETAIL_NOISE is set while
+    // it is. Views can turn the resample off.
+    var softness = material.profile.y;
+#ifdef CLOUD_DETAIL_NOISE
+    var erosion = material.profile.z;
+#ifdef CLOUD_VIEW_BINDING
+    erosion *= view_detail();
+#endif
+    if (erosion > 0.0) {
+        let detail = textureSampleLevel(noise_texture, noise_sampler, uv * EROSION_SCALE, 0.0).r;
+        softness *= mix(1.0, 2.0 * detail, erosion);
+    }
+#endif
+    var edge = 1.0;
+    if (softness > 0.0) {
+        edge = smoothstep(threshold, threshold + softness, value);
+    }
+    return max(value - threshold, 0.0) * edge * material.settings.x;
+#endif
+}
+
+// Factor on the ambient term from the occlusion baked into the noise's
+// green channel; 1 when the strength is 0.
+fn ambient_visibility(p: vec3<f32>) -> f32 {
+    if (material.occlusion.x <= 0.0) {
+        return 1.0;
+    }
+    let q = clamp_to_box(p);
+    let uv = noise_uv(anvil_position(q, anvil_weight(box_height(q))));
+    let visibility = textureSampleLevel(noise_texture, noise_sampler, uv, 0.0).g;
+    return mix(1.0, visibility, material.occlusion.x);
+}
+
+// Whole steps the march can skip from `p`: the ray's distance to the far side
+// of the current occupancy block, when the block's highest noise value cannot
+// clear the threshold. The profile, the weather map, the trail and the
+// softness ramp only lower the density, so the skip never drops a sample. Jitter may push
+// a sample up to half its amount into the next block, so that much is held
+// back. The fallback texture is white and never skips, and neither does an
+// FBM mix, whose taps the blocks do not describe, or an anvil, which samples
+// the noise away from the block the ray is in.
+fn empty_steps(p: vec3<f32>, ray_dir: vec3<f32>, step_size: f32) -> i32 {
+#ifdef CLOUD_GROUND_FOG
+    // Fog has no threshold, so nothing is ever skipped.
+    return 0;
+#else
+    // The blocks describe the noise, not the faces repeated in the outset.
+    if (!single_tap() || material.anvil.x > 0.0 || (material.bounds.y > 0.0 && any(p != clamp_to_box(p)))) {
+        return 0;
+    }
+    let threshold = mix(1.0, material.settings.y, material.shape.x);
+    let blocks = vec3<f32>(textureDimensions(occupancy));
+    let uv = fract(noise_uv(p));
+    let cell = min(floor(uv * blocks), blocks - 1.0);
+    if (textureLoad(occupancy, vec3<i32>(cell), 0).r > threshold) {
+        return 0;
+    }
+    let scale = (material.box_max.xyz - material.box_min.xyz) * material.shape.yzw;
+    // Axis-aligned rays would divide 0 by 0 on a block face.
+    let uv_dir = to_domain(ray_dir) / scale;
+    let safe_dir = select(uv_dir, vec3<f32>(1e-8), abs(uv_dir) < vec3<f32>(1e-8));
+    let exit = ray_box_intersection(uv, safe_dir, cell / blocks, (cell + 1.0) / blocks).y;
+    return i32(floor(max(exit, 0.0) / step_size - max(material.sampling.x, 0.0)));
+#endif
+}
+
+// Whether the pixel's alpha, before the crossfade, must end below the cutoff
+// however dense the `remaining` units of the ray turn out, so the march can
+// stop early. Noise and profile stay below 1 and the weather map and trail
+// only lower the coverage, which bounds the density as in `empty_steps`.
+fn below_alpha_cutoff(transmittance: vec3<f32>, remaining: f32) -> bool {
+    let cutoff = material.sampling.w / material.opacity;
+    if (cutoff <= 0.0) {
+        return false;
+    }
+    let sigma = extinction();
+#ifdef CLOUD_GROUND_FOG
+    // The falloff stays below 1 and the noise at most doubles the density.
+    let max_density = (1.0 + material.ground_fog.z) * material.shape.x * material.settings.x;
+#else
+    let max_density = max(1.0 - mix(1.0, material.settings.y, material.shape.x), 0.0) * material.settings.x;
+#endif
+    let max_depth = max_density * max(sigma.r, max(sigma.g, sigma.b)) * remaining;
+    return 1.0 - dot(transmittance, vec3<f32>(1.0 / 3.0)) * exp(-max_depth) < cutoff;
+}
@@ -0,0 +1,136 @@
+// Fragment stage of `CloudExtension`: the same raymarch as the standalone
+// material, lit by Bevy's directional lights (with their cascaded shadow
+// maps) and ambient light instead of the artistic sun and gradient.
+
+#import bevy_pbr::mesh_view_bindings as view_bindings
+#import bevy_pbr::mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT
+#import bevy_pbr::shadows::fetch_directional_shadow
+#import bevy_pbr::forward_io::{VertexOutput, FragmentOutput}
+#import bevy_clouds::bindings::{material, mask_texture, mask_sampler}
+#import bevy_clouds::common::{PI, extinction, merged_away, portal_mask_applies, step_jitter, to_volume, to_volume_dir, to_world}
+#import bevy_clouds::view::{march_steps, light_march_steps, sample_position, sample_step_jitter}
+#import bevy_clouds::intersection::{ray_box_intersection, march_box_min, march_box_max, march_entry}
+#import bevy_clouds::lighting::{henyey_greenstein, silver_lining}
+#import bevy_clouds::density::{sample_density, ambient_visibility, empty_steps, below_alpha_cutoff}
+
+fn light_march(p: vec3<f32>, light_dir: vec3<f32>, box_min: vec3<f32>, box_max: vec3<f32>, light_steps: i32) -> f32 {
+    let t_exit = ray_box_intersection(p, light_dir, box_min, box_max).y;
+    let step_size = max(t_exit, 0.0) / f32(light_steps);
+    var depth = 0.0;
+    for (var i = 0; i < light_steps; i = i + 1) {
+        depth += sample_density(p + light_dir * step_size * (f32(i) + 0.5)) * step_size;
+    }
+    return depth;
+}
+
+// Light from every directional light reaching `p`, after scene shadows and
+// self-shadowing. `p` and `ray_dir` are in volume space.
+fn direct_light(p: vec3<f32>, ray_dir: vec3<f32>, box_min: vec3<f32>, box_max: vec3<f32>, light_steps: i32) -> vec3<f32> {
+    let view = view_bindings::view;
+    let world_pos = to_world(p);
+    let view_z = dot(vec4<f32>(view.view_from_world[0].z, view.view_from_world[1].z, view.view_from_world[2].z, view.view_from_world[3].z), vec4<f32>(world_pos, 1.0));
+    var total = vec3<f32>(0.0);
+    for (var i = 0u; i < view_bindings::lights.n_directional_lights; i = i + 1u) {
+        let light = view_bindings::lights.directional_lights[i];
+        let world_light_dir = normalize(light.direction_to_light);
+        let light_dir = to_volume_dir(world_light_dir);
+        var shadow = 1.0;
+        if ((light.flags & DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u) {
+            shadow = fetch_directional_shadow(i, vec4<f32>(world_pos, 1.0), world_light_dir, view_z);
+        }
+        if (shadow <= 0.0) {
+            continue;
+        }
+        let phase = henyey_greenstein(dot(ray_dir, light_dir), material.lighting.x);
+        // `silver_lining` is relative to isotropic scattering.
+        let silver = silver_lining(dot(ray_dir, light_dir)) / (4.0 * PI);
+        let transmittance = exp(-light_march(p, light_dir, box_min, box_max, light_steps) * extinction());
+        total += light.color.rgb * (phase + silver * transmittance) * transmittance * shadow;
+    }
+    return total;
+}
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = vec4<f32>(0.0);
+    if (material.opacity <= 0.0) {
+        discard;
+    }
+    if (portal_mask_applies()) {
+        let viewport = view_bindings::view.viewport;
+        let mask_uv = (in.position.xy - viewport.xy) / viewport.zw;
+        if (textureSampleLevel(mask_texture, mask_sampler, mask_uv, 0.0).a < 0.5) {
+            discard;
+        }
+    }
+
+    let camera_pos = view_bindings::view.world_position;
+    let is_orthographic = view_bindings::view.clip_from_view[3].w == 1.0;
+    let world_position = sample_position(in.position, in.world_position.xyz);
+    var ray_origin = camera_pos;
+    var ray_dir = normalize(world_position - camera_pos);
+    if (is_orthographic) {
+        ray_dir = normalize(-view_bindings::view.world_from_view[2].xyz);
+        ray_origin = world_position - ray_dir * dot(world_position - camera_pos, ray_dir);
+    }
+    ray_origin = to_volume(ray_origin);
+    ray_dir = to_volume_dir(ray_dir);
+
+    let box_min = march_box_min();
+    let box_max = march_box_max();
+    let t = ray_box_intersection(ray_origin, ray_dir, box_min, box_max);
+    let t_entry = march_entry(t.x);
+    let t_exit = t.y;
+    if (t_entry >= t_exit) {
+        return out;
+    }
+
+    let steps = march_steps(material.settings.w);
+    let light_steps = light_march_steps(material.lighting.z);
+    let step_size = (t_exit - t_entry) / f32(steps);
+    let sigma = extinction();
+    let ambient = view_bindings::lights.ambient_color.rgb;
+
+    if (below_alpha_cutoff(vec3<f32>(1.0), t_exit - t_entry)) {
+        discard;
+    }
+    var total_transmittance = vec3<f32>(1.0);
+    var final_color = vec3<f32>(0.0);
+    for (var i = 0; i < steps; i = i + 1) {
+        let p = ray_origin + ray_dir * (t_entry + step_size * f32(i));
+        let skip = empty_steps(p, ray_dir, step_size);
+        if (skip > 0) {
+            i += skip - 1;
+            continue;
+        }
+        let sample_pos = p + ray_dir * step_size * sample_step_jitter(step_jitter(in.position.xy, i));
+        var density = 0.0;
+        if (!merged_away(to_world(sample_pos))) {
+            density = sample_density(sample_pos);
+        }
+        if (density > 0.0) {
+            let step_transmittance = exp(-density * step_size * sigma);
+            let ambient_light = ambient * ambient_visibility(sample_pos);
+            let in_scatter = material.color.rgb * (ambient_light + direct_light(sample_pos, ray_dir, box_min, box_max, light_steps));
+            final_color += total_transmittance * (1.0 - step_transmittance) * in_scatter;
+            total_transmittance *= step_transmittance;
+        }
+        if (max(total_transmittance.r, max(total_transmittance.g, total_transmittance.b)) <= 0.1) {
+            break;
+        }
+        if (below_alpha_cutoff(total_transmittance, step_size * f32(steps - i - 1))) {
+            discard;
+        }
+    }
+
+    // Physical light units, so exposure applies as for PBR surfaces.
+    let coverage = 1.0 - dot(total_transmittance, vec3<f32>(1.0 / 3.0));
+    if (coverage * material.opacity < material.sampling.w) {
+        discard;
+    }
+    out.color = vec4<f32>(final_color * view_bindings::view.exposure, coverage * material.opacity);
+    return out;
+}
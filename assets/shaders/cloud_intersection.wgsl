@@ -0,0 +1,71 @@
+#define_import_path bevy_clouds::intersection
+
+#import bevy_clouds::bindings::material
+#import bevy_clouds::common::box_half_extents
+
+fn ray_box_intersection(ray_origin: vec3<f32>, ray_dir: vec3<f32>, box_min: vec3<f32>, box_max: vec3<f32>) -> vec2<f32> {
+    let inv_dir = 1.0 / ray_dir;
+    let t0 = (box_min - ray_origin) * inv_dir;
+    let t1 = (box_max - ray_origin) * inv_dir;
+    let tmin = min(t0, t1);
+    let tmax = max(t0, t1);
+    let dist_a = max(max(tmin.x, tmin.y), tmin.z);
+    let dist_b = min(min(tmax.x, tmax.y), tmax.z);
+    return vec2<f32>(dist_a, dist_b);
+}
+
+// The march runs through the box grown by the outset; samples out there take
+// the density on the nearest face. With no outset both are the box itself.
+fn march_box_min() -> vec3<f32> {
+    return -box_half_extents() - max(material.bounds.y, 0.0);
+}
+
+fn march_box_max() -> vec3<f32> {
+    return box_half_extents() + max(material.bounds.y, 0.0);
+}
+
+fn clamp_to_box(p: vec3<f32>) -> vec3<f32> {
+    if (material.bounds.y <= 0.0) {
+        return p;
+    }
+    return clamp(p, -box_half_extents(), box_half_extents());
+}
+
+// Where the march starts for a ray entering the bounds at `t_near`: the
+// inset further in when entering from outside, the camera when inside.
+fn march_entry(t_near: f32) -> f32 {
+    if (t_near <= 0.0) {
+        return 0.0;
+    }
+    return t_near + max(material.bounds.x, 0.0);
+}
+
+// Density scale at distance `t` along a ray stopped by opaque geometry at
+// `t_contact`: fades to 0 over the contact fade distance in front of the
+// surface, like a soft particle.
+fn contact_fade(t: f32, t_contact: f32) -> f32 {
+    let distance = material.bounds.z;
+    if (distance <= 0.0) {
+        return 1.0;
+    }
+    return clamp((t_contact - t) / distance, 0.0, 1.0);
+}
+
+#ifdef CLOUD_GROUND_FOG
+// Ray distances between the box's bottom and top planes, ending at the fog
+// distance; the sides of the box are ignored.
+fn fog_range(origin: vec3<f32>, dir: vec3<f32>) -> vec2<f32> {
+    let top = box_half_extents().y;
+    let bottom = -top;
+    let distance = material.fog_color.a;
+    if (abs(dir.y) < 1e-6) {
+        if (origin.y < bottom || origin.y > top) {
+            return vec2<f32>(1.0, 0.0);
+        }
+        return vec2<f32>(0.0, distance);
+    }
+    let t0 = (bottom - origin.y) / dir.y;
+    let t1 = (top - origin.y) / dir.y;
+    return vec2<f32>(min(t0, t1), min(max(t0, t1), distance));
+}
+#endif
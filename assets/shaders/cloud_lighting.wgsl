@@ -0,0 +1,76 @@
+#define_import_path bevy_clouds::lighting
+
+// Phase functions and the sun's light scattered inside the volume. The
+// scattering is only compiled in with CLOUD_LIGHT_MARCH, which the material
+// sets while a sun lights the volume. The march towards the sun samples the
+// density, so it stays in the main shader next to `density_at`.
+
+#import bevy_clouds::bindings::material
+#import bevy_clouds::common::PI
+#ifdef CLOUD_VIEW_BINDING
+#import bevy_clouds::view::view_multi_scatter
+#endif
+
+fn henyey_greenstein(cos_theta: f32, g_in: f32) -> f32 {
+    // The denominator reaches 0 at |g| = 1.
+    let g = clamp(g_in, -0.99, 0.99);
+    let g2 = g * g;
+    return (1.0 - g2) / (4.0 * PI * pow(1.0 + g2 - 2.0 * g * cos_theta, 1.5));
+}
+
+// Extra forward scattering when looking towards the light, relative to
+// isotropic scattering. Callers weight it by the transmittance towards the
+// light, so it only brightens thin edges.
+fn silver_lining(cos_theta: f32) -> f32 {
+    if (material.silver.x <= 0.0) {
+        return 0.0;
+    }
+    return material.silver.x * exp((cos_theta - 1.0) / max(material.silver.y, 1e-3));
+}
+
+#ifdef CLOUD_LIGHT_MARCH
+#ifdef CLOUD_SINGLE_SCATTER
+// Weight of the back lobe in the two-lobe phase function, and its
+// anisotropy relative to the forward lobe.
+const BACK_LOBE_WEIGHT: f32 = 0.2;
+const BACK_LOBE_G: f32 = -0.5;
+// Wrenninge's multiple-scattering octaves: each scales the extinction, the
+// contribution and the anisotropy of the previous one by this factor.
+const SCATTER_OCTAVES: i32 = 4;
+const OCTAVE_FALLOFF: f32 = 0.5;
+
+// Sun light scattered towards the camera at a sample `depth` (before
+// extinction) inside the cloud as seen from the sun, per unit of sun color:
+// a two-lobe phase function and the powder term, which darkens the edges
+// facing the sun where little light has been scattered in yet. With
+// CLOUD_MULTI_SCATTER, fainter, softer and less shadowed octaves stand in
+// for light scattered more than once. Without it the view can still weigh
+// them in, as progressive refinement does once the view settles.
+fn scattered_sun(depth: f32, cos_theta: f32, sigma: vec3<f32>, phase_scale: f32, silver: f32) -> vec3<f32> {
+    // Weight of the octaves after the first.
+    var extra = 0.0;
+#ifdef CLOUD_MULTI_SCATTER
+    extra = 1.0;
+#else
+#ifdef CLOUD_VIEW_BINDING
+    extra = view_multi_scatter();
+#endif
+#endif
+    let octaves = select(1, SCATTER_OCTAVES, extra > 0.0);
+    var total = vec3<f32>(0.0);
+    var scale = 1.0;
+    for (var i = 0; i < octaves; i = i + 1) {
+        let g = material.lighting.x * scale;
+        let phase = mix(henyey_greenstein(cos_theta, g), henyey_greenstein(cos_theta, g * BACK_LOBE_G), BACK_LOBE_WEIGHT);
+        let transmittance = exp(-depth * sigma * scale);
+        let weight = select(extra, 1.0, i == 0);
+        total += weight * scale * (phase * phase_scale + silver * transmittance) * transmittance;
+        scale *= OCTAVE_FALLOFF;
+    }
+    // Full strength with the sun behind the camera, where the sunlit edges
+    // face it; none looking into the sun, where forward scattering dominates.
+    let powder = 1.0 - exp(-2.0 * depth * dot(sigma, vec3<f32>(1.0 / 3.0)));
+    return total * mix(powder, 1.0, 0.5 + 0.5 * cos_theta);
+}
+#endif
+#endif
@@ -2,96 +2,313 @@
 #import bevy_pbr::mesh_bindings as mesh_bindings
 #import bevy_pbr::forward_io::VertexOutput
 #import bevy_pbr::mesh_functions as mesh_functions
+#ifdef DEPTH_PREPASS
+#import bevy_pbr::prepass_utils::prepass_depth
+#import bevy_pbr::view_transformations::{frag_coord_to_ndc, position_ndc_to_world}
+#endif
+#import bevy_clouds::bindings::{material, mask_texture, mask_sampler, light_volume, light_volume_sampler}
+#import bevy_clouds::common::{to_volume, to_volume_dir, to_world, box_half_extents, extinction, merged_away, step_jitter, portal_mask_applies}
+#import bevy_clouds::view::{march_steps, light_march_steps, sample_position, sample_step_jitter}
+#import bevy_clouds::intersection::{ray_box_intersection, march_box_min, march_box_max, march_entry, clamp_to_box, contact_fade}
+#ifdef CLOUD_GROUND_FOG
+#import bevy_clouds::intersection::fog_range
+#endif
+#ifdef CLOUD_DENSITY_OVERRIDE
+#import bevy_clouds::density_override::sample_density
+#else
+#import bevy_clouds::density::{sample_density, ambient_visibility, empty_steps, below_alpha_cutoff}
+#endif
+#ifdef CLOUD_LIGHT_MARCH
+#import bevy_clouds::common::PI
+#import bevy_clouds::lighting::{henyey_greenstein, silver_lining}
+#ifdef CLOUD_SINGLE_SCATTER
+#import bevy_clouds::lighting::scattered_sun
+#endif
+#endif
+#import bevy_clouds::compositing::composite
+#ifdef CLOUD_DEBUG_VIEWS
+#import bevy_clouds::debug::{debug_view_active, step_heatmap}
+#endif
 
-struct CloudMaterial {
-    color: vec4<f32>,
-    settings: vec4<f32>, // x: density, y: threshold, z: absorption, w: steps
-};
-
-@group(2) @binding(0)
-var<uniform> material: CloudMaterial;
-@group(2) @binding(1)
-var noise_texture: texture_3d<f32>;
-@group(2) @binding(2)
-var noise_sampler: sampler;
+// The raymarch itself. Intersection, density, lighting, compositing and
+// debug views live in the imported modules; optional features are compiled
+// in only when the material's pipeline key sets their shader defs, so a
+// volume with the extras off runs the plain march. Whatever samples the
+// density stays here: only the main shader may import the density override
+// conditionally, as naga_oil requires every import of a module to exist.
 
 struct Vertex {
+    @builtin(instance_index) instance_index: u32,
     @location(0) position: vec3<f32>,
 };
 
+// Density at a volume-space point. Replacement densities are written
+// against world space, so they stay put when the volume turns.
+fn density_at(p: vec3<f32>) -> f32 {
+#ifdef CLOUD_DENSITY_OVERRIDE
+    return sample_density(to_world(clamp_to_box(p)));
+#else
+    return sample_density(p);
+#endif
+}
+
+#ifdef CLOUD_LIGHT_MARCH
+// Optical depth towards the sun, used to self-shadow the sun term.
+fn light_march(p: vec3<f32>, sun_dir: vec3<f32>, box_min: vec3<f32>, box_max: vec3<f32>, light_steps: i32) -> f32 {
+#ifdef CLOUD_GROUND_FOG
+    let t_exit = fog_range(p, sun_dir).y;
+#else
+    let t_exit = ray_box_intersection(p, sun_dir, box_min, box_max).y;
+#endif
+    let step_size = max(t_exit, 0.0) / f32(light_steps);
+    var depth = 0.0;
+    for (var i = 0; i < light_steps; i = i + 1) {
+        let sample_pos = p + sun_dir * step_size * (f32(i) + 0.5);
+        depth += density_at(sample_pos) * step_size;
+    }
+    return depth;
+}
+
+// The march above, or its result baked per texel by `LightVolumePlugin`
+// while the volume holds still.
+fn light_depth(p: vec3<f32>, sun_dir: vec3<f32>, box_min: vec3<f32>, box_max: vec3<f32>, light_steps: i32) -> f32 {
+    // The baked volume covers the box, not the fog around it or the outset.
+#ifndef CLOUD_GROUND_FOG
+    if (material.sampling.y > 0.0) {
+        let uvw = clamp_to_box(p) / (box_half_extents() * 2.0) + 0.5;
+        return textureSampleLevel(light_volume, light_volume_sampler, uvw, 0.0).r;
+    }
+#endif
+    return light_march(p, sun_dir, box_min, box_max, light_steps);
+}
+
+#endif
+
 @vertex
 fn vertex(vertex: Vertex) -> VertexOutput {
     var out: VertexOutput;
-    let world_matrix = mesh_functions::get_world_from_local(0u);
-    let world_pos4 = mesh_functions::mesh_position_local_to_world(world_matrix, vec4<f32>(vertex.position, 1.0));
+    let world_matrix = mesh_functions::get_world_from_local(vertex.instance_index);
+    var world_pos4 = mesh_functions::mesh_position_local_to_world(world_matrix, vec4<f32>(vertex.position, 1.0));
+#ifdef CLOUD_GROUND_FOG
+    // Only the top and bottom bound the fog, so the box is stretched around
+    // the camera out to the fog distance and its sides never show.
+    let camera = to_volume(view_bindings::view.world_position);
+    let proxy = vec3<f32>(
+        camera.x + vertex.position.x * material.fog_color.a,
+        vertex.position.y * box_half_extents().y,
+        camera.z + vertex.position.z * material.fog_color.a,
+    );
+    world_pos4 = vec4<f32>(to_world(proxy), 1.0);
+#endif
     out.world_position = world_pos4;
     out.position = view_bindings::view.clip_from_world * world_pos4;
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = vertex.instance_index;
+#endif
     return out;
 }
 
-fn ray_box_intersection(ray_origin: vec3<f32>, ray_dir: vec3<f32>, box_min: vec3<f32>, box_max: vec3<f32>) -> vec2<f32> {
-    let inv_dir = 1.0 / ray_dir;
-    let t0 = (box_min - ray_origin) * inv_dir;
-    let t1 = (box_max - ray_origin) * inv_dir;
-    let tmin = min(t0, t1);
-    let tmax = max(t0, t1);
-    let dist_a = max(max(tmin.x, tmin.y), tmin.z);
-    let dist_b = min(min(tmax.x, tmax.y), tmax.z);
-    return vec2<f32>(dist_a, dist_b);
-}
-
 @fragment
 fn fragment(
     in: VertexOutput,
 ) -> @location(0) vec4<f32> {
-    let ray_origin = view_bindings::view.world_position;
-    let ray_dir = normalize(in.world_position.xyz - ray_origin);
+    if (material.opacity <= 0.0) {
+        discard;
+    }
+#ifndef CLOUD_DENSITY_OVERRIDE
+#ifndef CLOUD_GROUND_FOG
+    // Nothing clears a threshold of 1 and zero density is empty, so skip the
+    // march entirely.
+    if (mix(1.0, material.settings.y, material.shape.x) >= 1.0 || material.settings.x <= 0.0) {
+        discard;
+    }
+#endif
+#endif
+    if (portal_mask_applies()) {
+        let viewport = view_bindings::view.viewport;
+        let mask_uv = (in.position.xy - viewport.xy) / viewport.zw;
+        if (textureSampleLevel(mask_texture, mask_sampler, mask_uv, 0.0).a < 0.5) {
+            discard;
+        }
+    }
 
-    let box_min = vec3<f32>(-1.0, 0.0, -1.0);
-    let box_max = vec3<f32>(1.0, 2.0, 1.0);
+    // Perspective rays start at the camera. Orthographic rays all share the
+    // view direction and start on the camera plane under the fragment.
+    let camera_pos = view_bindings::view.world_position;
+    let is_orthographic = view_bindings::view.clip_from_view[3].w == 1.0;
+    let world_position = sample_position(in.position, in.world_position.xyz);
+    var ray_origin = camera_pos;
+    var ray_dir = normalize(world_position - camera_pos);
+    if (is_orthographic) {
+        ray_dir = normalize(-view_bindings::view.world_from_view[2].xyz);
+        ray_origin = world_position - ray_dir * dot(world_position - camera_pos, ray_dir);
+    }
+    // Stop at opaque geometry from the depth prepass, so objects inside the
+    // volume sink into it. The pipeline skips the depth test in that case.
+    var scene_distance = 3.4e38;
+#ifdef DEPTH_PREPASS
+    let scene_depth = prepass_depth(in.position, 0u);
+    if (scene_depth > 0.0) {
+        let scene_pos = position_ndc_to_world(vec3<f32>(frag_coord_to_ndc(in.position).xy, scene_depth));
+        scene_distance = dot(scene_pos - ray_origin, ray_dir);
+    }
+#endif
+    // Distances are unchanged by the rotation, so the march needs no world
+    // positions except for the merged regions. The camera is taken relative
+    // to the box once here; see `to_volume`.
+    ray_origin = to_volume(ray_origin);
+    ray_dir = to_volume_dir(ray_dir);
+
+    let box_half = box_half_extents();
+    let bounds_min = march_box_min();
+    let bounds_max = march_box_max();
 
-    let t = ray_box_intersection(ray_origin, ray_dir, box_min, box_max);
-    let t_entry = max(t.x, 0.0); 
-    let t_exit = t.y;
+#ifdef CLOUD_GROUND_FOG
+    let t = fog_range(ray_origin, ray_dir);
+#else
+    let t = ray_box_intersection(ray_origin, ray_dir, bounds_min, bounds_max);
+#endif
+    let t_entry = march_entry(t.x);
+    let t_exit = min(t.y, scene_distance);
+    // Only geometry feathers the cloud; its own bounds are left to the
+    // edge softness.
+    let t_contact = select(3.4e38, scene_distance, scene_distance < t.y);
 
     if (t_entry < t_exit) {
-        var p = ray_origin + ray_dir * t_entry;
-        var total_transmittance = 1.0;
+        // Carried per channel, so thick regions shift towards the color
+        // extinguished least.
+        var total_transmittance = vec3<f32>(1.0);
         var final_color = vec3<f32>(0.0);
         
-        let density_multiplier = material.settings.x;
-        let threshold = material.settings.y;
-        let absorption = material.settings.z;
-        let steps = i32(material.settings.w); 
+        let sigma = extinction();
+        let steps = march_steps(material.settings.w);
+        let light_steps = light_march_steps(material.lighting.z);
 
         let step_size = (t_exit - t_entry) / f32(steps);
 
+#ifdef CLOUD_LIGHT_MARCH
+        let sun_intensity = material.lighting.y;
+        // The sun is chosen by `CloudSunPlugin`; without one the clouds keep
+        // their ambient light and CLOUD_LIGHT_MARCH is unset.
+        let has_sun = sun_intensity > 0.0 && material.sun_direction.w > 0.0;
+        var sun_dir = vec3<f32>(0.0, 1.0, 0.0);
+        var sun_color = vec3<f32>(0.0);
+        var phase = 0.0;
+        var phase_scale = 1.0;
+        var silver = 0.0;
+        // Phase-weighted light from the fill, added at every sample without
+        // a light march.
+        var fill = vec3<f32>(0.0);
+        if (has_sun) {
+            sun_dir = to_volume_dir(normalize(material.sun_direction.xyz));
+            var light_scale = vec3<f32>(view_bindings::view.exposure);
+            if (material.lighting.w <= 0.0) {
+                // Only the hue of the light is used; brightness is artistic.
+                // The fill keeps its brightness relative to the sun.
+                let sun = material.sun_color.rgb;
+                light_scale = vec3<f32>(1.0 / max(max(sun.r, max(sun.g, sun.b)), 1e-4));
+                // Relative to isotropic scattering so g = 0 leaves the sun at full intensity.
+                phase_scale = 4.0 * PI;
+            }
+            // Otherwise illuminance through the camera exposure, as for PBR
+            // surfaces, with the phase function per steradian.
+            sun_color = material.sun_color.rgb * light_scale;
+            phase = henyey_greenstein(dot(ray_dir, sun_dir), material.lighting.x) * phase_scale;
+            silver = silver_lining(dot(ray_dir, sun_dir)) * phase_scale / (4.0 * PI);
+            if (material.fill_direction.w > 0.0) {
+                let fill_dir = to_volume_dir(normalize(material.fill_direction.xyz));
+                let fill_phase = henyey_greenstein(dot(ray_dir, fill_dir), material.lighting.x) * phase_scale;
+                fill = material.fill_color.rgb * light_scale * sun_intensity * fill_phase;
+            }
+        }
+#endif
+
+#ifndef CLOUD_DENSITY_OVERRIDE
+        // Density overrides have no bound to stop early on. The heatmap
+        // shows every pixel.
+        var cutoff_enabled = true;
+#ifdef CLOUD_DEBUG_VIEWS
+        cutoff_enabled = !debug_view_active();
+#endif
+        if (cutoff_enabled && below_alpha_cutoff(vec3<f32>(1.0), t_exit - t_entry)) {
+            discard;
+        }
+#endif
+
+        // Density fetches, for the step heatmap.
+        var samples = 0;
         for (var i = 0; i < steps; i = i + 1) {
-            // Map world position to texture UV [0, 1]
-            let uv = (p - box_min) / (box_max - box_min);
-            
-            // Sample the pre-baked 3D texture
-            let noise_val = textureSampleLevel(noise_texture, noise_sampler, uv, 0.0).r;
-            
-            let density = max(noise_val - threshold, 0.0) * density_multiplier;
+            // From the step index rather than summed step by step, so
+            // rounding does not build up over long marches.
+            let p = ray_origin + ray_dir * (t_entry + step_size * f32(i));
+#ifndef CLOUD_DENSITY_OVERRIDE
+            let skip = empty_steps(p, ray_dir, step_size);
+            if (skip > 0) {
+                i += skip - 1;
+                continue;
+            }
+#endif
+            let sample_pos = p + ray_dir * step_size * sample_step_jitter(step_jitter(in.position.xy, i));
+            var density = 0.0;
+            if (!merged_away(to_world(sample_pos))) {
+                density = density_at(sample_pos) * contact_fade(dot(sample_pos - ray_origin, ray_dir), t_contact);
+                samples += 1;
+            }
             
             if (density > 0.0) {
-                let step_transmittance = exp(-density * step_size * absorption);
-                let height_factor = (p.y - box_min.y) / (box_max.y - box_min.y);
-                let light = mix(0.6, 1.0, height_factor);
-                let ambient = material.color.rgb * light;
+                let step_transmittance = exp(-density * step_size * sigma);
+                let height_factor = clamp(sample_pos.y / (box_half.y * 2.0) + 0.5, 0.0, 1.0);
+                var light = mix(material.ambient_bottom.rgb, material.ambient_top.rgb, height_factor);
+#ifndef CLOUD_DENSITY_OVERRIDE
+                // The baked occlusion follows the noise, not a replacement density.
+                light *= ambient_visibility(sample_pos);
+#endif
+                var in_scatter = material.color.rgb * light;
+#ifdef CLOUD_LIGHT_MARCH
+                if (has_sun) {
+#ifdef CLOUD_SINGLE_SCATTER
+                    let sun_depth = light_depth(sample_pos, sun_dir, bounds_min, bounds_max, light_steps);
+                    in_scatter += sun_color * sun_intensity * scattered_sun(sun_depth, dot(ray_dir, sun_dir), sigma, phase_scale, silver);
+#else
+                    let sun_transmittance = exp(-light_depth(sample_pos, sun_dir, bounds_min, bounds_max, light_steps) * sigma);
+                    in_scatter += sun_color * sun_intensity * (phase + silver * sun_transmittance) * sun_transmittance;
+#endif
+                    in_scatter += fill;
+                }
+#endif
+#ifdef CLOUD_GROUND_FOG
+                // Distant fog fades to its color, nearly all of it by the
+                // fog distance.
+                let haze = 1.0 - exp(-3.0 * length(sample_pos - ray_origin) / material.fog_color.a);
+                in_scatter = mix(in_scatter, material.fog_color.rgb, haze);
+#endif
                 
-                final_color += total_transmittance * (1.0 - step_transmittance) * ambient;
+                final_color += total_transmittance * (1.0 - step_transmittance) * in_scatter;
                 total_transmittance *= step_transmittance;
             }
 
-            if (total_transmittance <= 0.1) {
+            if (max(total_transmittance.r, max(total_transmittance.g, total_transmittance.b)) <= 0.1) {
                 break;
             }
-            p += ray_dir * step_size;
+#ifndef CLOUD_DENSITY_OVERRIDE
+            if (cutoff_enabled && below_alpha_cutoff(total_transmittance, step_size * f32(steps - i - 1))) {
+                discard;
+            }
+#endif
+        }
+
+#ifdef CLOUD_DEBUG_VIEWS
+        if (debug_view_active()) {
+            return step_heatmap(samples, steps);
         }
+#endif
 
-        return vec4<f32>(final_color, 1.0 - total_transmittance);
+        let coverage = 1.0 - dot(total_transmittance, vec3<f32>(1.0 / 3.0));
+        // Imperceptible wisps cost no blending.
+        if (coverage * material.opacity < material.sampling.w) {
+            discard;
+        }
+        return composite(final_color, coverage);
     } else {
         return vec4<f32>(0.0, 0.0, 0.0, 0.0);
     }
@@ -0,0 +1,137 @@
+// Screen-space motion of the clouds, for `CloudMotionVectorsPlugin`. Drawn by
+// an invisible companion of each volume: its main pass discards everything,
+// and its prepass writes the motion of the wind-blown field into the
+// motion-vector texture, blended over the geometry's by the cloud coverage.
+
+#import bevy_pbr::mesh_view_bindings::view
+#import bevy_clouds::bindings::material
+#import bevy_clouds::common::{to_volume, to_volume_dir, to_world, extinction, wind_weight}
+#import bevy_clouds::intersection::{ray_box_intersection, march_box_min, march_box_max, march_entry, clamp_to_box}
+#ifdef CLOUD_DENSITY_OVERRIDE
+#import bevy_clouds::density_override::sample_density
+#else
+#import bevy_clouds::density::sample_density
+#endif
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::prepass_io::VertexOutput
+#ifdef MOTION_VECTOR_PREPASS
+#import bevy_pbr::prepass_bindings::previous_view_uniforms
+#endif
+#else
+#import bevy_pbr::forward_io::VertexOutput
+#endif
+
+// xyz: volume-space wind scroll since the previous frame, at the lower and
+// upper wind altitudes.
+@group(2) @binding(12)
+var<uniform> motion: vec4<f32>;
+@group(2) @binding(13)
+var<uniform> motion_upper: vec4<f32>;
+
+// Coarse march: enough to find where the cloud is and how much of the pixel
+// it covers, not to light it.
+const MOTION_STEPS: i32 = 16;
+
+// Same as the main shader's: replacement densities are written against
+// world space.
+fn density_at(p: vec3<f32>) -> f32 {
+#ifdef CLOUD_DENSITY_OVERRIDE
+    return sample_density(to_world(clamp_to_box(p)));
+#else
+    return sample_density(p);
+#endif
+}
+
+// Mirrors `bevy_pbr::prepass_io::FragmentOutput`, except that the motion
+// vector carries the coverage in alpha for blending.
+#ifdef PREPASS_FRAGMENT
+struct FragmentOutput {
+#ifdef NORMAL_PREPASS
+    @location(0) normal: vec4<f32>,
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    @location(1) motion_vector: vec4<f32>,
+#endif
+#ifdef DEFERRED_PREPASS
+    @location(2) deferred: vec4<u32>,
+    @location(3) deferred_lighting_pass_id: u32,
+#endif
+}
+#endif
+
+#ifdef PREPASS_PIPELINE
+#ifdef MOTION_VECTOR_PREPASS
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOutput {
+    if (material.opacity <= 0.0) {
+        discard;
+    }
+    let camera_pos = view.world_position;
+    let ray_origin = to_volume(camera_pos);
+    let ray_dir = to_volume_dir(normalize(in.world_position.xyz - camera_pos));
+    let t = ray_box_intersection(ray_origin, ray_dir, march_box_min(), march_box_max());
+    let t_entry = march_entry(t.x);
+    if (t_entry >= t.y) {
+        discard;
+    }
+
+    // Coverage-weighted mean position along the ray: the depth the cloud
+    // appears at.
+    let sigma = dot(extinction(), vec3<f32>(1.0 / 3.0));
+    let step_size = (t.y - t_entry) / f32(MOTION_STEPS);
+    var transmittance = 1.0;
+    var weighted = vec3<f32>(0.0);
+    for (var i = 0; i < MOTION_STEPS; i = i + 1) {
+        let p = ray_origin + ray_dir * (t_entry + (f32(i) + 0.5) * step_size);
+        let density = density_at(p);
+        if (density > 0.0) {
+            let step_transmittance = exp(-density * step_size * sigma);
+            weighted += p * transmittance * (1.0 - step_transmittance);
+            transmittance *= step_transmittance;
+        }
+    }
+    let coverage = (1.0 - transmittance) * material.opacity;
+    if (coverage <= 0.0) {
+        discard;
+    }
+
+    // The noise scrolls with the wind at its height, so what is at `p` now
+    // was at `p - shift` a frame ago.
+    let p = weighted / (1.0 - transmittance);
+    let shift = mix(motion.xyz, motion_upper.xyz, wind_weight(to_world(p).y));
+    let clip = view.unjittered_clip_from_world * vec4<f32>(to_world(p), 1.0);
+    let previous_clip = previous_view_uniforms.clip_from_world * vec4<f32>(to_world(p - shift), 1.0);
+    let velocity = (clip.xy / clip.w - previous_clip.xy / previous_clip.w) * vec2<f32>(0.5, -0.5);
+
+    var out: FragmentOutput;
+#ifdef NORMAL_PREPASS
+    out.normal = vec4<f32>(0.0);
+#endif
+    out.motion_vector = vec4<f32>(velocity, 0.0, clamp(coverage, 0.0, 1.0));
+#ifdef DEFERRED_PREPASS
+    out.deferred = vec4<u32>(0u);
+    out.deferred_lighting_pass_id = 0u;
+#endif
+    return out;
+}
+#else ifdef PREPASS_FRAGMENT
+// Normal and deferred prepasses get nothing from the clouds.
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOutput {
+    discard;
+    var out: FragmentOutput;
+    return out;
+}
+#else
+@fragment
+fn fragment(in: VertexOutput) {
+    discard;
+}
+#endif
+#else
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The cloud itself is drawn by `CloudMaterial`.
+    discard;
+}
+#endif
@@ -0,0 +1,75 @@
+#define_import_path bevy_clouds::view
+
+#import bevy_pbr::view_transformations::{frag_coord_to_ndc, position_ndc_to_world}
+
+struct CloudView {
+    steps: vec4<f32>, // x: steps, y: light steps, negative keeps the material value; z: multiple of both; w: render scale
+    detail: vec4<f32>, // x: 0 skips the detail noise; y: weight of the extra scattering octaves
+    sample: vec4<f32>, // xy: ray offset within the pixel; z: step jitter rotation
+};
+
+// This view's `CloudViewOverrides`, or the defaults; bound per view after the
+// material's group.
+@group(3) @binding(0)
+var<uniform> cloud_view: CloudView;
+
+// Step count from the material or this view's override, bounded so a bad
+// value can neither skip the march nor hang the GPU. The bounds are
+// `CloudSettings::MAX_STEPS` and `MAX_LIGHT_STEPS`, set by the pipeline.
+fn resolve(material_value: f32, override_value: f32, bound: i32) -> i32 {
+    return clamp(i32(select(material_value, override_value, override_value >= 0.0)), 1, bound);
+}
+
+// A resolved count times the view's step scale, under the same bound. The
+// march weighs each step by its length, so more or fewer steps converge on
+// the same brightness rather than adding to it.
+fn refine(count: i32, bound: i32) -> i32 {
+    return clamp(i32(round(f32(count) * view_step_scale())), 1, bound);
+}
+
+// Main and light march counts in this view.
+fn march_steps(material_value: f32) -> i32 {
+    return refine(resolve(material_value, cloud_view.steps.x, #{CLOUD_MAX_STEPS}), #{CLOUD_MAX_STEPS});
+}
+
+fn light_march_steps(material_value: f32) -> i32 {
+    return refine(resolve(material_value, cloud_view.steps.y, #{CLOUD_MAX_LIGHT_STEPS}), #{CLOUD_MAX_LIGHT_STEPS});
+}
+
+// Factor on both step counts in this view: the refinement multiple times the
+// render scale.
+fn view_step_scale() -> f32 {
+    return max(cloud_view.steps.z, 1.0) * clamp(cloud_view.steps.w, 0.0, 1.0);
+}
+
+// Factor on the detail noise's erosion in this view.
+fn view_detail() -> f32 {
+    return clamp(cloud_view.detail.x, 0.0, 1.0);
+}
+
+// Weight of multiple scattering's extra octaves on single-scatter volumes in
+// this view.
+fn view_multi_scatter() -> f32 {
+    return clamp(cloud_view.detail.y, 0.0, 1.0);
+}
+
+// World position of the fragment at `frag_coord`, moved within its pixel by
+// this view's accumulation sample so the rays of successive samples cover
+// the pixel. Unchanged without accumulation.
+fn sample_position(frag_coord: vec4<f32>, world_position: vec3<f32>) -> vec3<f32> {
+    if (all(cloud_view.sample.xy == vec2<f32>(0.0))) {
+        return world_position;
+    }
+    let ndc = frag_coord_to_ndc(vec4<f32>(frag_coord.xy + cloud_view.sample.xy, frag_coord.zw));
+    return position_ndc_to_world(ndc);
+}
+
+// A step's jitter, rotated by this view's accumulation sample so successive
+// samples place it at every point of the step, even on volumes without
+// jitter of their own.
+fn sample_step_jitter(jitter: f32) -> f32 {
+    if (cloud_view.sample.z == 0.0) {
+        return jitter;
+    }
+    return fract(jitter + 0.5 + cloud_view.sample.z) - 0.5;
+}
@@ -0,0 +1,25 @@
+#define_import_path bevy_clouds::density_override
+
+#import bevy_clouds::bindings::material
+#import bevy_clouds::common::{rotate, wind_weight}
+
+// Example density hook: a checkerboard of soft spheres filling the volume,
+// drifting with the wind. Replaces `bevy_clouds::density::sample_density`;
+// `p` is in world space.
+fn sample_density(p: vec3<f32>) -> f32 {
+    let cell_size = 0.5;
+    // The material's wind scrolls are in the volume's rotated frame.
+    let scroll = mix(material.wind_offset.xyz, material.wind_offset_upper.xyz, wind_weight(p.y));
+    let wind = rotate(material.rotation, scroll);
+    let q = (p - wind) / cell_size;
+    let cell = floor(q);
+    // Only every other cell holds a sphere.
+    let parity = i32(cell.x + cell.y + cell.z) & 1;
+    if (parity != 0) {
+        return 0.0;
+    }
+    let radius = 0.45;
+    let distance = length(fract(q) - vec3<f32>(0.5));
+    let falloff = clamp((radius - distance) / radius, 0.0, 1.0);
+    return falloff * material.settings.x;
+}
@@ -0,0 +1,63 @@
+//! Rebake latency of the noise bake: `cargo bench --bench bake`.
+//!
+//! Bakes Worley noise with the default 32 points at 64³ and 128³ in process,
+//! so app startup and file writes stay out of the times. Each size is baked
+//! cold, with new scratch buffers and output every run, and as the editor
+//! rebakes, reusing both from the previous bake. Reports the fastest and the
+//! median run of each. Compare two trees by running this in each.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use bevy::math::UVec3;
+use bevy_clouds::{
+    noise::{bake_noise, bake_noise_into, BakeScratch},
+    CloudSettings,
+};
+
+const RUNS: usize = 7;
+const RESOLUTIONS: [u32; 2] = [64, 128];
+
+fn main() {
+    for resolution in RESOLUTIONS {
+        let mut settings = CloudSettings::defaults();
+        settings.resolution = UVec3::splat(resolution);
+        settings.cell_count = 32;
+
+        let mut scratch = BakeScratch::default();
+        let mut out = Vec::new();
+        // Sized by the previous rebake, as in the editor.
+        bake_noise_into(&settings, &mut scratch, &mut out);
+        // Alternated, so drift in the machine's load hits both alike.
+        let (mut cold, mut reused) = (Vec::new(), Vec::new());
+        for _ in 0..RUNS {
+            cold.push(time(|| {
+                black_box(bake_noise(&settings));
+            }));
+            reused.push(time(|| {
+                black_box(bake_noise_into(&settings, &mut scratch, &mut out));
+            }));
+        }
+        cold.sort();
+        reused.sort();
+
+        for (name, times) in [("cold", cold), ("reused", reused)] {
+            println!(
+                "bake {0}x{0}x{0} {1}: min {2:.1} ms, median {3:.1} ms ({4} runs)",
+                resolution,
+                name,
+                times[0].as_secs_f64() * 1000.0,
+                times[RUNS / 2].as_secs_f64() * 1000.0,
+                RUNS
+            );
+        }
+    }
+}
+
+fn time(bake: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    bake();
+    start.elapsed()
+}
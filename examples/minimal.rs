@@ -0,0 +1,65 @@
+//! One cloud volume drawn from the noise volume shipped in
+//! `assets/noise/cloud_noise.ktx2`, with nothing but `CloudPlugin`: the
+//! build a game gets with `default-features = false, features = ["render"]`,
+//! without egui or the noise bake.
+//!
+//! `cargo run --example minimal --no-default-features --features render`
+//!
+//! `--frames N` exits after N frames, so CI can check that the minimal build
+//! starts and renders.
+
+use bevy::{app::AppExit, prelude::*};
+use bevy_clouds::{noise, CloudPlugin, CloudSettings, CloudVolumeParams, OrbitCamera, SpawnCloudExt};
+
+/// Asset path of the shipped noise; `--bake-only --out assets/noise/cloud_noise.ktx2`
+/// on the editor replaces it with a bake of other settings.
+const NOISE_PATH: &str = "noise/cloud_noise.ktx2";
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let frames = args
+        .iter()
+        .position(|arg| arg == "--frames")
+        .and_then(|index| args.get(index + 1))
+        .map(|value| value.parse::<u32>().expect("--frames takes a frame count"));
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins)
+        .add_plugins(CloudPlugin)
+        .add_systems(Startup, setup);
+    if let Some(frames) = frames {
+        app.insert_resource(FramesLeft(frames))
+            .add_systems(Update, exit_after_frames_system);
+    }
+    app.run();
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut settings: ResMut<CloudSettings>) {
+    settings.noise_handle = noise::load_noise(&asset_server, NOISE_PATH);
+
+    commands.spawn_cloud(CloudVolumeParams::new(Vec3::new(2.0, 1.0, 2.0)).at(Vec3::new(0.0, 1.0, 0.0)));
+
+    commands.spawn((
+        DirectionalLight::default(),
+        Transform::from_xyz(4.0, 8.0, 4.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+
+    // The runtime takes the `OrbitCamera` for the main view when sorting
+    // volumes and placing impostors; nothing orbits it here.
+    let camera_transform = Transform::from_xyz(-4.0, 3.0, 7.0).looking_at(Vec3::new(0.0, 1.0, 0.0), Vec3::Y);
+    commands.spawn((
+        Camera3d::default(),
+        camera_transform,
+        OrbitCamera::new(Vec3::new(0.0, 1.0, 0.0), &camera_transform),
+    ));
+}
+
+#[derive(Resource)]
+struct FramesLeft(u32);
+
+fn exit_after_frames_system(mut frames: ResMut<FramesLeft>, mut exit: EventWriter<AppExit>) {
+    frames.0 = frames.0.saturating_sub(1);
+    if frames.0 == 0 {
+        exit.send(AppExit::Success);
+    }
+}
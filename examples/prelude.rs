@@ -0,0 +1,166 @@
+//! Compile test of `bevy_clouds::prelude`: touches every item it re-exports
+//! using nothing else from the crate, so a change that breaks the stable API
+//! fails `cargo build --examples` rather than a downstream game.
+//!
+//! `cargo run --example prelude --no-default-features --features render -- --frames 10`
+//!
+//! Without `--frames` it keeps running like `minimal`, with a trail circling
+//! the volume.
+
+use bevy::{app::AppExit, prelude::*};
+use bevy_clouds::prelude::*;
+
+const NOISE_PATH: &str = "noise/cloud_noise.ktx2";
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let frames = args
+        .iter()
+        .position(|arg| arg == "--frames")
+        .and_then(|index| args.get(index + 1))
+        .map(|value| value.parse::<u32>().expect("--frames takes a frame count"));
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins)
+        .add_plugins(CloudPlugin)
+        .add_plugins((WeatherPlugin, TimeOfDayPlugin, PrecipitationPlugin, CloudMotionVectorsPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (circle_system, noise_baked_system, precipitation_system));
+    if let Some(frames) = frames {
+        app.insert_resource(FramesLeft(frames))
+            .add_systems(Update, exit_after_frames_system);
+    }
+    app.run();
+}
+
+/// The sphere the trail follows.
+#[derive(Component)]
+struct Mover;
+
+#[allow(clippy::too_many_arguments)]
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut settings: ResMut<CloudSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut standard_materials: ResMut<Assets<StandardMaterial>>,
+    mut lit_materials: ResMut<Assets<LitCloudMaterial>>,
+    mut time_of_day: ResMut<TimeOfDay>,
+    mut weather: ResMut<WeatherController>,
+    mut set_weather: EventWriter<SetWeather>,
+    mut precipitation: ResMut<PrecipitationSettings>,
+    mut motion_vectors: ResMut<CloudMotionVectors>,
+) {
+    // A "Copy as RON" dump from the editor, migrated like the editor's own
+    // files.
+    let (dumped, _warnings): (CloudSettings, Vec<String>) =
+        versioning::from_ron(&versioning::to_ron(&*settings).unwrap()).unwrap();
+    *settings = dumped;
+    settings.noise_handle = load_noise(&asset_server, NOISE_PATH);
+    settings.ao_strength = 0.0;
+    settings.noise_type = NoiseType::Worley;
+    settings.fractal_type = FractalType::Standard;
+    QualityPreset::Medium.apply(&mut settings);
+
+    commands.spawn_cloud(CloudVolumeParams {
+        transform: Transform::from_xyz(0.0, 1.0, 0.0).with_scale(Vec3::new(2.0, 1.0, 2.0)),
+        volume: CloudVolume {
+            blend: CloudBlendMode::Blend,
+            lighting: CloudLightingMode::SingleScatter,
+            noise: Some(NoiseOverride::from_settings(&settings)),
+            ..default()
+        },
+    });
+    commands.spawn_cloud(CloudVolumeParams {
+        transform: Transform::from_xyz(0.0, 0.5, 0.0).with_scale(Vec3::new(20.0, 0.5, 20.0)),
+        volume: CloudVolume {
+            ground_fog: Some(GroundFog::DEFAULT),
+            ..default()
+        },
+    });
+
+    // `CloudMaterial::new` and the lit material, as a game spawning its own
+    // meshes would use them.
+    let _unlit: CloudMaterial = CloudMaterial::new(&settings);
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::new(2.0, 2.0, 2.0))),
+        MeshMaterial3d(lit_materials.add(CloudExtension::material(&settings))),
+        Transform::from_xyz(4.0, 1.0, 0.0),
+        CloudVolume::default(),
+    ));
+
+    let mover = commands
+        .spawn((
+            Mesh3d(meshes.add(Sphere::new(0.15))),
+            MeshMaterial3d(standard_materials.add(Color::srgb(0.9, 0.45, 0.2))),
+            Transform::from_xyz(3.0, 2.0, 0.0),
+            Mover,
+        ))
+        .id();
+    let mut trail = CloudTrail::new(mover);
+    trail.radius = 0.4;
+    commands.spawn_cloud(CloudVolumeParams::default()).insert(trail);
+
+    commands.spawn((
+        DirectionalLight::default(),
+        Transform::from_xyz(4.0, 8.0, 4.0).looking_at(Vec3::ZERO, Vec3::Y),
+        CloudSun,
+        Sun,
+    ));
+    time_of_day.paused = false;
+    weather.enabled = true;
+    set_weather.send(SetWeather("Scattered".to_string()));
+    precipitation.show_gizmos = true;
+    motion_vectors.enabled = true;
+    motion_vectors.motion_blur = true;
+
+    let camera_transform = Transform::from_xyz(-4.0, 3.0, 7.0).looking_at(Vec3::new(0.0, 1.0, 0.0), Vec3::Y);
+    commands.spawn((
+        Camera3d::default(),
+        camera_transform,
+        OrbitCamera::new(Vec3::new(0.0, 1.0, 0.0), &camera_transform),
+        OrbitCameraConfig {
+            ground_limit: true,
+            ..default()
+        },
+    ));
+}
+
+fn circle_system(time: Res<Time>, mut movers: Query<&mut Transform, With<Mover>>) {
+    let (sin, cos) = time.elapsed_secs().sin_cos();
+    for mut transform in &mut movers {
+        transform.translation = Vec3::new(cos * 3.0, 2.0, sin * 3.0);
+    }
+}
+
+fn noise_baked_system(mut baked: EventReader<NoiseBaked>) {
+    for event in baked.read() {
+        info!("New cloud noise: {:?}", event.image.id());
+    }
+}
+
+fn precipitation_system(map: Res<PrecipitationMap>, stats: Res<BakeStats>, mut cells: EventReader<PrecipitationCell>) {
+    for cell in cells.read() {
+        debug!("Rain at {} ({:.2}) of {} cells", cell.position, cell.intensity, map.intensity.len());
+    }
+    let _half_covered = stats.threshold_for_coverage(0.5);
+}
+
+/// The bake tier of the prelude.
+#[cfg(feature = "bake")]
+#[allow(dead_code)]
+fn bake_api(settings: &CloudSettings, budget: &BakeBudget) -> BakeStats {
+    let (_data, stats) = bake_noise(settings);
+    let _per_frame = budget.per_frame;
+    stats
+}
+
+#[derive(Resource)]
+struct FramesLeft(u32);
+
+fn exit_after_frames_system(mut frames: ResMut<FramesLeft>, mut exit: EventWriter<AppExit>) {
+    frames.0 = frames.0.saturating_sub(1);
+    if frames.0 == 0 {
+        exit.send(AppExit::Success);
+    }
+}
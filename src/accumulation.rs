@@ -0,0 +1,404 @@
+use bevy::{
+    asset::load_internal_asset,
+    core_pipeline::{
+        core_3d::graph::{Core3d, Node3d},
+        fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    },
+    ecs::{entity::EntityHashMap, query::QueryItem},
+    log::warn_once,
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        extract_component::{
+            ComponentUniforms, DynamicUniformIndex, ExtractComponent, ExtractComponentPlugin, UniformComponentPlugin,
+        },
+        render_graph::{NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner},
+        render_resource::{
+            binding_types::{texture_2d, uniform_buffer},
+            BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, CachedRenderPipelineId, ColorTargetState,
+            ColorWrites, Extent3d, FragmentState, Operations, PipelineCache, RenderPassColorAttachment,
+            RenderPassDescriptor, RenderPipelineDescriptor, ShaderStages, ShaderType, SpecializedRenderPipeline,
+            SpecializedRenderPipelines, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
+            TextureUsages, TextureView, TextureViewDescriptor,
+        },
+        renderer::{RenderContext, RenderDevice},
+        view::ViewTarget,
+        Render, RenderApp, RenderSet,
+    },
+};
+
+const ACCUMULATE_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(0xd29e_61b4_07ac_4f3d_95e8_3c1a_7b40_e2f6);
+
+/// Full precision, so a long average keeps adding small contributions.
+const HISTORY_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
+
+/// Averages the frames of cameras with a `CloudAccumulation` into a history
+/// texture per view, after the main pass and before bloom and tonemapping.
+///
+/// The running average weighs the new frame by `1 / (sample + 1)`, so every
+/// frame counts the same and the average keeps the brightness of a single
+/// frame. Nothing is reprojected: the owner starts over at sample 0 whenever
+/// the image would change. Each sample shifts the cloud rays within their
+/// pixel and rotates the step jitter, through the view's `CloudViewUniform`,
+/// so the average resolves the pixel footprint and the march between steps.
+///
+/// The pass reads and writes the view's main texture through
+/// `post_process_write`, which is the resolved, single-sample target after
+/// the main pass, so it works the same with MSAA on or off; geometry edges
+/// keep the MSAA they were drawn with. A view whose main texture is still
+/// multisampled is skipped with a warning rather than averaged wrongly.
+pub struct AccumulationPlugin;
+
+impl Plugin for AccumulationPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            ACCUMULATE_SHADER_HANDLE,
+            "../assets/shaders/cloud_accumulate.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugins((
+            ExtractComponentPlugin::<CloudAccumulation>::default(),
+            UniformComponentPlugin::<AccumulationUniform>::default(),
+        ));
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<SpecializedRenderPipelines<AccumulatePipeline>>()
+            .init_resource::<AccumulationHistories>()
+            .add_systems(Render, prepare_accumulation_system.in_set(RenderSet::PrepareResources))
+            .add_render_graph_node::<ViewNodeRunner<AccumulateNode>>(Core3d, AccumulateLabel)
+            .add_render_graph_edges(Core3d, (Node3d::EndMainPass, AccumulateLabel, Node3d::Bloom));
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<AccumulatePipeline>();
+    }
+}
+
+/// Averages this camera's frames. Only meaningful while the image holds
+/// still; set `sample` back to 0 the frame anything changes.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct CloudAccumulation {
+    /// Frames averaged before this one. 0 starts over from this frame.
+    pub sample: u32,
+    /// Shows the average without adding this frame, once it has enough.
+    /// `sample` stays at the count averaged.
+    pub hold: bool,
+}
+
+impl ExtractComponent for CloudAccumulation {
+    type QueryData = &'static Self;
+    type QueryFilter = ();
+    type Out = (AccumulationUniform, AccumulationSample);
+
+    fn extract_component(accumulation: QueryItem<'_, Self::QueryData>) -> Option<Self::Out> {
+        let weight = if accumulation.hold {
+            0.0
+        } else {
+            1.0 / (accumulation.sample as f32 + 1.0)
+        };
+        Some((
+            AccumulationUniform {
+                weight: Vec4::new(weight, 0.0, 0.0, 0.0),
+            },
+            AccumulationSample(accumulation.sample),
+        ))
+    }
+}
+
+/// `accumulation` in `cloud_accumulate.wgsl`.
+#[derive(Component, ShaderType, Clone, Copy, Debug)]
+pub struct AccumulationUniform {
+    pub weight: Vec4, // x: weight of this frame against the history
+}
+
+/// The extracted `CloudAccumulation::sample`. Public only because it is part
+/// of `CloudAccumulation`'s `ExtractComponent::Out`.
+#[doc(hidden)]
+#[derive(Component, Clone, Copy, Debug)]
+pub struct AccumulationSample(pub u32);
+
+impl AccumulationSample {
+    /// This sample's offsets: the rays' within the pixel in `xy`, in pixels,
+    /// and the step jitter's rotation in `z`, in steps. All zero for sample
+    /// 0, which draws the frame unjittered. From the R2 and golden-ratio
+    /// sequences, so any run of samples covers the pixel and the step evenly.
+    pub(crate) fn jitter(self) -> Vec3 {
+        const PLASTIC: f32 = 1.324_718;
+        const GOLDEN: f32 = 0.618_034;
+        let n = self.0 as f32;
+        let offset = |alpha: f32| (0.5 + alpha * n).fract() - 0.5;
+        Vec3::new(offset(1.0 / PLASTIC), offset(1.0 / (PLASTIC * PLASTIC)), offset(GOLDEN))
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct AccumulateLabel;
+
+#[derive(Resource)]
+struct AccumulatePipeline {
+    layout: BindGroupLayout,
+}
+
+impl FromWorld for AccumulatePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let texture = texture_2d(TextureSampleType::Float { filterable: false });
+        Self {
+            layout: render_device.create_bind_group_layout(
+                "cloud_accumulate_layout",
+                &BindGroupLayoutEntries::sequential(
+                    ShaderStages::FRAGMENT,
+                    (texture, texture, uniform_buffer::<AccumulationUniform>(true)),
+                ),
+            ),
+        }
+    }
+}
+
+impl SpecializedRenderPipeline for AccumulatePipeline {
+    /// Format of the view's main texture.
+    type Key = TextureFormat;
+
+    fn specialize(&self, format: Self::Key) -> RenderPipelineDescriptor {
+        let target = |format| {
+            Some(ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })
+        };
+        RenderPipelineDescriptor {
+            label: Some("cloud_accumulate_pipeline".into()),
+            layout: vec![self.layout.clone()],
+            push_constant_ranges: Vec::new(),
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: ACCUMULATE_SHADER_HANDLE,
+                shader_defs: Vec::new(),
+                entry_point: "fragment".into(),
+                targets: vec![target(format), target(HISTORY_FORMAT)],
+            }),
+            primitive: default(),
+            depth_stencil: None,
+            multisample: default(),
+            zero_initialize_workgroup_memory: false,
+        }
+    }
+}
+
+/// A view's pipeline and the history textures it reads and writes this frame.
+#[derive(Component)]
+struct AccumulationView {
+    pipeline: CachedRenderPipelineId,
+    read: TextureView,
+    write: TextureView,
+}
+
+/// Two history textures per accumulating view, read and written in turns.
+/// Kept here rather than in the `TextureCache`, which would hand views of the
+/// same size each other's history.
+#[derive(Resource, Default)]
+struct AccumulationHistories(EntityHashMap<History>);
+
+struct History {
+    size: UVec2,
+    textures: [TextureView; 2],
+}
+
+impl History {
+    fn new(render_device: &RenderDevice, size: UVec2) -> Self {
+        let texture = || {
+            render_device
+                .create_texture(&TextureDescriptor {
+                    label: Some("cloud_accumulation_history"),
+                    size: Extent3d {
+                        width: size.x,
+                        height: size.y,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: HISTORY_FORMAT,
+                    usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                })
+                .create_view(&TextureViewDescriptor::default())
+        };
+        Self {
+            size,
+            textures: [texture(), texture()],
+        }
+    }
+}
+
+type AccumulatingViews<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static ExtractedCamera,
+        &'static ViewTarget,
+        Option<&'static Msaa>,
+        &'static AccumulationSample,
+    ),
+>;
+
+fn prepare_accumulation_system(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline_cache: Res<PipelineCache>,
+    pipeline: Res<AccumulatePipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<AccumulatePipeline>>,
+    mut histories: ResMut<AccumulationHistories>,
+    views: AccumulatingViews,
+) {
+    // Views that stopped accumulating let go of their history.
+    histories.0.retain(|entity, _| views.contains(*entity));
+    for (entity, camera, target, msaa, sample) in &views {
+        // The pipeline is single-sampled, like the resolved main texture.
+        if msaa.is_some_and(|msaa| *msaa != Msaa::Off) && target.main_texture().sample_count() > 1 {
+            warn_once!("Skipping cloud accumulation: the view's main texture is multisampled");
+            histories.0.remove(&entity);
+            commands.entity(entity).remove::<AccumulationView>();
+            continue;
+        }
+        let Some(size) = camera.physical_target_size else {
+            continue;
+        };
+        let history = histories
+            .0
+            .entry(entity)
+            .or_insert_with(|| History::new(&render_device, size));
+        if history.size != size {
+            *history = History::new(&render_device, size);
+        }
+        let read = sample.0 as usize % 2;
+        commands.entity(entity).insert(AccumulationView {
+            pipeline: pipelines.specialize(&pipeline_cache, &pipeline, target.main_texture_format()),
+            read: history.textures[read].clone(),
+            write: history.textures[1 - read].clone(),
+        });
+    }
+}
+
+#[derive(Default)]
+struct AccumulateNode;
+
+impl ViewNode for AccumulateNode {
+    // The sample too, so a view whose camera stopped accumulating skips the
+    // pass even where the earlier frames' components linger.
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static AccumulationSample,
+        &'static AccumulationView,
+        &'static DynamicUniformIndex<AccumulationUniform>,
+    );
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (target, _, view, index): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let pipeline = world.resource::<AccumulatePipeline>();
+        let Some(render_pipeline) = world.resource::<PipelineCache>().get_render_pipeline(view.pipeline) else {
+            return Ok(());
+        };
+        let Some(uniforms) = world
+            .resource::<ComponentUniforms<AccumulationUniform>>()
+            .uniforms()
+            .binding()
+        else {
+            return Ok(());
+        };
+
+        let post_process = target.post_process_write();
+        let bind_group = render_context.render_device().create_bind_group(
+            "cloud_accumulate_bind_group",
+            &pipeline.layout,
+            &BindGroupEntries::sequential((post_process.source, &view.read, uniforms)),
+        );
+        let mut pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("cloud_accumulate_pass"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    view: post_process.destination,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                }),
+                Some(RenderPassColorAttachment {
+                    view: &view.write,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                }),
+            ],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_render_pipeline(render_pipeline);
+        pass.set_bind_group(0, &bind_group, &[index.index()]);
+        pass.draw(0..3, 0..1);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The running average must weigh every frame the same, so an
+    // accumulating view keeps the brightness of a single frame, and the first
+    // sample must draw the frame as it is.
+
+    fn weight(accumulation: CloudAccumulation) -> f32 {
+        let (uniform, _) = CloudAccumulation::extract_component(&accumulation).unwrap();
+        uniform.weight.x
+    }
+
+    #[test]
+    fn running_average_is_the_mean() {
+        let frames = [0.8, 0.2, 0.5, 0.9, 0.1, 0.3, 0.7, 0.4];
+        let mut history = f32::NAN;
+        for (sample, frame) in frames.iter().enumerate() {
+            let weight = weight(CloudAccumulation {
+                sample: sample as u32,
+                hold: false,
+            });
+            history = if weight >= 1.0 {
+                *frame
+            } else {
+                history + (frame - history) * weight
+            };
+        }
+        let mean = frames.iter().sum::<f32>() / frames.len() as f32;
+        assert!((history - mean).abs() < 1e-5, "{history} != {mean}");
+    }
+
+    #[test]
+    fn held_average_ignores_the_frame() {
+        let held = CloudAccumulation { sample: 64, hold: true };
+        assert_eq!(weight(held), 0.0);
+    }
+
+    #[test]
+    fn first_sample_is_unjittered() {
+        assert_eq!(AccumulationSample(0).jitter(), Vec3::ZERO);
+        for sample in 1..256 {
+            let jitter = AccumulationSample(sample).jitter();
+            assert!(
+                jitter.abs().max_element() <= 0.5,
+                "sample {sample} leaves its pixel: {jitter}"
+            );
+            assert_ne!(jitter, Vec3::ZERO, "sample {sample} repeats the first");
+        }
+    }
+}
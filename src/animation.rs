@@ -0,0 +1,109 @@
+use bevy::{
+    animation::{animated_field, AnimationTarget, AnimationTargetId},
+    prelude::*,
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{focus::CameraFocus, CloudUiSet, CloudVolume};
+
+/// Lets `AnimationClip`s drive `CloudVolume` fields through an
+/// `AnimationPlayer` on the volume. The material sync picks the animated
+/// values up like any other edit.
+///
+/// Curves target fields with `animated_field!`, for example
+/// `animated_field!(CloudVolume::density_scale)`; `density_scale`,
+/// `threshold_offset`, `noise_stretch`, `cloud_type` and `tint` all
+/// animate.
+pub struct CloudAnimationPlugin;
+
+impl Plugin for CloudAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<CloudVolume>()
+            .add_systems(Update, animation_ui_system.in_set(CloudUiSet));
+    }
+}
+
+/// Target id of the volume in clips built for it; every volume plays its own
+/// clip, so one id serves them all.
+pub fn volume_target_id() -> AnimationTargetId {
+    AnimationTargetId::from_name(&Name::new("cloud_volume"))
+}
+
+/// Ten seconds of the cloud swelling and warming up, then settling back.
+pub fn swell_clip() -> AnimationClip {
+    let target = volume_target_id();
+    let times = [0.0, 5.0, 10.0];
+    let mut clip = AnimationClip::default();
+    clip.add_curve_to_target(
+        target,
+        AnimatableCurve::new(
+            animated_field!(CloudVolume::density_scale),
+            AnimatableKeyframeCurve::new(times.into_iter().zip([1.0, 2.5, 1.0]))
+                .expect("keyframes are sorted and non-empty"),
+        ),
+    );
+    clip.add_curve_to_target(
+        target,
+        AnimatableCurve::new(
+            animated_field!(CloudVolume::tint),
+            AnimatableKeyframeCurve::new(times.into_iter().zip([
+                LinearRgba::WHITE,
+                LinearRgba::rgb(1.0, 0.55, 0.35),
+                LinearRgba::WHITE,
+            ]))
+            .expect("keyframes are sorted and non-empty"),
+        ),
+    );
+    clip
+}
+
+/// Plays `clip` on `volume`, adding the player and target components it needs.
+pub fn play_on_volume(
+    commands: &mut Commands,
+    volume: Entity,
+    clip: Handle<AnimationClip>,
+    graphs: &mut Assets<AnimationGraph>,
+) {
+    let (graph, node) = AnimationGraph::from_clip(clip);
+    let mut player = AnimationPlayer::default();
+    player.play(node);
+    commands.entity(volume).insert((
+        player,
+        AnimationGraphHandle(graphs.add(graph)),
+        AnimationTarget {
+            id: volume_target_id(),
+            player: volume,
+        },
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn animation_ui_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    focus: Res<CameraFocus>,
+    mut clips: ResMut<Assets<AnimationClip>>,
+    mut graphs: ResMut<Assets<AnimationGraph>>,
+    mut demo_clip: Local<Option<Handle<AnimationClip>>>,
+    volumes: Query<Entity, With<CloudVolume>>,
+    players: Query<&AnimationPlayer, With<CloudVolume>>,
+) {
+    egui::Window::new("Animation")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let selected = focus.selected.filter(|entity| volumes.contains(*entity));
+            let Some(volume) = selected.or_else(|| volumes.iter().next()) else {
+                ui.label("No volumes");
+                return;
+            };
+            ui.label(format!("Volume: {}", volume));
+            let playing = players.get(volume).is_ok_and(|player| !player.all_finished());
+            if ui
+                .add_enabled(!playing, egui::Button::new("Swell & Tint (10 s)"))
+                .clicked()
+            {
+                let clip = demo_clip.get_or_insert_with(|| clips.add(swell_clip())).clone();
+                play_on_volume(&mut commands, volume, clip, &mut graphs);
+            }
+        });
+}
@@ -0,0 +1,207 @@
+//! Periodic autosave of the whole session, for getting a tuning session back
+//! after a crash.
+//!
+//! Autosaves alternate between two session files in the cache directory, so
+//! one interrupted write never costs both. A clean exit writes a final
+//! autosave and then touches a marker; on startup, an autosave newer than the
+//! marker means the last run did not exit cleanly, and the user is offered to
+//! restore it.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    session::{read_session, LoadSession, SessionSource},
+    CloudUiSet,
+};
+
+const SLOTS: [&str; 2] = ["autosave-0.ron", "autosave-1.ron"];
+const CLEAN_EXIT_MARKER: &str = "clean-exit";
+
+pub struct AutosavePlugin;
+
+impl Plugin for AutosavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Autosave>()
+            .add_systems(Update, (autosave_ui_system.in_set(CloudUiSet), autosave_system).chain())
+            .add_systems(Last, clean_exit_system);
+    }
+}
+
+#[derive(Resource)]
+pub struct Autosave {
+    pub enabled: bool,
+    /// Seconds of real time between autosaves.
+    pub interval: f32,
+    /// `None` when the platform has no cache directory.
+    dir: Option<PathBuf>,
+    elapsed: f32,
+    next_slot: usize,
+    /// Autosave left behind by a run that did not exit cleanly, until the
+    /// user restores or discards it. Autosaving waits for the answer.
+    pub pending_restore: Option<PathBuf>,
+    pub last_saved: Option<String>,
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        let dir = directories::ProjectDirs::from("", "", "bevy-clouds").map(|dirs| dirs.cache_dir().join("autosave"));
+        let mut autosave = Self {
+            enabled: true,
+            interval: 60.0,
+            dir,
+            elapsed: 0.0,
+            next_slot: 0,
+            pending_restore: None,
+            last_saved: None,
+        };
+        autosave.find_previous();
+        autosave
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+impl Autosave {
+    /// Looks at what the last run left behind. New autosaves start in the
+    /// older slot, so the newest one survives until the next write after it.
+    fn find_previous(&mut self) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+        let mut slots: Vec<(SystemTime, usize)> = SLOTS
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, name)| Some((modified(&dir.join(name))?, slot)))
+            .collect();
+        slots.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        let Some(&(newest, newest_slot)) = slots.first() else {
+            return;
+        };
+        self.next_slot = (newest_slot + 1) % SLOTS.len();
+
+        let clean_exit = modified(&dir.join(CLEAN_EXIT_MARKER));
+        if clean_exit.is_some_and(|clean_exit| clean_exit >= newest) {
+            return;
+        }
+        // The newest slot may be the write the crash interrupted.
+        self.pending_restore = slots
+            .iter()
+            .map(|&(_, slot)| dir.join(SLOTS[slot]))
+            .find(|path| match read_session(path) {
+                Ok(_) => true,
+                Err(err) => {
+                    warn!("Ignoring unreadable autosave {}: {}", path.display(), err);
+                    false
+                }
+            });
+    }
+
+    fn save(&mut self, source: &SessionSource) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+        let path = dir.join(SLOTS[self.next_slot]);
+        match source.save(&path) {
+            Ok(()) => {
+                self.next_slot = (self.next_slot + 1) % SLOTS.len();
+                self.last_saved = Some(utc_clock_time());
+            }
+            Err(err) => warn!("Autosave to {} failed: {}", path.display(), err),
+        }
+    }
+}
+
+/// Wall clock time of day as `HH:MM:SS` UTC, enough to tell autosaves apart.
+fn utc_clock_time() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    format!("{:02}:{:02}:{:02} UTC", seconds / 3600 % 24, seconds / 60 % 60, seconds % 60)
+}
+
+fn autosave_system(time: Res<Time<Real>>, mut autosave: ResMut<Autosave>, source: SessionSource) {
+    if !autosave.enabled || autosave.pending_restore.is_some() {
+        return;
+    }
+    autosave.elapsed += time.delta_secs();
+    if autosave.elapsed < autosave.interval {
+        return;
+    }
+    autosave.elapsed = 0.0;
+    autosave.save(&source);
+}
+
+/// Runs on the frame the app exits. A final autosave, then the marker saying
+/// the autosaves are not left over from a crash.
+fn clean_exit_system(mut exit: EventReader<AppExit>, mut autosave: ResMut<Autosave>, source: SessionSource) {
+    if exit.read().last().is_none() {
+        return;
+    }
+    if autosave.enabled {
+        autosave.save(&source);
+    }
+    let Some(dir) = &autosave.dir else {
+        return;
+    };
+    let written = fs::create_dir_all(dir).and_then(|()| fs::write(dir.join(CLEAN_EXIT_MARKER), ""));
+    if let Err(err) = written {
+        warn!("Failed to mark clean exit in {}: {}", dir.display(), err);
+    }
+}
+
+fn autosave_ui_system(
+    mut contexts: EguiContexts,
+    mut autosave: ResMut<Autosave>,
+    mut load: EventWriter<LoadSession>,
+) {
+    let ctx = contexts.ctx_mut();
+    if let Some(path) = autosave.pending_restore.clone() {
+        egui::Window::new("Restore previous session?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("The last run did not exit cleanly. Restore its most recent autosave?");
+                if let Some(modified) = modified(&path).and_then(|modified| modified.elapsed().ok()) {
+                    ui.weak(format!("Saved {} min ago", modified.as_secs() / 60));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Restore").clicked() {
+                        load.send(LoadSession(path.clone()));
+                        autosave.pending_restore = None;
+                    }
+                    if ui.button("Discard").clicked() {
+                        autosave.pending_restore = None;
+                    }
+                });
+            });
+    }
+
+    egui::Window::new("Autosave").default_open(false).show(ctx, |ui| {
+        if autosave.dir.is_none() {
+            ui.label("No cache directory on this platform; autosave is unavailable.");
+            return;
+        }
+        ui.checkbox(&mut autosave.enabled, "Enabled");
+        ui.add_enabled(
+            autosave.enabled,
+            egui::Slider::new(&mut autosave.interval, 10.0..=600.0)
+                .logarithmic(true)
+                .text("Interval")
+                .suffix(" s"),
+        );
+        match &autosave.last_saved {
+            Some(time) => ui.label(format!("Last autosave at {}", time)),
+            None => ui.label("Not autosaved yet"),
+        };
+    });
+}
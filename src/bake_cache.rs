@@ -0,0 +1,183 @@
+//! On-disk cache of baked noise volumes, keyed by a hash of every parameter
+//! that affects the bake.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    noise::{self, BakeStats},
+    CloudSettings,
+};
+
+/// Bump whenever the bake output changes for the same parameters, so stale
+/// entries stop matching.
+const BAKE_FORMAT_VERSION: u32 = 3;
+const MAGIC: [u8; 4] = *b"BCNC";
+const HEADER_LENGTH: usize = 4 + 4 * 4 + 4 * 2 + 8;
+
+#[derive(Resource)]
+pub struct BakeCache {
+    pub enabled: bool,
+    /// `None` when the platform has no cache directory.
+    dir: Option<PathBuf>,
+    /// Whether the current global noise came from the cache.
+    pub last_hit: bool,
+}
+
+impl Default for BakeCache {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: directories::ProjectDirs::from("", "", "bevy-clouds").map(|dirs| dirs.cache_dir().join("noise")),
+            last_hit: false,
+        }
+    }
+}
+
+impl BakeCache {
+    fn path(&self, settings: &CloudSettings) -> Option<PathBuf> {
+        let dir = self.dir.as_ref().filter(|_| self.enabled)?;
+        Some(dir.join(format!("{:016x}.bin", cache_key(settings))))
+    }
+
+    /// Cached bake for `settings`. Unreadable, truncated or corrupted entries
+    /// are deleted and reported as misses.
+    pub fn load(&self, settings: &CloudSettings) -> Option<(Vec<u8>, BakeStats)> {
+        let path = self.path(settings)?;
+        let bytes = fs::read(&path).ok()?;
+        match decode(&bytes, noise::clamp_resolution(settings.resolution)) {
+            Some(entry) => Some(entry),
+            None => {
+                warn!("Discarding corrupted noise cache entry {}", path.display());
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    pub fn store(&self, settings: &CloudSettings, data: &[u8], stats: &BakeStats) {
+        let Some(path) = self.path(settings) else {
+            return;
+        };
+        let size = noise::clamp_resolution(settings.resolution);
+        if let Err(err) = write_entry(&path, &encode(data, size, stats)) {
+            warn!("Failed to write noise cache entry {}: {}", path.display(), err);
+        }
+    }
+
+    /// Deletes every cached bake; returns how many were removed.
+    #[cfg(feature = "ui")]
+    pub fn clear(&self) -> usize {
+        let Some(entries) = self.dir.as_ref().and_then(|dir| fs::read_dir(dir).ok()) else {
+            return 0;
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "bin"))
+            .filter(|entry| fs::remove_file(entry.path()).is_ok())
+            .count()
+    }
+}
+
+/// Hash of everything that changes the baked bytes, including the format
+/// version. FNV-1a, so keys are stable across runs and toolchains.
+fn cache_key(settings: &CloudSettings) -> u64 {
+    let size = noise::clamp_resolution(settings.resolution);
+    let mut hash = Fnv::default();
+    for word in [
+        BAKE_FORMAT_VERSION,
+        settings.seed,
+        settings.frequency.to_bits(),
+        settings.cell_count,
+        settings.octaves,
+        size.x,
+        size.y,
+        size.z,
+        settings.noise_type as u32,
+        settings.fractal_type as u32,
+        settings.warp_amplitude.to_bits(),
+        settings.warp_frequency,
+        settings.normalize as u32,
+    ] {
+        hash.write(&word.to_le_bytes());
+    }
+    // The stored texel format: noise and ambient visibility.
+    hash.write(b"rg8unorm");
+    hash.0
+}
+
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// FNV-1a of `data`; stable across runs, so usable in file names.
+pub(crate) fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash = Fnv::default();
+    hash.write(data);
+    hash.0
+}
+
+/// Magic, version, size, raw range and data checksum, then the RG8 texels.
+fn encode(data: &[u8], size: UVec3, stats: &BakeStats) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LENGTH + data.len());
+    out.extend_from_slice(&MAGIC);
+    for word in [BAKE_FORMAT_VERSION, size.x, size.y, size.z] {
+        out.extend_from_slice(&word.to_le_bytes());
+    }
+    out.extend_from_slice(&stats.min.to_le_bytes());
+    out.extend_from_slice(&stats.max.to_le_bytes());
+    out.extend_from_slice(&fnv1a(data).to_le_bytes());
+    out.extend_from_slice(data);
+    out
+}
+
+fn decode(bytes: &[u8], expected_size: UVec3) -> Option<(Vec<u8>, BakeStats)> {
+    let (header, data) = bytes.split_at_checked(HEADER_LENGTH)?;
+    let word = |index: usize| u32::from_le_bytes(header[4 + index * 4..8 + index * 4].try_into().unwrap());
+    if header[..4] != MAGIC || word(0) != BAKE_FORMAT_VERSION {
+        return None;
+    }
+    let size = UVec3::new(word(1), word(2), word(3));
+    if size != expected_size || data.len() != (size.x * size.y * size.z) as usize * noise::NOISE_CHANNELS {
+        return None;
+    }
+    let stored_checksum = u64::from_le_bytes(header[28..36].try_into().unwrap());
+    if fnv1a(data) != stored_checksum {
+        return None;
+    }
+
+    let mut stats = BakeStats {
+        min: f32::from_bits(word(4)),
+        max: f32::from_bits(word(5)),
+        ..default()
+    };
+    stats.count_texels(data.iter().step_by(noise::NOISE_CHANNELS).copied());
+    Some((data.to_vec(), stats))
+}
+
+/// Writes through a temporary file so an interrupted write can't leave a
+/// truncated entry under the real name.
+fn write_entry(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, bytes)?;
+    fs::rename(&temp, path)
+}
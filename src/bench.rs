@@ -0,0 +1,600 @@
+use std::{
+    f32::consts::TAU,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    diagnostic::{DiagnosticPath, DiagnosticsStore},
+    prelude::*,
+    render::{
+        camera::RenderTarget, diagnostic::RenderDiagnosticsPlugin, renderer::RenderAdapterInfo,
+    },
+    window::{PresentMode, WindowResolution},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bake_cache::BakeCache,
+    deterministic::DeterministicTime,
+    golden,
+    noise::{BakeProgress, BakeStats, NoiseBaked, PendingNoise},
+    presets::PRESETS,
+    spawn::{CloudVolumeParams, SpawnCloudExt},
+    CloudSettings, CloudVolume, QualityPreset,
+};
+
+/// Bumped whenever a field of `BenchReport` changes meaning or goes away.
+/// New fields are only ever added.
+const SCHEMA: u32 = 1;
+/// Measured frames per case unless `--bench-frames` says otherwise.
+const DEFAULT_FRAMES: u32 = 120;
+/// Frames rendered after the bake and before measuring, so pipeline
+/// compilation and uploads stay out of the numbers.
+const WARM_UP_FRAMES: u32 = 30;
+/// Regression threshold for `--bench-compare` unless `--bench-threshold`
+/// says otherwise, in percent.
+const DEFAULT_THRESHOLD: f64 = 5.0;
+/// Metrics faster than this in the old report are not compared; their
+/// relative changes are timer noise. Milliseconds.
+const COMPARE_FLOOR_MS: f64 = 0.01;
+const DEFAULT_REPORT: &str = "bench.json";
+/// Render size of every case.
+const RESOLUTION: UVec2 = UVec2::new(1280, 720);
+/// GPU time of the whole transparent pass, from `RenderDiagnosticsPlugin`.
+/// The clouds are the only transparent draws in the bench scene, but the
+/// pass's own setup is included. Present only where the adapter supports
+/// timestamp queries.
+const TRANSPARENT_PASS_DIAGNOSTIC: DiagnosticPath =
+    DiagnosticPath::const_new("render/main_transparent_pass_3d/elapsed_gpu");
+/// Orbit of the camera around the volume during a case.
+const ORBIT_RADIUS: f32 = 6.7;
+const ORBIT_HEIGHT: f32 = 3.0;
+
+/// Benchmark run: `--bench [report.json] [--bench-frames N]
+/// [--bench-windowed] [--bench-compare old.json [--bench-threshold percent]]`.
+///
+/// Renders every preset at every quality tier on deterministic time, with
+/// the camera orbiting the volume once per case, and writes a JSON report of
+/// CPU frame times, GPU time of the transparent pass and bake durations, with the
+/// adapter it ran on. Each preset rebakes its noise, uncached. Headless by
+/// default; `--bench-windowed` renders into a window without vsync instead.
+///
+/// With `--bench-compare` the new report is checked against an older one and
+/// every metric slower by more than the threshold is printed; the run then
+/// fails, so CI can gate on it.
+pub fn run(args: &[String]) -> AppExit {
+    let options = match BenchOptions::from_args(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            return AppExit::error();
+        }
+    };
+    let window = options.windowed.then(|| Window {
+        title: "bevy-clouds bench".into(),
+        resolution: WindowResolution::new(RESOLUTION.x as f32, RESOLUTION.y as f32).with_scale_factor_override(1.0),
+        present_mode: PresentMode::AutoNoVsync,
+        resizable: false,
+        ..default()
+    });
+    // No frame cap: the frame time is the work.
+    let mut app = golden::cloud_app(window, Duration::ZERO);
+    app.world_mut().resource_mut::<BakeCache>().enabled = false;
+    app.add_plugins(RenderDiagnosticsPlugin)
+        .insert_resource(BenchRun { options, ..default() })
+        .add_systems(Startup, bench_setup)
+        .add_systems(
+            Update,
+            (
+                bench_system,
+                crate::wind_system,
+                crate::swap_noise_system,
+                crate::update_material_system,
+            )
+                .chain(),
+        );
+    app.run()
+}
+
+#[derive(Clone, Debug)]
+struct BenchOptions {
+    report: PathBuf,
+    frames: u32,
+    windowed: bool,
+    compare: Option<PathBuf>,
+    /// Percent.
+    threshold: f64,
+}
+
+impl BenchOptions {
+    fn from_args(args: &[String]) -> Result<Self, String> {
+        let value = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|index| args.get(index + 1))
+                .filter(|value| !value.starts_with("--"))
+        };
+        let frames = match value("--bench-frames") {
+            Some(frames) => frames
+                .parse::<u32>()
+                .ok()
+                .filter(|&frames| frames > 0)
+                .ok_or_else(|| format!("--bench-frames: expected a positive frame count, got {}", frames))?,
+            None => DEFAULT_FRAMES,
+        };
+        let threshold = match value("--bench-threshold") {
+            Some(threshold) => threshold
+                .parse::<f64>()
+                .ok()
+                .filter(|threshold| threshold.is_finite() && *threshold >= 0.0)
+                .ok_or_else(|| format!("--bench-threshold: expected a percentage, got {}", threshold))?,
+            None => DEFAULT_THRESHOLD,
+        };
+        if args.iter().any(|arg| arg == "--bench-compare") && value("--bench-compare").is_none() {
+            return Err("--bench-compare: expected the path of an earlier report".into());
+        }
+        Ok(Self {
+            report: PathBuf::from(value("--bench").map_or(DEFAULT_REPORT, String::as_str)),
+            frames,
+            windowed: args.iter().any(|arg| arg == "--bench-windowed"),
+            compare: value("--bench-compare").map(PathBuf::from),
+            threshold,
+        })
+    }
+}
+
+/// The JSON report. Field names are the schema; see `SCHEMA`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BenchReport {
+    pub schema: u32,
+    pub version: String,
+    pub hardware: Hardware,
+    pub config: BenchConfig,
+    pub cases: Vec<BenchCase>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Hardware {
+    pub os: String,
+    pub arch: String,
+    pub cpu_threads: usize,
+    pub adapter: Option<Adapter>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Adapter {
+    pub name: String,
+    pub vendor: u32,
+    pub device: u32,
+    pub device_type: String,
+    pub driver: String,
+    pub driver_info: String,
+    pub backend: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BenchConfig {
+    pub frames: u32,
+    pub warm_up_frames: u32,
+    pub frame_rate: f32,
+    pub resolution: [u32; 2],
+    pub windowed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BenchCase {
+    /// `<preset>/<quality>`; cases are matched by name when comparing.
+    pub name: String,
+    pub preset: String,
+    pub quality: String,
+    pub steps: u32,
+    /// CPU time of the noise bake, on the first tier of each preset only.
+    pub bake_ms: Option<f64>,
+    /// Wall time between frames.
+    pub cpu_frame_ms: Percentiles,
+    /// The whole transparent pass, not only the cloud draw; `None` without
+    /// timestamp query support.
+    pub gpu_transparent_pass_ms: Option<Percentiles>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Percentiles {
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+    pub samples: usize,
+}
+
+impl Percentiles {
+    fn of(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        // Nearest rank.
+        let rank = |p: f64| sorted[((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1];
+        Some(Self {
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            p50: rank(0.5),
+            p90: rank(0.9),
+            p99: rank(0.99),
+            max: sorted[sorted.len() - 1],
+            samples: sorted.len(),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Phase {
+    #[default]
+    Setup,
+    Baking,
+    WarmUp,
+    Measure,
+}
+
+#[derive(Resource, Default)]
+struct BenchRun {
+    options: BenchOptions,
+    /// Index into presets × tiers.
+    index: usize,
+    phase: Phase,
+    frame: u32,
+    bake_ms: Option<f64>,
+    cpu: Vec<f64>,
+    gpu: Vec<f64>,
+    cases: Vec<BenchCase>,
+    camera: Option<Entity>,
+    /// Start of the frame being measured. `Time<Real>` advances by the
+    /// deterministic step, so frame times come from the wall clock.
+    frame_start: Option<Instant>,
+    /// The report is written; waiting for the exit.
+    done: bool,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            report: PathBuf::from(DEFAULT_REPORT),
+            frames: DEFAULT_FRAMES,
+            windowed: false,
+            compare: None,
+            threshold: DEFAULT_THRESHOLD,
+        }
+    }
+}
+
+fn bench_setup(mut commands: Commands, mut images: ResMut<Assets<Image>>, mut run: ResMut<BenchRun>) {
+    commands.spawn((
+        DirectionalLight {
+            shadows_enabled: true,
+            ..default()
+        },
+        Transform::from_xyz(4.0, 8.0, 4.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+    commands.spawn_cloud(CloudVolumeParams::default().at(Vec3::new(0.0, 1.0, 0.0)));
+    let target = if run.options.windowed {
+        RenderTarget::default()
+    } else {
+        RenderTarget::Image(images.add(target_image()))
+    };
+    let camera = commands
+        .spawn((
+            Camera3d::default(),
+            Camera {
+                target,
+                clear_color: ClearColorConfig::Custom(Color::srgb(0.35, 0.5, 0.75)),
+                ..default()
+            },
+            orbit(0.0),
+        ))
+        .id();
+    run.camera = Some(camera);
+}
+
+/// A `RESOLUTION` render target; never read back.
+fn target_image() -> Image {
+    let mut image = golden::target_image();
+    image.resize(bevy::render::render_resource::Extent3d {
+        width: RESOLUTION.x,
+        height: RESOLUTION.y,
+        depth_or_array_layers: 1,
+    });
+    image
+}
+
+/// Camera at `turn` (0..1) of its orbit, looking at the volume.
+fn orbit(turn: f32) -> Transform {
+    let (sin, cos) = (turn * TAU).sin_cos();
+    Transform::from_xyz(ORBIT_RADIUS * sin, ORBIT_HEIGHT, ORBIT_RADIUS * cos).looking_at(Vec3::new(0.0, 1.0, 0.0), Vec3::Y)
+}
+
+/// The case at `index`: presets outer, tiers inner, so each preset bakes once.
+fn case(index: usize) -> Option<(usize, QualityPreset)> {
+    let tiers = QualityPreset::ALL.len();
+    (index < PRESETS.len() * tiers).then(|| (index / tiers, QualityPreset::ALL[index % tiers]))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn bench_system(
+    mut run: ResMut<BenchRun>,
+    mut settings: ResMut<CloudSettings>,
+    bake_progress: Res<BakeProgress>,
+    pending_noise: Res<PendingNoise>,
+    bake_stats: Res<BakeStats>,
+    mut baked: EventReader<NoiseBaked>,
+    mut deterministic: ResMut<DeterministicTime>,
+    diagnostics: Res<DiagnosticsStore>,
+    adapter: Option<Res<RenderAdapterInfo>>,
+    mut volumes: Query<&mut CloudVolume>,
+    mut transforms: Query<&mut Transform, With<Camera3d>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let baked = baked.read().count() > 0;
+    if run.done {
+        return;
+    }
+    let Some((preset_index, quality)) = case(run.index) else {
+        run.done = true;
+        exit.send(finish(&run, adapter.as_deref(), deterministic.frame_rate));
+        return;
+    };
+    let preset = &PRESETS[preset_index];
+    let frames = run.options.frames;
+
+    match run.phase {
+        Phase::Setup => {
+            preset.apply(&mut settings, volumes.iter_mut());
+            quality.apply(&mut settings);
+            if quality == QualityPreset::ALL[0] {
+                // Every preset reports its own bake, even one matching the last.
+                settings.needs_rebuild = true;
+            }
+            if let Some(mut transform) = run.camera.and_then(|camera| transforms.get_mut(camera).ok()) {
+                *transform = orbit(0.0);
+            }
+            deterministic.start();
+            run.bake_ms = None;
+            run.cpu.clear();
+            run.gpu.clear();
+            run.phase = Phase::Baking;
+        }
+        Phase::Baking => {
+            if baked {
+                run.bake_ms = Some(bake_stats.duration.as_secs_f64() * 1000.0);
+            }
+            if !(settings.needs_rebuild || bake_progress.bake.is_some() || pending_noise.image.is_some()) {
+                run.frame = 0;
+                run.phase = Phase::WarmUp;
+            }
+        }
+        Phase::WarmUp => {
+            run.frame += 1;
+            if run.frame >= WARM_UP_FRAMES {
+                run.frame = 0;
+                // The sequence restarts from the same simulated time every case.
+                deterministic.start();
+                run.phase = Phase::Measure;
+            }
+        }
+        Phase::Measure => {
+            // Each sample is the frame that just finished.
+            let now = Instant::now();
+            if let Some(start) = run.frame_start.replace(now).filter(|_| run.frame > 0) {
+                run.cpu.push((now - start).as_secs_f64() * 1000.0);
+                if let Some(measurement) = diagnostics.get_measurement(&TRANSPARENT_PASS_DIAGNOSTIC) {
+                    run.gpu.push(measurement.value);
+                }
+            }
+            if run.frame >= frames {
+                let cpu_frame_ms = Percentiles::of(&run.cpu).expect("at least one measured frame");
+                let case = BenchCase {
+                    name: format!("{}/{}", preset.name, quality_name(quality)),
+                    preset: preset.name.to_string(),
+                    quality: quality_name(quality),
+                    steps: settings.steps,
+                    bake_ms: run.bake_ms,
+                    cpu_frame_ms,
+                    gpu_transparent_pass_ms: Percentiles::of(&run.gpu),
+                };
+                println!(
+                    "{}: {:.2} ms CPU p50{}",
+                    case.name,
+                    case.cpu_frame_ms.p50,
+                    case.gpu_transparent_pass_ms
+                        .map(|gpu| format!(", {:.3} ms transparent pass p50", gpu.p50))
+                        .unwrap_or_default()
+                );
+                run.cases.push(case);
+                run.frame_start = None;
+                run.index += 1;
+                run.phase = Phase::Setup;
+                return;
+            }
+            if let Some(mut transform) = run.camera.and_then(|camera| transforms.get_mut(camera).ok()) {
+                *transform = orbit(run.frame as f32 / frames as f32);
+            }
+            run.frame += 1;
+        }
+    }
+}
+
+/// Stable, untranslated name of a tier for the report.
+fn quality_name(quality: QualityPreset) -> String {
+    format!("{:?}", quality).to_lowercase()
+}
+
+/// Writes the report, compares it if asked, and returns the exit status.
+fn finish(run: &BenchRun, adapter: Option<&RenderAdapterInfo>, frame_rate: f32) -> AppExit {
+    let options = &run.options;
+    let report = BenchReport {
+        schema: SCHEMA,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        hardware: Hardware {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cpu_threads: std::thread::available_parallelism().map_or(1, usize::from),
+            adapter: adapter.map(|info| Adapter {
+                name: info.name.clone(),
+                vendor: info.vendor,
+                device: info.device,
+                device_type: format!("{:?}", info.device_type),
+                driver: info.driver.clone(),
+                driver_info: info.driver_info.clone(),
+                backend: format!("{:?}", info.backend),
+            }),
+        },
+        config: BenchConfig {
+            frames: options.frames,
+            warm_up_frames: WARM_UP_FRAMES,
+            frame_rate,
+            resolution: RESOLUTION.to_array(),
+            windowed: options.windowed,
+        },
+        cases: run.cases.clone(),
+    };
+    if let Err(err) = write_report(&options.report, &report) {
+        eprintln!("{}", err);
+        return AppExit::error();
+    }
+    println!("Wrote {} cases to {}", report.cases.len(), options.report.display());
+
+    let Some(old) = &options.compare else {
+        return AppExit::Success;
+    };
+    let old = match read_report(old) {
+        Ok(old) => old,
+        Err(err) => {
+            eprintln!("{}", err);
+            return AppExit::error();
+        }
+    };
+    let regressions = compare(&old, &report, options.threshold);
+    for regression in &regressions {
+        println!("{}", regression);
+    }
+    if regressions.is_empty() {
+        println!("No regressions over {}%", options.threshold);
+        AppExit::Success
+    } else {
+        println!("{} regressions over {}%", regressions.len(), options.threshold);
+        AppExit::error()
+    }
+}
+
+fn write_report(path: &Path, report: &BenchReport) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(report).map_err(|err| err.to_string())?;
+    std::fs::write(path, json + "\n").map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+fn read_report(path: &Path) -> Result<BenchReport, String> {
+    let json = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let report: BenchReport = serde_json::from_str(&json).map_err(|err| format!("{}: {}", path.display(), err))?;
+    if report.schema != SCHEMA {
+        return Err(format!("{}: schema {}, expected {}", path.display(), report.schema, SCHEMA));
+    }
+    Ok(report)
+}
+
+/// Metrics of `new` slower than in `old` by more than `threshold` percent,
+/// one line each. Cases only in one report and metrics under
+/// `COMPARE_FLOOR_MS` are skipped.
+pub(crate) fn compare(old: &BenchReport, new: &BenchReport, threshold: f64) -> Vec<String> {
+    if old.hardware.adapter.as_ref().map(|a| &a.name) != new.hardware.adapter.as_ref().map(|a| &a.name) {
+        println!("Note: the reports come from different adapters");
+    }
+    let mut regressions = Vec::new();
+    for case in &new.cases {
+        let Some(before) = old.cases.iter().find(|before| before.name == case.name) else {
+            continue;
+        };
+        let gpu = |case: &BenchCase, p: fn(&Percentiles) -> f64| case.gpu_transparent_pass_ms.as_ref().map(p);
+        let metrics = [
+            ("cpu_frame_ms.p50", Some(before.cpu_frame_ms.p50), Some(case.cpu_frame_ms.p50)),
+            ("cpu_frame_ms.p90", Some(before.cpu_frame_ms.p90), Some(case.cpu_frame_ms.p90)),
+            ("gpu_transparent_pass_ms.p50", gpu(before, |gpu| gpu.p50), gpu(case, |gpu| gpu.p50)),
+            ("gpu_transparent_pass_ms.p90", gpu(before, |gpu| gpu.p90), gpu(case, |gpu| gpu.p90)),
+            ("bake_ms", before.bake_ms, case.bake_ms),
+        ];
+        for (metric, before, after) in metrics {
+            let (Some(before), Some(after)) = (before, after) else {
+                continue;
+            };
+            if before < COMPARE_FLOOR_MS {
+                continue;
+            }
+            let change = (after / before - 1.0) * 100.0;
+            if change > threshold {
+                regressions.push(format!(
+                    "REGRESSION {} {}: {:.3} -> {:.3} (+{:.1}%)",
+                    case.name, metric, before, after, change
+                ));
+            }
+        }
+    }
+    regressions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(ms: f64) -> Percentiles {
+        Percentiles {
+            mean: ms,
+            p50: ms,
+            p90: ms,
+            p99: ms,
+            max: ms,
+            samples: 1,
+        }
+    }
+
+    fn report(cpu_ms: f64, gpu_ms: Option<f64>) -> BenchReport {
+        BenchReport {
+            schema: 1,
+            version: String::new(),
+            hardware: Hardware {
+                os: String::new(),
+                arch: String::new(),
+                cpu_threads: 1,
+                adapter: None,
+            },
+            config: BenchConfig {
+                frames: 1,
+                warm_up_frames: 0,
+                frame_rate: 30.0,
+                resolution: [1, 1],
+                windowed: false,
+            },
+            cases: vec![BenchCase {
+                name: "Cumulus/low".into(),
+                preset: "Cumulus".into(),
+                quality: "low".into(),
+                steps: 8,
+                bake_ms: None,
+                cpu_frame_ms: timing(cpu_ms),
+                gpu_transparent_pass_ms: gpu_ms.map(timing),
+            }],
+        }
+    }
+
+    #[test]
+    fn slower_frames_are_regressions() {
+        let regressions = compare(&report(10.0, Some(2.0)), &report(11.0, Some(2.5)), 5.0);
+        assert_eq!(regressions.len(), 4, "{:?}", regressions);
+        assert!(compare(&report(10.0, Some(2.0)), &report(10.4, Some(1.0)), 5.0).is_empty());
+    }
+
+    /// Timestamps that barely register, as on software rasterizers, change by
+    /// hundreds of percent between runs.
+    #[test]
+    fn times_under_the_floor_are_not_compared() {
+        assert!(compare(&report(10.0, Some(0.0001)), &report(10.0, Some(0.0004)), 5.0).is_empty());
+        assert!(compare(&report(10.0, None), &report(10.0, Some(5.0)), 5.0).is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+use bevy::{
+    ecs::system::IntoObserverSystem,
+    prelude::*,
+    render::gpu_readback::{Readback, ReadbackComplete},
+};
+
+/// A render target read back once, with its rows stripped of the copy
+/// padding. Triggered on the entity `read_back` returned.
+#[derive(Event)]
+pub(crate) struct Captured(pub Vec<u8>);
+
+/// Row layout of a readback in flight.
+#[derive(Component)]
+struct CaptureRows {
+    row_bytes: usize,
+    rows: usize,
+}
+
+/// Reads `target`, `size` texels of `texel_bytes` each, back from the GPU
+/// once, and runs `observer` with the pixels. The offscreen captures share
+/// this so that the readback is despawned and unpadded in one place.
+pub(crate) fn read_back<B: Bundle, M>(
+    commands: &mut Commands,
+    target: Handle<Image>,
+    size: UVec2,
+    texel_bytes: usize,
+    observer: impl IntoObserverSystem<Captured, B, M>,
+) -> Entity {
+    commands
+        .spawn((
+            Readback::texture(target),
+            CaptureRows {
+                row_bytes: size.x as usize * texel_bytes,
+                rows: size.y as usize,
+            },
+        ))
+        .observe(finish_readback)
+        .observe(observer)
+        .id()
+}
+
+fn finish_readback(trigger: Trigger<ReadbackComplete>, mut commands: Commands, captures: Query<&CaptureRows>) {
+    let entity = trigger.entity();
+    if let Ok(capture) = captures.get(entity) {
+        let data = unpad_rows(&trigger.event().0, capture.row_bytes, capture.rows);
+        commands.trigger_targets(Captured(data), entity);
+    }
+    // `Readback` repeats every frame until despawned. Queued after the
+    // trigger, so the entity's observers still see `Captured`.
+    commands.entity(entity).despawn();
+}
+
+/// The rows of a texture readback, dropping the padding that aligns each row
+/// to 256 bytes.
+fn unpad_rows(data: &[u8], row_bytes: usize, rows: usize) -> Vec<u8> {
+    let padded_row_bytes = row_bytes.next_multiple_of(256);
+    let stride = if data.len() >= padded_row_bytes * rows { padded_row_bytes } else { row_bytes };
+    data.chunks(stride)
+        .take(rows)
+        .flat_map(|row| &row[..row_bytes.min(row.len())])
+        .copied()
+        .collect()
+}
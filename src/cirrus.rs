@@ -0,0 +1,334 @@
+use bevy::{
+    asset::load_internal_asset,
+    pbr::{MaterialPipeline, MaterialPipelineKey, NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::{
+        mesh::{MeshVertexBufferLayoutRef, VertexAttributeValues},
+        render_resource::{AsBindGroup, RenderPipelineDescriptor, ShaderRef, ShaderType, SpecializedMeshPipelineError},
+    },
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{cloud_sun::CloudLights, noise, CloudSettings, CloudUiSet, OrbitCamera};
+
+const CIRRUS_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(0xa43d_1e87_5c02_4f69_b8d1_7e3a_0c95_62f4);
+/// Texels per side of the tileable sheet noise.
+const SHEET_NOISE_SIZE: u32 = 256;
+/// Quads per side of the sheet mesh; enough for the bend to look smooth.
+const SHEET_SUBDIVISIONS: u32 = 48;
+
+/// Thin high-altitude cloud sheets drawn without raymarching.
+///
+/// A `CirrusLayer` is a large bent sheet with stretched 2D noise, lit by the
+/// clouds' sun (`cloud_sun::CloudLights`) and ambient top color and scrolled
+/// by their wind, so it follows the time of day and weather alongside the
+/// volumes. It costs one texture-sampled fragment per pixel.
+pub struct CirrusPlugin;
+
+impl Plugin for CirrusPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(app, CIRRUS_SHADER_HANDLE, "../assets/shaders/cirrus.wgsl", Shader::from_wgsl);
+        app.add_plugins(MaterialPlugin::<CirrusMaterial>::default())
+            .init_resource::<CirrusNoise>()
+            .add_systems(
+                Update,
+                (
+                    cirrus_ui_system.in_set(CloudUiSet),
+                    attach_sheet_system,
+                    sync_cirrus_system.after(crate::update_material_system),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// A cirrus sheet. Spawn it on its own; the mesh and material are attached
+/// the next frame.
+#[derive(Component, Clone, Debug, PartialEq)]
+#[require(Transform, Visibility)]
+pub struct CirrusLayer {
+    /// Height of the sheet's center.
+    pub altitude: f32,
+    /// Horizontal radius; the sheet fades out towards its rim.
+    pub radius: f32,
+    /// Radius of the sphere the sheet is bent on, so it drops towards the
+    /// horizon instead of ending in a visible edge.
+    pub curvature: f32,
+    /// Keep the sheet centered above the camera.
+    pub follow_camera: bool,
+    /// World size of one noise tile across the streaks.
+    pub scale: f32,
+    /// How many times longer the streaks are than wide.
+    pub stretch: f32,
+    /// Direction of the streaks in degrees, from +X towards +Z.
+    pub stretch_angle: f32,
+    /// Multiplies the volumes' wind scroll at the layer's altitude.
+    pub scroll: f32,
+    pub coverage: f32,
+    /// Width of the fade from clear sky into the wisps.
+    pub softness: f32,
+    pub opacity: f32,
+    /// Forward-scattered glow around the sun.
+    pub silver: f32,
+    /// Below this cosine between the view ray and the sheet, the sheet fades
+    /// out rather than showing edge-on.
+    pub edge_fade: f32,
+    pub tint: Color,
+}
+
+impl Default for CirrusLayer {
+    fn default() -> Self {
+        Self {
+            altitude: 30.0,
+            radius: 300.0,
+            curvature: 1500.0,
+            follow_camera: true,
+            scale: 40.0,
+            stretch: 4.0,
+            stretch_angle: 30.0,
+            scroll: 4.0,
+            coverage: 0.45,
+            softness: 0.3,
+            opacity: 0.6,
+            silver: 0.5,
+            edge_fade: 0.15,
+            tint: Color::WHITE,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, ShaderType)]
+pub struct CirrusUniform {
+    pub tint: LinearRgba,
+    pub ambient: LinearRgba,
+    /// xyz: world direction towards the sun, w: 1 when there is one.
+    pub sun_direction: Vec4,
+    pub sun_color: LinearRgba,
+    /// x: sun intensity, y: physical sun, z: phase g, w: silver.
+    pub lighting: Vec4,
+    /// x: coverage, y: softness, z: opacity, w: edge fade.
+    pub shape: Vec4,
+    /// x: tile size, y: stretch, zw: streak direction.
+    pub stretch: Vec4,
+    /// xy: world XZ scroll.
+    pub scroll: Vec4,
+}
+
+impl CirrusUniform {
+    pub fn new(layer: &CirrusLayer, settings: &CloudSettings, lights: &CloudLights) -> Self {
+        let (sun_direction, sun_color) = match &lights.sun {
+            Some(sun) => (sun.direction.extend(1.0), sun.color),
+            None => (Vec4::Y, LinearRgba::BLACK),
+        };
+        let angle = layer.stretch_angle.to_radians();
+        Self {
+            tint: LinearRgba::from(layer.tint),
+            ambient: LinearRgba::from(settings.ambient_top),
+            sun_direction,
+            sun_color,
+            lighting: Vec4::new(
+                settings.sun_intensity,
+                settings.physical_sun as u32 as f32,
+                settings.phase_g,
+                layer.silver,
+            ),
+            shape: Vec4::new(layer.coverage, layer.softness.max(1e-3), layer.opacity, layer.edge_fade.max(1e-3)),
+            stretch: Vec4::new(layer.scale.max(1e-3), layer.stretch.max(1.0), angle.cos(), angle.sin()),
+            scroll: (settings.wind_offset_at(layer.altitude).xz() * layer.scroll).extend(0.0).extend(0.0),
+        }
+    }
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct CirrusMaterial {
+    #[uniform(0)]
+    pub data: CirrusUniform,
+    #[texture(1)]
+    #[sampler(2)]
+    pub noise_texture: Handle<Image>,
+}
+
+impl Material for CirrusMaterial {
+    fn fragment_shader() -> ShaderRef {
+        CIRRUS_SHADER_HANDLE.into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Blend
+    }
+
+    // Seen from below as well as from above, when the camera climbs past it.
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        descriptor.primitive.cull_mode = None;
+        Ok(())
+    }
+}
+
+/// Noise shared by every sheet.
+#[derive(Resource)]
+struct CirrusNoise(Handle<Image>);
+
+impl FromWorld for CirrusNoise {
+    fn from_world(world: &mut World) -> Self {
+        let image = noise::sheet_noise_image(0, SHEET_NOISE_SIZE);
+        Self(world.resource_mut::<Assets<Image>>().add(image))
+    }
+}
+
+/// Radius and curvature the sheet's mesh was built with.
+#[derive(Component, Clone, Copy, PartialEq)]
+struct SheetShape(Vec2);
+
+impl SheetShape {
+    fn of(layer: &CirrusLayer) -> Self {
+        Self(Vec2::new(layer.radius.max(1.0), layer.curvature.max(layer.radius).max(1.0)))
+    }
+
+    /// A disc-covering grid bent down onto a sphere, with UVs spanning it
+    /// for the rim fade.
+    fn mesh(self) -> Mesh {
+        let Vec2 { x: radius, y: curvature } = self.0;
+        let mut mesh = Plane3d::default()
+            .mesh()
+            .size(2.0 * radius, 2.0 * radius)
+            .subdivisions(SHEET_SUBDIVISIONS)
+            .build();
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            return mesh;
+        };
+        let (positions, normals): (Vec<[f32; 3]>, Vec<[f32; 3]>) = positions
+            .iter()
+            .map(|&[x, _, z]| {
+                let y = -(x * x + z * z) / (2.0 * curvature);
+                let normal = Vec3::new(x / curvature, 1.0, z / curvature).normalize();
+                ([x, y, z], normal.to_array())
+            })
+            .unzip();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh
+    }
+}
+
+fn attach_sheet_system(
+    mut commands: Commands,
+    layers: Query<(Entity, &CirrusLayer), Added<CirrusLayer>>,
+    noise: Res<CirrusNoise>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<CirrusMaterial>>,
+) {
+    for (entity, layer) in &layers {
+        let shape = SheetShape::of(layer);
+        commands.entity(entity).insert((
+            Mesh3d(meshes.add(shape.mesh())),
+            MeshMaterial3d(materials.add(CirrusMaterial {
+                data: CirrusUniform::default(),
+                noise_texture: noise.0.clone(),
+            })),
+            shape,
+            NotShadowCaster,
+            NotShadowReceiver,
+        ));
+    }
+}
+
+/// Places each sheet, rebuilds its mesh when its shape changed and writes
+/// its uniform when stale.
+fn sync_cirrus_system(
+    settings: Res<CloudSettings>,
+    cloud_lights: Res<CloudLights>,
+    cameras: Query<&GlobalTransform, With<OrbitCamera>>,
+    mut layers: Query<(
+        &CirrusLayer,
+        &mut Transform,
+        &mut SheetShape,
+        &Mesh3d,
+        &MeshMaterial3d<CirrusMaterial>,
+    )>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<CirrusMaterial>>,
+) {
+    let camera = cameras.get_single().ok().map(|transform| transform.translation());
+    for (layer, mut transform, mut shape, mesh, material) in &mut layers {
+        let center = match camera {
+            Some(camera) if layer.follow_camera => camera.xz(),
+            _ => transform.translation.xz(),
+        };
+        let translation = Vec3::new(center.x, layer.altitude, center.y);
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+
+        let wanted = SheetShape::of(layer);
+        if *shape != wanted {
+            meshes.insert(&mesh.0, wanted.mesh());
+            *shape = wanted;
+        }
+
+        let data = CirrusUniform::new(layer, &settings, &cloud_lights);
+        if materials.get(&material.0).is_some_and(|material| material.data != data) {
+            if let Some(material) = materials.get_mut(&material.0) {
+                material.data = data;
+            }
+        }
+    }
+}
+
+fn cirrus_ui_system(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut layers: Query<(Entity, &mut CirrusLayer)>,
+) {
+    egui::Window::new("Cirrus")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            if ui
+                .button("Add Layer")
+                .on_hover_text("A thin sheet of wisps high above the volumes")
+                .clicked()
+            {
+                commands.spawn((Name::new("Cirrus"), CirrusLayer::default()));
+            }
+            for (index, (entity, mut layer)) in layers.iter_mut().enumerate() {
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.strong(format!("Layer {}", index + 1));
+                    if ui.small_button("Remove").clicked() {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                });
+                ui.push_id(entity, |ui| layer_ui(ui, &mut layer));
+            }
+        });
+}
+
+fn layer_ui(ui: &mut egui::Ui, layer: &mut CirrusLayer) {
+    ui.add(egui::Slider::new(&mut layer.altitude, 5.0..=500.0).logarithmic(true).text("Altitude"));
+    ui.add(egui::Slider::new(&mut layer.radius, 10.0..=2000.0).logarithmic(true).text("Radius"));
+    ui.add(egui::Slider::new(&mut layer.curvature, 10.0..=10_000.0).logarithmic(true).text("Curvature Radius"))
+        .on_hover_text("Smaller bends the rim further towards the horizon");
+    ui.checkbox(&mut layer.follow_camera, "Follow Camera");
+    ui.add(egui::Slider::new(&mut layer.scale, 1.0..=500.0).logarithmic(true).text("Scale"));
+    ui.add(egui::Slider::new(&mut layer.stretch, 1.0..=16.0).logarithmic(true).text("Stretch"));
+    ui.add(egui::Slider::new(&mut layer.stretch_angle, -180.0..=180.0).suffix("°").text("Stretch Direction"));
+    ui.add(egui::Slider::new(&mut layer.scroll, 0.0..=20.0).text("Wind Scroll"))
+        .on_hover_text("Times the volumes' wind; 0 holds the sheet still");
+    ui.add(egui::Slider::new(&mut layer.coverage, 0.0..=1.0).text("Coverage"));
+    ui.add(egui::Slider::new(&mut layer.softness, 0.01..=1.0).text("Softness"));
+    ui.add(egui::Slider::new(&mut layer.opacity, 0.0..=1.0).text("Opacity"));
+    ui.add(egui::Slider::new(&mut layer.silver, 0.0..=4.0).text("Sun Silvering"));
+    ui.add(egui::Slider::new(&mut layer.edge_fade, 0.01..=1.0).text("Edge Fade"))
+        .on_hover_text("Fade the sheet where it is seen edge-on");
+    ui.horizontal(|ui| {
+        ui.label("Tint");
+        let mut tint = layer.tint.to_srgba().to_f32_array_no_alpha();
+        if ui.color_edit_button_rgb(&mut tint).changed() {
+            layer.tint = Color::srgb_from_array(tint);
+        }
+    });
+}
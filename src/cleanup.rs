@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{ShaderType, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    compare::CompareState, extension::LitCloudMaterial, noise::PendingNoise, CloudMaterial, CloudMaterialUniform,
+    CloudSettings,
+};
+
+/// Frees cloud materials whose volumes are gone and noise volumes nothing
+/// samples any more, and keeps an estimate of what the rest occupy on the GPU.
+pub struct CloudCleanupPlugin;
+
+impl Plugin for CloudCleanupPlugin {
+    fn build(&self, app: &mut App) {
+        // In `Last`, after every spawn of the frame has been applied.
+        app.init_resource::<CloudAssetStats>()
+            .add_systems(Last, cloud_asset_cleanup_system);
+    }
+}
+
+/// Live cloud assets after the last cleanup.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct CloudAssetStats {
+    pub materials: usize,
+    pub textures: usize,
+    /// Texture data plus material uniforms, in bytes.
+    pub gpu_bytes: u64,
+}
+
+pub(crate) fn is_noise_volume(image: &Image) -> bool {
+    image.texture_descriptor.dimension == TextureDimension::D3
+        && image.texture_descriptor.format == TextureFormat::Rg8Unorm
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cloud_asset_cleanup_system(
+    settings: Res<CloudSettings>,
+    compare: Res<CompareState>,
+    pending_noise: Res<PendingNoise>,
+    mut materials: ResMut<Assets<CloudMaterial>>,
+    mut lit_materials: ResMut<Assets<LitCloudMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    users: Query<&MeshMaterial3d<CloudMaterial>>,
+    lit_users: Query<&MeshMaterial3d<LitCloudMaterial>>,
+    mut stats: ResMut<CloudAssetStats>,
+) {
+    let used: HashSet<_> = users.iter().map(|material| material.id()).collect();
+    let orphans: Vec<_> = materials.ids().filter(|id| !used.contains(id)).collect();
+    for id in orphans {
+        materials.remove(id);
+    }
+    let lit_used: HashSet<_> = lit_users.iter().map(|material| material.id()).collect();
+    let lit_orphans: Vec<_> = lit_materials.ids().filter(|id| !lit_used.contains(id)).collect();
+    for id in lit_orphans {
+        lit_materials.remove(id);
+    }
+
+    let mut textures = HashSet::new();
+    for (_, material) in materials.iter() {
+        textures.extend([material.noise_texture.id(), material.profile_lut.id(), material.mask_texture.id()]);
+        textures.extend(material.light_volume.iter().chain(&material.occupancy).map(Handle::id));
+    }
+    for (_, material) in lit_materials.iter() {
+        let extension = &material.extension;
+        textures.extend([extension.noise_texture.id(), extension.profile_lut.id(), extension.mask_texture.id()]);
+        textures.extend(extension.occupancy.as_ref().map(Handle::id));
+    }
+    textures.insert(settings.noise_handle.id());
+    textures.insert(settings.profile_handle.id());
+    textures.extend(compare.snapshot_noise());
+    // Waiting to be swapped in, and the bake buffers.
+    textures.extend(
+        [&pending_noise.image, &pending_noise.live, &pending_noise.spare]
+            .into_iter()
+            .flatten()
+            .map(Handle::id),
+    );
+
+    // Noise volumes left behind by superseded bakes.
+    let superseded: Vec<_> = images
+        .iter()
+        .filter(|(id, image)| is_noise_volume(image) && !textures.contains(id))
+        .map(|(id, _)| id)
+        .collect();
+    for id in superseded {
+        images.remove(id);
+    }
+
+    let texture_bytes: u64 = textures
+        .iter()
+        .filter_map(|id| images.get(*id))
+        .map(|image| image.data.len() as u64)
+        .sum();
+    let material_count = materials.len() + lit_materials.len();
+    *stats = CloudAssetStats {
+        materials: material_count,
+        textures: textures.iter().filter(|id| images.contains(**id)).count(),
+        gpu_bytes: texture_bytes + material_count as u64 * CloudMaterialUniform::min_size().get(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bake_cache::BakeCache,
+        extension::CloudExtension,
+        noise::{BakeBudget, BakeProgress, BakeScratch, BakeStats, NoiseBaked},
+        occupancy::OccupancyPlugin,
+        spawn::{CloudVolumeParams, SpawnCloudExt},
+        swap_noise_system, testing, update_material_system, CloudVolume,
+    };
+
+    // Adds and removes volumes and rebakes the noise at changing resolutions
+    // through the editor's own spawn, bake, swap, occupancy and cleanup systems,
+    // without a renderer. Once the churn is over, the cloud materials, noise
+    // volumes and images in general must be back to what the scene held before.
+
+    const VOLUMES: usize = 100;
+    const REBAKES: usize = 20;
+
+    #[test]
+    fn volume_and_bake_churn_returns_to_the_baseline() {
+        let mut check = CleanupCheck::new();
+        for index in 0..4 {
+            check.spawn_volume(index, index % 2 == 1);
+        }
+        // Both bake buffers exist from the second bake on.
+        check.rebake(0);
+        check.rebake(1);
+        let baseline = check.counts();
+
+        let mut volumes: Vec<Entity> = (0..VOLUMES).map(|index| check.spawn_volume(index, index % 2 == 1)).collect();
+        check.app.update();
+        assert_eq!(check.counts().materials, baseline.materials + VOLUMES / 2);
+        assert_eq!(check.counts().lit_materials, baseline.lit_materials + VOLUMES / 2);
+
+        // A share of the volumes goes with every rebake.
+        for bake in 0..REBAKES {
+            let removed = volumes.len().min(VOLUMES.div_ceil(REBAKES));
+            for entity in volumes.drain(..removed) {
+                check.app.world_mut().entity_mut(entity).despawn_recursive();
+            }
+            check.rebake(bake + 2);
+        }
+        assert!(volumes.is_empty());
+        check.app.update();
+        assert_eq!(check.counts(), baseline);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Counts {
+        materials: usize,
+        lit_materials: usize,
+        noise_volumes: usize,
+        images: usize,
+    }
+
+    struct CleanupCheck {
+        app: App,
+    }
+
+    impl CleanupCheck {
+        fn new() -> Self {
+            let app = testing::headless_app(|app| {
+                // Bakes stay off the disk cache, which would skip the bake systems.
+                let mut cache = BakeCache::default();
+                cache.enabled = false;
+                app.add_plugins((CloudCleanupPlugin, OccupancyPlugin))
+                    .init_asset::<Mesh>()
+                    .init_asset::<LitCloudMaterial>()
+                    .init_resource::<CompareState>()
+                    .init_resource::<PendingNoise>()
+                    .init_resource::<BakeStats>()
+                    .init_resource::<BakeScratch>()
+                    .init_resource::<BakeProgress>()
+                    .init_resource::<BakeBudget>()
+                    .insert_resource(cache)
+                    .insert_resource(CloudSettings {
+                        resolution: UVec3::splat(8),
+                        ..CloudSettings::defaults()
+                    })
+                    .add_event::<NoiseBaked>()
+                    .add_systems(Update, (swap_noise_system, update_material_system).chain());
+            });
+            Self { app }
+        }
+
+        /// Spawns a volume the way the editor does, with a lit material when
+        /// `lit` is set.
+        fn spawn_volume(&mut self, index: usize, lit: bool) -> Entity {
+            let world = self.app.world_mut();
+            let params = CloudVolumeParams::default().at(Vec3::X * index as f32 * 3.0);
+            let entity = if lit {
+                let material = CloudExtension::material(world.resource::<CloudSettings>());
+                let material = world.resource_mut::<Assets<LitCloudMaterial>>().add(material);
+                world
+                    .spawn((params.transform, CloudVolume::default(), MeshMaterial3d(material)))
+                    .id()
+            } else {
+                world.commands().spawn_cloud(params).id()
+            };
+            world.flush();
+            entity
+        }
+
+        /// Rebakes at alternating resolutions and waits for the swap.
+        fn rebake(&mut self, index: usize) {
+            let mut settings = self.app.world_mut().resource_mut::<CloudSettings>();
+            settings.resolution = UVec3::splat(if index.is_multiple_of(2) { 8 } else { 12 });
+            settings.seed = index as u32;
+            settings.needs_rebuild = true;
+            self.settle();
+        }
+
+        /// Updates until no bake is running or waiting to be swapped in.
+        fn settle(&mut self) {
+            for _ in 0..100 {
+                self.app.update();
+                let world = self.app.world();
+                if !world.resource::<CloudSettings>().needs_rebuild
+                    && world.resource::<BakeProgress>().bake.is_none()
+                    && world.resource::<PendingNoise>().image.is_none()
+                {
+                    // One more frame for the cleanup after the swap.
+                    self.app.update();
+                    return;
+                }
+            }
+            panic!("the bake didn't finish");
+        }
+
+        fn counts(&self) -> Counts {
+            let world = self.app.world();
+            Counts {
+                materials: world.resource::<Assets<CloudMaterial>>().len(),
+                lit_materials: world.resource::<Assets<LitCloudMaterial>>().len(),
+                noise_volumes: world
+                    .resource::<Assets<Image>>()
+                    .iter()
+                    .filter(|(_, image)| is_noise_volume(image))
+                    .count(),
+                images: world.resource::<Assets<Image>>().len(),
+            }
+        }
+    }
+}
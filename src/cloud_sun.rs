@@ -0,0 +1,112 @@
+use bevy::{prelude::*, render::view::RenderLayers};
+
+use crate::CloudMaterialUniform;
+
+/// Which directional lights `CloudMaterial` scatters.
+///
+/// Each frame the brightest directional light the main view can see becomes
+/// the sun, unless a light carries `CloudSun`. The sun drives the light
+/// march; with `CloudSettings::fill_light` the next brightest light adds
+/// phase-weighted light on top, without a march of its own. When no light is
+/// left the clouds keep their ambient light. `LitCloudMaterial` is lit by
+/// every directional light, as lit geometry is, and ignores the choice.
+pub struct CloudSunPlugin;
+
+impl Plugin for CloudSunPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CloudLights>()
+            .add_systems(Update, select_lights_system.before(crate::update_material_system));
+    }
+}
+
+/// Makes a directional light the clouds' sun regardless of brightness.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct CloudSun;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CloudLight {
+    pub entity: Entity,
+    pub name: Option<Name>,
+    /// World-space direction towards the light.
+    pub direction: Vec3,
+    /// Linear color times illuminance, as Bevy passes lights to shaders.
+    pub color: LinearRgba,
+}
+
+#[cfg(feature = "ui")]
+impl CloudLight {
+    /// The light's name, or its entity when unnamed.
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{} ({})", name, self.entity),
+            None => self.entity.to_string(),
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub struct CloudLights {
+    pub sun: Option<CloudLight>,
+    /// The brightest light after the sun.
+    pub fill: Option<CloudLight>,
+}
+
+impl CloudLights {
+    /// Writes the lights into a material uniform; the fill only when enabled.
+    pub fn apply(&self, data: &mut CloudMaterialUniform, fill_light: bool) {
+        data.set_sun(self.sun.as_ref().map(|light| (light.direction, light.color)));
+        data.set_fill(
+            self.fill
+                .as_ref()
+                .filter(|_| fill_light)
+                .map(|light| (light.direction, light.color)),
+        );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn select_lights_system(
+    mut cloud_lights: ResMut<CloudLights>,
+    lights: Query<(
+        Entity,
+        &DirectionalLight,
+        &GlobalTransform,
+        Has<CloudSun>,
+        Option<&RenderLayers>,
+        Option<&InheritedVisibility>,
+        Option<&Name>,
+    )>,
+) {
+    // Offscreen rigs (thumbnails) keep their lights on their own layers.
+    let main_layers = RenderLayers::default();
+    let mut candidates: Vec<_> = lights
+        .iter()
+        .filter(|(.., layers, visibility, _)| {
+            layers.is_none_or(|layers| layers.intersects(&main_layers))
+                && visibility.is_none_or(|visibility| visibility.get())
+        })
+        .filter(|(_, light, ..)| light.illuminance > 0.0)
+        .map(|(entity, light, transform, marked, _, _, name)| {
+            let cloud_light = CloudLight {
+                entity,
+                name: name.cloned(),
+                direction: transform.back().as_vec3(),
+                color: light.color.to_linear() * light.illuminance,
+            };
+            (marked, light.illuminance, cloud_light)
+        })
+        .collect();
+    // Marked lights first, then the brightest; ties by entity so the choice
+    // doesn't flicker.
+    candidates.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then(b.1.total_cmp(&a.1))
+            .then(a.2.entity.cmp(&b.2.entity))
+    });
+    let mut ranked = candidates.into_iter().map(|(_, _, light)| light);
+    let selected = CloudLights {
+        sun: ranked.next(),
+        fill: ranked.next(),
+    };
+    cloud_lights.set_if_neq(selected);
+}
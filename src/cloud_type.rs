@@ -0,0 +1,201 @@
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts};
+
+use crate::CloudSettings;
+#[cfg(feature = "ui")]
+use crate::CloudUiSet;
+
+/// Height samples along the LUT's X axis.
+pub const LUT_HEIGHTS: u32 = 64;
+/// Cloud type samples along the LUT's Y axis, 0 (stratus) to 1
+/// (cumulonimbus).
+pub const LUT_TYPES: u32 = 16;
+
+/// Bakes the height-density profiles of the stock cloud types into a small
+/// 2D LUT the shader indexes by normalized height and `CloudVolume::cloud_type`.
+pub struct CloudTypePlugin;
+
+impl Plugin for CloudTypePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CloudTypeProfiles>()
+            .add_systems(Update, profile_lut_system.before(crate::update_material_system));
+        #[cfg(feature = "ui")]
+        app.add_systems(Update, cloud_type_ui_system.in_set(CloudUiSet).before(profile_lut_system));
+    }
+}
+
+/// Piecewise-linear density over normalized height. Points are sorted by X;
+/// the first and last stay pinned to heights 0 and 1.
+#[derive(Clone, Debug)]
+pub struct HeightProfile {
+    pub points: Vec<Vec2>,
+}
+
+impl HeightProfile {
+    pub fn new(points: &[(f32, f32)]) -> Self {
+        Self {
+            points: points.iter().map(|&(x, y)| Vec2::new(x, y)).collect(),
+        }
+    }
+
+    pub fn sample(&self, height: f32) -> f32 {
+        let Some(first) = self.points.first() else {
+            return 1.0;
+        };
+        if height <= first.x {
+            return first.y;
+        }
+        for pair in self.points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if height <= b.x {
+                let t = ((height - a.x) / (b.x - a.x).max(1e-5)).clamp(0.0, 1.0);
+                return a.y.lerp(b.y, t);
+            }
+        }
+        self.points.last().map_or(1.0, |last| last.y)
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct CloudTypeProfiles {
+    pub stratus: HeightProfile,
+    pub cumulus: HeightProfile,
+    pub cumulonimbus: HeightProfile,
+    pub needs_rebuild: bool,
+}
+
+impl Default for CloudTypeProfiles {
+    fn default() -> Self {
+        Self {
+            // A thin layer low in the box.
+            stratus: HeightProfile::new(&[(0.0, 0.0), (0.1, 1.0), (0.3, 1.0), (0.4, 0.0), (1.0, 0.0)]),
+            // Flat base, rounded top.
+            cumulus: HeightProfile::new(&[(0.0, 0.0), (0.08, 1.0), (0.6, 1.0), (0.9, 0.5), (1.0, 0.0)]),
+            // Full height, swelling into an anvil at the top.
+            cumulonimbus: HeightProfile::new(&[(0.0, 0.0), (0.05, 1.0), (0.7, 0.8), (0.9, 1.0), (1.0, 0.0)]),
+            needs_rebuild: true,
+        }
+    }
+}
+
+/// The named type nearest to `cloud_type`.
+#[cfg(feature = "ui")]
+pub fn type_name(cloud_type: f32) -> &'static str {
+    match cloud_type {
+        t if t < 0.25 => "Stratus",
+        t if t < 0.75 => "Cumulus",
+        _ => "Cumulonimbus",
+    }
+}
+
+impl CloudTypeProfiles {
+    /// Blends stratus → cumulus → cumulonimbus as `cloud_type` goes 0 → 1.
+    pub fn sample(&self, height: f32, cloud_type: f32) -> f32 {
+        let t = cloud_type.clamp(0.0, 1.0) * 2.0;
+        if t <= 1.0 {
+            self.stratus.sample(height).lerp(self.cumulus.sample(height), t)
+        } else {
+            self.cumulus.sample(height).lerp(self.cumulonimbus.sample(height), t - 1.0)
+        }
+    }
+
+    /// Row-major R8 data, `LUT_HEIGHTS` wide and `LUT_TYPES` tall.
+    pub fn bake_lut(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity((LUT_HEIGHTS * LUT_TYPES) as usize);
+        for y in 0..LUT_TYPES {
+            let cloud_type = y as f32 / (LUT_TYPES - 1) as f32;
+            for x in 0..LUT_HEIGHTS {
+                let height = x as f32 / (LUT_HEIGHTS - 1) as f32;
+                data.push((self.sample(height, cloud_type).clamp(0.0, 1.0) * 255.0) as u8);
+            }
+        }
+        data
+    }
+}
+
+fn profile_lut_system(
+    mut profiles: ResMut<CloudTypeProfiles>,
+    settings: Res<CloudSettings>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if !profiles.needs_rebuild {
+        return;
+    }
+    if let Some(image) = images.get_mut(&settings.profile_handle) {
+        let _span = info_span!("profile_lut_bake").entered();
+        image.data = profiles.bake_lut();
+        profiles.needs_rebuild = false;
+    }
+}
+
+/// Small graph editor for a `HeightProfile`: height runs left to right,
+/// density bottom to top. Returns true when a point moved.
+#[cfg(feature = "ui")]
+pub fn curve_editor(ui: &mut egui::Ui, id: &str, profile: &mut HeightProfile) -> bool {
+    let size = egui::vec2(ui.available_width().min(240.0), 100.0);
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let to_screen = |p: Vec2| egui::pos2(rect.left() + p.x * rect.width(), rect.bottom() - p.y * rect.height());
+    let from_screen = |p: egui::Pos2| {
+        Vec2::new(
+            (p.x - rect.left()) / rect.width(),
+            (rect.bottom() - p.y) / rect.height(),
+        )
+    };
+
+    let stroke = ui.visuals().widgets.active.fg_stroke;
+    let line: Vec<_> = profile.points.iter().map(|p| to_screen(*p)).collect();
+    painter.add(egui::Shape::line(line, stroke));
+
+    let mut changed = false;
+    let count = profile.points.len();
+    for i in 0..count {
+        let center = to_screen(profile.points[i]);
+        let handle = egui::Rect::from_center_size(center, egui::vec2(10.0, 10.0));
+        let response = ui.interact(handle, ui.id().with(id).with(i), egui::Sense::drag());
+        if response.dragged() {
+            if let Some(pointer) = response.interact_pointer_pos() {
+                let mut p = from_screen(pointer).clamp(Vec2::ZERO, Vec2::ONE);
+                // Keep the ends pinned and interior points ordered.
+                p.x = match i {
+                    0 => 0.0,
+                    i if i == count - 1 => 1.0,
+                    i => p.x.clamp(profile.points[i - 1].x, profile.points[i + 1].x),
+                };
+                profile.points[i] = p;
+                changed = true;
+            }
+        }
+        let radius = if response.hovered() || response.dragged() { 5.0 } else { 3.5 };
+        painter.circle_filled(center, radius, stroke.color);
+    }
+    changed
+}
+
+#[cfg(feature = "ui")]
+fn cloud_type_ui_system(mut contexts: EguiContexts, mut profiles: ResMut<CloudTypeProfiles>) {
+    egui::Window::new("Cloud Types")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("Density over height, bottom to top of the volume");
+            let profiles = &mut *profiles;
+            let mut changed = false;
+            for (name, profile) in [
+                ("Stratus (0.0)", &mut profiles.stratus),
+                ("Cumulus (0.5)", &mut profiles.cumulus),
+                ("Cumulonimbus (1.0)", &mut profiles.cumulonimbus),
+            ] {
+                ui.label(name);
+                changed |= curve_editor(ui, name, profile);
+            }
+            if ui.button("Reset Profiles").clicked() {
+                *profiles = CloudTypeProfiles::default();
+            }
+            if changed {
+                profiles.needs_rebuild = true;
+            }
+        });
+}
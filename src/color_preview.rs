@@ -0,0 +1,179 @@
+use std::f32::consts::PI;
+
+use bevy::{core_pipeline::tonemapping::Tonemapping, prelude::*, render::camera::Exposure};
+use bevy_egui::egui;
+
+use crate::{cloud_sun::CloudLights, CloudSettings, CloudUiSet, OrbitCamera};
+
+/// Swatches next to the cloud color pickers showing roughly how a color
+/// ends up on screen.
+///
+/// A swatch lights the color as a fully scattering texel of the march does
+/// and runs the result through the main camera's tonemapper on the CPU. The
+/// lighting and tonemapper are read every frame, so swatches follow exposure,
+/// sun and tonemapping changes. Tony McMapface and Blender Filmic are lookup
+/// tables on the GPU and are approximated here by AgX, whose curve is close.
+pub struct ColorPreviewPlugin;
+
+impl Plugin for ColorPreviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ColorPreview>()
+            .add_systems(Update, color_preview_system.before(CloudUiSet));
+    }
+}
+
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct ColorPreview {
+    /// `None` unless the camera is HDR: without an HDR target the clouds are
+    /// written without tonemapping.
+    tonemapping: Option<Tonemapping>,
+    /// Linear sun color with its brightest channel at 1, as the artistic sun
+    /// uses it.
+    sun_hue: Vec3,
+    /// Linear sun illuminance through the camera exposure, as the physical
+    /// sun uses it.
+    sun_exposed: Vec3,
+}
+
+impl Default for ColorPreview {
+    fn default() -> Self {
+        Self {
+            tonemapping: None,
+            sun_hue: Vec3::ONE,
+            sun_exposed: Vec3::ZERO,
+        }
+    }
+}
+
+impl ColorPreview {
+    /// Sun light scattered towards the camera, with isotropic phase.
+    pub fn sun(&self, settings: &CloudSettings) -> Vec3 {
+        if settings.physical_sun {
+            self.sun_exposed * settings.sun_intensity / (4.0 * PI)
+        } else {
+            self.sun_hue * settings.sun_intensity
+        }
+    }
+
+    /// A texel of `albedo` in the shade, lit by `ambient` alone.
+    pub fn shaded(albedo: LinearRgba, ambient: LinearRgba) -> Vec3 {
+        Vec3::from_array(albedo.to_f32_array_no_alpha()) * Vec3::from_array(ambient.to_f32_array_no_alpha())
+    }
+
+    /// A texel of `albedo` in full sun, halfway up the volume.
+    pub fn sunlit(&self, settings: &CloudSettings, albedo: LinearRgba) -> Vec3 {
+        let ambient = LinearRgba::from(settings.ambient_bottom).mix(&LinearRgba::from(settings.ambient_top), 0.5);
+        Self::shaded(albedo, ambient) + self.sun(settings)
+    }
+
+    /// On-screen color of linear scene `radiance`.
+    pub fn display(&self, radiance: Vec3) -> egui::Color32 {
+        let radiance = radiance.max(Vec3::ZERO);
+        let mapped = match self.tonemapping {
+            None | Some(Tonemapping::None) => radiance,
+            Some(Tonemapping::Reinhard) => radiance / (1.0 + radiance),
+            Some(Tonemapping::ReinhardLuminance) | Some(Tonemapping::SomewhatBoringDisplayTransform) => {
+                reinhard_luminance(radiance)
+            }
+            Some(Tonemapping::AcesFitted) => aces_fitted(radiance),
+            Some(Tonemapping::AgX) | Some(Tonemapping::TonyMcMapface) | Some(Tonemapping::BlenderFilmic) => {
+                agx(radiance)
+            }
+        };
+        let [r, g, b, _] = Color::linear_rgb(mapped.x, mapped.y, mapped.z).to_srgba().to_u8_array();
+        egui::Color32::from_rgb(r, g, b)
+    }
+
+    /// A small square showing `radiance` as it will roughly appear.
+    pub fn swatch(&self, ui: &mut egui::Ui, radiance: Vec3) -> egui::Response {
+        let size = ui.spacing().interact_size.y;
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::hover());
+        ui.painter().rect_filled(rect, 2.0, self.display(radiance));
+        response.on_hover_text("Approximately how this looks in the scene, after lighting and tonemapping")
+    }
+}
+
+fn luminance(color: Vec3) -> f32 {
+    color.dot(Vec3::new(0.2126, 0.7152, 0.0722))
+}
+
+fn reinhard_luminance(color: Vec3) -> Vec3 {
+    let l = luminance(color);
+    if l <= 0.0 {
+        return Vec3::ZERO;
+    }
+    color * (l / (1.0 + l)) / l
+}
+
+/// Stephen Hill's fit, as `ACESFitted` in Bevy's tonemapping shader.
+fn aces_fitted(color: Vec3) -> Vec3 {
+    let input = Mat3::from_cols_array(&[0.59719, 0.07600, 0.02840, 0.35458, 0.90834, 0.13383, 0.04823, 0.01566, 0.83777]);
+    let output =
+        Mat3::from_cols_array(&[1.60475, -0.10208, -0.00327, -0.53108, 1.10813, -0.07276, -0.07367, -0.00605, 1.07602]);
+    let v = input * color;
+    let fitted = (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.432951) + 0.238081);
+    (output * fitted).clamp(Vec3::ZERO, Vec3::ONE)
+}
+
+/// AgX with the usual polynomial fit of its default contrast curve.
+#[allow(clippy::excessive_precision)]
+fn agx(color: Vec3) -> Vec3 {
+    const MIN_EV: f32 = -12.47393;
+    const MAX_EV: f32 = 4.026069;
+    let inset = Mat3::from_cols_array(&[
+        0.842479062253094,
+        0.0423282422610123,
+        0.0423756549057051,
+        0.0784335999999992,
+        0.878468636469772,
+        0.0784336,
+        0.0792237451477643,
+        0.0791661274605434,
+        0.879142973793104,
+    ]);
+    let outset = Mat3::from_cols_array(&[
+        1.19687900512017,
+        -0.0528968517574562,
+        -0.0529716355144438,
+        -0.0980208811401368,
+        1.15190312990417,
+        -0.0980434501171241,
+        -0.0990297440797205,
+        -0.0989611768448433,
+        1.15107367264116,
+    ]);
+    let v = inset * color;
+    let v = v.max(Vec3::splat(1e-10));
+    let ev = Vec3::new(v.x.log2(), v.y.log2(), v.z.log2()).clamp(Vec3::splat(MIN_EV), Vec3::splat(MAX_EV));
+    let x = (ev - MIN_EV) / (MAX_EV - MIN_EV);
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    let curve = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+    (outset * curve).max(Vec3::ZERO).powf(2.2).min(Vec3::ONE)
+}
+
+#[allow(clippy::type_complexity)]
+fn color_preview_system(
+    mut preview: ResMut<ColorPreview>,
+    cameras: Query<(&Camera, Option<&Tonemapping>, Option<&Exposure>), With<OrbitCamera>>,
+    cloud_lights: Res<CloudLights>,
+) {
+    let (tonemapping, exposure) = match cameras.get_single() {
+        Ok((camera, tonemapping, exposure)) => (
+            camera.hdr.then(|| tonemapping.copied().unwrap_or_default()),
+            exposure.copied().unwrap_or_default().exposure(),
+        ),
+        Err(_) => (None, Exposure::default().exposure()),
+    };
+    // Without a sun the clouds are lit by their ambient light alone.
+    let sun_color = cloud_lights
+        .sun
+        .as_ref()
+        .map_or(Vec3::ZERO, |sun| Vec3::from_array(sun.color.to_f32_array_no_alpha()));
+    let updated = ColorPreview {
+        tonemapping,
+        sun_hue: sun_color / sun_color.max_element().max(1e-4),
+        sun_exposed: sun_color * exposure,
+    };
+    preview.set_if_neq(updated);
+}
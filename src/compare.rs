@@ -0,0 +1,306 @@
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{SubCameraView, Viewport},
+        view::RenderLayers,
+    },
+    window::PrimaryWindow,
+};
+use bevy_atmosphere::prelude::AtmosphereCamera;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    material_sync::CloudMaterialMirror, time_of_day::Sun, CloudMaterial, CloudSettings, CloudUiSet, CloudVolume, OrbitCamera,
+};
+
+/// Layer holding the snapshot copies of the volumes; only the left camera
+/// sees it.
+const COMPARE_LAYER: usize = 31;
+
+/// A/B compare: snapshot the settings as A, keep editing as B, and view both
+/// side by side split by a draggable divider.
+pub struct ComparePlugin;
+
+impl Plugin for ComparePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CompareState>().add_systems(
+            Update,
+            (
+                compare_ui_system.in_set(CloudUiSet),
+                compare_divider_system,
+                compare_system.after(crate::update_material_system),
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareAction {
+    /// Snapshot the current settings as A and enter compare mode.
+    Start,
+    /// Leave compare mode, restoring the A settings.
+    KeepA,
+    /// Leave compare mode with the current (B) settings.
+    KeepB,
+}
+
+#[derive(Resource)]
+pub struct CompareState {
+    /// Divider position as a fraction of the window width.
+    pub split: f32,
+    pub pending: Option<CompareAction>,
+    snapshot: Option<CompareSnapshot>,
+}
+
+struct CompareSnapshot {
+    settings: CloudSettings,
+    /// Copy of the noise baked for A, so B can rebake freely.
+    noise: Handle<Image>,
+    camera: Entity,
+}
+
+impl Default for CompareState {
+    fn default() -> Self {
+        Self {
+            split: 0.5,
+            pending: None,
+            snapshot: None,
+        }
+    }
+}
+
+impl CompareState {
+    pub fn active(&self) -> bool {
+        self.snapshot.is_some()
+    }
+
+    /// The noise copy baked for A, while comparing.
+    pub fn snapshot_noise(&self) -> Option<AssetId<Image>> {
+        self.snapshot.as_ref().map(|snapshot| snapshot.noise.id())
+    }
+}
+
+/// Snapshot copy of a volume, rendered only by the A camera.
+#[derive(Component)]
+struct CompareProxy {
+    owner: Entity,
+    material: Handle<CloudMaterial>,
+}
+
+#[derive(Component)]
+struct CompareCamera;
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn compare_system(
+    mut commands: Commands,
+    mut state: ResMut<CompareState>,
+    mut settings: ResMut<CloudSettings>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<CloudMaterial>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    volumes: Query<(Entity, &GlobalTransform, &Mesh3d), With<CloudVolume>>,
+    mut proxies: Query<(Entity, &CompareProxy, &mut CloudMaterialMirror)>,
+    mut main_camera: Query<(&mut Camera, &Transform), (With<OrbitCamera>, Without<CompareCamera>)>,
+    mut compare_camera: Query<(&mut Camera, &mut Transform), (With<CompareCamera>, Without<OrbitCamera>)>,
+    suns: Query<Entity, With<Sun>>,
+) {
+    match state.pending.take() {
+        Some(CompareAction::Start) if !state.active() => {
+            let Some(noise) = images.get(&settings.noise_handle).cloned() else {
+                return;
+            };
+            let camera = commands
+                .spawn((
+                    Camera3d::default(),
+                    Camera {
+                        order: 1,
+                        ..default()
+                    },
+                    RenderLayers::layer(COMPARE_LAYER),
+                    AtmosphereCamera::default(),
+                    CompareCamera,
+                ))
+                .id();
+            // The sun has to light both sides.
+            for sun in &suns {
+                commands.entity(sun).insert(RenderLayers::from_layers(&[0, COMPARE_LAYER]));
+            }
+            state.snapshot = Some(CompareSnapshot {
+                settings: settings.clone(),
+                noise: images.add(noise),
+                camera,
+            });
+        }
+        Some(action @ (CompareAction::KeepA | CompareAction::KeepB)) => {
+            if let Some(snapshot) = state.snapshot.take() {
+                if action == CompareAction::KeepA {
+                    let live = settings.clone();
+                    *settings = CloudSettings {
+                        noise_handle: live.noise_handle,
+                        profile_handle: live.profile_handle,
+                        wind_offset: live.wind_offset,
+                        wind_offset_upper: live.wind_offset_upper,
+                        needs_rebuild: true,
+                        ..snapshot.settings
+                    };
+                }
+                for (entity, proxy, _) in &proxies {
+                    materials.remove(&proxy.material);
+                    commands.entity(entity).despawn_recursive();
+                }
+                images.remove(&snapshot.noise);
+                commands.entity(snapshot.camera).despawn_recursive();
+                for sun in &suns {
+                    commands.entity(sun).remove::<RenderLayers>();
+                }
+                if let Ok((mut camera, _)) = main_camera.get_single_mut() {
+                    camera.viewport = None;
+                    camera.sub_camera_view = None;
+                }
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    let Some(snapshot) = &state.snapshot else {
+        return;
+    };
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+
+    // Keep one proxy per volume, drawn by the material sync with the
+    // snapshot settings and noise.
+    let a_settings = CloudSettings {
+        wind_offset: settings.wind_offset,
+        wind_offset_upper: settings.wind_offset_upper,
+        noise_handle: snapshot.noise.clone(),
+        ..snapshot.settings.clone()
+    };
+    for (entity, proxy, _) in &proxies {
+        if !volumes.contains(proxy.owner) {
+            materials.remove(&proxy.material);
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    for (owner, transform, mesh) in &volumes {
+        let mirror = CloudMaterialMirror::Snapshot {
+            source: owner,
+            settings: Box::new(a_settings.clone()),
+        };
+        match proxies.iter_mut().find(|(_, proxy, _)| proxy.owner == owner) {
+            Some((entity, _, mut existing)) => {
+                *existing = mirror;
+                commands.entity(entity).insert(transform.compute_transform());
+            }
+            None => {
+                let material = materials.add(CloudMaterial::new(&a_settings));
+                commands.spawn((
+                    Mesh3d(mesh.0.clone()),
+                    MeshMaterial3d(material.clone()),
+                    transform.compute_transform(),
+                    RenderLayers::layer(COMPARE_LAYER),
+                    CompareProxy { owner, material },
+                    mirror,
+                ));
+            }
+        }
+    }
+
+    // Both cameras render the same full-window view; each draws its side.
+    let full = window.physical_size();
+    let split = ((state.split.clamp(0.0, 1.0) * full.x as f32) as u32).clamp(1, full.x.saturating_sub(1).max(1));
+    let Ok((mut b_camera, b_transform)) = main_camera.get_single_mut() else {
+        return;
+    };
+    let Ok((mut a_camera, mut a_transform)) = compare_camera.get_single_mut() else {
+        return;
+    };
+    *a_transform = *b_transform;
+    a_camera.viewport = Some(Viewport {
+        physical_position: UVec2::ZERO,
+        physical_size: UVec2::new(split, full.y),
+        ..default()
+    });
+    a_camera.sub_camera_view = Some(SubCameraView {
+        full_size: full,
+        offset: Vec2::ZERO,
+        size: UVec2::new(split, full.y),
+    });
+    b_camera.viewport = Some(Viewport {
+        physical_position: UVec2::new(split, 0),
+        physical_size: UVec2::new(full.x - split, full.y),
+        ..default()
+    });
+    b_camera.sub_camera_view = Some(SubCameraView {
+        full_size: full,
+        offset: Vec2::new(split as f32, 0.0),
+        size: UVec2::new(full.x - split, full.y),
+    });
+}
+
+fn compare_divider_system(mut contexts: EguiContexts, mut state: ResMut<CompareState>) {
+    if !state.active() {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+    let screen = ctx.screen_rect();
+    let x = screen.left() + state.split * screen.width();
+    egui::Area::new(egui::Id::new("compare_divider"))
+        .fixed_pos(egui::pos2(x - 4.0, screen.top()))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            let (rect, response) = ui.allocate_exact_size(egui::vec2(8.0, screen.height()), egui::Sense::drag());
+            let color = if response.hovered() || response.dragged() {
+                egui::Color32::WHITE
+            } else {
+                egui::Color32::from_white_alpha(160)
+            };
+            ui.painter().vline(rect.center().x, rect.y_range(), egui::Stroke::new(2.0, color));
+            ui.painter().text(
+                rect.center_top() + egui::vec2(-12.0, 8.0),
+                egui::Align2::RIGHT_TOP,
+                "A",
+                egui::FontId::proportional(16.0),
+                egui::Color32::WHITE,
+            );
+            ui.painter().text(
+                rect.center_top() + egui::vec2(12.0, 8.0),
+                egui::Align2::LEFT_TOP,
+                "B",
+                egui::FontId::proportional(16.0),
+                egui::Color32::WHITE,
+            );
+            if response.dragged() {
+                state.split = (state.split + response.drag_delta().x / screen.width()).clamp(0.05, 0.95);
+            }
+            response.on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+        });
+}
+
+fn compare_ui_system(mut contexts: EguiContexts, mut state: ResMut<CompareState>) {
+    egui::Window::new("Compare")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            if !state.active() {
+                ui.label("Stores the current settings as A; further edits are B.");
+                if ui.button("Store A and Compare").clicked() {
+                    state.pending = Some(CompareAction::Start);
+                }
+                return;
+            }
+            ui.add(egui::Slider::new(&mut state.split, 0.05..=0.95).text("Split"));
+            ui.horizontal(|ui| {
+                if ui.button("Keep A").clicked() {
+                    state.pending = Some(CompareAction::KeepA);
+                }
+                if ui.button("Keep B").clicked() {
+                    state.pending = Some(CompareAction::KeepB);
+                }
+            });
+        });
+}
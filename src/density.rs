@@ -0,0 +1,360 @@
+//! CPU-side evaluation of the cloud density, mirroring `cloud_shader.wgsl`.
+//!
+//! The baked noise image keeps its data in the main world, so gameplay code
+//! can query density along rays without a GPU readback.
+
+use bevy::{prelude::*, render::render_resource::TextureFormat};
+
+use crate::{CloudMaterialUniform, CloudSettings};
+
+/// Frequency of the softness erosion resample, as in `cloud_density.wgsl`.
+const EROSION_SCALE: f32 = 4.0;
+/// Most an anvil widens the top, as in `cloud_density.wgsl`.
+const ANVIL_SPREAD: f32 = 1.5;
+
+/// Read-only view of a baked noise volume, plus the cloud-type height
+/// profile LUT when available.
+pub struct DensityField<'a> {
+    data: &'a [u8],
+    size: UVec3,
+    /// Bytes per texel; the noise is the first.
+    channels: usize,
+    profile: Option<(&'a [u8], UVec2)>,
+    weather_map: Option<(&'a [u8], UVec2)>,
+}
+
+impl<'a> DensityField<'a> {
+    pub fn from_image(image: &'a Image) -> Option<Self> {
+        let extent = image.texture_descriptor.size;
+        let size = UVec3::new(extent.width, extent.height, extent.depth_or_array_layers);
+        let channels = match image.texture_descriptor.format {
+            TextureFormat::Rg8Unorm => 2,
+            _ => 1,
+        };
+        if size.min_element() == 0 || image.data.len() < (size.x * size.y * size.z) as usize * channels {
+            return None;
+        }
+        Some(Self {
+            data: &image.data,
+            size,
+            channels,
+            profile: None,
+            weather_map: None,
+        })
+    }
+
+    /// Noise and profile LUT of the live settings.
+    pub fn from_settings(images: &'a Assets<Image>, settings: &CloudSettings) -> Option<Self> {
+        Self::new(images, &settings.noise_handle, &settings.profile_handle)
+    }
+
+    /// A noise volume, such as a per-volume override, with a profile LUT.
+    pub fn new(images: &'a Assets<Image>, noise: &Handle<Image>, profile: &Handle<Image>) -> Option<Self> {
+        let mut field = Self::from_image(images.get(noise)?)?;
+        field.profile = images.get(profile).and_then(|image| {
+            let size = UVec2::new(image.texture_descriptor.size.width, image.texture_descriptor.size.height);
+            (size.min_element() > 0 && image.data.len() >= (size.x * size.y) as usize)
+                .then_some((&image.data[..], size))
+        });
+        Some(field)
+    }
+
+    /// Adds the painted coverage of `weather_map::WeatherMap`, applied to
+    /// materials whose uniform enables it.
+    pub fn with_weather_map(mut self, image: Option<&'a Image>) -> Self {
+        self.weather_map = image.and_then(|image| {
+            let size = UVec2::new(image.texture_descriptor.size.width, image.texture_descriptor.size.height);
+            (size.min_element() > 0 && image.data.len() >= (size.x * size.y) as usize)
+                .then_some((&image.data[..], size))
+        });
+        self
+    }
+
+    fn texel(&self, x: u32, y: u32, z: u32) -> f32 {
+        let index = (z * self.size.y + y) * self.size.x + x;
+        self.data[index as usize * self.channels] as f32 / 255.0
+    }
+
+    /// Trilinear sample at normalized coordinates, repeating like the GPU
+    /// sampler.
+    pub fn sample(&self, uvw: Vec3) -> f32 {
+        let size = self.size.as_ivec3();
+        let p = uvw * self.size.as_vec3() - 0.5;
+        let base = p.floor();
+        let f = p - base;
+        let p0 = base.as_ivec3().rem_euclid(size).as_uvec3();
+        let p1 = (base.as_ivec3() + IVec3::ONE).rem_euclid(size).as_uvec3();
+
+        let c00 = self.texel(p0.x, p0.y, p0.z).lerp(self.texel(p1.x, p0.y, p0.z), f.x);
+        let c10 = self.texel(p0.x, p1.y, p0.z).lerp(self.texel(p1.x, p1.y, p0.z), f.x);
+        let c01 = self.texel(p0.x, p0.y, p1.z).lerp(self.texel(p1.x, p0.y, p1.z), f.x);
+        let c11 = self.texel(p0.x, p1.y, p1.z).lerp(self.texel(p1.x, p1.y, p1.z), f.x);
+        c00.lerp(c10, f.y).lerp(c01.lerp(c11, f.y), f.z)
+    }
+
+    /// Bilinear height-profile lookup, clamped like the GPU. Full density
+    /// without a LUT.
+    pub fn profile(&self, height: f32, cloud_type: f32) -> f32 {
+        let Some((data, size)) = self.profile else {
+            return 1.0;
+        };
+        let texel = |x: u32, y: u32| data[(y * size.x + x) as usize] as f32 / 255.0;
+        let p = Vec2::new(height, cloud_type).clamp(Vec2::ZERO, Vec2::ONE) * (size - 1).as_vec2();
+        let p0 = p.floor().as_uvec2();
+        let p1 = (p0 + 1).min(size - 1);
+        let f = p - p.floor();
+        let bottom = texel(p0.x, p0.y).lerp(texel(p1.x, p0.y), f.x);
+        let top = texel(p0.x, p1.y).lerp(texel(p1.x, p1.y), f.x);
+        bottom.lerp(top, f.y)
+    }
+
+    /// Bilinear weather-map coverage at map UV, clamped to the edge like the
+    /// GPU sampler. Full coverage without a map.
+    pub fn weather_coverage(&self, uv: Vec2) -> f32 {
+        let Some((data, size)) = self.weather_map else {
+            return 1.0;
+        };
+        let texel = |x: u32, y: u32| data[(y * size.x + x) as usize] as f32 / 255.0;
+        let p = (uv * size.as_vec2() - 0.5).clamp(Vec2::ZERO, (size - 1).as_vec2());
+        let p0 = p.floor().as_uvec2();
+        let p1 = (p0 + 1).min(size - 1);
+        let f = p - p.floor();
+        let bottom = texel(p0.x, p0.y).lerp(texel(p1.x, p0.y), f.x);
+        let top = texel(p0.x, p1.y).lerp(texel(p1.x, p1.y), f.x);
+        bottom.lerp(top, f.y)
+    }
+}
+
+/// Density at a world-space point for a volume whose material uniform has
+/// already been synced. Zero outside the volume's bounds; in the outset
+/// around the box, the density on the nearest face.
+pub fn sample_density(field: &DensityField, data: &CloudMaterialUniform, world_pos: Vec3) -> f32 {
+    sample_volume_density(field, data, data.to_volume(world_pos))
+}
+
+/// `sample_density` at a point already in the volume's rotated frame.
+pub fn sample_volume_density(field: &DensityField, data: &CloudMaterialUniform, p: Vec3) -> f32 {
+    let box_max = data.half_extents();
+    let box_min = -box_max;
+    if data.ground_fog.y > 0.0 {
+        return sample_fog_density(field, data, p);
+    }
+    let outset = data.bounds.y.max(0.0);
+    if p.cmplt(box_min - outset).any() || p.cmpgt(box_max + outset).any() {
+        return 0.0;
+    }
+    let p = p.clamp(box_min, box_max);
+    let height = ((p.y - box_min.y) / (box_max.y - box_min.y)).clamp(0.0, 1.0);
+    let anvil = anvil_weight(data, height);
+    let q = anvil_position(field, p, anvil);
+    let local = to_domain(data, q - data.wind_scroll(q));
+    let uvw = (local / (box_max - box_min) + 0.5) / data.shape.yzw() + data.noise_offset.truncate();
+    let noise = mixed_noise(field, data, uvw);
+    let profile = field
+        .profile(height, data.profile.x)
+        .lerp(1.0 - smoothstep(0.9, 1.0, height), anvil);
+    let mut coverage = data.shape.x;
+    if data.weather_map.z > 0.0 {
+        let uv = (data.to_world(p).xz() - data.weather_map.xy()) * data.weather_map.zw();
+        coverage *= field.weather_coverage(uv);
+    }
+    if data.trail_info.x > 0.0 {
+        coverage *= trail_coverage(data, data.to_world(p));
+    }
+    let threshold = 1.0_f32.lerp(data.settings.y, coverage);
+    let value = noise * profile;
+    let mut softness = data.profile.y;
+    if data.profile.z > 0.0 {
+        let detail = field.sample(uvw * EROSION_SCALE);
+        softness *= 1.0_f32.lerp(2.0 * detail, data.profile.z);
+    }
+    let edge = if softness > 0.0 {
+        smoothstep(threshold, threshold + softness, value)
+    } else {
+        1.0
+    };
+    (value - threshold).max(0.0) * edge * data.settings.x
+}
+
+/// Ground fog density, as `fog_density` in `cloud_density.wgsl`. Only the
+/// top and bottom of the box bound it.
+fn sample_fog_density(field: &DensityField, data: &CloudMaterialUniform, p: Vec3) -> f32 {
+    if p.y.abs() > data.half_extents().y {
+        return 0.0;
+    }
+    let fog = data.ground_fog;
+    let falloff = (-(p.y - fog.x).max(0.0) / fog.y).exp();
+    let uvw = to_domain(data, p + data.box_center() - data.wind_scroll(p)) / (fog.w * data.shape.yzw()) + data.noise_offset.truncate();
+    let variation = 1.0_f32.lerp(2.0 * mixed_noise(field, data, uvw), fog.z);
+    let mut coverage = data.shape.x;
+    if data.weather_map.z > 0.0 {
+        let uv = (data.to_world(p).xz() - data.weather_map.xy()) * data.weather_map.zw();
+        coverage *= field.weather_coverage(uv);
+    }
+    falloff * variation * coverage * data.settings.x
+}
+
+/// How far the anvil has taken over at a box height, as `anvil_weight` in
+/// `cloud_density.wgsl`.
+fn anvil_weight(data: &CloudMaterialUniform, height: f32) -> f32 {
+    let start = data.anvil.y;
+    data.anvil.x * smoothstep(start, start.lerp(1.0, 0.5), height)
+}
+
+/// Where `p` samples the noise under an anvil, as `anvil_position` in
+/// `cloud_density.wgsl`.
+fn anvil_position(field: &DensityField, p: Vec3, weight: f32) -> Vec3 {
+    let radial = p.xz();
+    let distance = radial.length();
+    if weight <= 0.0 || distance < 1e-4 {
+        return p;
+    }
+    let ring = (radial / distance * 0.25 + 0.5).extend(0.5);
+    let variation = field.sample(ring);
+    let spread = 1.0 + weight * ANVIL_SPREAD * 0.5_f32.lerp(1.5, variation);
+    let pulled = radial / spread;
+    Vec3::new(pulled.x, p.y, pulled.y)
+}
+
+/// Coverage along a `CloudTrail` at a world position, as `trail_coverage` in
+/// `cloud_density.wgsl`.
+fn trail_coverage(data: &CloudMaterialUniform, world: Vec3) -> f32 {
+    let count = (data.trail_info.x as usize).min(data.trail.len());
+    (0..count)
+        .map(|i| {
+            let (a, b) = (data.trail[i], data.trail[(i + 1).min(count - 1)]);
+            let ab = (b - a).truncate();
+            let t = ((world - a.truncate()).dot(ab) / ab.length_squared().max(1e-8)).clamp(0.0, 1.0);
+            let distance = (world - a.truncate() - ab * t).length();
+            a.w.lerp(b.w, t) * (1.0 - smoothstep(0.0, data.trail_info.y, distance))
+        })
+        .fold(0.0, f32::max)
+}
+
+/// The noise recombined by the FBM mix, as `mixed_noise` in
+/// `cloud_density.wgsl`.
+fn mixed_noise(field: &DensityField, data: &CloudMaterialUniform, uvw: Vec3) -> f32 {
+    if data.fbm_weight.x >= 1.0 && data.fbm_scale.x == 1.0 {
+        return field.sample(uvw);
+    }
+    (0..4)
+        .filter(|&i| data.fbm_weight[i] > 0.0)
+        .map(|i| field.sample(uvw * data.fbm_scale[i]) * data.fbm_weight[i])
+        .sum()
+}
+
+/// The volume's mirror and turn of the noise domain, as `to_domain` in
+/// `cloud_density.wgsl`.
+fn to_domain(data: &CloudMaterialUniform, v: Vec3) -> Vec3 {
+    let domain = data.noise_domain;
+    let x = v.x * domain.z;
+    Vec3::new(domain.x * x - domain.y * v.z, v.y, domain.y * x + domain.x * v.z)
+}
+
+/// WGSL's `smoothstep`.
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Optical depth (density × absorption × distance) along a segment.
+pub fn optical_depth(
+    field: &DensityField,
+    data: &CloudMaterialUniform,
+    start: Vec3,
+    end: Vec3,
+    steps: u32,
+) -> f32 {
+    let steps = steps.max(1);
+    let step = (end - start) / steps as f32;
+    let step_length = step.length();
+    (0..steps)
+        .map(|i| {
+            let p = start + step * (i as f32 + 0.5);
+            sample_density(field, data, p) * step_length * data.settings.z
+        })
+        .sum()
+}
+
+/// Entry and exit distances of a ray through an axis-aligned box, if it hits.
+pub fn ray_box(origin: Vec3, dir: Vec3, box_min: Vec3, box_max: Vec3) -> Option<(f32, f32)> {
+    let inv = dir.recip();
+    let t0 = (box_min - origin) * inv;
+    let t1 = (box_max - origin) * inv;
+    let near = t0.min(t1).max_element().max(0.0);
+    let far = t0.max(t1).min_element();
+    (near < far).then_some((near, far))
+}
+
+/// `ray_box` against a volume's rotated box grown by the outset, for a
+/// world-space ray.
+#[cfg(feature = "ui")]
+pub fn volume_ray_box(data: &CloudMaterialUniform, origin: Vec3, dir: Vec3) -> Option<(f32, f32)> {
+    let outset = data.bounds.y.max(0.0);
+    ray_box(
+        data.to_volume(origin),
+        data.dir_to_volume(dir),
+        -data.half_extents() - outset,
+        data.half_extents() + outset,
+    )
+}
+
+/// First point along a ray where the volume has any density, found by
+/// stepping through its bounds.
+#[cfg(feature = "ui")]
+pub fn first_hit(
+    field: &DensityField,
+    data: &CloudMaterialUniform,
+    origin: Vec3,
+    dir: Vec3,
+    steps: u32,
+) -> Option<Vec3> {
+    let dir = dir.normalize_or_zero();
+    let (near, far) = volume_ray_box(data, origin, dir)?;
+    let steps = steps.max(1);
+    let step = (far - near) / steps as f32;
+    (0..steps)
+        .map(|i| origin + dir * (near + step * (i as f32 + 0.5)))
+        .find(|p| sample_density(field, data, *p) > 0.0)
+}
+
+/// Result of marching a ray through one volume.
+#[cfg(feature = "ui")]
+#[derive(Clone, Copy, Debug)]
+pub struct RayMarch {
+    /// Ray distance where transmittance first fell to the threshold.
+    pub crossing: Option<f32>,
+    /// Transmittance after the full traversal, including `start`.
+    pub transmittance: f32,
+}
+
+/// Beer-Lambert march through a volume's bounds starting from an incoming
+/// transmittance, as the fragment shader does. `None` if the ray misses.
+#[cfg(feature = "ui")]
+pub fn march_transmittance(
+    field: &DensityField,
+    data: &CloudMaterialUniform,
+    origin: Vec3,
+    dir: Vec3,
+    steps: u32,
+    start: f32,
+    threshold: f32,
+) -> Option<RayMarch> {
+    let dir = dir.normalize_or_zero();
+    let (near, far) = volume_ray_box(data, origin, dir)?;
+    let steps = steps.max(1);
+    let step = (far - near) / steps as f32;
+    let mut march = RayMarch {
+        crossing: (start <= threshold).then_some(near),
+        transmittance: start,
+    };
+    for i in 0..steps {
+        let t = near + step * (i as f32 + 0.5);
+        let density = sample_density(field, data, origin + dir * t);
+        march.transmittance *= (-density * step * data.settings.z).exp();
+        if march.crossing.is_none() && march.transmittance <= threshold {
+            march.crossing = Some(t);
+        }
+    }
+    Some(march)
+}
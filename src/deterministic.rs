@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{CloudSettings, CloudUiSet};
+
+/// Advances time by a fixed step per rendered frame instead of the wall
+/// clock, so wind, weather, time of day and every other `Time` consumer
+/// evolve identically from run to run. Captures with the same settings and
+/// frame count are then byte-identical.
+pub struct DeterministicTimePlugin;
+
+impl Plugin for DeterministicTimePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DeterministicTime>()
+            .add_systems(Update, deterministic_time_ui_system.in_set(CloudUiSet))
+            // Applied at the end of the frame, so the next `Time` update uses it.
+            .add_systems(Last, time_strategy_system)
+            .add_systems(First, frame_counter_system);
+    }
+}
+
+/// Virtual clock for captures. Capture code calls `start` before its first
+/// frame and reads `frame` to know how far the sequence has advanced.
+#[derive(Resource, Clone, Debug)]
+pub struct DeterministicTime {
+    pub enabled: bool,
+    /// Simulated frames per second; each rendered frame advances `1 / frame_rate`.
+    pub frame_rate: f32,
+    /// Frames rendered since `start`.
+    pub frame: u64,
+}
+
+impl Default for DeterministicTime {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frame_rate: 30.0,
+            frame: 0,
+        }
+    }
+}
+
+impl DeterministicTime {
+    pub fn delta(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.frame_rate.max(1.0) as f64)
+    }
+
+    /// Switches to the fixed step and rewinds the frame counter.
+    pub fn start(&mut self) {
+        self.enabled = true;
+        self.frame = 0;
+    }
+
+    /// Simulated seconds since `start`.
+    pub fn elapsed(&self) -> Duration {
+        self.delta().mul_f64(self.frame as f64)
+    }
+}
+
+fn time_strategy_system(deterministic: Res<DeterministicTime>, mut strategy: ResMut<TimeUpdateStrategy>) {
+    if !deterministic.is_changed() {
+        return;
+    }
+    *strategy = if deterministic.enabled {
+        TimeUpdateStrategy::ManualDuration(deterministic.delta())
+    } else {
+        TimeUpdateStrategy::Automatic
+    };
+}
+
+fn frame_counter_system(mut deterministic: ResMut<DeterministicTime>) {
+    if deterministic.enabled {
+        // Bypass change detection so the strategy is not reapplied every frame.
+        deterministic.bypass_change_detection().frame += 1;
+    }
+}
+
+fn deterministic_time_ui_system(
+    mut contexts: EguiContexts,
+    mut deterministic: ResMut<DeterministicTime>,
+    mut settings: ResMut<CloudSettings>,
+) {
+    egui::Window::new("Time Step")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let mut enabled = deterministic.enabled;
+            if ui.checkbox(&mut enabled, "Fixed Step").changed() {
+                deterministic.enabled = enabled;
+            }
+            let mut frame_rate = deterministic.frame_rate;
+            if ui
+                .add(egui::Slider::new(&mut frame_rate, 1.0..=120.0).text("Frames per Second"))
+                .changed()
+            {
+                deterministic.frame_rate = frame_rate;
+            }
+            if ui
+                .button("Restart")
+                .on_hover_text("Start the fixed step from frame 0 with the wind scroll reset")
+                .clicked()
+            {
+                deterministic.start();
+                settings.wind_offset = Vec3::ZERO;
+                settings.wind_offset_upper = Vec3::ZERO;
+            }
+            if deterministic.enabled {
+                ui.label(format!(
+                    "Frame {} ({:.2} s)",
+                    deterministic.frame,
+                    deterministic.elapsed().as_secs_f32()
+                ));
+            }
+        });
+}
@@ -0,0 +1,238 @@
+//! Writes baked noise volumes and captured skyboxes as KTX2 files a game can
+//! load directly.
+//!
+//! Compressed volumes are stored as 4x4 blocks per Z slice. Sampling a
+//! block-compressed 3D texture needs `WgpuFeatures::TEXTURE_COMPRESSION_BC_SLICED_3D`
+//! on the loading side.
+
+use std::{fs, path::Path};
+
+use bevy::prelude::*;
+use intel_tex_2::{bc5, bc7, RgSurface, RgbaSurface};
+
+use crate::noise;
+
+const KTX2_IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+const HEADER_LENGTH: usize = 12 + 9 * 4 + 4 * 4 + 2 * 8;
+const LEVEL_INDEX_LENGTH: usize = 3 * 8;
+
+/// Storage format of an exported volume. Every format keeps both channels of
+/// the bake, the noise in red and the ambient visibility in green, so
+/// `CloudSettings::ao_strength` works on loaded volumes as on fresh bakes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// Uncompressed `Rg8Unorm`.
+    None,
+    /// Two channels, 16 bytes per 4x4 block.
+    Bc5,
+    /// RGBA with blue empty and alpha opaque, 16 bytes per 4x4 block.
+    Bc7,
+}
+
+impl Compression {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" | "rg8" => Some(Self::None),
+            "bc5" => Some(Self::Bc5),
+            "bc7" => Some(Self::Bc7),
+            _ => None,
+        }
+    }
+
+    fn vk_format(self) -> u32 {
+        match self {
+            Self::None => 16,  // VK_FORMAT_R8G8_UNORM
+            Self::Bc5 => 141,  // VK_FORMAT_BC5_UNORM_BLOCK
+            Self::Bc7 => 145,  // VK_FORMAT_BC7_UNORM_BLOCK
+        }
+    }
+
+    /// Bytes per texel block and its edge length in texels.
+    fn block(self) -> (u32, u32) {
+        match self {
+            Self::None => (2, 1),
+            Self::Bc5 => (16, 4),
+            Self::Bc7 => (16, 4),
+        }
+    }
+}
+
+/// Encodes RG8 volume data (X fastest, then Y, then Z) slice by slice.
+pub fn compress_volume(data: &[u8], size: UVec3, compression: Compression) -> Result<Vec<u8>, String> {
+    let (_, block) = compression.block();
+    if !size.x.is_multiple_of(block) || !size.y.is_multiple_of(block) {
+        return Err(format!(
+            "{:?} needs width and height in multiples of {}, got {}x{}",
+            compression, block, size.x, size.y
+        ));
+    }
+    let slice_len = (size.x * size.y) as usize * noise::NOISE_CHANNELS;
+    if data.len() < slice_len * size.z as usize {
+        return Err("volume data is shorter than its size".to_string());
+    }
+
+    let mut out = Vec::new();
+    for slice in data.chunks_exact(slice_len).take(size.z as usize) {
+        match compression {
+            Compression::None => out.extend_from_slice(slice),
+            Compression::Bc5 => out.extend(bc5::compress_blocks(&RgSurface {
+                width: size.x,
+                height: size.y,
+                stride: size.x * 2,
+                data: slice,
+            })),
+            Compression::Bc7 => {
+                let rgba: Vec<u8> = slice.chunks_exact(2).flat_map(|rg| [rg[0], rg[1], 0, 255]).collect();
+                out.extend(bc7::compress_blocks(
+                    &bc7::opaque_basic_settings(),
+                    &RgbaSurface {
+                        width: size.x,
+                        height: size.y,
+                        stride: size.x * 4,
+                        data: &rgba,
+                    },
+                ));
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Writes RG8 volume data as a single-level 3D KTX2 texture.
+pub fn write_ktx2(path: &Path, data: &[u8], size: UVec3, compression: Compression) -> Result<(), String> {
+    let level = compress_volume(data, size, compression)?;
+    let (block_bytes, _) = compression.block();
+    let header = Ktx2Header {
+        vk_format: compression.vk_format(),
+        type_size: 1,
+        size,
+        faces: 1,
+        block_bytes,
+    };
+    write_file(path, &header, &data_format_descriptor(compression), &level)
+}
+
+/// Writes six `Rgba16Float` faces, each `size` squared texels in rows, as a
+/// single-level KTX2 cubemap for Bevy's `Skybox`. Faces are in KTX2 order:
+/// +X, -X, +Y, -Y, +Z, -Z.
+pub fn write_ktx2_cubemap(path: &Path, faces: &[Vec<u8>; 6], size: u32) -> Result<(), String> {
+    let face_len = size as usize * size as usize * 8;
+    if faces.iter().any(|face| face.len() != face_len) {
+        return Err(format!("cubemap faces must hold {}x{} RGBA16F texels", size, size));
+    }
+    let header = Ktx2Header {
+        vk_format: 97, // VK_FORMAT_R16G16B16A16_SFLOAT
+        type_size: 2,
+        // Cubemaps are 2D: no depth.
+        size: UVec3::new(size, size, 0),
+        faces: 6,
+        block_bytes: 8,
+    };
+    write_file(path, &header, &rgba16f_data_format_descriptor(), &faces.concat())
+}
+
+struct Ktx2Header {
+    vk_format: u32,
+    type_size: u32,
+    size: UVec3,
+    faces: u32,
+    /// Bytes per texel block.
+    block_bytes: u32,
+}
+
+/// Writes a single-level texture, its faces one after another in `level`.
+fn write_file(path: &Path, header: &Ktx2Header, dfd: &[u8], level: &[u8]) -> Result<(), String> {
+    let dfd_offset = HEADER_LENGTH + LEVEL_INDEX_LENGTH;
+    // Level data must be aligned to lcm(block size, 4).
+    let alignment = (header.block_bytes as usize).max(4);
+    let level_offset = (dfd_offset + dfd.len()).next_multiple_of(alignment);
+
+    let mut out = Vec::with_capacity(level_offset + level.len());
+    out.extend_from_slice(&KTX2_IDENTIFIER);
+    for value in [
+        header.vk_format,
+        header.type_size,
+        header.size.x,
+        header.size.y,
+        header.size.z,
+        0, // layerCount: not an array
+        header.faces,
+        1, // levelCount
+        0, // supercompressionScheme
+    ] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    // Index: DFD, then empty key/value and supercompression data.
+    for value in [dfd_offset as u32, dfd.len() as u32, 0, 0] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&0u64.to_le_bytes());
+    out.extend_from_slice(&0u64.to_le_bytes());
+    // Level index.
+    for value in [level_offset as u64, level.len() as u64, level.len() as u64] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(dfd);
+    out.resize(level_offset, 0);
+    out.extend_from_slice(level);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    fs::write(path, out).map_err(|err| err.to_string())
+}
+
+/// Khronos basic data format descriptor for an exported volume: a sample per
+/// channel, or a single one for BC7's whole block.
+fn data_format_descriptor(compression: Compression) -> Vec<u8> {
+    // (color model, block dimensions - 1, bytes per plane, samples, sample bit length - 1, sample upper)
+    let (model, block_dim, bytes_plane, samples, bit_length, upper): (u8, u8, u8, u16, u8, u32) = match compression {
+        Compression::None => (1, 0, 2, 2, 7, 255),         // KHR_DF_MODEL_RGBSDA, red and green
+        Compression::Bc5 => (132, 3, 16, 2, 63, u32::MAX), // KHR_DF_MODEL_BC5, red and green
+        Compression::Bc7 => (134, 3, 16, 1, 127, u32::MAX), // KHR_DF_MODEL_BC7
+    };
+    let block_size: u16 = 24 + 16 * samples;
+
+    let mut dfd = Vec::with_capacity(4 + block_size as usize);
+    dfd.extend_from_slice(&(4 + block_size as u32).to_le_bytes()); // dfdTotalSize
+    dfd.extend_from_slice(&0u32.to_le_bytes()); // vendor 0 (Khronos), descriptor type 0 (basic)
+    dfd.extend_from_slice(&2u16.to_le_bytes()); // version
+    dfd.extend_from_slice(&block_size.to_le_bytes());
+    dfd.extend_from_slice(&[model, 1, 1, 0]); // BT.709 primaries, linear transfer, straight alpha
+    dfd.extend_from_slice(&[block_dim, block_dim, 0, 0]);
+    dfd.extend_from_slice(&[bytes_plane, 0, 0, 0, 0, 0, 0, 0]);
+    // Samples: bit offset, bit length, channel (0 red / data, 1 green),
+    // position, range.
+    for channel in 0..samples {
+        dfd.extend_from_slice(&(channel * (bit_length as u16 + 1)).to_le_bytes());
+        dfd.extend_from_slice(&[bit_length, channel as u8]);
+        dfd.extend_from_slice(&[0, 0, 0, 0]);
+        dfd.extend_from_slice(&0u32.to_le_bytes());
+        dfd.extend_from_slice(&upper.to_le_bytes());
+    }
+    dfd
+}
+
+/// Basic data format descriptor for linear `R16G16B16A16_SFLOAT`.
+fn rgba16f_data_format_descriptor() -> Vec<u8> {
+    let block_size: u16 = 24 + 4 * 16;
+
+    let mut dfd = Vec::with_capacity(4 + block_size as usize);
+    dfd.extend_from_slice(&(4 + block_size as u32).to_le_bytes()); // dfdTotalSize
+    dfd.extend_from_slice(&0u32.to_le_bytes()); // vendor 0 (Khronos), descriptor type 0 (basic)
+    dfd.extend_from_slice(&2u16.to_le_bytes()); // version
+    dfd.extend_from_slice(&block_size.to_le_bytes());
+    dfd.extend_from_slice(&[1, 1, 1, 0]); // KHR_DF_MODEL_RGBSDA, BT.709 primaries, linear transfer, straight alpha
+    dfd.extend_from_slice(&[0, 0, 0, 0]);
+    dfd.extend_from_slice(&[8, 0, 0, 0, 0, 0, 0, 0]);
+    // Samples: red, green, blue, alpha (channel 15), each a signed float half
+    // ranging over -1..1.
+    for (index, channel) in [0u8, 1, 2, 15].into_iter().enumerate() {
+        dfd.extend_from_slice(&(index as u16 * 16).to_le_bytes());
+        dfd.extend_from_slice(&[15, channel | 0xC0]);
+        dfd.extend_from_slice(&[0, 0, 0, 0]);
+        dfd.extend_from_slice(&(-1.0f32).to_bits().to_le_bytes());
+        dfd.extend_from_slice(&1.0f32.to_bits().to_le_bytes());
+    }
+    dfd
+}
@@ -0,0 +1,329 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    core_pipeline::tonemapping::Tonemapping,
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+    },
+};
+use bevy_atmosphere::prelude::AtmosphereCamera;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    accumulation::CloudAccumulation,
+    capture::{self, Captured},
+    refine::ProgressiveRefinement,
+    resize::CloudTargetSize,
+    view_overrides::CloudViewOverrides,
+    CloudUiSet, OrbitCamera,
+};
+
+const CAPTURE_DIR: &str = "captures";
+/// Frames the capture camera renders before the readback, so the target
+/// exists on the GPU and the views are prepared.
+const WARMUP_FRAMES: u32 = 2;
+/// Linear value of the grey reference quad.
+const REFERENCE_GREY: f32 = 0.18;
+
+/// Linear HDR stills for compositing.
+///
+/// An offscreen HDR camera copies the main camera's view into an
+/// `Rgba16Float` target without tonemapping, and the readback is written as
+/// an OpenEXR file with 32-bit float channels. With a transparent background
+/// the sky is left out and alpha is the clouds' coverage; the color is then
+/// premultiplied, as EXR expects.
+pub struct ExrCapturePlugin;
+
+impl Plugin for ExrCapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ExrCaptureSettings>()
+            .init_resource::<ExrCapture>()
+            .add_systems(
+                Update,
+                (exr_ui_system.in_set(CloudUiSet), reference_quad_system, exr_capture_system).chain(),
+            );
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default)]
+pub struct ExrCaptureSettings {
+    pub transparent: bool,
+    /// Shows an unlit quad at linear 0.18, whose captured value is reported.
+    pub reference_quad: bool,
+    /// Holds a requested capture until progressive refinement has converged.
+    pub wait_for_refinement: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct ExrCapture {
+    requested: bool,
+    in_flight: Option<InFlight>,
+    pub status: Option<String>,
+}
+
+struct InFlight {
+    camera: Entity,
+    target: Handle<Image>,
+    size: UVec2,
+    frames: u32,
+    /// Frames averaged after the warmup, as many as the main view had.
+    samples: u32,
+    readback: Option<Entity>,
+    /// Pixel of the reference quad's center in the capture.
+    reference_pixel: Option<UVec2>,
+}
+
+#[derive(Component)]
+struct ReferenceQuad;
+
+fn reference_quad_system(
+    mut commands: Commands,
+    settings: Res<ExrCaptureSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    quads: Query<Entity, With<ReferenceQuad>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    match (settings.reference_quad, quads.is_empty()) {
+        (true, true) => {
+            commands.spawn((
+                Mesh3d(meshes.add(Rectangle::new(0.8, 0.8))),
+                MeshMaterial3d(materials.add(StandardMaterial {
+                    base_color: Color::linear_rgb(REFERENCE_GREY, REFERENCE_GREY, REFERENCE_GREY),
+                    unlit: true,
+                    cull_mode: None,
+                    ..default()
+                })),
+                Transform::from_xyz(-2.2, 1.0, 0.0),
+                ReferenceQuad,
+            ));
+        }
+        (false, false) => {
+            for entity in &quads {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        _ => {}
+    }
+}
+
+type MainCamera<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Camera,
+        &'static GlobalTransform,
+        &'static Transform,
+        &'static Projection,
+        Option<&'static CloudViewOverrides>,
+    ),
+    With<OrbitCamera>,
+>;
+
+#[allow(clippy::too_many_arguments)]
+fn exr_capture_system(
+    mut commands: Commands,
+    mut capture: ResMut<ExrCapture>,
+    settings: Res<ExrCaptureSettings>,
+    target_size: Res<CloudTargetSize>,
+    mut images: ResMut<Assets<Image>>,
+    main_camera: MainCamera,
+    quads: Query<&GlobalTransform, With<ReferenceQuad>>,
+    refinement: Option<Res<ProgressiveRefinement>>,
+) {
+    let Ok((camera, camera_transform, transform, projection, overrides)) = main_camera.get_single() else {
+        return;
+    };
+
+    if capture.requested && capture.in_flight.is_none() {
+        if settings.wait_for_refinement && refinement.as_ref().is_some_and(|refinement| !refinement.converged()) {
+            capture.status = Some("Waiting for refinement...".to_string());
+            return;
+        }
+        // The main view's average lives in its own history, so the capture
+        // camera averages as many frames of its own.
+        let samples = refinement
+            .filter(|_| settings.wait_for_refinement)
+            .map_or(0, |refinement| refinement.samples());
+        capture.requested = false;
+        let size = target_size.physical;
+        let mut image = Image::new_fill(
+            Extent3d {
+                width: size.x,
+                height: size.y,
+                ..default()
+            },
+            TextureDimension::D2,
+            &[0; 8],
+            TextureFormat::Rgba16Float,
+            RenderAssetUsages::default(),
+        );
+        image.texture_descriptor.usage =
+            TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT;
+        let target = images.add(image);
+        let mut entity = commands.spawn((
+            Camera3d::default(),
+            Camera {
+                target: RenderTarget::Image(target.clone()),
+                order: -3,
+                hdr: true,
+                clear_color: if settings.transparent {
+                    ClearColorConfig::Custom(Color::NONE)
+                } else {
+                    ClearColorConfig::Default
+                },
+                ..default()
+            },
+            Tonemapping::None,
+            *transform,
+            projection.clone(),
+        ));
+        if !settings.transparent {
+            entity.insert(AtmosphereCamera::default());
+        }
+        // The view's refined step counts and scattering, or other overrides.
+        if let Some(overrides) = overrides {
+            entity.insert(*overrides);
+        }
+        if samples > 1 {
+            entity.insert(CloudAccumulation::default());
+        }
+        // The capture has the main view's size and projection, so the main
+        // camera's NDC maps straight onto it.
+        let reference_pixel = quads
+            .get_single()
+            .ok()
+            .and_then(|quad| camera.world_to_ndc(camera_transform, quad.translation()))
+            .filter(|ndc| ndc.x.abs() < 1.0 && ndc.y.abs() < 1.0)
+            .map(|ndc| {
+                let uv = Vec2::new(ndc.x + 1.0, 1.0 - ndc.y) * 0.5;
+                (uv * size.as_vec2()).as_uvec2().min(size - 1)
+            });
+        capture.in_flight = Some(InFlight {
+            camera: entity.id(),
+            target,
+            size,
+            frames: 0,
+            samples,
+            readback: None,
+            reference_pixel,
+        });
+        capture.status = Some("Capturing...".to_string());
+        return;
+    }
+
+    let Some(in_flight) = capture.in_flight.as_mut() else {
+        return;
+    };
+    in_flight.frames += 1;
+    if in_flight.samples > 1 {
+        commands.entity(in_flight.camera).insert(CloudAccumulation {
+            sample: in_flight.frames.saturating_sub(WARMUP_FRAMES),
+            hold: false,
+        });
+    }
+    if in_flight.frames == WARMUP_FRAMES + in_flight.samples.max(1) - 1 {
+        let readback = capture::read_back(&mut commands, in_flight.target.clone(), in_flight.size, 8, finish_capture);
+        in_flight.readback = Some(readback);
+    }
+}
+
+fn finish_capture(
+    trigger: Trigger<Captured>,
+    mut commands: Commands,
+    mut capture: ResMut<ExrCapture>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Some(in_flight) = capture.in_flight.take_if(|in_flight| in_flight.readback == Some(trigger.entity())) else {
+        return;
+    };
+    commands.entity(in_flight.camera).despawn_recursive();
+    images.remove(&in_flight.target);
+
+    let pixels = decode_rgba16f(&trigger.event().0);
+    let path = capture_path();
+    let mut status = match write_exr(&path, &pixels, in_flight.size) {
+        Ok(()) => format!("Wrote {}", path.display()),
+        Err(err) => {
+            error!("Failed to write {}: {}", path.display(), err);
+            format!("Export failed: {}", err)
+        }
+    };
+    if let Some(pixel) = in_flight.reference_pixel {
+        let index = (pixel.y * in_flight.size.x + pixel.x) as usize * 4;
+        status += &format!(
+            "\nReference quad: {:.3} (expected {:.3})",
+            pixels[index + 1],
+            REFERENCE_GREY
+        );
+    }
+    capture.status = Some(status);
+}
+
+fn capture_path() -> PathBuf {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+    Path::new(CAPTURE_DIR).join(format!("clouds_{}.exr", stamp))
+}
+
+/// RGBA floats from the captured pixels of an `Rgba16Float` texture.
+pub(crate) fn decode_rgba16f(data: &[u8]) -> Vec<f32> {
+    data.chunks_exact(2)
+        .map(|half| f16_to_f32(u16::from_le_bytes([half[0], half[1]])))
+        .collect()
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = (half >> 10) & 0x1f;
+    let mantissa = (half & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent as i32 - 15),
+    }
+}
+
+pub(crate) fn write_exr(path: &Path, pixels: &[f32], size: UVec2) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    }
+    let image = image::Rgba32FImage::from_raw(size.x, size.y, pixels.to_vec())
+        .ok_or_else(|| "readback is smaller than the capture".to_string())?;
+    image::DynamicImage::ImageRgba32F(image)
+        .save_with_format(path, image::ImageFormat::OpenExr)
+        .map_err(|err| err.to_string())
+}
+
+fn exr_ui_system(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<ExrCaptureSettings>,
+    mut capture: ResMut<ExrCapture>,
+) {
+    egui::Window::new("EXR Export")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("Linear HDR still of the main view, before tonemapping.");
+            ui.checkbox(&mut settings.transparent, "Transparent Background")
+                .on_hover_text("Leave out the sky; alpha is the cloud coverage");
+            ui.checkbox(&mut settings.reference_quad, "Grey Reference Quad")
+                .on_hover_text("Unlit quad at linear 0.18 to check the exported values");
+            ui.checkbox(&mut settings.wait_for_refinement, "Wait for Refinement")
+                .on_hover_text("Capture once progressive refinement has converged; hold the camera still");
+            let busy = capture.requested || capture.in_flight.is_some();
+            if ui.add_enabled(!busy, egui::Button::new("Capture EXR")).clicked() {
+                capture.requested = true;
+            }
+            if let Some(status) = &capture.status {
+                ui.label(status);
+            }
+        });
+}
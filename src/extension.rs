@@ -0,0 +1,197 @@
+use bevy::{
+    asset::load_internal_asset,
+    pbr::{ExtendedMaterial, MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline, NotShadowCaster},
+    prelude::*,
+    render::{
+        mesh::MeshVertexBufferLayoutRef,
+        render_resource::{AsBindGroup, Face, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError},
+    },
+};
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    spawn::{CloudVolumeParams, SpawnCloudExt},
+    view_overrides, CloudMaterial, CloudMaterialUniform, CloudSettings, CloudVolume,
+};
+#[cfg(feature = "ui")]
+use crate::CloudUiSet;
+
+const CLOUD_EXTENSION_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(0x6e0a_37c4_51b8_4d92_9f1c_a8d7_2e64_b503);
+
+/// A cloud material layered on `StandardMaterial`, lit by Bevy's directional
+/// lights, shadow maps and ambient light instead of the artistic sun of
+/// `CloudMaterial`.
+pub type LitCloudMaterial = ExtendedMaterial<StandardMaterial, CloudExtension>;
+
+pub struct CloudExtensionPlugin;
+
+impl Plugin for CloudExtensionPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            CLOUD_EXTENSION_SHADER_HANDLE,
+            "../assets/shaders/cloud_extension.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugins(MaterialPlugin::<LitCloudMaterial>::default())
+            .init_resource::<LitCompareDemo>()
+            .add_systems(
+                Update,
+                (lit_demo_system, sync_lit_materials_system.after(crate::update_material_system)).chain(),
+            );
+        #[cfg(feature = "ui")]
+        app.add_systems(Update, lit_demo_ui_system.in_set(CloudUiSet).before(lit_demo_system));
+    }
+}
+
+/// Raymarch parameters for `LitCloudMaterial`; the bindings follow those of
+/// `StandardMaterial`.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct CloudExtension {
+    #[uniform(100)]
+    pub data: CloudMaterialUniform,
+    #[texture(101, dimension = "3d")]
+    #[sampler(102)]
+    pub noise_texture: Handle<Image>,
+    #[texture(103)]
+    #[sampler(104)]
+    pub profile_lut: Handle<Image>,
+    #[texture(105)]
+    #[sampler(106)]
+    pub mask_texture: Handle<Image>,
+    #[texture(107, dimension = "3d")]
+    pub occupancy: Option<Handle<Image>>,
+    #[texture(108)]
+    #[sampler(109)]
+    pub weather_map: Option<Handle<Image>>,
+}
+
+impl CloudExtension {
+    /// A lit material with the same parameters as `CloudMaterial::new`.
+    pub fn material(settings: &CloudSettings) -> LitCloudMaterial {
+        let unlit = CloudMaterial::new(settings);
+        ExtendedMaterial {
+            base: StandardMaterial {
+                alpha_mode: AlphaMode::Blend,
+                // Back faces only, as for `CloudMaterial`.
+                cull_mode: Some(Face::Front),
+                ..default()
+            },
+            extension: Self {
+                data: unlit.data,
+                noise_texture: unlit.noise_texture,
+                profile_lut: unlit.profile_lut,
+                mask_texture: unlit.mask_texture,
+                occupancy: unlit.occupancy,
+                weather_map: unlit.weather_map,
+            },
+        }
+    }
+}
+
+impl MaterialExtension for CloudExtension {
+    fn fragment_shader() -> ShaderRef {
+        CLOUD_EXTENSION_SHADER_HANDLE.into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        view_overrides::specialize_view(descriptor);
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment.shader_defs.push("CLOUD_EXTENSION".into());
+            // Lit volumes are few, so they keep one pipeline and check these
+            // features at run time instead of keying on them.
+            for def in ["CLOUD_DETAIL_NOISE", "CLOUD_WEATHER_MAP", "CLOUD_STEP_JITTER"] {
+                fragment.shader_defs.push(def.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Mirrors `sync_cloud_materials` for lit volumes.
+fn sync_lit_materials_system(
+    settings: Res<CloudSettings>,
+    mut materials: ResMut<Assets<LitCloudMaterial>>,
+    volumes: Query<(&CloudVolume, &GlobalTransform, &MeshMaterial3d<LitCloudMaterial>)>,
+) {
+    for (volume, transform, handle) in &volumes {
+        let Some(material) = materials.get(&handle.0) else {
+            continue;
+        };
+        let mut data = material.extension.data.clone();
+        data.sync(&settings, volume, transform);
+        data.opacity = 1.0;
+        if data != material.extension.data {
+            if let Some(material) = materials.get_mut(&handle.0) {
+                material.extension.data = data;
+            }
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct LitCompareDemo {
+    enabled: bool,
+}
+
+/// One volume of each kind, spawned side by side by the demo toggle.
+#[derive(Component)]
+struct LitCompareVolume;
+
+#[allow(clippy::type_complexity)]
+fn lit_demo_system(
+    mut commands: Commands,
+    demo: Res<LitCompareDemo>,
+    settings: Res<CloudSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut unlit_materials: ResMut<Assets<CloudMaterial>>,
+    mut lit_materials: ResMut<Assets<LitCloudMaterial>>,
+    spawned: Query<(Entity, Option<&MeshMaterial3d<CloudMaterial>>, Option<&MeshMaterial3d<LitCloudMaterial>>), With<LitCompareVolume>>,
+) {
+    if !demo.is_changed() {
+        return;
+    }
+    match (demo.enabled, spawned.is_empty()) {
+        (true, true) => {
+            commands
+                .spawn_cloud(CloudVolumeParams::default().at(Vec3::new(-3.0, 1.0, -4.0)))
+                .insert(LitCompareVolume);
+            commands.spawn((
+                Mesh3d(meshes.add(Cuboid::new(2.0, 2.0, 2.0))),
+                MeshMaterial3d(lit_materials.add(CloudExtension::material(&settings))),
+                Transform::from_xyz(3.0, 1.0, -4.0),
+                CloudVolume::default(),
+                NotShadowCaster,
+                LitCompareVolume,
+            ));
+        }
+        (false, false) => {
+            for (entity, unlit, lit) in &spawned {
+                if let Some(unlit) = unlit {
+                    unlit_materials.remove(&unlit.0);
+                }
+                if let Some(lit) = lit {
+                    lit_materials.remove(&lit.0);
+                }
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(feature = "ui")]
+fn lit_demo_ui_system(mut contexts: EguiContexts, mut demo: ResMut<LitCompareDemo>) {
+    egui::Window::new("Lit Material")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("CloudMaterial (left) next to the StandardMaterial extension (right).");
+            ui.checkbox(&mut demo.enabled, "Spawn Side by Side");
+        });
+}
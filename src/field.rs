@@ -0,0 +1,239 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use rand::Rng;
+
+use crate::{
+    seed_streams::{SeedStream, SeedStreams},
+    spawn::{CloudVolumeParams, SpawnCloudExt},
+    CloudMaterial, CloudUiSet, CloudVolume,
+};
+
+/// Scatters instanced cloud volumes over an area. Every volume shares the
+/// global noise texture, so memory stays flat regardless of `count`.
+pub struct CloudFieldPlugin;
+
+impl Plugin for CloudFieldPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CloudFieldDescriptor>()
+            .init_resource::<CloudField>()
+            .add_systems(Update, (field_ui_system.in_set(CloudUiSet), cloud_field_system).chain());
+    }
+}
+
+/// Describes how a cloud field is scattered.
+#[derive(Resource, Clone, Debug)]
+pub struct CloudFieldDescriptor {
+    pub center: Vec2,     // XZ center of the scatter area
+    pub extents: Vec2,    // XZ half-size of the scatter area
+    pub count: u32,
+    pub seed: u64,
+    pub min_scale: f32,   // half-extent of the smallest volume
+    pub max_scale: f32,
+    pub min_altitude: f32,
+    pub max_altitude: f32,
+    pub min_spacing: f32, // minimum XZ distance between volume centers
+    pub density_jitter: f32,   // +/- fraction applied to `CloudVolume::density_scale`
+    pub threshold_jitter: f32, // +/- offset applied to `CloudVolume::threshold_offset`
+    /// Give each volume a random noise offset, turn and mirror, so volumes
+    /// sharing the bake stop looking alike.
+    pub vary_noise: bool,
+}
+
+impl Default for CloudFieldDescriptor {
+    fn default() -> Self {
+        Self {
+            center: Vec2::ZERO,
+            extents: Vec2::new(40.0, 40.0),
+            count: 50,
+            seed: 1,
+            min_scale: 2.0,
+            max_scale: 5.0,
+            min_altitude: 8.0,
+            max_altitude: 14.0,
+            min_spacing: 4.0,
+            density_jitter: 0.25,
+            threshold_jitter: 0.05,
+            vary_noise: true,
+        }
+    }
+}
+
+/// Marks a volume spawned by the field so it can be cleared again.
+#[derive(Component)]
+pub struct CloudFieldMember;
+
+/// Runtime state of the field; the UI sets the request flags.
+#[derive(Resource, Default)]
+pub struct CloudField {
+    pub active: bool,
+    pub needs_respawn: bool,
+    pub needs_clear: bool,
+}
+
+/// Picks positions and per-volume jitter for a field. Deterministic for a
+/// given descriptor. Fewer than `count` volumes are returned when the spacing
+/// constraint can't be satisfied within the area.
+pub fn scatter_cloud_field(desc: &CloudFieldDescriptor) -> Vec<(Transform, CloudVolume)> {
+    let streams = SeedStreams::new(desc.seed);
+    let mut rng = streams.rng(SeedStream::FieldLayout);
+    // A stream of its own, so a seed keeps its layout with variation on.
+    let mut variation_rng = streams.rng(SeedStream::FieldVariation);
+    let mut placed: Vec<(Transform, CloudVolume)> = Vec::with_capacity(desc.count as usize);
+    let max_attempts = desc.count as usize * 30;
+
+    let min_scale = desc.min_scale.min(desc.max_scale);
+    let max_scale = desc.min_scale.max(desc.max_scale);
+    let min_altitude = desc.min_altitude.min(desc.max_altitude);
+    let max_altitude = desc.min_altitude.max(desc.max_altitude);
+
+    for _ in 0..max_attempts {
+        if placed.len() >= desc.count as usize {
+            break;
+        }
+
+        let x = desc.center.x + rng.gen_range(-1.0..=1.0) * desc.extents.x;
+        let z = desc.center.y + rng.gen_range(-1.0..=1.0) * desc.extents.y;
+        let too_close = placed.iter().any(|(transform, _)| {
+            Vec2::new(transform.translation.x, transform.translation.z).distance(Vec2::new(x, z))
+                < desc.min_spacing
+        });
+        if too_close {
+            continue;
+        }
+
+        let scale = rng.gen_range(min_scale..=max_scale);
+        let y = rng.gen_range(min_altitude..=max_altitude);
+        let mut volume = CloudVolume {
+            density_scale: 1.0 + rng.gen_range(-1.0..=1.0) * desc.density_jitter,
+            threshold_offset: rng.gen_range(-1.0..=1.0) * desc.threshold_jitter,
+            ..default()
+        };
+        if desc.vary_noise {
+            volume.noise_offset = Vec3::new(variation_rng.gen(), variation_rng.gen(), variation_rng.gen());
+            volume.noise_angle = variation_rng.gen_range(0.0..TAU);
+            volume.noise_mirror = variation_rng.gen_bool(0.5);
+        }
+        placed.push((
+            Transform::from_xyz(x, y, z).with_scale(Vec3::splat(scale)),
+            volume,
+        ));
+    }
+
+    placed
+}
+
+fn cloud_field_system(
+    mut commands: Commands,
+    mut field: ResMut<CloudField>,
+    mut materials: ResMut<Assets<CloudMaterial>>,
+    desc: Res<CloudFieldDescriptor>,
+    members: Query<(Entity, &MeshMaterial3d<CloudMaterial>), With<CloudFieldMember>>,
+) {
+    if !field.needs_clear && !field.needs_respawn {
+        return;
+    }
+
+    for (entity, material) in &members {
+        materials.remove(&material.0);
+        commands.entity(entity).despawn_recursive();
+    }
+    field.needs_clear = false;
+
+    if !field.needs_respawn {
+        field.active = false;
+        return;
+    }
+    field.needs_respawn = false;
+    field.active = true;
+
+    for (transform, volume) in scatter_cloud_field(&desc) {
+        commands
+            .spawn_cloud(CloudVolumeParams { transform, volume })
+            .insert(CloudFieldMember);
+    }
+}
+
+fn field_ui_system(
+    mut contexts: EguiContexts,
+    mut desc: ResMut<CloudFieldDescriptor>,
+    mut field: ResMut<CloudField>,
+) {
+    egui::Window::new("Cloud Field")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let mut changed = false;
+            changed |= ui.add(egui::Slider::new(&mut desc.count, 1..=200).text("Count")).changed();
+            changed |= ui.add(egui::Slider::new(&mut desc.seed, 0..=100).text("Seed")).changed();
+            changed |= ui.add(egui::Slider::new(&mut desc.extents.x, 5.0..=200.0).text("Extent X")).changed();
+            changed |= ui.add(egui::Slider::new(&mut desc.extents.y, 5.0..=200.0).text("Extent Z")).changed();
+            changed |= ui.add(egui::Slider::new(&mut desc.min_scale, 0.5..=20.0).text("Min Scale")).changed();
+            changed |= ui.add(egui::Slider::new(&mut desc.max_scale, 0.5..=20.0).text("Max Scale")).changed();
+            changed |= ui.add(egui::Slider::new(&mut desc.min_altitude, 0.0..=100.0).text("Min Altitude")).changed();
+            changed |= ui.add(egui::Slider::new(&mut desc.max_altitude, 0.0..=100.0).text("Max Altitude")).changed();
+            changed |= ui.add(egui::Slider::new(&mut desc.min_spacing, 0.0..=50.0).text("Min Spacing")).changed();
+            changed |= ui.add(egui::Slider::new(&mut desc.density_jitter, 0.0..=1.0).text("Density Jitter")).changed();
+            changed |= ui.add(egui::Slider::new(&mut desc.threshold_jitter, 0.0..=0.5).text("Threshold Jitter")).changed();
+            changed |= ui
+                .checkbox(&mut desc.vary_noise, "Vary Noise")
+                .on_hover_text("Random noise offset, turn and mirror per volume, at no memory cost")
+                .changed();
+
+            // Re-scatter live while a field exists; otherwise wait for Create.
+            if changed && field.active {
+                field.needs_respawn = true;
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Create").clicked() {
+                    field.needs_respawn = true;
+                }
+                if ui.button("Clear").clicked() {
+                    field.needs_clear = true;
+                    field.needs_respawn = false;
+                }
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The same seed must scatter the same field every time, and turning the
+    // noise variation on or off must not move the volumes.
+
+    #[test]
+    fn a_seed_scatters_the_same_field_twice() {
+        let field = CloudFieldDescriptor::default();
+        assert_eq!(layout(&field), layout(&field));
+    }
+
+    #[test]
+    fn noise_variation_leaves_the_field_layout_alone() {
+        let field = CloudFieldDescriptor::default();
+        let varied = CloudFieldDescriptor {
+            vary_noise: !field.vary_noise,
+            ..field.clone()
+        };
+        assert_eq!(layout(&field), layout(&varied));
+    }
+
+    #[test]
+    fn another_seed_scatters_another_field() {
+        let field = CloudFieldDescriptor::default();
+        let reseeded = CloudFieldDescriptor {
+            seed: field.seed + 1,
+            ..field.clone()
+        };
+        assert_ne!(layout(&field), layout(&reseeded));
+    }
+
+    fn layout(desc: &CloudFieldDescriptor) -> Vec<Transform> {
+        scatter_cloud_field(desc)
+            .into_iter()
+            .map(|(transform, _)| transform)
+            .collect()
+    }
+}
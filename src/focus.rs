@@ -0,0 +1,213 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::EguiContexts;
+
+use crate::{
+    density::{first_hit, volume_ray_box, DensityField},
+    pick::CloudPickSettings,
+    shortcuts::{KeyBindings, UiState},
+    volume_visibility::CloudHidden,
+    weather_map::WeatherMap,
+    CloudMaterial, CloudSettings, CloudVolume, OrbitCamera,
+};
+
+const DOUBLE_CLICK_TIME: f32 = 0.3;
+const DOUBLE_CLICK_SLOP: f32 = 4.0; // pixels
+const FOCUS_DURATION: f32 = 0.3;
+const HIT_STEPS: u32 = 64;
+
+/// Re-centers the orbit camera: double-click a cloud to orbit around the
+/// point under the cursor, or press the frame key to fit the selected volume
+/// in view.
+pub struct CameraFocusPlugin;
+
+impl Plugin for CameraFocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraFocus>().add_systems(
+            Update,
+            (double_click_focus_system, frame_selected_system, focus_animation_system)
+                .chain()
+                .after(crate::update_material_system)
+                .before(crate::camera_control_system),
+        );
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct CameraFocus {
+    /// Volume last double-clicked; framed by `KeyBindings::frame_selected`.
+    pub selected: Option<Entity>,
+    animation: Option<FocusAnimation>,
+}
+
+struct FocusAnimation {
+    from_center: Vec3,
+    to_center: Vec3,
+    from_distance: f32,
+    to_distance: f32,
+    elapsed: f32,
+}
+
+impl CameraFocus {
+    /// Smoothly moves the orbit center (and optionally the distance) to a new
+    /// target, starting from the camera's current state.
+    pub fn focus_on(&mut self, orbit: &OrbitCamera, center: Vec3, distance: Option<f32>) {
+        self.animation = Some(FocusAnimation {
+            from_center: orbit.center,
+            to_center: center,
+            from_distance: orbit.distance,
+            to_distance: distance.unwrap_or(orbit.distance),
+            elapsed: 0.0,
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn double_click_focus_system(
+    time: Res<Time>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform, &OrbitCamera)>,
+    volumes: Query<(Entity, &MeshMaterial3d<CloudMaterial>, Has<CloudHidden>), With<CloudVolume>>,
+    pick: Res<CloudPickSettings>,
+    materials: Res<Assets<CloudMaterial>>,
+    images: Res<Assets<Image>>,
+    settings: Res<CloudSettings>,
+    weather_map: Res<WeatherMap>,
+    mut focus: ResMut<CameraFocus>,
+    mut contexts: EguiContexts,
+    ui_state: Res<UiState>,
+    mut last_click: Local<Option<(f32, Vec2)>>,
+) {
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    if ui_state.show_ui && contexts.try_ctx_mut().is_some_and(|ctx| ctx.is_pointer_over_area()) {
+        return;
+    }
+    let Some(cursor) = windows.get_single().ok().and_then(|window| window.cursor_position()) else {
+        return;
+    };
+    let now = time.elapsed_secs();
+    let is_double = last_click.is_some_and(|(at, position)| {
+        now - at <= DOUBLE_CLICK_TIME && position.distance(cursor) <= DOUBLE_CLICK_SLOP
+    });
+    *last_click = if is_double { None } else { Some((now, cursor)) };
+    if !is_double {
+        return;
+    }
+
+    let Ok((camera, camera_transform, orbit)) = cameras.get_single() else {
+        return;
+    };
+    let Ok(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+    let field = DensityField::from_settings(&images, &settings)
+        .map(|field| field.with_weather_map(images.get(&weather_map.image)));
+
+    // Closest density hit along the ray; failing that, the volume whose
+    // center passes nearest to the ray.
+    let mut best_hit: Option<(f32, Entity, Vec3)> = None;
+    let mut nearest: Option<(f32, Entity, Vec3)> = None;
+    for (entity, material, _) in volumes.iter().filter(|(_, _, hidden)| pick.include_hidden || !hidden) {
+        let Some(material) = materials.get(&material.0) else {
+            continue;
+        };
+        let data = &material.data;
+        let hit = match &field {
+            Some(field) => first_hit(field, data, ray.origin, *ray.direction, HIT_STEPS),
+            None => volume_ray_box(data, ray.origin, *ray.direction)
+                .map(|(near, _)| ray.origin + *ray.direction * near),
+        };
+        if let Some(point) = hit {
+            let t = (point - ray.origin).dot(*ray.direction);
+            if best_hit.is_none_or(|(best, ..)| t < best) {
+                best_hit = Some((t, entity, point));
+            }
+        }
+
+        let center = (data.box_min + data.box_max).truncate() * 0.5;
+        let along = (center - ray.origin).dot(*ray.direction).max(0.0);
+        let miss = center.distance(ray.origin + *ray.direction * along);
+        if nearest.is_none_or(|(best, ..)| miss < best) {
+            nearest = Some((miss, entity, center));
+        }
+    }
+
+    if let Some((_, entity, point)) = best_hit.or(nearest) {
+        focus.selected = Some(entity);
+        focus.focus_on(orbit, point, None);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn frame_selected_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    cameras: Query<(&OrbitCamera, &Projection)>,
+    volumes: Query<(Entity, &GlobalTransform), With<CloudVolume>>,
+    mut focus: ResMut<CameraFocus>,
+    mut contexts: EguiContexts,
+    ui_state: Res<UiState>,
+) {
+    if !keys.just_pressed(bindings.frame_selected) {
+        return;
+    }
+    if ui_state.show_ui && contexts.try_ctx_mut().is_some_and(|ctx| ctx.wants_keyboard_input()) {
+        return;
+    }
+    let Ok((orbit, projection)) = cameras.get_single() else {
+        return;
+    };
+
+    // Without a selection, frame the volume closest to the orbit center.
+    let target = focus
+        .selected
+        .and_then(|entity| volumes.get(entity).ok())
+        .or_else(|| {
+            volumes.iter().min_by(|(_, a), (_, b)| {
+                let a = a.translation().distance_squared(orbit.center);
+                let b = b.translation().distance_squared(orbit.center);
+                a.total_cmp(&b)
+            })
+        });
+    let Some((entity, transform)) = target else {
+        return;
+    };
+
+    // The cube mesh spans ±1, so the scale is the half-extent.
+    let (scale, _, translation) = transform.to_scale_rotation_translation();
+    let radius = scale.abs().length();
+    let fov = match projection {
+        Projection::Perspective(perspective) => perspective.fov,
+        _ => std::f32::consts::FRAC_PI_4,
+    };
+    let distance = radius / (fov * 0.5).sin() * 1.1;
+
+    focus.selected = Some(entity);
+    focus.focus_on(orbit, translation, Some(distance));
+}
+
+fn focus_animation_system(
+    time: Res<Time>,
+    mut focus: ResMut<CameraFocus>,
+    mut cameras: Query<&mut OrbitCamera>,
+) {
+    let Some(animation) = focus.animation.as_mut() else {
+        return;
+    };
+    let Ok(mut orbit) = cameras.get_single_mut() else {
+        focus.animation = None;
+        return;
+    };
+
+    animation.elapsed += time.delta_secs();
+    let t = (animation.elapsed / FOCUS_DURATION).min(1.0);
+    let t = t * t * (3.0 - 2.0 * t);
+    orbit.center = animation.from_center.lerp(animation.to_center, t);
+    orbit.distance = animation.from_distance.lerp(animation.to_distance, t);
+
+    if animation.elapsed >= FOCUS_DURATION {
+        focus.animation = None;
+    }
+}
@@ -0,0 +1,475 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bevy::{
+    app::ScheduleRunnerPlugin,
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, PipelineCache, TextureDimension, TextureFormat, TextureUsages},
+        renderer::RenderAdapterInfo,
+        settings::Backends,
+        Render, RenderApp, RenderSet,
+    },
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+
+use crate::{
+    bake_cache::BakeCache,
+    capture::{self, Captured},
+    cloud_sun::CloudSunPlugin,
+    cloud_type::CloudTypePlugin,
+    deterministic::{DeterministicTime, DeterministicTimePlugin},
+    extension::{CloudExtension, CloudExtensionPlugin},
+    material_sync::CloudMaterialSyncPlugin,
+    noise::{BakeBudget, BakeProgress, BakeScratch, BakeStats, NoiseBaked, PendingNoise},
+    shader::CloudShaderPlugin,
+    spawn::{CloudVolumeParams, SpawnCloudExt},
+    view_overrides::ViewOverridesPlugin,
+    CloudLightingMode, CloudMaterial, CloudSettings, CloudUiSet, CloudVolume,
+};
+
+/// Width and height of the captures. Rows of 512 RGBA8 texels are a multiple
+/// of the 256-byte copy alignment, so the readback has no row padding.
+pub(crate) const SIZE: UVec2 = UVec2::new(512, 288);
+/// Fixed-step frames rendered before each capture.
+const FRAMES: u32 = 8;
+/// Mean weighted difference per pixel, in 0..255, above which a capture fails.
+const MEAN_TOLERANCE: f64 = 1.0;
+/// Share of pixels allowed to differ by more than `PIXEL_TOLERANCE`.
+const OUTLIER_FRACTION: f64 = 0.005;
+/// Weighted per-pixel difference, in 0..255, counted as an outlier.
+const PIXEL_TOLERANCE: f64 = 12.0;
+
+const GOLDEN_DIR: &str = "goldens";
+
+/// One regression scene: settings on top of the defaults and a camera.
+#[derive(Clone, Copy)]
+struct GoldenCase {
+    name: &'static str,
+    tweak: fn(&mut CloudSettings),
+    camera: fn() -> Transform,
+    /// Ignored by the lit material, which has its own lighting.
+    lighting: CloudLightingMode,
+    /// Draw the volume with `LitCloudMaterial` instead of `CloudMaterial`.
+    /// Skipped on the GL backend, whose 16 texture slots the PBR bindings
+    /// plus the cloud textures overflow.
+    lit: bool,
+}
+
+pub(crate) fn outside() -> Transform {
+    Transform::from_xyz(-3.0, 3.0, 6.0).looking_at(Vec3::new(0.0, 1.0, 0.0), Vec3::Y)
+}
+
+/// From the sun's side, where the powder term and the extra lobes and octaves
+/// set the lighting modes apart.
+fn sunward() -> Transform {
+    Transform::from_xyz(3.0, 4.0, 3.0).looking_at(Vec3::new(0.0, 1.0, 0.0), Vec3::Y)
+}
+
+fn bright_sun(settings: &mut CloudSettings) {
+    settings.sun_intensity = 1.5;
+}
+
+const CASES: &[GoldenCase] = &[
+    GoldenCase {
+        name: "default",
+        tweak: |_| {},
+        camera: outside,
+        lighting: CloudLightingMode::Unlit,
+        lit: false,
+    },
+    GoldenCase {
+        name: "high_absorption",
+        tweak: |settings| settings.absorption = 8.0,
+        camera: outside,
+        lighting: CloudLightingMode::Unlit,
+        lit: false,
+    },
+    GoldenCase {
+        name: "inside_volume",
+        tweak: |_| {},
+        camera: || Transform::from_xyz(0.2, 1.0, 0.3).looking_at(Vec3::new(0.0, 1.0, -1.0), Vec3::Y),
+        lighting: CloudLightingMode::Unlit,
+        lit: false,
+    },
+    GoldenCase {
+        name: "unlit",
+        tweak: bright_sun,
+        camera: sunward,
+        lighting: CloudLightingMode::Unlit,
+        lit: false,
+    },
+    GoldenCase {
+        name: "single_scatter",
+        tweak: bright_sun,
+        camera: sunward,
+        lighting: CloudLightingMode::SingleScatter,
+        lit: false,
+    },
+    GoldenCase {
+        name: "multi_scatter",
+        tweak: bright_sun,
+        camera: sunward,
+        lighting: CloudLightingMode::MultiScatter,
+        lit: false,
+    },
+    GoldenCase {
+        name: "sun_lit",
+        tweak: |settings| {
+            settings.sun_intensity = 1.5;
+            settings.phase_g = 0.5;
+        },
+        camera: outside,
+        lighting: CloudLightingMode::Unlit,
+        lit: false,
+    },
+    GoldenCase {
+        name: "lit_material",
+        tweak: |_| {},
+        camera: outside,
+        lighting: CloudLightingMode::Unlit,
+        lit: true,
+    },
+];
+
+/// Headless regression run: `--golden [--update-goldens]`.
+///
+/// Renders every case offscreen with deterministic time, reads the frame back
+/// and compares it with `goldens/<case>.png`. Failing cases leave
+/// `<case>.actual.png` and `<case>.diff.png` next to the golden. With
+/// `--update-goldens` the captures replace the goldens instead.
+pub fn run(args: &[String]) -> AppExit {
+    let update = args.iter().any(|arg| arg == "--update-goldens");
+
+    let mut app = headless_app();
+    app.add_systems(Startup, golden_setup).add_systems(
+        Update,
+        (
+            golden_system,
+            crate::wind_system,
+            crate::swap_noise_system,
+            crate::update_material_system,
+        )
+            .chain(),
+    );
+    app.insert_resource(GoldenRun {
+        update,
+        ..default()
+    });
+    app.run()
+}
+
+/// Windowless app with the cloud rendering plugins and deterministic time,
+/// shared by the offscreen checks.
+pub(crate) fn headless_app() -> App {
+    cloud_app(None, Duration::from_secs_f64(1.0 / 60.0))
+}
+
+/// `headless_app` with a window instead when given one. Headless frames
+/// start no sooner than `frame_wait` apart.
+pub(crate) fn cloud_app(window: Option<Window>, frame_wait: Duration) -> App {
+    let mut app = App::new();
+    match window {
+        Some(window) => app.add_plugins(DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(window),
+            ..default()
+        })),
+        None => app
+            .add_plugins(
+                DefaultPlugins
+                    .set(WindowPlugin {
+                        primary_window: None,
+                        exit_condition: ExitCondition::DontExit,
+                        close_when_requested: false,
+                    })
+                    .disable::<WinitPlugin>(),
+            )
+            .add_plugins(ScheduleRunnerPlugin::run_loop(frame_wait)),
+    };
+    app.add_plugins(CloudShaderPlugin)
+    .add_plugins(MaterialPlugin::<CloudMaterial>::default())
+    .add_plugins(CloudMaterialSyncPlugin)
+    .add_plugins(CloudExtensionPlugin)
+    .add_plugins(ViewOverridesPlugin)
+    .add_plugins(CloudTypePlugin)
+    .add_plugins(CloudSunPlugin)
+    .add_plugins(DeterministicTimePlugin)
+    .init_resource::<CloudSettings>()
+    .init_resource::<BakeStats>()
+    .init_resource::<BakeScratch>()
+    .init_resource::<BakeBudget>()
+    .init_resource::<BakeProgress>()
+    .init_resource::<PendingNoise>()
+    .init_resource::<BakeCache>()
+    .add_event::<NoiseBaked>()
+    // No egui.
+    .configure_sets(Update, CloudUiSet.run_if(|| false));
+
+    let ready = PipelinesReady::default();
+    app.insert_resource(ready.clone());
+    app.sub_app_mut(RenderApp)
+        .insert_resource(ready)
+        .add_systems(Render, pipelines_ready_system.in_set(RenderSet::Cleanup));
+    app
+}
+
+/// Whether the render world has no pipeline left compiling. Pipelines compile
+/// in the background, so a capture taken while the cloud pipeline is still
+/// queued shows the clear color where the clouds should be.
+#[derive(Resource, Clone, Default)]
+pub(crate) struct PipelinesReady(Arc<AtomicBool>);
+
+impl PipelinesReady {
+    pub(crate) fn get(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+fn pipelines_ready_system(ready: Res<PipelinesReady>, pipeline_cache: Res<PipelineCache>) {
+    ready.0.store(pipeline_cache.waiting_pipelines().next().is_none(), Ordering::Relaxed);
+}
+
+/// A `SIZE` render target that can be read back.
+pub(crate) fn target_image() -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: SIZE.x,
+            height: SIZE.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT;
+    image
+}
+
+#[derive(Resource, Default)]
+struct GoldenRun {
+    update: bool,
+    index: usize,
+    frame: u32,
+    /// A readback is in flight for the current case.
+    waiting: bool,
+    target: Handle<Image>,
+    camera: Option<Entity>,
+    volume: Option<Entity>,
+    failures: Vec<String>,
+    skipped: usize,
+}
+
+fn golden_setup(mut commands: Commands, mut images: ResMut<Assets<Image>>, mut run: ResMut<GoldenRun>) {
+    run.target = images.add(target_image());
+
+    commands.spawn((
+        DirectionalLight {
+            shadows_enabled: true,
+            ..default()
+        },
+        Transform::from_xyz(4.0, 8.0, 4.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+    let camera = commands
+        .spawn((
+            Camera3d::default(),
+            Camera {
+                target: RenderTarget::Image(run.target.clone()),
+                clear_color: ClearColorConfig::Custom(Color::srgb(0.35, 0.5, 0.75)),
+                ..default()
+            },
+            outside(),
+        ))
+        .id();
+    run.camera = Some(camera);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn golden_system(
+    mut commands: Commands,
+    mut run: ResMut<GoldenRun>,
+    mut settings: ResMut<CloudSettings>,
+    bake_progress: Res<BakeProgress>,
+    pending_noise: Res<PendingNoise>,
+    pipelines_ready: Res<PipelinesReady>,
+    mut deterministic: ResMut<DeterministicTime>,
+    mut lit_materials: ResMut<Assets<crate::extension::LitCloudMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    adapter: Res<RenderAdapterInfo>,
+    mut transforms: Query<&mut Transform, With<Camera3d>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // Start only once the noise is in place.
+    if run.waiting || settings.needs_rebuild || bake_progress.bake.is_some() || pending_noise.image.is_some() {
+        return;
+    }
+    let Some(case) = CASES.get(run.index).copied() else {
+        for failure in &run.failures {
+            error!("{}", failure);
+        }
+        println!(
+            "{} of {} golden images match, {} skipped",
+            CASES.len() - run.failures.len() - run.skipped,
+            CASES.len() - run.skipped,
+            run.skipped
+        );
+        exit.send(if run.failures.is_empty() { AppExit::Success } else { AppExit::error() });
+        return;
+    };
+
+    if case.lit && Backends::from(adapter.backend).contains(Backends::GL) {
+        println!("{}: skipped, the GL backend can't bind the lit material", case.name);
+        run.skipped += 1;
+        run.index += 1;
+        return;
+    }
+    if run.frame == 0 {
+        // Reset the tunables, keeping the textures and any pending bake.
+        *settings = CloudSettings {
+            noise_handle: settings.noise_handle.clone(),
+            profile_handle: settings.profile_handle.clone(),
+            needs_rebuild: settings.needs_rebuild,
+            ..CloudSettings::defaults()
+        };
+        (case.tweak)(&mut settings);
+        if let Some(mut transform) = run.camera.and_then(|camera| transforms.get_mut(camera).ok()) {
+            *transform = (case.camera)();
+        }
+        if let Some(volume) = run.volume.take() {
+            commands.entity(volume).despawn_recursive();
+        }
+        let mut params = CloudVolumeParams::default().at(Vec3::new(0.0, 1.0, 0.0));
+        // Set per case rather than left to the default, so lighting work
+        // can't silently change the unlit look.
+        params.volume.lighting = case.lighting;
+        run.volume = Some(if case.lit {
+            commands
+                .spawn((
+                    Mesh3d(meshes.add(Cuboid::new(2.0, 2.0, 2.0))),
+                    MeshMaterial3d(lit_materials.add(CloudExtension::material(&settings))),
+                    params.transform,
+                    CloudVolume::default(),
+                ))
+                .id()
+        } else {
+            commands.spawn_cloud(params).id()
+        });
+        deterministic.start();
+    }
+
+    run.frame += 1;
+    if run.frame > FRAMES {
+        if !pipelines_ready.get() {
+            // Start the case over; its pipelines are cached by then.
+            run.frame = 0;
+            return;
+        }
+        run.waiting = true;
+        capture::read_back(&mut commands, run.target.clone(), SIZE, 4, finish_case);
+    }
+}
+
+fn finish_case(trigger: Trigger<Captured>, mut run: ResMut<GoldenRun>) {
+    if !run.waiting {
+        return;
+    }
+    let case = CASES[run.index];
+    match check(case.name, &trigger.event().0, run.update) {
+        Ok(message) => println!("{}: {}", case.name, message),
+        Err(err) => run.failures.push(format!("{}: {}", case.name, err)),
+    }
+    run.index += 1;
+    run.frame = 0;
+    run.waiting = false;
+}
+
+fn golden_path(name: &str, suffix: &str) -> PathBuf {
+    Path::new(GOLDEN_DIR).join(format!("{}{}.png", name, suffix))
+}
+
+fn save_png(path: &Path, data: &[u8]) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    }
+    image::save_buffer(path, data, SIZE.x, SIZE.y, image::ExtendedColorType::Rgba8)
+        .map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Compares a capture with its golden, or replaces the golden when updating.
+fn check(name: &str, actual: &[u8], update: bool) -> Result<String, String> {
+    let golden = golden_path(name, "");
+    if update {
+        save_png(&golden, actual)?;
+        return Ok(format!("wrote {}", golden.display()));
+    }
+    let expected = image::open(&golden)
+        .map_err(|err| format!("{}: {} (run with --update-goldens to create it)", golden.display(), err))?
+        .to_rgba8();
+    if expected.dimensions() != (SIZE.x, SIZE.y) {
+        return Err(format!(
+            "{} is {:?}, captures are {}x{}",
+            golden.display(),
+            expected.dimensions(),
+            SIZE.x,
+            SIZE.y
+        ));
+    }
+
+    let comparison = compare(expected.as_raw(), actual);
+    let summary = format!(
+        "mean difference {:.3}, {:.3}% of pixels over tolerance",
+        comparison.mean,
+        comparison.outliers * 100.0
+    );
+    if comparison.mean <= MEAN_TOLERANCE && comparison.outliers <= OUTLIER_FRACTION {
+        return Ok(summary);
+    }
+    save_png(&golden_path(name, ".actual"), actual)?;
+    save_png(&golden_path(name, ".diff"), &comparison.diff)?;
+    Err(format!("{}; see {}", summary, golden_path(name, ".diff").display()))
+}
+
+struct Comparison {
+    /// Mean weighted difference per pixel, 0..255.
+    mean: f64,
+    /// Fraction of pixels above `PIXEL_TOLERANCE`.
+    outliers: f64,
+    /// Differences amplified 8x, red where over tolerance.
+    diff: Vec<u8>,
+}
+
+/// Luma-weighted RGB plus alpha difference, so small hue shifts in dark
+/// regions count less than visible changes in brightness.
+fn compare(expected: &[u8], actual: &[u8]) -> Comparison {
+    let mut total = 0.0;
+    let mut outliers = 0usize;
+    let mut diff = Vec::with_capacity(actual.len());
+    for (a, b) in expected.chunks_exact(4).zip(actual.chunks_exact(4)) {
+        let d = |channel: usize| (a[channel] as f64 - b[channel] as f64).abs();
+        let difference = 0.299 * d(0) + 0.587 * d(1) + 0.114 * d(2) + 0.25 * d(3);
+        total += difference;
+        let gray = (difference * 8.0).min(255.0) as u8;
+        if difference > PIXEL_TOLERANCE {
+            outliers += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            diff.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+    let pixels = (expected.len() / 4).max(1) as f64;
+    Comparison {
+        mean: total / pixels,
+        outliers: outliers as f64 / pixels,
+        diff,
+    }
+}
@@ -0,0 +1,126 @@
+use bevy::{prelude::*, render::view::NoFrustumCulling};
+#[cfg(feature = "ui")]
+use bevy_egui::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::CloudVolume;
+
+/// Low fog hugging the ground, as a mode of `CloudVolume`.
+///
+/// The density falls off exponentially above a base height and the noise
+/// only varies it gently. Only the top and bottom of the box bound the
+/// march: the shader stretches the box around the camera out to the fog
+/// distance, so its sides never show, and with a depth prepass on the camera
+/// the march stops at scene geometry, so objects sink into the fog instead of
+/// being drawn over by it.
+pub struct GroundFogPlugin;
+
+impl Plugin for GroundFogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, fog_culling_system);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GroundFog {
+    /// Height above the bottom of the box where the falloff starts.
+    pub base_height: f32,
+    /// Height over which the density drops to 1/e.
+    pub scale_height: f32,
+    /// How far the noise varies the density: 0 is a smooth layer.
+    pub noise_blend: f32,
+    /// World size of one noise tile, independent of the box.
+    pub noise_scale: f32,
+    /// Fog far away takes on this color, as distance fog does.
+    pub color: LinearRgba,
+    /// Where the march ends and the color has all but taken over.
+    pub distance: f32,
+}
+
+impl GroundFog {
+    pub const DEFAULT: Self = Self {
+        base_height: 0.0,
+        scale_height: 0.4,
+        noise_blend: 0.25,
+        noise_scale: 8.0,
+        color: LinearRgba::rgb(0.62, 0.68, 0.76),
+        distance: 60.0,
+    };
+
+    /// `CloudMaterialUniform::ground_fog` for a box starting at volume-space
+    /// height `box_bottom`.
+    pub fn uniform(&self, box_bottom: f32) -> Vec4 {
+        Vec4::new(
+            box_bottom + self.base_height,
+            self.scale_height.max(1e-3),
+            self.noise_blend.clamp(0.0, 1.0),
+            self.noise_scale.max(1e-3),
+        )
+    }
+
+    /// `CloudMaterialUniform::fog_color`; alpha carries the distance.
+    pub fn color_uniform(&self) -> LinearRgba {
+        self.color.with_alpha(self.distance.max(1e-3))
+    }
+}
+
+impl Default for GroundFog {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Set on volumes whose culling this plugin turned off.
+#[derive(Component)]
+struct FogUnculled;
+
+/// The stretched box is far larger than the mesh bounds, so fog volumes are
+/// never frustum culled.
+fn fog_culling_system(
+    mut commands: Commands,
+    volumes: Query<(Entity, &CloudVolume, Has<FogUnculled>), Changed<CloudVolume>>,
+) {
+    for (entity, volume, unculled) in &volumes {
+        match (volume.ground_fog.is_some(), unculled) {
+            (true, false) => {
+                commands.entity(entity).insert((NoFrustumCulling, FogUnculled));
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<(NoFrustumCulling, FogUnculled)>();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Inspector section toggling and editing a volume's fog mode.
+#[cfg(feature = "ui")]
+pub fn ground_fog_ui(ui: &mut egui::Ui, ground_fog: &mut Option<GroundFog>) {
+    let mut enabled = ground_fog.is_some();
+    if ui
+        .checkbox(&mut enabled, "Ground Fog")
+        .on_hover_text("Height falloff instead of cloud shapes, unbounded sideways")
+        .changed()
+    {
+        *ground_fog = enabled.then_some(GroundFog::DEFAULT);
+    }
+    let Some(mut fog) = *ground_fog else {
+        return;
+    };
+    ui.add(egui::Slider::new(&mut fog.base_height, -10.0..=10.0).text("Base Height"));
+    ui.add(egui::Slider::new(&mut fog.scale_height, 0.05..=10.0).logarithmic(true).text("Scale Height"));
+    ui.add(egui::Slider::new(&mut fog.noise_blend, 0.0..=1.0).text("Noise Blend"));
+    ui.add(egui::Slider::new(&mut fog.noise_scale, 0.5..=100.0).logarithmic(true).text("Noise Scale"));
+    ui.add(egui::Slider::new(&mut fog.distance, 5.0..=1000.0).logarithmic(true).text("Distance"));
+    ui.horizontal(|ui| {
+        ui.label("Distance Color");
+        let mut color = fog.color.to_f32_array_no_alpha();
+        if ui.color_edit_button_rgb(&mut color).changed() {
+            fog.color = LinearRgba::rgb(color[0], color[1], color[2]);
+        }
+    });
+    if *ground_fog != Some(fog) {
+        *ground_fog = Some(fog);
+    }
+}
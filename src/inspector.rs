@@ -0,0 +1,385 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    cloud_type,
+    color_preview::ColorPreview,
+    focus::CameraFocus,
+    ground_fog,
+    spawn::{CloudVolumeParams, SpawnCloudExt},
+    volume_noise::NoiseOverride,
+    volume_visibility::CloudHidden,
+    CloudBlendMode, CloudLightingMode, CloudMaterial, CloudSettings, CloudUiSet, CloudVolume,
+};
+
+/// Lists the volumes by name and edits the per-volume parameters of the
+/// selected one (the one last double-clicked or clicked in the list, or the
+/// first volume when nothing is selected). The eye next to each name hides
+/// the volume with `CloudHidden`; "Solo" hides all but the selected one.
+pub struct VolumeInspectorPlugin;
+
+impl Plugin for VolumeInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (volume_name_system, inspector_ui_system.in_set(CloudUiSet)).chain(),
+        );
+    }
+}
+
+/// Offset of a duplicate from its source, so both stay visible.
+const DUPLICATE_OFFSET: Vec3 = Vec3::new(0.5, 0.0, 0.5);
+
+/// Copied volume parameters, pasted onto another volume without its
+/// transform. The noise override travels only with "Include Noise".
+#[derive(Default)]
+struct VolumeClipboard {
+    volume: Option<CloudVolume>,
+    include_noise: bool,
+}
+
+impl VolumeClipboard {
+    fn paste(&self, target: &mut CloudVolume) {
+        let Some(source) = &self.volume else {
+            return;
+        };
+        if self.include_noise {
+            *target = source.clone();
+            return;
+        }
+        // Where a volume samples the noise is part of its noise too.
+        *target = CloudVolume {
+            noise: target.noise,
+            noise_offset: target.noise_offset,
+            noise_angle: target.noise_angle,
+            noise_mirror: target.noise_mirror,
+            ..source.clone()
+        };
+    }
+}
+
+/// Names new volumes after their type and a running number ("Cumulus 3").
+/// Volumes spawned with a `Name`, e.g. from a session, keep it.
+#[allow(clippy::type_complexity)]
+fn volume_name_system(
+    mut commands: Commands,
+    volumes: Query<(Entity, &CloudVolume), (Added<CloudVolume>, Without<Name>)>,
+    mut count: Local<u32>,
+) {
+    for (entity, volume) in &volumes {
+        *count += 1;
+        let name = format!("{} {}", cloud_type::type_name(volume.cloud_type), *count);
+        commands.entity(entity).insert(Name::new(name));
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn inspector_ui_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut focus: ResMut<CameraFocus>,
+    settings: Res<CloudSettings>,
+    color_preview: Res<ColorPreview>,
+    materials: Res<Assets<CloudMaterial>>,
+    mut volumes: Query<(
+        Entity,
+        &mut CloudVolume,
+        Option<&MeshMaterial3d<CloudMaterial>>,
+        Option<&mut Name>,
+        &mut Transform,
+        Has<CloudHidden>,
+    )>,
+    mut lock_stretch: Local<bool>,
+    mut filter: Local<String>,
+    mut last_selected: Local<Option<Entity>>,
+    mut clipboard: Local<VolumeClipboard>,
+) {
+    let command = keys.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ]);
+    egui::Window::new("Volume")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                // Side by side along X, clear of the existing volumes.
+                let x = volumes.iter().count() as f32 * 3.0;
+                let mut spawned = None;
+                if ui.button("Add Volume").clicked() {
+                    spawned = Some(CloudVolumeParams::default().at(Vec3::new(x, 1.0, 0.0)));
+                }
+                // The two ends of the scale range, with their parameters
+                // derived from their size.
+                if ui.button("Add 0.5 m").on_hover_text("A bottle-sized volume").clicked() {
+                    spawned = Some(scaled_volume(Vec3::splat(0.25)).at(Vec3::new(x, 0.25, 0.0)));
+                }
+                if ui.button("Add 5 km").on_hover_text("A weather system overhead").clicked() {
+                    spawned = Some(scaled_volume(Vec3::new(2500.0, 400.0, 2500.0)).at(Vec3::new(0.0, 1000.0, 0.0)));
+                }
+                if let Some(params) = spawned {
+                    focus.selected = Some(commands.spawn_cloud(params).id());
+                }
+            });
+
+            let mut entries: Vec<(Entity, String, bool)> = volumes
+                .iter()
+                .map(|(entity, _, _, name, _, hidden)| {
+                    let name = name.map_or_else(|| entity.to_string(), |name| name.to_string());
+                    (entity, name, hidden)
+                })
+                .collect();
+            entries.sort_by_key(|(entity, ..)| *entity);
+            let selected = focus.selected.filter(|entity| volumes.contains(*entity));
+            let current = selected.or_else(|| entries.first().map(|(entity, ..)| *entity));
+            // Selection made elsewhere (double-click in the viewport) scrolls
+            // the list to the entry.
+            let scroll = focus.selected != *last_selected;
+            *last_selected = focus.selected;
+
+            ui.horizontal(|ui| {
+                ui.label("Filter");
+                ui.text_edit_singleline(&mut *filter);
+            });
+            ui.horizontal(|ui| {
+                let solo = ui
+                    .add_enabled(current.is_some(), egui::Button::new("Solo"))
+                    .on_hover_text("Hide every volume but the selected one");
+                if solo.clicked() {
+                    for (entity, ..) in &entries {
+                        if Some(*entity) == current {
+                            commands.entity(*entity).remove::<CloudHidden>();
+                        } else {
+                            commands.entity(*entity).insert(CloudHidden);
+                        }
+                    }
+                }
+                let any_hidden = entries.iter().any(|(.., hidden)| *hidden);
+                if ui.add_enabled(any_hidden, egui::Button::new("Show All")).clicked() {
+                    for (entity, ..) in &entries {
+                        commands.entity(*entity).remove::<CloudHidden>();
+                    }
+                }
+            });
+            let needle = filter.to_lowercase();
+            egui::ScrollArea::vertical().max_height(160.0).show(ui, |ui| {
+                for (entity, name, hidden) in entries
+                    .iter()
+                    .filter(|(_, name, _)| name.to_lowercase().contains(&needle))
+                {
+                    ui.horizontal(|ui| {
+                        let eye = ui
+                            .selectable_label(!hidden, "👁")
+                            .on_hover_text(if *hidden { "Show this volume" } else { "Hide this volume" });
+                        if eye.clicked() {
+                            if *hidden {
+                                commands.entity(*entity).remove::<CloudHidden>();
+                            } else {
+                                commands.entity(*entity).insert(CloudHidden);
+                            }
+                        }
+                        let text = if *hidden {
+                            egui::RichText::new(name).weak()
+                        } else {
+                            egui::RichText::new(name)
+                        };
+                        let mut response = ui.selectable_label(current == Some(*entity), text);
+                        if entries.iter().filter(|(_, other, _)| other == name).count() > 1 {
+                            response = response.on_hover_text(format!("Entity {}", entity));
+                        }
+                        if response.clicked() {
+                            focus.selected = Some(*entity);
+                            *last_selected = focus.selected;
+                        }
+                        if scroll && current == Some(*entity) {
+                            response.scroll_to_me(Some(egui::Align::Center));
+                        }
+                    });
+                }
+            });
+            ui.separator();
+
+            let Some((entity, mut volume, material, name, mut transform, _)) =
+                current.and_then(|entity| volumes.get_mut(entity).ok())
+            else {
+                ui.label("No volumes");
+                return;
+            };
+
+            // Shortcuts apply while the pointer is over this window and no
+            // text field has the keyboard.
+            let shortcuts = ui.ui_contains_pointer() && !ui.ctx().wants_keyboard_input() && command;
+            ui.horizontal(|ui| {
+                if ui.button("Duplicate").clicked() {
+                    let mut params = CloudVolumeParams {
+                        transform: *transform,
+                        volume: volume.clone(),
+                    };
+                    params.transform.translation += DUPLICATE_OFFSET;
+                    let mut duplicate = commands.spawn_cloud(params);
+                    if let Some(name) = &name {
+                        duplicate.insert(Name::new(format!("{} copy", name.as_str())));
+                    }
+                    focus.selected = Some(duplicate.id());
+                }
+                if ui.button("Copy Params").clicked() || (shortcuts && keys.just_pressed(KeyCode::KeyC)) {
+                    clipboard.volume = Some(volume.clone());
+                }
+                let paste = ui.add_enabled(clipboard.volume.is_some(), egui::Button::new("Paste Params"));
+                if paste.clicked() || (shortcuts && keys.just_pressed(KeyCode::KeyV)) {
+                    clipboard.paste(&mut volume);
+                }
+                ui.checkbox(&mut clipboard.include_noise, "Include Noise");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Name");
+                if let Some(mut name) = name {
+                    let mut text = name.to_string();
+                    if ui.text_edit_singleline(&mut text).changed() {
+                        name.set(text);
+                    }
+                }
+            })
+            .response
+            .on_hover_text(format!("Entity {}", entity));
+            ui.add(egui::Slider::new(&mut volume.density_scale, 0.0..=4.0).text("Density Scale"));
+            ui.add(egui::Slider::new(&mut volume.threshold_offset, -0.5..=0.5).text("Threshold Offset"));
+            ui.add(egui::Slider::new(&mut volume.cloud_type, 0.0..=1.0).text("Cloud Type"));
+            ui.add(egui::Slider::new(&mut volume.anvil_amount, 0.0..=1.0).text("Anvil"))
+                .on_hover_text("Spreads the top into a flat, overhanging anvil, as storm clouds do");
+            ui.add_enabled(
+                volume.anvil_amount > 0.0,
+                egui::Slider::new(&mut volume.anvil_start_height, 0.0..=0.95).text("Anvil Start"),
+            )
+            .on_hover_text("Fraction of the box height where the anvil begins");
+            ui.checkbox(&mut volume.auto_scale_params, "Auto Scale Params").on_hover_text(
+                "Derive density, steps, noise tiling, edge fades and LOD distances from the box size",
+            );
+            ui.horizontal(|ui| {
+                ui.label("Blend");
+                for mode in CloudBlendMode::ALL {
+                    if ui.selectable_label(volume.blend == mode, format!("{:?}", mode)).clicked() {
+                        volume.blend = mode;
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Lighting");
+                for mode in CloudLightingMode::ALL {
+                    if ui.selectable_label(volume.lighting == mode, format!("{:?}", mode)).clicked() {
+                        volume.lighting = mode;
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Unlit is the original look; the scatter modes add powder, then multiple scattering");
+            ui.horizontal(|ui| {
+                ui.label("Tint");
+                let mut tint = volume.tint.to_f32_array_no_alpha();
+                if ui.color_edit_button_rgb(&mut tint).changed() {
+                    volume.tint = LinearRgba::rgb(tint[0], tint[1], tint[2]);
+                }
+                let color = LinearRgba::from(settings.color);
+                let albedo = LinearRgba::rgb(
+                    color.red * volume.tint.red,
+                    color.green * volume.tint.green,
+                    color.blue * volume.tint.blue,
+                );
+                color_preview.swatch(ui, color_preview.sunlit(&settings, albedo));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Rotation");
+                let (yaw, pitch, roll) = transform.rotation.to_euler(EulerRot::YXZ);
+                let mut degrees = [yaw.to_degrees(), pitch.to_degrees(), roll.to_degrees()];
+                let mut changed = false;
+                for (angle, label) in degrees.iter_mut().zip(["Yaw ", "Pitch ", "Roll "]) {
+                    let drag = egui::DragValue::new(angle).range(-180.0..=180.0).prefix(label).suffix("°");
+                    changed |= ui.add(drag).changed();
+                }
+                if ui.button("Reset").clicked() {
+                    degrees = [0.0; 3];
+                    changed = true;
+                }
+                if changed {
+                    let [yaw, pitch, roll] = degrees.map(f32::to_radians);
+                    transform.rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, roll);
+                }
+            });
+
+            ui.separator();
+            ground_fog::ground_fog_ui(ui, &mut volume.ground_fog);
+
+            ui.separator();
+            ui.checkbox(&mut lock_stretch, "Lock Stretch Axes");
+            let before = volume.noise_stretch;
+            let mut stretch = before;
+            ui.add(egui::Slider::new(&mut stretch.x, 0.25..=8.0).logarithmic(true).text("Stretch X"));
+            ui.add(egui::Slider::new(&mut stretch.y, 0.25..=8.0).logarithmic(true).text("Stretch Y"));
+            ui.add(egui::Slider::new(&mut stretch.z, 0.25..=8.0).logarithmic(true).text("Stretch Z"));
+            if stretch != before {
+                if *lock_stretch {
+                    // Scale all axes by the edited axis' ratio so the
+                    // proportions are kept.
+                    let ratio = (stretch / before).to_array();
+                    let ratio = ratio.into_iter().find(|r| *r != 1.0).unwrap_or(1.0);
+                    stretch = before * ratio;
+                }
+                volume.noise_stretch = stretch;
+            }
+            ui.horizontal(|ui| {
+                ui.label("Noise Offset");
+                let mut offset = volume.noise_offset;
+                for (axis, label) in [&mut offset.x, &mut offset.y, &mut offset.z].into_iter().zip(["x ", "y ", "z "]) {
+                    ui.add(egui::DragValue::new(axis).speed(0.01).prefix(label));
+                }
+                if offset != volume.noise_offset {
+                    volume.noise_offset = offset;
+                }
+            });
+            ui.horizontal(|ui| {
+                let mut degrees = volume.noise_angle.to_degrees();
+                if ui
+                    .add(egui::DragValue::new(&mut degrees).range(-180.0..=180.0).prefix("Noise Turn ").suffix("°"))
+                    .changed()
+                {
+                    volume.noise_angle = degrees.to_radians();
+                }
+                let mut mirror = volume.noise_mirror;
+                if ui.checkbox(&mut mirror, "Mirror").changed() {
+                    volume.noise_mirror = mirror;
+                }
+            });
+
+            ui.separator();
+            let texture = material
+                .and_then(|material| materials.get(&material.0))
+                .map(|material| &material.noise_texture);
+            ui.label(match texture {
+                Some(texture) if *texture == settings.noise_handle => "Noise: shared global bake".to_string(),
+                Some(texture) => format!("Noise: own texture {:?}", texture.id()),
+                None => "Noise: none".to_string(),
+            });
+            let mut own = volume.noise.is_some();
+            if ui.checkbox(&mut own, "Own Noise").changed() {
+                volume.noise = own.then(|| NoiseOverride::from_settings(&settings));
+            }
+            if let Some(mut noise) = volume.noise {
+                ui.add(egui::DragValue::new(&mut noise.seed).prefix("Seed: "));
+                ui.add(egui::Slider::new(&mut noise.frequency, 1.0..=10.0).text("Frequency"));
+                ui.add(egui::Slider::new(&mut noise.cell_count, 4..=64).text("Cell Count"));
+                if volume.noise != Some(noise) {
+                    volume.noise = Some(noise);
+                }
+            }
+        });
+}
+
+/// A volume of the given half extents that scales its own parameters.
+fn scaled_volume(half_extents: Vec3) -> CloudVolumeParams {
+    let mut params = CloudVolumeParams::new(half_extents);
+    params.volume.auto_scale_params = true;
+    params
+}
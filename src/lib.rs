@@ -0,0 +1,2623 @@
+//! Raymarched volumetric clouds for Bevy.
+//!
+//! The crate is split by Cargo features, each including the one before:
+//!
+//! - `render`: `CloudPlugin` with `CloudMaterial`, the material sync and the
+//!   runtime systems, drawing noise volumes baked ahead of time and loaded
+//!   as KTX2 (see `noise::load_noise`), plus the opt-in weather, time of
+//!   day, precipitation and motion-vector plugins.
+//! - `bake`: the procedural noise bake and its cache, with the rand stack.
+//! - `ui`: the egui panels and the tools of the editor app; on by default.
+//!
+//! Games should import from `prelude`, which is the API kept stable across
+//! releases. The other public paths are there for the editor and may move.
+
+use bevy::{
+    prelude::*,
+    image::ImageSampler,
+    pbr::{MaterialPipeline, MaterialPipelineKey, MeshPipelineKey},
+    render::{
+        mesh::MeshVertexBufferLayoutRef,
+        render_resource::{
+            AsBindGroup, CompareFunction, Face, RenderPipelineDescriptor, ShaderRef, ShaderType,
+            SpecializedMeshPipelineError, TextureDimension, TextureFormat,
+        },
+    },
+    render::render_asset::RenderAssetUsages,
+};
+#[cfg(feature = "ui")]
+use bevy::{
+    core_pipeline::prepass::DepthPrepass,
+    input::mouse::MouseMotion,
+    render::camera::ScalingMode,
+    window::{CursorGrabMode, PrimaryWindow},
+};
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+#[cfg(feature = "ui")]
+use bevy_atmosphere::prelude::*;
+use serde::{Deserialize, Serialize};
+
+mod accumulation;
+mod cloud_sun;
+mod cloud_type;
+mod density;
+mod extension;
+mod ground_fog;
+mod light_volume;
+mod lod;
+mod material_sync;
+mod motion_vectors;
+pub mod noise;
+mod occupancy;
+mod portal;
+mod precipitation;
+pub mod prelude;
+mod resize;
+mod shader;
+mod sorting;
+mod spawn;
+mod time_of_day;
+mod trail;
+pub mod versioning;
+mod view_overrides;
+mod volume_noise;
+mod volume_visibility;
+mod weather;
+mod weather_map;
+
+#[cfg(feature = "bake")]
+mod bake_cache;
+#[cfg(feature = "bake")]
+mod seed_streams;
+
+#[cfg(feature = "ui")]
+mod animation;
+#[cfg(feature = "ui")]
+mod autosave;
+#[cfg(feature = "ui")]
+mod bench;
+#[cfg(feature = "ui")]
+mod capture;
+#[cfg(feature = "ui")]
+mod cirrus;
+#[cfg(feature = "ui")]
+mod cleanup;
+#[cfg(feature = "ui")]
+mod color_preview;
+#[cfg(feature = "ui")]
+mod compare;
+#[cfg(feature = "ui")]
+mod deterministic;
+#[cfg(feature = "ui")]
+mod exr_capture;
+#[cfg(feature = "ui")]
+mod export;
+#[cfg(feature = "ui")]
+mod field;
+#[cfg(feature = "ui")]
+mod focus;
+#[cfg(feature = "ui")]
+mod golden;
+#[cfg(feature = "ui")]
+mod inspector;
+#[cfg(feature = "ui")]
+mod map_view;
+#[cfg(feature = "ui")]
+mod minimap;
+#[cfg(feature = "ui")]
+mod modulation;
+#[cfg(feature = "ui")]
+mod pick;
+#[cfg(feature = "ui")]
+mod presets;
+#[cfg(feature = "ui")]
+mod reference;
+#[cfg(feature = "ui")]
+mod refine;
+#[cfg(feature = "ui")]
+mod render_matrix;
+#[cfg(feature = "ui")]
+mod session;
+#[cfg(feature = "ui")]
+mod shortcuts;
+#[cfg(feature = "ui")]
+mod skybox_capture;
+#[cfg(feature = "ui")]
+mod snippet;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "ui")]
+mod texture_limits;
+#[cfg(feature = "ui")]
+mod thumbnails;
+#[cfg(feature = "ui")]
+mod timeline;
+#[cfg(feature = "ui")]
+mod touch;
+#[cfg(feature = "ui")]
+mod ui_cache;
+#[cfg(feature = "ui")]
+mod ui_strings;
+#[cfg(feature = "ui")]
+mod windows;
+
+use accumulation::AccumulationPlugin;
+use cloud_sun::CloudSunPlugin;
+#[cfg(feature = "ui")]
+use cloud_sun::CloudLights;
+use cloud_type::CloudTypePlugin;
+use extension::{CloudExtensionPlugin, LitCloudMaterial};
+use ground_fog::{GroundFog, GroundFogPlugin};
+use light_volume::LightVolumePlugin;
+use lod::CloudLodPlugin;
+use material_sync::{CloudMaterialInputs, CloudMaterialSyncPlugin};
+use noise::{BakeStats, FractalType, NoiseBaked, NoiseType, PendingNoise};
+use occupancy::OccupancyPlugin;
+use portal::CloudPortalPlugin;
+use resize::CloudResizePlugin;
+use shader::CloudShaderPlugin;
+use sorting::CloudSortingPlugin;
+pub use motion_vectors::{CloudMotionVectors, CloudMotionVectorsPlugin};
+pub use precipitation::{PrecipitationCell, PrecipitationMap, PrecipitationPlugin, PrecipitationSettings};
+pub use spawn::{CloudVolumeParams, SpawnCloudExt};
+pub use time_of_day::{Sun, TimeOfDay, TimeOfDayPlugin};
+pub use trail::CloudTrail;
+use trail::CloudTrailPlugin;
+use view_overrides::ViewOverridesPlugin;
+use volume_noise::NoiseOverride;
+pub use volume_visibility::CloudHidden;
+use volume_visibility::VolumeVisibilityPlugin;
+#[cfg(feature = "bake")]
+use volume_noise::VolumeNoisePlugin;
+pub use weather::{SetWeather, WeatherController, WeatherPlugin};
+use weather_map::WeatherMapPlugin;
+
+#[cfg(feature = "bake")]
+use bake_cache::BakeCache;
+#[cfg(feature = "bake")]
+use bevy::ecs::system::SystemParam;
+#[cfg(feature = "bake")]
+use noise::{BakeBudget, BakeProgress, BakeScratch, NoiseBake};
+
+#[cfg(feature = "ui")]
+use animation::CloudAnimationPlugin;
+#[cfg(feature = "ui")]
+use autosave::AutosavePlugin;
+#[cfg(feature = "ui")]
+use cirrus::CirrusPlugin;
+#[cfg(feature = "ui")]
+use cleanup::{CloudAssetStats, CloudCleanupPlugin};
+#[cfg(feature = "ui")]
+use color_preview::{ColorPreview, ColorPreviewPlugin};
+#[cfg(feature = "ui")]
+use compare::ComparePlugin;
+#[cfg(feature = "ui")]
+use deterministic::DeterministicTimePlugin;
+#[cfg(feature = "ui")]
+use exr_capture::ExrCapturePlugin;
+#[cfg(feature = "ui")]
+use field::CloudFieldPlugin;
+#[cfg(feature = "ui")]
+use focus::CameraFocusPlugin;
+#[cfg(feature = "ui")]
+use inspector::VolumeInspectorPlugin;
+#[cfg(feature = "ui")]
+use light_volume::{LightVolumeSettings, LightVolumeStats};
+#[cfg(feature = "ui")]
+use lod::CloudImpostor;
+#[cfg(feature = "ui")]
+use map_view::{MapViewPlugin, MapViewSettings};
+#[cfg(feature = "ui")]
+use minimap::MinimapPlugin;
+#[cfg(feature = "ui")]
+use modulation::ModulationPlugin;
+#[cfg(feature = "ui")]
+use pick::CloudPickPlugin;
+#[cfg(feature = "ui")]
+use presets::CloudPresetsPlugin;
+#[cfg(feature = "ui")]
+use reference::ReferenceSpherePlugin;
+#[cfg(feature = "ui")]
+use refine::ProgressiveRefinementPlugin;
+#[cfg(feature = "ui")]
+use session::SessionPlugin;
+#[cfg(feature = "ui")]
+pub use session::CloudSession;
+#[cfg(feature = "ui")]
+use shortcuts::{GamepadSettings, ShortcutsPlugin, UiState};
+#[cfg(feature = "ui")]
+use skybox_capture::SkyboxCapturePlugin;
+#[cfg(feature = "ui")]
+use texture_limits::{TextureLimits, TextureLimitsPlugin};
+#[cfg(feature = "ui")]
+use thumbnails::PresetThumbnailPlugin;
+#[cfg(feature = "ui")]
+use timeline::TimelinePlugin;
+#[cfg(feature = "ui")]
+use touch::TouchGesture;
+#[cfg(feature = "ui")]
+use ui_cache::{NoisePreviews, UiCachePlugin, UiFrameTime};
+#[cfg(feature = "ui")]
+use ui_strings::UiStrings;
+#[cfg(feature = "ui")]
+use weather_map::WeatherBrush;
+#[cfg(feature = "ui")]
+use windows::{camera_window, MultiWindowPlugin, SecondWindow};
+
+/// Runs the editor app, or the headless mode its command line asks for.
+#[cfg(feature = "ui")]
+pub fn run() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--bake-only") {
+        if let Err(err) = bake_only(&args) {
+            eprintln!("Bake failed: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    shader::parse_args(&args);
+    if args.iter().any(|arg| arg == "--golden") {
+        if golden::run(&args) != AppExit::Success {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.iter().any(|arg| arg == "--capture-skybox") {
+        if skybox_capture::run(&args) != AppExit::Success {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.iter().any(|arg| arg == "--bench") {
+        if bench::run(&args) != AppExit::Success {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.iter().any(|arg| arg == "--render-matrix") {
+        if render_matrix::run() != AppExit::Success {
+            std::process::exit(1);
+        }
+        return;
+    }
+    let texture_limits = match TextureLimits::from_args(&args) {
+        Ok(limits) => limits,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let ui_strings = match UiStrings::from_args(&args) {
+        Ok(strings) => strings,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(EguiPlugin)
+        .add_plugins(AtmospherePlugin)
+        .add_plugins(CloudPlugin)
+        .add_plugins(CloudFieldPlugin)
+        .add_plugins(PrecipitationPlugin)
+        .add_plugins(WeatherPlugin)
+        .add_plugins(TimeOfDayPlugin)
+        .add_plugins(SessionPlugin)
+        .add_plugins(ShortcutsPlugin)
+        .add_plugins(CameraFocusPlugin)
+        .add_plugins(CloudPickPlugin)
+        .add_plugins(CloudPresetsPlugin)
+        .add_plugins(VolumeInspectorPlugin)
+        .add_plugins(ComparePlugin)
+        .add_plugins(MapViewPlugin)
+        .add_plugins(MinimapPlugin)
+        .add_plugins(CloudCleanupPlugin)
+        .add_plugins(DeterministicTimePlugin)
+        .add_plugins(ReferenceSpherePlugin)
+        .add_plugins(ProgressiveRefinementPlugin)
+        .add_plugins(CloudAnimationPlugin)
+        .add_plugins(TimelinePlugin)
+        .add_plugins(ExrCapturePlugin)
+        .add_plugins(SkyboxCapturePlugin)
+        .add_plugins(PresetThumbnailPlugin)
+        .add_plugins(ColorPreviewPlugin)
+        .add_plugins(CirrusPlugin)
+        .add_plugins(ModulationPlugin)
+        .add_plugins(CloudMotionVectorsPlugin)
+        .add_plugins(AutosavePlugin)
+        .add_plugins(MultiWindowPlugin)
+        .insert_resource(SecondWindow::from_args(&args))
+        .insert_resource(texture_limits)
+        .add_plugins(TextureLimitsPlugin)
+        .insert_resource(ui_strings)
+        .add_plugins(UiCachePlugin)
+        .add_systems(Startup, setup)
+        .add_systems(
+            Update,
+            (
+                cursor_grab_system.before(camera_control_system),
+                camera_control_system,
+                ui_system.in_set(CloudUiSet),
+                stats_ui_system
+                    .in_set(CloudUiSet)
+                    .run_if(|state: Res<UiState>| state.show_stats),
+            ),
+        )
+        .run();
+}
+
+/// Draws `CloudVolume`s: the cloud material and its sync, the plugins
+/// feeding it, the wind and the global `CloudSettings`. The noise is
+/// `CloudSettings::noise_handle`: baked at runtime with the `bake` feature,
+/// or a volume baked ahead of time and loaded with `noise::load_noise`.
+pub struct CloudPlugin;
+
+impl Plugin for CloudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(CloudShaderPlugin)
+            .add_plugins(MaterialPlugin::<CloudMaterial>::default())
+            .add_plugins(CloudMaterialSyncPlugin)
+            .add_plugins(CloudExtensionPlugin)
+            .add_plugins(CloudLodPlugin)
+            .add_plugins(CloudTypePlugin)
+            .add_plugins(ViewOverridesPlugin)
+            .add_plugins(AccumulationPlugin)
+            .add_plugins(CloudPortalPlugin)
+            .add_plugins(CloudResizePlugin)
+            .add_plugins(CloudSortingPlugin)
+            .add_plugins(LightVolumePlugin)
+            .add_plugins(OccupancyPlugin)
+            .add_plugins(WeatherMapPlugin)
+            .add_plugins(CloudSunPlugin)
+            .add_plugins(GroundFogPlugin)
+            .add_plugins(CloudTrailPlugin)
+            .add_plugins(VolumeVisibilityPlugin)
+            .init_resource::<CloudSettings>()
+            .init_resource::<PendingNoise>()
+            .init_resource::<BakeStats>()
+            .add_event::<NoiseBaked>()
+            .add_systems(
+                Update,
+                (
+                    wind_system,
+                    swap_noise_system.before(update_material_system),
+                    update_material_system,
+                    (noise::loaded_noise_stats_system, auto_threshold_system)
+                        .chain()
+                        .after(update_material_system),
+                ),
+            );
+        #[cfg(feature = "bake")]
+        app.add_plugins(VolumeNoisePlugin)
+            .init_resource::<BakeScratch>()
+            .init_resource::<BakeBudget>()
+            .init_resource::<BakeProgress>()
+            .init_resource::<BakeCache>();
+    }
+}
+
+/// Headless bake: `--bake-only [--settings file.ron] [--compress none|bc5|bc7] [--out file.ktx2]
+/// [--texture-budget MB]`. Settings come from a "Copy as RON" dump, or the defaults. Without a GPU
+/// to ask, the resolution is checked against wgpu's default limits and the budget.
+#[cfg(feature = "ui")]
+fn bake_only(args: &[String]) -> Result<(), String> {
+    let value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|index| args.get(index + 1))
+    };
+
+    let mut settings: CloudSettings = match value("--settings") {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+            let (settings, warnings) = versioning::from_ron(&text).map_err(|err| format!("{}: {}", path, err))?;
+            for warning in warnings {
+                eprintln!("{}: {}", path, warning);
+            }
+            settings
+        }
+        None => CloudSettings::defaults(),
+    };
+    let clamped = settings.sanitize();
+    if !clamped.is_empty() {
+        eprintln!("Clamped out-of-range settings: {}", clamped.join(", "));
+    }
+    let compression = match value("--compress") {
+        Some(name) => export::Compression::parse(name).ok_or_else(|| format!("unknown compression {:?}", name))?,
+        None => export::Compression::None,
+    };
+    let out = std::path::PathBuf::from(value("--out").map_or("cloud_noise.ktx2", String::as_str));
+    let limits = TextureLimits::from_args(args)?;
+    limits.check(noise::clamp_resolution(settings.resolution))?;
+
+    let (data, stats) = noise::bake_noise(&settings);
+    let size = noise::clamp_resolution(settings.resolution);
+    export::write_ktx2(&out, &data, size, compression)?;
+    println!(
+        "Wrote {} ({}x{}x{}, {:?}, raw range {:.2}..{:.2})",
+        out.display(),
+        size.x,
+        size.y,
+        size.z,
+        compression,
+        stats.min,
+        stats.max
+    );
+    Ok(())
+}
+
+/// All egui panels; hidden together by the UI toggle.
+#[cfg(feature = "ui")]
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CloudUiSet;
+
+/// Step-count tiers selectable from the UI and the number keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+    Ultra,
+}
+
+impl QualityPreset {
+    pub const ALL: [QualityPreset; 4] = [Self::Low, Self::Medium, Self::High, Self::Ultra];
+
+    pub fn steps(self) -> u32 {
+        match self {
+            Self::Low => 8,
+            Self::Medium => 16,
+            Self::High => 32,
+            Self::Ultra => 64,
+        }
+    }
+
+    pub fn apply(self, settings: &mut CloudSettings) {
+        settings.steps = self.steps();
+    }
+
+    /// `UiStrings` key of the preset's name.
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Low => "quality.low",
+            Self::Medium => "quality.medium",
+            Self::High => "quality.high",
+            Self::Ultra => "quality.ultra",
+        }
+    }
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default = "CloudSettings::defaults")]
+pub struct CloudSettings {
+    /// `versioning::FORMAT_VERSION` the values were written with; missing
+    /// in files from before versioning, which read as 0.
+    #[serde(default)]
+    pub version: u32,
+    pub color: Color,
+    /// Density above the threshold. With `absorption` it gives extinction
+    /// per world unit: the march weighs every step by its world-space length,
+    /// so a volume's local translucency does not depend on its size.
+    pub density_multiplier: f32,
+    pub threshold: f32,
+    /// Auto threshold mode: the share of noise texels to keep above
+    /// `threshold`, which is then solved from the bake's histogram whenever
+    /// the target or the bake changes. Editing the threshold by hand turns
+    /// it off. Loaded volumes are solved from their texels, except
+    /// block-compressed ones, which can't be read on the CPU.
+    pub auto_threshold: Option<f32>,
+    /// Width of the smooth ramp above the threshold, so dense volumes fade in
+    /// instead of forming a hard shell; 0 keeps the hard cut.
+    pub threshold_softness: f32,
+    /// Varies the softness with a finer resample of the noise, so the
+    /// boundary erodes irregularly; 0 disables.
+    pub softness_erosion: f32,
+    pub absorption: f32,
+    /// Per-channel extinction, scaled by `absorption`; a slight blue keeps
+    /// thick cores colder.
+    pub extinction_color: Color,
+    pub steps: u32,
+    pub light_steps: u32, // samples toward the sun per main step
+    /// Moves each sample along the ray by up to this fraction of a step,
+    /// turning banding on thin features into fine noise; 0 disables.
+    pub step_jitter: f32,
+    /// Debug view coloring each pixel by the share of steps that fetched
+    /// density, to see what empty-space skipping saves.
+    #[serde(skip)]
+    pub step_heatmap: bool,
+    /// Fragments whose final alpha stays below this are discarded, so
+    /// imperceptible wisps cost neither blending nor the rest of the march.
+    pub alpha_cutoff: f32,
+    /// World units past a volume's surface before the march starts, so
+    /// geometry touching the box does not meet the very first samples.
+    pub bounds_inset: f32,
+    /// World units the density extends past the box, repeating the density
+    /// on its faces, so it covers geometry poking out through them.
+    pub bounds_outset: f32,
+    /// World units over which the density fades out in front of opaque
+    /// geometry that stops the march, so clouds feather into surfaces
+    /// instead of cutting off. 0 keeps the hard cut.
+    pub contact_fade_distance: f32,
+    pub coverage: f32,    // 1.0 keeps the threshold as set, 0.0 clears the sky
+    /// March the intersection of overlapping volumes only once.
+    pub merge_overlaps: bool,
+    pub wind: Vec3,       // world units per second, at the lower wind altitude
+    /// Wind at the upper wind altitude. Between the two the velocity is
+    /// interpolated by height; outside it stays at the nearer one.
+    pub wind_upper: Vec3,
+    /// World heights of `wind` and `wind_upper`, lower first.
+    pub wind_altitudes: Vec2,
+    #[serde(skip)]
+    pub wind_offset: Vec3, // accumulated wind scroll at the lower altitude
+    #[serde(skip)]
+    pub wind_offset_upper: Vec3,
+    #[serde(skip)]
+    pub wind_paused: bool,
+    pub phase_g: f32,     // Henyey-Greenstein anisotropy of the sun term
+    pub silver_intensity: f32, // extra sun scattering at thin, backlit edges
+    pub silver_spread: f32,    // angular width of the silver lining, in 1 - cos(angle)
+    pub sun_intensity: f32,
+    /// Light the clouds with the sun's illuminance through the camera
+    /// exposure, as Bevy's PBR does; `sun_intensity` becomes a scale on top.
+    pub physical_sun: bool,
+    /// Add the second brightest directional light, phase-weighted but
+    /// without a light march; see `cloud_sun::CloudSunPlugin`.
+    pub fill_light: bool,
+    pub ambient_top: Color, // ambient at the top of the volume
+    pub ambient_bottom: Color,
+    /// How much the baked occlusion from the noise above darkens the
+    /// ambient term; 0 disables.
+    pub ao_strength: f32,
+    pub seed: u32,
+    pub frequency: f32,
+    pub cell_count: u32,
+    pub warp_amplitude: f32, // domain warp strength in noise-space units, 0 disables
+    pub warp_frequency: u32, // warp lattice cells per tile
+    pub noise_type: NoiseType,
+    pub octaves: u32, // Perlin only
+    pub fractal_type: FractalType,
+    /// FBM mix applied by the shader on top of the bake: each tap samples
+    /// the noise at its frequency times the baked one, and the taps are
+    /// summed by their weights, normalized. Changes need no rebake; the
+    /// defaults sample the bake once, as it is.
+    pub fbm_frequencies: [f32; 4],
+    pub fbm_weights: [f32; 4],
+    /// Stretch the baked values to the full 0..1 range.
+    pub normalize: bool,
+    /// Texels per axis of the baked volume; need not be cubic.
+    pub resolution: UVec3,
+    #[serde(skip)]
+    pub noise_handle: Handle<Image>,
+    /// Height-profile LUT baked by `CloudTypePlugin`.
+    #[serde(skip)]
+    pub profile_handle: Handle<Image>,
+    #[serde(skip)]
+    pub needs_rebuild: bool,
+}
+
+impl CloudSettings {
+    /// Default parameters without textures; `FromWorld` creates them, and
+    /// loaders keep the live ones.
+    pub fn defaults() -> Self {
+        Self {
+            version: versioning::FORMAT_VERSION,
+            color: Color::srgb(0.9, 0.9, 1.0),
+            density_multiplier: 2.0,
+            threshold: 0.2,
+            auto_threshold: None,
+            threshold_softness: 0.05,
+            softness_erosion: 0.0,
+            absorption: 3.0,
+            extinction_color: Color::WHITE,
+            steps: 16,
+            light_steps: 4,
+            step_jitter: 0.0,
+            step_heatmap: false,
+            alpha_cutoff: Self::DEFAULT_ALPHA_CUTOFF,
+            bounds_inset: 0.0,
+            bounds_outset: 0.0,
+            contact_fade_distance: 0.25,
+            coverage: 1.0,
+            merge_overlaps: false,
+            wind: Vec3::new(0.2, 0.0, 0.1),
+            wind_upper: Vec3::new(0.35, 0.0, -0.05),
+            wind_altitudes: Vec2::new(0.0, 2.0),
+            wind_offset: Vec3::ZERO,
+            wind_offset_upper: Vec3::ZERO,
+            wind_paused: false,
+            phase_g: 0.2,
+            silver_intensity: 0.0,
+            silver_spread: 0.2,
+            sun_intensity: 0.0,
+            physical_sun: false,
+            fill_light: false,
+            ambient_top: Color::linear_rgb(1.0, 1.0, 1.0),
+            ambient_bottom: Color::linear_rgb(0.6, 0.6, 0.6),
+            ao_strength: 0.5,
+            seed: 1,
+            frequency: 4.0,
+            cell_count: 16,
+            warp_amplitude: 0.0,
+            warp_frequency: 2,
+            noise_type: NoiseType::Worley,
+            octaves: 4,
+            fractal_type: FractalType::Standard,
+            fbm_frequencies: Self::DEFAULT_FBM_FREQUENCIES,
+            fbm_weights: [1.0, 0.0, 0.0, 0.0],
+            normalize: false,
+            resolution: UVec3::splat(noise::DEFAULT_RESOLUTION),
+            noise_handle: Handle::default(),
+            profile_handle: Handle::default(),
+            needs_rebuild: true,
+        }
+    }
+
+    /// Upper bound on main march steps. The shader caps them too, after the
+    /// view overrides and the refinement's step scale, through a shader def.
+    pub const MAX_STEPS: u32 = 256;
+    /// Upper bound on light march steps, capped in the shader likewise.
+    pub const MAX_LIGHT_STEPS: u32 = 32;
+    /// Henyey-Greenstein is singular at |g| = 1.
+    pub const MAX_PHASE_G: f32 = 0.99;
+    /// Worley features collapse to a point at frequency 0.
+    pub const MIN_FREQUENCY: f32 = 0.1;
+    pub const MAX_FREQUENCY: f32 = 64.0;
+    pub const MAX_CELL_COUNT: u32 = 256;
+    pub const MAX_OCTAVES: u32 = 8;
+    /// The octave ratios of an FBM sum.
+    pub const DEFAULT_FBM_FREQUENCIES: [f32; 4] = [1.0, 2.0, 4.0, 8.0];
+    pub const MIN_FBM_FREQUENCY: f32 = 0.25;
+    pub const MAX_FBM_FREQUENCY: f32 = 16.0;
+    /// Bound on density, absorption and the intensities, so their products
+    /// stay finite in the march.
+    pub const MAX_SCALE: f32 = 1.0e4;
+    /// The warp lattice holds this many cells cubed.
+    pub const MAX_WARP_FREQUENCY: u32 = 32;
+    /// Contact tweaks, not resizing; larger outsets stretch the faces' density.
+    pub const MAX_BOUNDS_OFFSET: f32 = 10.0;
+    /// Wind altitudes stay within this many world units of the ground.
+    pub const MAX_WIND_ALTITUDE: f32 = 1.0e4;
+    /// Smallest gap between the wind altitudes.
+    pub const MIN_WIND_SPAN: f32 = 0.01;
+    /// Below what an 8-bit target can show over most skies, so edges don't pop.
+    pub const DEFAULT_ALPHA_CUTOFF: f32 = 0.003;
+    /// Above this, edges visibly pop as they cross the cutoff.
+    pub const ALPHA_CUTOFF_WARNING: f32 = 0.02;
+
+    /// World-space wind scroll at a world height, between the two altitudes'
+    /// scrolls as the shader lerps them.
+    pub fn wind_offset_at(&self, height: f32) -> Vec3 {
+        let (lower, upper) = (self.wind_altitudes.x, self.wind_altitudes.y);
+        let weight = ((height - lower) / (upper - lower).max(Self::MIN_WIND_SPAN)).clamp(0.0, 1.0);
+        self.wind_offset.lerp(self.wind_offset_upper, weight)
+    }
+
+    /// `CloudMaterialUniform::fbm_weight`: the weights normalized to sum to
+    /// 1, so the mix stays in the bake's range; all zero falls back to the
+    /// bake alone.
+    pub fn fbm_weight_uniform(&self) -> Vec4 {
+        let weights = Vec4::from_array(self.fbm_weights).max(Vec4::ZERO);
+        let sum = weights.element_sum();
+        if sum > 0.0 {
+            weights / sum
+        } else {
+            Vec4::X
+        }
+    }
+
+    /// Clamps every parameter into the range the bake and the shader handle,
+    /// returning the names of those that were out of it. NaN counts as out of
+    /// range and becomes the lower bound.
+    ///
+    /// Safe ranges: density, absorption, the colors, sun and silver
+    /// intensity, warp amplitude `0..=MAX_SCALE`; threshold, auto threshold
+    /// target, coverage, threshold softness, softness erosion, step jitter and AO strength
+    /// `0..=1`; bounds inset, outset and contact fade `0..=MAX_BOUNDS_OFFSET`; phase g `±MAX_PHASE_G`; silver spread `0.001..=2`; steps
+    /// `1..=MAX_STEPS`; light steps `1..=MAX_LIGHT_STEPS`; frequency
+    /// `MIN_FREQUENCY..=MAX_FREQUENCY`; cell count `1..=MAX_CELL_COUNT`;
+    /// octaves `1..=MAX_OCTAVES`; warp frequency `1..=MAX_WARP_FREQUENCY`;
+    /// finite wind; wind altitudes within `MAX_WIND_ALTITUDE`, the upper at
+    /// least `MIN_WIND_SPAN` above the lower; and `noise::clamp_resolution`
+    /// for the resolution.
+    ///
+    /// FBM mix frequencies are clamped to
+    /// `MIN_FBM_FREQUENCY..=MAX_FBM_FREQUENCY` and weights to `0..=1`.
+    pub fn sanitize(&mut self) -> Vec<&'static str> {
+        fn clamp(value: &mut f32, min: f32, max: f32) -> bool {
+            let clamped = if value.is_nan() { min } else { value.clamp(min, max) };
+            let changed = clamped != *value;
+            *value = clamped;
+            changed
+        }
+        fn clamp_u32(value: &mut u32, min: u32, max: u32) -> bool {
+            let clamped = (*value).clamp(min, max);
+            let changed = clamped != *value;
+            *value = clamped;
+            changed
+        }
+        fn clamp_color(color: &mut Color) -> bool {
+            let mut linear = color.to_linear();
+            let mut changed = false;
+            for channel in [&mut linear.red, &mut linear.green, &mut linear.blue] {
+                changed |= clamp(channel, 0.0, CloudSettings::MAX_SCALE);
+            }
+            if changed {
+                *color = linear.into();
+            }
+            changed
+        }
+
+        let mut clamped = Vec::new();
+        let mut check = |changed: bool, name: &'static str| {
+            if changed {
+                clamped.push(name);
+            }
+        };
+        check(clamp(&mut self.density_multiplier, 0.0, Self::MAX_SCALE), "density");
+        check(clamp(&mut self.threshold, 0.0, 1.0), "threshold");
+        if let Some(target) = &mut self.auto_threshold {
+            check(clamp(target, 0.0, 1.0), "auto threshold");
+        }
+        check(clamp(&mut self.threshold_softness, 0.0, 1.0), "threshold softness");
+        check(clamp(&mut self.softness_erosion, 0.0, 1.0), "softness erosion");
+        check(clamp(&mut self.absorption, 0.0, Self::MAX_SCALE), "absorption");
+        check(clamp_color(&mut self.extinction_color), "extinction color");
+        check(clamp_color(&mut self.color), "color");
+        check(clamp_color(&mut self.ambient_top), "ambient top");
+        check(clamp_color(&mut self.ambient_bottom), "ambient bottom");
+        check(clamp(&mut self.ao_strength, 0.0, 1.0), "ao strength");
+        check(clamp_u32(&mut self.steps, 1, Self::MAX_STEPS), "steps");
+        check(clamp_u32(&mut self.light_steps, 1, Self::MAX_LIGHT_STEPS), "light steps");
+        check(clamp(&mut self.step_jitter, 0.0, 1.0), "step jitter");
+        check(clamp(&mut self.alpha_cutoff, 0.0, 1.0), "alpha cutoff");
+        check(clamp(&mut self.bounds_inset, 0.0, Self::MAX_BOUNDS_OFFSET), "bounds inset");
+        check(clamp(&mut self.bounds_outset, 0.0, Self::MAX_BOUNDS_OFFSET), "bounds outset");
+        check(clamp(&mut self.contact_fade_distance, 0.0, Self::MAX_BOUNDS_OFFSET), "contact fade distance");
+        check(clamp(&mut self.coverage, 0.0, 1.0), "coverage");
+        if !self.wind.is_finite() {
+            self.wind = Vec3::ZERO;
+            check(true, "wind");
+        }
+        if !self.wind_upper.is_finite() {
+            self.wind_upper = self.wind;
+            check(true, "wind upper");
+        }
+        check(
+            clamp(&mut self.wind_altitudes.x, -Self::MAX_WIND_ALTITUDE, Self::MAX_WIND_ALTITUDE),
+            "wind lower altitude",
+        );
+        // Keep the interpolation from dividing by zero.
+        check(
+            clamp(
+                &mut self.wind_altitudes.y,
+                self.wind_altitudes.x + Self::MIN_WIND_SPAN,
+                Self::MAX_WIND_ALTITUDE + Self::MIN_WIND_SPAN,
+            ),
+            "wind upper altitude",
+        );
+        check(clamp(&mut self.phase_g, -Self::MAX_PHASE_G, Self::MAX_PHASE_G), "phase g");
+        check(clamp(&mut self.silver_intensity, 0.0, Self::MAX_SCALE), "silver intensity");
+        check(clamp(&mut self.silver_spread, 1e-3, 2.0), "silver spread");
+        check(clamp(&mut self.sun_intensity, 0.0, Self::MAX_SCALE), "sun intensity");
+        check(clamp(&mut self.frequency, Self::MIN_FREQUENCY, Self::MAX_FREQUENCY), "frequency");
+        check(clamp_u32(&mut self.cell_count, 1, Self::MAX_CELL_COUNT), "cell count");
+        check(clamp_u32(&mut self.octaves, 1, Self::MAX_OCTAVES), "octaves");
+        for frequency in &mut self.fbm_frequencies {
+            check(clamp(frequency, Self::MIN_FBM_FREQUENCY, Self::MAX_FBM_FREQUENCY), "fbm frequency");
+        }
+        for weight in &mut self.fbm_weights {
+            check(clamp(weight, 0.0, 1.0), "fbm weight");
+        }
+        check(clamp(&mut self.warp_amplitude, 0.0, Self::MAX_SCALE), "warp amplitude");
+        check(clamp_u32(&mut self.warp_frequency, 1, Self::MAX_WARP_FREQUENCY), "warp frequency");
+        let resolution = noise::clamp_resolution(self.resolution);
+        check(resolution != self.resolution, "resolution");
+        self.resolution = resolution;
+        let baked = ["frequency", "cell count", "octaves", "warp amplitude", "warp frequency", "resolution"];
+        if clamped.iter().any(|name| baked.contains(name)) {
+            self.needs_rebuild = true;
+        }
+        clamped
+    }
+}
+
+impl FromWorld for CloudSettings {
+    fn from_world(world: &mut World) -> Self {
+        // A coarse synchronous bake, so the volumes show a blurry version of
+        // the default look instead of nothing until the real bake lands.
+        // Without the bake the volume stays empty until a noise is loaded.
+        let placeholder = Self {
+            resolution: UVec3::splat(noise::PLACEHOLDER_RESOLUTION),
+            ..Self::defaults()
+        };
+        #[allow(unused_mut)]
+        let mut image = noise::noise_image(placeholder.resolution);
+        #[cfg(feature = "bake")]
+        {
+            image.data = noise::bake_noise(&placeholder).0;
+        }
+        let mut images = world.resource_mut::<Assets<Image>>();
+        let noise_handle = images.add(image);
+
+        // Filled with full density until the first profile bake.
+        let mut profile = Image::new_fill(
+            bevy::render::render_resource::Extent3d {
+                width: cloud_type::LUT_HEIGHTS,
+                height: cloud_type::LUT_TYPES,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[255],
+            TextureFormat::R8Unorm,
+            RenderAssetUsages::default(),
+        );
+        profile.sampler = ImageSampler::linear();
+        let profile_handle = images.add(profile);
+
+        Self {
+            noise_handle,
+            profile_handle,
+            ..Self::defaults()
+        }
+    }
+}
+
+/// Per-volume parameters layered on top of the global `CloudSettings`.
+///
+/// Volumes are rendered with a 2x2x2 cuboid, so the entity's scale is the
+/// half-extent of the raymarched box.
+#[derive(Component, Reflect, Clone, Debug, Serialize, Deserialize)]
+#[reflect(Component, Default)]
+#[require(CloudMaterialInputs)]
+#[serde(default)]
+pub struct CloudVolume {
+    pub density_scale: f32,
+    pub threshold_offset: f32,
+    /// Per-axis feature size multiplier; `(4, 1, 4)` gives flat, wide
+    /// stratiform shapes.
+    pub noise_stretch: Vec3,
+    /// Shifts where the volume samples the noise, in noise tiles, on top of
+    /// the wind scroll.
+    pub noise_offset: Vec3,
+    /// Turns the sampling domain about the volume's vertical axis, in
+    /// radians, so volumes sharing one bake show different sides of it.
+    pub noise_angle: f32,
+    /// Mirrors the sampling domain along X.
+    pub noise_mirror: bool,
+    /// Selects the height profile: 0 stratus, 0.5 cumulus, 1 cumulonimbus.
+    pub cloud_type: f32,
+    /// Spreads the top of the volume outwards into a flat, overhanging
+    /// anvil, as storm clouds do at the tropopause; 0 leaves the shape alone.
+    pub anvil_amount: f32,
+    /// Fraction of the box height where the anvil begins, up to 0.95.
+    pub anvil_start_height: f32,
+    /// Multiplies the global cloud color.
+    pub tint: LinearRgba,
+    pub blend: CloudBlendMode,
+    pub lighting: CloudLightingMode,
+    /// Own seed and cell layout instead of the shared global bake.
+    pub noise: Option<NoiseOverride>,
+    /// Fog falling off with height instead of cloud shapes; part of the
+    /// pipeline key.
+    pub ground_fog: Option<GroundFog>,
+    /// Derives the size-dependent parameters from the box instead of taking
+    /// them as tuned for `REFERENCE_SIZE`: the density, so the optical depth
+    /// through the box stays the same; the step count and noise tiling, so
+    /// boxes kilometres across do not stretch one noise tile over their
+    /// whole extent; the edge and contact fades; and the LOD distances. For
+    /// volumes from a bottle of fog to a weather system.
+    pub auto_scale_params: bool,
+}
+
+impl Default for CloudVolume {
+    fn default() -> Self {
+        Self {
+            density_scale: 1.0,
+            threshold_offset: 0.0,
+            noise_stretch: Vec3::ONE,
+            noise_offset: Vec3::ZERO,
+            noise_angle: 0.0,
+            noise_mirror: false,
+            cloud_type: 0.5,
+            anvil_amount: 0.0,
+            anvil_start_height: 0.75,
+            tint: LinearRgba::WHITE,
+            noise: None,
+            blend: CloudBlendMode::Blend,
+            lighting: CloudLightingMode::Unlit,
+            ground_fog: None,
+            auto_scale_params: false,
+        }
+    }
+}
+
+impl CloudVolume {
+    /// Box size the defaults are tuned for: the 2 m cube
+    /// `CloudVolumeParams::default` spawns.
+    pub const REFERENCE_SIZE: f32 = 2.0;
+    /// World size above which `auto_scale_params` tiles the noise instead
+    /// of stretching it further.
+    pub const AUTO_NOISE_TILE: f32 = 500.0;
+
+    /// How many times `REFERENCE_SIZE` the smallest side of a box with these
+    /// half extents is when `auto_scale_params` is on; 1 otherwise. The
+    /// smallest side, so flat layers scale by their thickness.
+    pub fn scale_factor(&self, half_extents: Vec3) -> f32 {
+        if !self.auto_scale_params {
+            return 1.0;
+        }
+        let factor = half_extents.abs().min_element() * 2.0 / Self::REFERENCE_SIZE;
+        if factor.is_finite() {
+            factor.max(1e-3)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// How a volume is composited over the scene.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub enum CloudBlendMode {
+    /// Straight alpha blending.
+    #[default]
+    Blend,
+    /// Premultiplied output; avoids dark fringes over UI and bright skies.
+    Premultiplied,
+    /// Adds the scattered light without occluding, for glowing wisps.
+    Add,
+    /// Darkens what is behind by the cloud color.
+    Multiply,
+}
+
+impl CloudBlendMode {
+    pub const ALL: [CloudBlendMode; 4] = [Self::Blend, Self::Premultiplied, Self::Add, Self::Multiply];
+
+    pub fn alpha_mode(self) -> AlphaMode {
+        match self {
+            Self::Blend => AlphaMode::Blend,
+            Self::Premultiplied => AlphaMode::Premultiplied,
+            Self::Add => AlphaMode::Add,
+            Self::Multiply => AlphaMode::Multiply,
+        }
+    }
+}
+
+/// How much scattering math a volume's march runs; each mode is its own
+/// pipeline variant, so simpler modes carry none of the code of the others.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub enum CloudLightingMode {
+    /// The original look: sun and ambient gradient with a single phase lobe.
+    /// The golden images pin its output.
+    #[default]
+    Unlit,
+    /// Adds a back-scattering phase lobe and the powder darkening of
+    /// sun-facing edges.
+    SingleScatter,
+    /// Adds softer, fainter octaves approximating light scattered many times,
+    /// which brightens thick cores.
+    MultiScatter,
+}
+
+impl CloudLightingMode {
+    pub const ALL: [CloudLightingMode; 3] = [Self::Unlit, Self::SingleScatter, Self::MultiScatter];
+}
+
+#[derive(Component)]
+#[require(OrbitCameraConfig)]
+pub struct OrbitCamera {
+    pub center: Vec3,
+    pub distance: f32,
+    // Kept explicitly rather than re-derived from the transform each frame,
+    // which wobbles near the pitch clamp.
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+/// Limits and input response of an `OrbitCamera`; saved with sessions.
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrbitCameraConfig {
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Radians, within `±OrbitCamera::PITCH_LIMIT`.
+    pub min_pitch: f32,
+    pub max_pitch: f32,
+    /// Radians per pixel of mouse or touch drag.
+    pub rotate_sensitivity: f32,
+    /// Multiplies gamepad and pinch zoom.
+    pub zoom_sensitivity: f32,
+    /// Multiplies gamepad and two-finger pan.
+    pub pan_sensitivity: f32,
+    pub invert_y: bool,
+    /// Ignore drags that start over egui windows.
+    pub block_on_ui: bool,
+    /// Keep the camera above a horizontal ground plane at `ground_height`,
+    /// by at least `ground_clearance`.
+    pub ground_limit: bool,
+    pub ground_height: f32,
+    pub ground_clearance: f32,
+    /// How fast the camera eases back above the ground once below it, per
+    /// second; input can never push it further down.
+    pub ground_push_rate: f32,
+}
+
+impl Default for OrbitCameraConfig {
+    fn default() -> Self {
+        Self {
+            min_distance: 0.5,
+            max_distance: 500.0,
+            min_pitch: -OrbitCamera::PITCH_LIMIT,
+            max_pitch: OrbitCamera::PITCH_LIMIT,
+            rotate_sensitivity: 0.005,
+            zoom_sensitivity: 1.0,
+            pan_sensitivity: 1.0,
+            invert_y: false,
+            block_on_ui: true,
+            ground_limit: true,
+            ground_height: 0.0,
+            ground_clearance: 0.1,
+            ground_push_rate: 8.0,
+        }
+    }
+}
+
+impl OrbitCameraConfig {
+    /// Highest pitch, at which the camera sits lowest, that keeps it clear of
+    /// the ground when orbiting `center` at `distance`; `None` without a
+    /// ground limit. The camera sits `distance * sin(pitch)` below the center.
+    pub fn ground_pitch(&self, center: Vec3, distance: f32) -> Option<f32> {
+        if !self.ground_limit {
+            return None;
+        }
+        let headroom = center.y - self.ground_height - self.ground_clearance.max(0.0);
+        Some((headroom / distance.max(0.01)).clamp(-1.0, 1.0).asin())
+    }
+
+    /// Keeps `orbit` clear of the ground, over `min_pitch` if need be.
+    /// Input may not take the camera further below the ground than it was at
+    /// `previous_pitch`, and what is already below eases back up over `dt`.
+    /// A center too low for any pitch within `OrbitCamera::PITCH_LIMIT` to
+    /// clear the ground is raised until the lowest one does.
+    pub fn limit_to_ground(&self, orbit: &mut OrbitCamera, previous_pitch: f32, dt: f32) {
+        let Some(ground_pitch) = self.ground_pitch(orbit.center, orbit.distance) else {
+            return;
+        };
+        let floor = -OrbitCamera::PITCH_LIMIT;
+        if ground_pitch < floor {
+            let lowest = self.ground_height + self.ground_clearance.max(0.0) + orbit.distance * floor.sin();
+            orbit.center.y = orbit.center.y.max(lowest);
+        }
+        let ceiling = ground_pitch.max(floor);
+        if orbit.pitch > ceiling {
+            let below = orbit.pitch.min(previous_pitch.max(ceiling)) - ceiling;
+            orbit.pitch = ceiling + below * (-self.ground_push_rate.max(0.0) * dt).exp();
+        }
+    }
+}
+
+impl OrbitCamera {
+    /// Just short of straight up or down, where yaw becomes degenerate.
+    pub const PITCH_LIMIT: f32 = 1.5;
+
+    pub fn new(center: Vec3, transform: &Transform) -> Self {
+        let mut orbit = Self {
+            center,
+            distance: transform.translation.distance(center),
+            yaw: 0.0,
+            pitch: 0.0,
+        };
+        orbit.set_rotation(transform.rotation);
+        orbit
+    }
+
+    pub fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0)
+    }
+
+    /// Adopts the yaw and pitch of an existing orientation, e.g. a loaded
+    /// camera transform.
+    pub fn set_rotation(&mut self, rotation: Quat) {
+        let (yaw, pitch, _) = rotation.to_euler(EulerRot::YXZ);
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-Self::PITCH_LIMIT, Self::PITCH_LIMIT);
+    }
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone, PartialEq)]
+#[bind_group_data(CloudMaterialKey)]
+pub struct CloudMaterial {
+    #[uniform(0)]
+    pub data: CloudMaterialUniform,
+    #[texture(1, dimension = "3d")]
+    #[sampler(2)]
+    pub noise_texture: Handle<Image>,
+    #[texture(3)]
+    #[sampler(4)]
+    pub profile_lut: Handle<Image>,
+    /// Screen-space portal mask written by `CloudPortalPlugin`.
+    #[texture(5)]
+    #[sampler(6)]
+    pub mask_texture: Handle<Image>,
+    /// Shader providing `bevy_clouds::density_override::sample_density`;
+    /// see `shader::DensityOverride`.
+    pub density_override: Option<Handle<Shader>>,
+    /// Offset of the transparent sort distance; see `sorting::CloudSortingPlugin`.
+    pub depth_bias: f32,
+    /// Copied from `CloudVolume::blend`; part of the pipeline key.
+    pub blend: CloudBlendMode,
+    /// Copied from `CloudVolume::lighting`; part of the pipeline key.
+    pub lighting: CloudLightingMode,
+    /// Baked optical depth towards the sun, written by `LightVolumePlugin`.
+    #[texture(7, dimension = "3d")]
+    #[sampler(8)]
+    pub light_volume: Option<Handle<Image>>,
+    /// Per-block maximum of the noise, written by `OccupancyPlugin`.
+    #[texture(9, dimension = "3d")]
+    pub occupancy: Option<Handle<Image>>,
+    /// Painted coverage, written by `WeatherMapPlugin`.
+    #[texture(10)]
+    #[sampler(11)]
+    pub weather_map: Option<Handle<Image>>,
+}
+
+/// Pipeline variant of a `CloudMaterial`. Optional features compile into the
+/// shader only while in use, so toggling one builds a new pipeline once.
+/// Public only as the material's bind group data; not part of the stable API.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CloudMaterialKey {
+    density_override: bool,
+    blend: CloudBlendMode,
+    lighting: CloudLightingMode,
+    ground_fog: bool,
+    detail_noise: bool,
+    weather_map: bool,
+    trail: bool,
+    light_march: bool,
+    step_jitter: bool,
+    debug_views: bool,
+}
+
+impl From<&CloudMaterial> for CloudMaterialKey {
+    fn from(material: &CloudMaterial) -> Self {
+        Self {
+            density_override: material.density_override.is_some(),
+            blend: material.blend,
+            lighting: material.lighting,
+            ground_fog: material.data.ground_fog.y > 0.0,
+            detail_noise: material.data.profile.z > 0.0,
+            weather_map: material.data.weather_map.z > 0.0,
+            trail: material.data.trail_info.x > 0.0,
+            light_march: material.data.lighting.y > 0.0 && material.data.sun_direction.w > 0.0,
+            step_jitter: material.data.sampling.x > 0.0,
+            debug_views: material.data.sampling.z > 0.0,
+        }
+    }
+}
+
+/// The uniform block of `cloud_bindings.wgsl`, written by the material sync.
+/// Public as `CloudMaterial::data` and `CloudExtension::data`, but its layout
+/// follows the shader and changes with it; not part of the stable API.
+#[doc(hidden)]
+#[derive(ShaderType, Debug, Clone, PartialEq)]
+pub struct CloudMaterialUniform {
+    pub color: LinearRgba,
+    pub extinction: LinearRgba, // rgb: per-channel extinction, scaled by settings.z
+    pub settings: Vec4, // x: density, y: threshold, z: absorption, w: steps
+    pub box_min: Vec4, // xyz: world bounds of the unrotated box; volume space is relative to its center
+    pub box_max: Vec4,
+    pub rotation: Vec4, // world-from-volume rotation quaternion
+    pub wind_offset: Vec4, // xyz: wind scroll in volume space at the lower wind altitude, w: that world height
+    pub wind_offset_upper: Vec4, // xyz: wind scroll at the upper wind altitude, w: that world height
+    pub shape: Vec4, // x: coverage, yzw: noise stretch
+    pub lighting: Vec4, // x: phase g, y: sun intensity, z: light steps, w: 1 for physical sun
+    pub silver: Vec4, // x: silver lining intensity, y: spread
+    pub ambient_top: LinearRgba,
+    pub ambient_bottom: LinearRgba,
+    pub profile: Vec4, // x: cloud type, y: threshold softness, z: softness erosion
+    pub anvil: Vec4, // x: amount, y: start as a fraction of the box height
+    pub sampling: Vec4, // x: per-step jitter as a fraction of the step, y: 1 to sample the light volume, z: 1 for the step heatmap, w: alpha cutoff
+    pub occlusion: Vec4, // x: ambient occlusion strength
+    pub weather_map: Vec4, // xy: world XZ of the map's min corner, zw: reciprocal size; zw 0 disables
+    pub sun_direction: Vec4, // xyz: world direction towards the sun, w: 1 when there is one
+    pub sun_color: LinearRgba, // rgb: linear color times illuminance
+    pub fill_direction: Vec4, // xyz: world direction towards the fill light, w: 1 when enabled
+    pub fill_color: LinearRgba,
+    pub ground_fog: Vec4, // x: volume-space base height, y: scale height (0 off), z: noise blend, w: noise tile size
+    pub fog_color: LinearRgba, // rgb: distance color, a: fog distance
+    pub bounds: Vec4, // x: march start inset, y: density outset past the box, z: contact fade distance, all in world units
+    pub noise_offset: Vec4, // xyz: offset of the sampling domain in noise tiles
+    pub noise_domain: Vec4, // xy: cos and sin of the domain's turn about Y, z: -1 to mirror X, else 1
+    pub fbm_scale: Vec4, // per-tap frequency relative to the bake
+    pub fbm_weight: Vec4, // per-tap weight, summing to 1
+    pub mask: Vec4, // x: 1 when the portal mask applies, yzw: world position of the view it is drawn for
+    pub merge_count: Vec4, // x: boxes in use below
+    pub merge_min: [Vec4; sorting::MAX_MERGED], // overlapping volumes that march these regions instead
+    pub merge_max: [Vec4; sorting::MAX_MERGED],
+    pub trail_info: Vec4, // x: path points in use, y: world radius
+    pub trail: [Vec4; trail::MAX_TRAIL_POINTS], // xyz: world position along a `CloudTrail`, newest first, w: coverage there
+    pub opacity: f32, // crossfade with the LOD impostor
+}
+
+impl CloudMaterialUniform {
+    /// Writes everything derived from the global settings and one volume.
+    /// Opacity is left to the caller.
+    #[allow(clippy::manual_clamp)]
+    pub fn sync(&mut self, settings: &CloudSettings, volume: &CloudVolume, transform: &GlobalTransform) {
+        let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+        // A zero-sized box would divide by zero in the ray-box test.
+        let half_extents = scale.abs().max(Vec3::splat(1e-4));
+
+        let color = LinearRgba::from(settings.color);
+        let tint = volume.tint;
+        self.color = LinearRgba::new(
+            color.red * tint.red,
+            color.green * tint.green,
+            color.blue * tint.blue,
+            color.alpha * tint.alpha,
+        );
+        self.extinction = LinearRgba::from(settings.extinction_color);
+        // Past `AUTO_NOISE_TILE` the noise repeats instead of stretching,
+        // and the march takes more steps to resolve the extra tiles: the
+        // square root of the most along any axis, which keeps huge volumes
+        // from aliasing without multiplying their cost by the tile count.
+        let scale_factor = volume.scale_factor(half_extents);
+        let tiles = if volume.auto_scale_params {
+            (half_extents * 2.0 / CloudVolume::AUTO_NOISE_TILE).clamp(Vec3::ONE, Vec3::splat(1.0e4))
+        } else {
+            Vec3::ONE
+        };
+        self.settings = Vec4::new(
+            settings.density_multiplier * volume.density_scale.max(0.0).min(CloudSettings::MAX_SCALE) / scale_factor,
+            // `max` then `min` so a NaN offset lands on 0.
+            (settings.threshold + volume.threshold_offset).max(0.0).min(1.0),
+            settings.absorption,
+            (settings.steps as f32 * tiles.max_element().sqrt().min(8.0)).round(),
+        );
+        self.box_min = (translation - half_extents).extend(0.0);
+        self.box_max = (translation + half_extents).extend(0.0);
+        let rotation = rotation.normalize();
+        self.rotation = Vec4::from(if rotation.is_finite() { rotation } else { Quat::IDENTITY });
+        // The wind keeps blowing along its world direction.
+        self.wind_offset = self.dir_to_volume(settings.wind_offset).extend(settings.wind_altitudes.x);
+        self.wind_offset_upper = self.dir_to_volume(settings.wind_offset_upper).extend(settings.wind_altitudes.y);
+        let stretch = volume.noise_stretch.max(Vec3::splat(0.01)) / tiles;
+        self.shape = Vec4::new(settings.coverage, stretch.x, stretch.y, stretch.z);
+        self.lighting = Vec4::new(
+            settings.phase_g,
+            settings.sun_intensity,
+            settings.light_steps as f32,
+            settings.physical_sun as u32 as f32,
+        );
+        self.silver = Vec4::new(settings.silver_intensity, settings.silver_spread, 0.0, 0.0);
+        self.ambient_top = LinearRgba::from(settings.ambient_top);
+        self.ambient_bottom = LinearRgba::from(settings.ambient_bottom);
+        self.profile = Vec4::new(volume.cloud_type, settings.threshold_softness, settings.softness_erosion, 0.0);
+        // `max` then `min` so NaN lands on 0; the anvil needs some height
+        // above its start to ramp in over.
+        self.anvil = Vec4::new(
+            volume.anvil_amount.max(0.0).min(1.0),
+            volume.anvil_start_height.max(0.0).min(0.95),
+            0.0,
+            0.0,
+        );
+        // `sampling.y` belongs to `LightVolumePlugin`.
+        self.sampling.x = settings.step_jitter;
+        self.sampling.z = settings.step_heatmap as u32 as f32;
+        self.sampling.w = settings.alpha_cutoff;
+        self.occlusion = Vec4::new(settings.ao_strength, 0.0, 0.0, 0.0);
+        self.ground_fog = volume.ground_fog.map_or(Vec4::ZERO, |fog| fog.uniform(-half_extents.y));
+        self.fog_color = volume.ground_fog.map_or(LinearRgba::NONE, |fog| fog.color_uniform());
+        self.bounds = Vec4::new(settings.bounds_inset, settings.bounds_outset, settings.contact_fade_distance, 0.0)
+            * scale_factor;
+        // The noise tiles, so only the fraction of the offset matters; keeping
+        // just that holds the shader's texture coordinates small.
+        let offset = volume.noise_offset;
+        self.noise_offset = if offset.is_finite() { offset - offset.floor() } else { Vec3::ZERO }.extend(0.0);
+        let angle = if volume.noise_angle.is_finite() { volume.noise_angle } else { 0.0 };
+        let (sin, cos) = angle.sin_cos();
+        self.noise_domain = Vec4::new(cos, sin, if volume.noise_mirror { -1.0 } else { 1.0 }, 0.0);
+        self.fbm_scale = Vec4::from_array(settings.fbm_frequencies);
+        self.fbm_weight = settings.fbm_weight_uniform();
+    }
+
+    /// Sets the light the march scatters, as a world direction towards it
+    /// and its linear color times illuminance; `None` leaves ambient light
+    /// only. See `cloud_sun::CloudLights`.
+    pub fn set_sun(&mut self, sun: Option<(Vec3, LinearRgba)>) {
+        let (direction, color) = sun.unwrap_or((Vec3::Y, LinearRgba::BLACK));
+        self.sun_direction = direction.extend(sun.is_some() as u32 as f32);
+        self.sun_color = color;
+    }
+
+    /// Sets the light added without a light march, as `set_sun`.
+    pub fn set_fill(&mut self, fill: Option<(Vec3, LinearRgba)>) {
+        let (direction, color) = fill.unwrap_or((Vec3::Y, LinearRgba::BLACK));
+        self.fill_direction = direction.extend(fill.is_some() as u32 as f32);
+        self.fill_color = color;
+    }
+
+    fn box_center(&self) -> Vec3 {
+        (self.box_min + self.box_max).truncate() * 0.5
+    }
+
+    /// Half extents of the box, which spans `-half..half` in volume space.
+    pub fn half_extents(&self) -> Vec3 {
+        (self.box_max - self.box_min).truncate() * 0.5
+    }
+
+    /// Volume-space position of a world-space point, relative to the box
+    /// center, as `to_volume` in `cloud_common.wgsl`.
+    pub fn to_volume(&self, p: Vec3) -> Vec3 {
+        self.dir_to_volume(p - self.box_center())
+    }
+
+    pub fn to_world(&self, p: Vec3) -> Vec3 {
+        self.box_center() + Quat::from_vec4(self.rotation) * p
+    }
+
+    pub fn dir_to_volume(&self, dir: Vec3) -> Vec3 {
+        Quat::from_vec4(self.rotation).inverse() * dir
+    }
+
+    /// Wind scroll at a volume-space position, as `wind_scroll` in
+    /// `cloud_common.wgsl`.
+    pub fn wind_scroll(&self, p: Vec3) -> Vec3 {
+        let (lower, upper) = (self.wind_offset, self.wind_offset_upper);
+        let weight = ((self.to_world(p).y - lower.w) / (upper.w - lower.w)).clamp(0.0, 1.0);
+        lower.truncate().lerp(upper.truncate(), weight)
+    }
+
+    /// World-space bounds of the rotated box.
+    pub fn world_bounds(&self) -> (Vec3, Vec3) {
+        let half_extents = self.half_extents();
+        let rotation = Mat3::from_quat(Quat::from_vec4(self.rotation));
+        let reach = rotation.x_axis.abs() * half_extents.x
+            + rotation.y_axis.abs() * half_extents.y
+            + rotation.z_axis.abs() * half_extents.z;
+        (self.box_center() - reach, self.box_center() + reach)
+    }
+}
+
+impl CloudMaterial {
+    pub fn new(settings: &CloudSettings) -> Self {
+        Self {
+            data: CloudMaterialUniform {
+                color: LinearRgba::from(settings.color),
+                extinction: LinearRgba::from(settings.extinction_color),
+                settings: Vec4::new(
+                    settings.density_multiplier,
+                    settings.threshold,
+                    settings.absorption,
+                    settings.steps as f32,
+                ),
+                box_min: Vec4::new(-1.0, 0.0, -1.0, 0.0),
+                box_max: Vec4::new(1.0, 2.0, 1.0, 0.0),
+                rotation: Vec4::from(Quat::IDENTITY),
+                wind_offset: settings.wind_offset.extend(settings.wind_altitudes.x),
+                wind_offset_upper: settings.wind_offset_upper.extend(settings.wind_altitudes.y),
+                shape: Vec4::new(settings.coverage, 1.0, 1.0, 1.0),
+                lighting: Vec4::new(
+                    settings.phase_g,
+                    settings.sun_intensity,
+                    settings.light_steps as f32,
+                    settings.physical_sun as u32 as f32,
+                ),
+                silver: Vec4::new(settings.silver_intensity, settings.silver_spread, 0.0, 0.0),
+                ambient_top: LinearRgba::from(settings.ambient_top),
+                ambient_bottom: LinearRgba::from(settings.ambient_bottom),
+                profile: Vec4::new(0.5, settings.threshold_softness, settings.softness_erosion, 0.0),
+                anvil: Vec4::new(0.0, 0.75, 0.0, 0.0),
+                sampling: Vec4::new(
+                    settings.step_jitter,
+                    0.0,
+                    settings.step_heatmap as u32 as f32,
+                    settings.alpha_cutoff,
+                ),
+                occlusion: Vec4::new(settings.ao_strength, 0.0, 0.0, 0.0),
+                weather_map: Vec4::ZERO,
+                sun_direction: Vec4::new(0.0, 1.0, 0.0, 0.0),
+                sun_color: LinearRgba::BLACK,
+                fill_direction: Vec4::new(0.0, 1.0, 0.0, 0.0),
+                fill_color: LinearRgba::BLACK,
+                ground_fog: Vec4::ZERO,
+                fog_color: LinearRgba::NONE,
+                bounds: Vec4::new(settings.bounds_inset, settings.bounds_outset, settings.contact_fade_distance, 0.0),
+                noise_offset: Vec4::ZERO,
+                noise_domain: Vec4::new(1.0, 0.0, 1.0, 0.0),
+                fbm_scale: Vec4::from_array(settings.fbm_frequencies),
+                fbm_weight: settings.fbm_weight_uniform(),
+                mask: Vec4::ZERO,
+                merge_count: Vec4::ZERO,
+                merge_min: [Vec4::ZERO; sorting::MAX_MERGED],
+                merge_max: [Vec4::ZERO; sorting::MAX_MERGED],
+                trail_info: Vec4::ZERO,
+                trail: [Vec4::ZERO; trail::MAX_TRAIL_POINTS],
+                opacity: 1.0,
+            },
+            noise_texture: settings.noise_handle.clone(),
+            profile_lut: settings.profile_handle.clone(),
+            mask_texture: Handle::default(),
+            density_override: None,
+            depth_bias: 0.0,
+            blend: CloudBlendMode::Blend,
+            lighting: CloudLightingMode::Unlit,
+            light_volume: None,
+            occupancy: None,
+            weather_map: None,
+        }
+    }
+}
+
+impl Material for CloudMaterial {
+    fn fragment_shader() -> ShaderRef {
+        shader::fragment_shader()
+    }
+
+    // The same file; its vertex stage stretches ground fog boxes.
+    fn vertex_shader() -> ShaderRef {
+        shader::fragment_shader()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.blend.alpha_mode()
+    }
+
+    fn depth_bias(&self) -> f32 {
+        self.depth_bias
+    }
+
+    // Draw the back faces: every covered pixel gets exactly one fragment,
+    // including when the camera is inside the volume.
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        descriptor.primitive.cull_mode = Some(Face::Front);
+        // With a depth prepass the fragment stops the march at the scene
+        // instead, so geometry inside the volume is covered by the part in
+        // front of it rather than cutting the whole pixel away.
+        if key.mesh_key.contains(MeshPipelineKey::DEPTH_PREPASS) {
+            if let Some(depth_stencil) = descriptor.depth_stencil.as_mut() {
+                depth_stencil.depth_compare = CompareFunction::Always;
+            }
+        }
+        view_overrides::specialize_view(descriptor);
+        if key.bind_group_data.ground_fog {
+            descriptor.vertex.shader_defs.push("CLOUD_GROUND_FOG".into());
+        }
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            if key.bind_group_data.ground_fog {
+                fragment.shader_defs.push("CLOUD_GROUND_FOG".into());
+            }
+            if key.bind_group_data.density_override {
+                fragment.shader_defs.push("CLOUD_DENSITY_OVERRIDE".into());
+            }
+            match key.bind_group_data.blend {
+                CloudBlendMode::Blend => {}
+                CloudBlendMode::Premultiplied | CloudBlendMode::Multiply => {
+                    fragment.shader_defs.push("CLOUD_PREMULTIPLIED".into());
+                }
+                CloudBlendMode::Add => fragment.shader_defs.push("CLOUD_ADDITIVE".into()),
+            }
+            match key.bind_group_data.lighting {
+                CloudLightingMode::Unlit => {}
+                CloudLightingMode::SingleScatter => fragment.shader_defs.push("CLOUD_SINGLE_SCATTER".into()),
+                CloudLightingMode::MultiScatter => {
+                    fragment.shader_defs.push("CLOUD_SINGLE_SCATTER".into());
+                    fragment.shader_defs.push("CLOUD_MULTI_SCATTER".into());
+                }
+            }
+            let features = [
+                (key.bind_group_data.detail_noise, "CLOUD_DETAIL_NOISE"),
+                (key.bind_group_data.weather_map, "CLOUD_WEATHER_MAP"),
+                (key.bind_group_data.trail, "CLOUD_TRAIL"),
+                (key.bind_group_data.light_march, "CLOUD_LIGHT_MARCH"),
+                (key.bind_group_data.step_jitter, "CLOUD_STEP_JITTER"),
+                (key.bind_group_data.debug_views, "CLOUD_DEBUG_VIEWS"),
+            ];
+            for (enabled, def) in features {
+                if enabled {
+                    fragment.shader_defs.push(def.into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ui")]
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<StandardMaterial>>) {
+    // Cloud Cube
+    commands.spawn_cloud(CloudVolumeParams::default().at(Vec3::new(0.0, 1.0, 0.0)));
+
+    // Boulder half-buried in the cloud's side, to judge the contact fade
+    commands.spawn((
+        Mesh3d(meshes.add(Sphere::new(0.5).mesh().uv(32, 18))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: Color::srgb(0.35, 0.33, 0.3),
+            perceptual_roughness: 0.9,
+            ..default()
+        })),
+        Transform::from_xyz(1.0, 0.6, 0.3),
+    ));
+
+    // Light (Sun)
+    commands.spawn((
+        DirectionalLight {
+            shadows_enabled: true,
+            ..default()
+        },
+        Transform::from_xyz(4.0, 8.0, 4.0).looking_at(Vec3::ZERO, Vec3::Y),
+        Sun,
+    ));
+
+    // Camera with Atmosphere
+    let camera_transform = Transform::from_xyz(-3.0, 3.0, 6.0).looking_at(Vec3::new(0.0, 1.0, 0.0), Vec3::Y);
+    commands.spawn((
+        Camera3d::default(),
+        camera_transform,
+        OrbitCamera::new(Vec3::new(0.0, 1.0, 0.0), &camera_transform),
+        AtmosphereCamera::default(),
+        // Lets the clouds stop their march at scene geometry.
+        DepthPrepass,
+    ));
+}
+
+/// Default width of the settings window on a 1080p-class screen, in points.
+#[cfg(feature = "ui")]
+const SETTINGS_MIN_WIDTH: f32 = 320.0;
+
+#[cfg(feature = "ui")]
+#[allow(clippy::too_many_arguments)]
+fn ui_system(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<CloudSettings>,
+    bake_stats: Res<BakeStats>,
+    mut bake_progress: ResMut<BakeProgress>,
+    mut bake_cache: ResMut<BakeCache>,
+    mut previews: ResMut<NoisePreviews>,
+    mut light_volume: ResMut<LightVolumeSettings>,
+    color_preview: Res<ColorPreview>,
+    cloud_lights: Res<CloudLights>,
+    mut texture_limits: ResMut<TextureLimits>,
+    mut strings: ResMut<UiStrings>,
+    mut unlinked_resolution: Local<bool>,
+    mut cameras: Query<(&mut Projection, &mut OrbitCameraConfig), With<OrbitCamera>>,
+) {
+    let ctx = contexts.ctx_mut();
+    // Points already include the window's scale factor; a screen that is
+    // still wide in points (4K at 100%) gets a proportionally wider panel.
+    let default_width = (ctx.screen_rect().width() * 0.2).max(SETTINGS_MIN_WIDTH);
+    // Picked languages load once the window has let go of the strings.
+    let mut language = None;
+    // A fixed id keeps the window's position and size across languages.
+    let window = egui::Window::new(strings.get("settings.title")).id(egui::Id::new("cloud_settings"));
+    window.default_width(default_width).show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.label(strings.get("settings.language"));
+            egui::ComboBox::from_id_salt("ui_language")
+                .selected_text(&strings.language)
+                .show_ui(ui, |ui| {
+                    for code in &strings.available {
+                        if ui.selectable_label(*code == strings.language, code).clicked() {
+                            language = Some(code.clone());
+                        }
+                    }
+                });
+        });
+        ui.add(
+            egui::Slider::new(&mut settings.density_multiplier, 0.0..=10.0)
+                .suffix(" /m")
+                .text(strings.get("density")),
+        )
+        .on_hover_text(strings.get("density.hover"));
+        ui.horizontal(|ui| {
+            if ui.add(egui::Slider::new(&mut settings.threshold, 0.0..=1.0).text(strings.get("threshold"))).changed() {
+                settings.auto_threshold = None;
+            }
+            let achieved = format!("{:.0}", bake_stats.coverage_above(settings.threshold) * 100.0);
+            ui.label(strings.fill("threshold.achieved", &[&achieved]))
+                .on_hover_text(strings.get("threshold.achieved.hover"));
+        });
+        ui.horizontal(|ui| {
+            let mut auto = settings.auto_threshold.is_some();
+            if ui
+                .checkbox(&mut auto, strings.get("threshold.auto"))
+                .on_hover_text(strings.get("threshold.auto.hover"))
+                .changed()
+            {
+                // Start from the coverage the current threshold gives.
+                settings.auto_threshold = auto.then(|| bake_stats.coverage_above(settings.threshold));
+            }
+            if let Some(mut percent) = settings.auto_threshold.map(|target| target * 100.0) {
+                if ui
+                    .add(egui::Slider::new(&mut percent, 0.0..=100.0).suffix("%").text(strings.get("threshold.target")))
+                    .changed()
+                {
+                    settings.auto_threshold = Some(percent / 100.0);
+                }
+            }
+        });
+        ui.add(egui::Slider::new(&mut settings.threshold_softness, 0.0..=0.5).text(strings.get("softness")))
+            .on_hover_text(strings.get("softness.hover"));
+        ui.add_enabled(
+            settings.threshold_softness > 0.0,
+            egui::Slider::new(&mut settings.softness_erosion, 0.0..=1.0).text(strings.get("edge_erosion")),
+        )
+        .on_hover_text(strings.get("edge_erosion.hover"));
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::Slider::new(&mut settings.absorption, 0.0..=10.0)
+                    .suffix(" /m")
+                    .text(strings.get("absorption")),
+            );
+            let mut extinction = settings.extinction_color.to_linear().to_f32_array_no_alpha();
+            if ui
+                .color_edit_button_rgb(&mut extinction)
+                .on_hover_text(strings.get("extinction.hover"))
+                .changed()
+            {
+                settings.extinction_color = Color::linear_rgb(extinction[0], extinction[1], extinction[2]);
+            }
+        });
+        ui.add(egui::Slider::new(&mut settings.coverage, 0.0..=1.0).text(strings.get("coverage")));
+        
+        let mut steps_f32 = settings.steps as f32;
+        ui.add(egui::Slider::new(&mut steps_f32, 4.0..=64.0).text(strings.get("steps")));
+        settings.steps = steps_f32 as u32;
+        ui.add(egui::Slider::new(&mut settings.light_steps, 1..=16).text(strings.get("light_steps")));
+        ui.checkbox(&mut settings.merge_overlaps, strings.get("merge_overlaps"))
+            .on_hover_text(strings.get("merge_overlaps.hover"));
+        ui.horizontal(|ui| {
+            ui.label(strings.get("quality"));
+            for preset in QualityPreset::ALL {
+                if ui.selectable_label(settings.steps == preset.steps(), strings.get(preset.key())).clicked() {
+                    preset.apply(&mut settings);
+                }
+            }
+        });
+        ui.collapsing(strings.get("dithering"), |ui| {
+            ui.add(egui::Slider::new(&mut settings.step_jitter, 0.0..=1.0).text(strings.get("step_jitter")))
+                .on_hover_text(strings.get("step_jitter.hover"));
+            ui.checkbox(&mut settings.step_heatmap, strings.get("step_heatmap"))
+                .on_hover_text(strings.get("step_heatmap.hover"));
+            ui.horizontal(|ui| {
+                ui.add(egui::Slider::new(&mut settings.alpha_cutoff, 0.0..=0.05).show_value(false))
+                    .on_hover_text(strings.get("alpha_cutoff.hover"));
+                let color = if settings.alpha_cutoff > CloudSettings::ALPHA_CUTOFF_WARNING {
+                    egui::Color32::from_rgb(255, 170, 60)
+                } else {
+                    ui.visuals().text_color()
+                };
+                ui.colored_label(color, format!("{:.4}", settings.alpha_cutoff));
+                ui.label(strings.get("alpha_cutoff"));
+            });
+        });
+        ui.collapsing(strings.get("bounds"), |ui| {
+            ui.add(
+                egui::Slider::new(&mut settings.bounds_inset, 0.0..=1.0)
+                    .suffix(" m")
+                    .text(strings.get("bounds.inset")),
+            )
+            .on_hover_text(strings.get("bounds.inset.hover"));
+            ui.add(
+                egui::Slider::new(&mut settings.bounds_outset, 0.0..=1.0)
+                    .suffix(" m")
+                    .text(strings.get("bounds.outset")),
+            )
+            .on_hover_text(strings.get("bounds.outset.hover"));
+            ui.add(
+                egui::Slider::new(&mut settings.contact_fade_distance, 0.0..=1.0)
+                    .suffix(" m")
+                    .text(strings.get("bounds.contact_fade")),
+            )
+            .on_hover_text(strings.get("bounds.contact_fade.hover"));
+        });
+
+        ui.separator();
+        ui.label(strings.get("sun_wind"));
+        ui.horizontal(|ui| {
+            let label = strings.get(if settings.physical_sun { "sun.scale" } else { "sun.intensity" });
+            ui.add(egui::Slider::new(&mut settings.sun_intensity, 0.0..=4.0).text(label));
+            if ui
+                .checkbox(&mut settings.physical_sun, strings.get("sun.physical"))
+                .on_hover_text(strings.get("sun.physical.hover"))
+                .changed()
+                && settings.physical_sun
+                && settings.sun_intensity == 0.0
+            {
+                settings.sun_intensity = 1.0;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(strings.get("sun.light"));
+            match &cloud_lights.sun {
+                Some(sun) => ui.label(sun.label()),
+                None => ui.label(strings.get("sun.light.none")),
+            }
+            .on_hover_text(strings.get("sun.light.hover"));
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut settings.fill_light, strings.get("fill_light"))
+                .on_hover_text(strings.get("fill_light.hover"));
+            if let (true, Some(fill)) = (settings.fill_light, &cloud_lights.fill) {
+                ui.label(fill.label());
+            }
+        });
+        ui.add(egui::Slider::new(&mut settings.phase_g, -0.9..=0.9).text(strings.get("phase_g")));
+        ui.add(egui::Slider::new(&mut settings.silver_intensity, 0.0..=4.0).text(strings.get("silver_lining")));
+        ui.add_enabled(
+            settings.silver_intensity > 0.0,
+            egui::Slider::new(&mut settings.silver_spread, 0.01..=1.0).text(strings.get("silver_spread")),
+        );
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut light_volume.enabled, strings.get("baked_lighting"))
+                .on_hover_text(strings.get("baked_lighting.hover"));
+            ui.add_enabled(
+                light_volume.enabled,
+                egui::DragValue::new(&mut light_volume.max_angle).range(0.5..=45.0).suffix("°"),
+            )
+            .on_hover_text(strings.get("baked_lighting.angle.hover"));
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut settings.wind_paused, strings.get("wind.pause"));
+            ui.label(strings.get("wind")).on_hover_text(strings.get("wind.hover"));
+        });
+        ui.horizontal(|ui| {
+            ui.label(strings.get("wind.lower"));
+            ui.add(egui::DragValue::new(&mut settings.wind_altitudes.x).speed(0.05).prefix("y: ").suffix(" m"));
+            ui.add(egui::DragValue::new(&mut settings.wind.x).speed(0.01).prefix("x: ").suffix(" m/s"));
+            ui.add(egui::DragValue::new(&mut settings.wind.z).speed(0.01).prefix("z: ").suffix(" m/s"));
+        });
+        ui.horizontal(|ui| {
+            ui.label(strings.get("wind.upper"));
+            let lower = settings.wind_altitudes.x;
+            ui.add(
+                egui::DragValue::new(&mut settings.wind_altitudes.y)
+                    .speed(0.05)
+                    .range(lower + CloudSettings::MIN_WIND_SPAN..=CloudSettings::MAX_WIND_ALTITUDE)
+                    .prefix("y: ")
+                    .suffix(" m"),
+            );
+            ui.add(egui::DragValue::new(&mut settings.wind_upper.x).speed(0.01).prefix("x: ").suffix(" m/s"));
+            ui.add(egui::DragValue::new(&mut settings.wind_upper.z).speed(0.01).prefix("z: ").suffix(" m/s"));
+        });
+        // Swatches show a sunlit texel for the cloud color and a shaded one
+        // for each ambient color.
+        ui.horizontal(|ui| {
+            ui.label(strings.get("cloud_color"));
+            let mut color = settings.color.to_srgba().to_f32_array_no_alpha();
+            if ui.color_edit_button_rgb(&mut color).changed() {
+                settings.color = Color::srgb_from_array(color);
+            }
+            color_preview.swatch(ui, color_preview.sunlit(&settings, settings.color.into()));
+        });
+        ui.horizontal(|ui| {
+            ui.label(strings.get("ambient_top"));
+            let mut top = settings.ambient_top.to_srgba().to_f32_array_no_alpha();
+            if ui.color_edit_button_rgb(&mut top).changed() {
+                settings.ambient_top = Color::srgb_from_array(top);
+            }
+            color_preview.swatch(ui, ColorPreview::shaded(settings.color.into(), settings.ambient_top.into()));
+            ui.label(strings.get("ambient_bottom"));
+            let mut bottom = settings.ambient_bottom.to_srgba().to_f32_array_no_alpha();
+            if ui.color_edit_button_rgb(&mut bottom).changed() {
+                settings.ambient_bottom = Color::srgb_from_array(bottom);
+            }
+            color_preview.swatch(ui, ColorPreview::shaded(settings.color.into(), settings.ambient_bottom.into()));
+        });
+        ui.add(egui::Slider::new(&mut settings.ao_strength, 0.0..=1.0).text(strings.get("ambient_occlusion")))
+            .on_hover_text(strings.get("ambient_occlusion.hover"));
+
+        ui.separator();
+        ui.label(strings.get("noise"));
+        ui.horizontal(|ui| {
+            ui.label(strings.get("noise.type"));
+            for noise_type in NoiseType::ALL {
+                if ui
+                    .selectable_value(&mut settings.noise_type, noise_type, format!("{:?}", noise_type))
+                    .changed()
+                {
+                    settings.needs_rebuild = true;
+                }
+            }
+        });
+        if settings.noise_type == NoiseType::Perlin {
+            ui.horizontal(|ui| {
+                if ui.add(egui::Slider::new(&mut settings.octaves, 1..=6).text(strings.get("noise.octaves"))).changed() {
+                    settings.needs_rebuild = true;
+                }
+                let before = settings.fractal_type;
+                egui::ComboBox::from_id_salt("fractal_type")
+                    .selected_text(format!("{:?}", settings.fractal_type))
+                    .show_ui(ui, |ui| {
+                        for fractal in FractalType::ALL {
+                            ui.selectable_value(&mut settings.fractal_type, fractal, format!("{:?}", fractal));
+                        }
+                    });
+                if settings.fractal_type != before {
+                    settings.needs_rebuild = true;
+                }
+            });
+        }
+        if ui.add(egui::Slider::new(&mut settings.seed, 0..=100).text(strings.get("noise.seed"))).changed() {
+            settings.needs_rebuild = true;
+        }
+        if ui.add(egui::Slider::new(&mut settings.frequency, 1.0..=10.0).text(strings.get("noise.frequency"))).changed() {
+            settings.needs_rebuild = true;
+        }
+        if ui.add(egui::Slider::new(&mut settings.cell_count, 4..=64).text(strings.get("noise.cell_count"))).changed() {
+            settings.needs_rebuild = true;
+        }
+        let warp_amplitude = egui::Slider::new(&mut settings.warp_amplitude, 0.0..=0.5);
+        if ui.add(warp_amplitude.text(strings.get("noise.warp_amplitude"))).changed() {
+            settings.needs_rebuild = true;
+        }
+        let warp_frequency = egui::Slider::new(&mut settings.warp_frequency, 1..=8);
+        if ui.add(warp_frequency.text(strings.get("noise.warp_frequency"))).changed() {
+            settings.needs_rebuild = true;
+        }
+        if ui.checkbox(&mut settings.normalize, strings.get("noise.normalize")).changed() {
+            settings.needs_rebuild = true;
+        }
+        // Applied by the shader, so no rebake.
+        ui.collapsing(strings.get("fbm"), |ui| {
+            let settings = &mut *settings;
+            for (i, (frequency, weight)) in
+                settings.fbm_frequencies.iter_mut().zip(&mut settings.fbm_weights).enumerate()
+            {
+                ui.horizontal(|ui| {
+                    let range = CloudSettings::MIN_FBM_FREQUENCY..=CloudSettings::MAX_FBM_FREQUENCY;
+                    ui.add(egui::Slider::new(frequency, range).logarithmic(true).prefix("×"))
+                        .on_hover_text(strings.get("fbm.frequency.hover"));
+                    ui.add(egui::Slider::new(weight, 0.0..=1.0).text(strings.fill("fbm.tap", &[&(i + 1)])))
+                        .on_hover_text(strings.get("fbm.weight.hover"));
+                });
+            }
+            if ui.button(strings.get("fbm.reset")).clicked() {
+                settings.fbm_frequencies = CloudSettings::DEFAULT_FBM_FREQUENCIES;
+                settings.fbm_weights = CloudSettings::defaults().fbm_weights;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(strings.get("noise.resolution"));
+            ui.checkbox(&mut unlinked_resolution, strings.get("noise.per_axis"));
+        });
+        let range = noise::MIN_RESOLUTION..=texture_limits.max_resolution();
+        let mut resolution = settings.resolution;
+        if *unlinked_resolution {
+            ui.add(egui::Slider::new(&mut resolution.x, range.clone()).text(strings.get("noise.width")));
+            ui.add(egui::Slider::new(&mut resolution.y, range.clone()).text(strings.get("noise.height")));
+            ui.add(egui::Slider::new(&mut resolution.z, range).text(strings.get("noise.depth")));
+        } else {
+            let mut edge = resolution.max_element();
+            if ui.add(egui::Slider::new(&mut edge, range).text(strings.get("noise.size"))).changed() {
+                resolution = UVec3::splat(edge);
+            }
+        }
+        let resolution = noise::clamp_resolution(resolution);
+        if resolution != settings.resolution {
+            settings.resolution = resolution;
+            settings.needs_rebuild = true;
+        }
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut texture_limits.budget_mb)
+                    .range(1..=4096)
+                    .prefix(format!("{} ", strings.get("noise.budget")))
+                    .suffix(" MB"),
+            )
+            .on_hover_text(strings.get("noise.budget.hover"));
+            ui.label(format!("{} MB", texture_limits::noise_bytes(settings.resolution).div_ceil(1 << 20)));
+        });
+        if let Some(error) = &texture_limits.error {
+            ui.colored_label(egui::Color32::from_rgb(255, 170, 60), error);
+        }
+        let (min, max) = (format!("{:.2}", bake_stats.min), format!("{:.2}", bake_stats.max));
+        ui.label(strings.fill("noise.raw_range", &[&min, &max]));
+        ui.horizontal(|ui| {
+            if bake_cache.last_hit {
+                ui.label(strings.get("noise.last_bake.cached"));
+            } else {
+                let ms = format!("{:.1}", bake_stats.duration.as_secs_f64() * 1000.0);
+                ui.label(strings.fill("noise.last_bake", &[&ms]));
+            }
+            if ui.button(strings.get("noise.clear_cache")).clicked() {
+                let removed = bake_cache.clear();
+                info!("Removed {} cached noise bakes", removed);
+            }
+            ui.checkbox(&mut bake_cache.enabled, strings.get("noise.cache"));
+        });
+        if let Some(fraction) = bake_progress.fraction() {
+            ui.horizontal(|ui| {
+                ui.add(egui::ProgressBar::new(fraction).show_percentage().desired_width(160.0));
+                if ui.button(strings.get("noise.cancel")).on_hover_text(strings.get("noise.cancel.hover")).clicked() {
+                    bake_progress.cancel_requested = true;
+                }
+            });
+        }
+        // The line marks the effective threshold after coverage.
+        noise::histogram_ui(ui, &previews.histogram, 1.0_f32.lerp(settings.threshold, settings.coverage));
+        ui.collapsing(strings.get("noise.slice_preview"), |ui| {
+            noise::slice_preview_ui(ui, &mut previews.slice)
+        });
+
+        ui.separator();
+        if let Ok((mut projection, mut config)) = cameras.get_single_mut() {
+            let mut orthographic = matches!(*projection, Projection::Orthographic(_));
+            if ui.checkbox(&mut orthographic, strings.get("camera.orthographic")).changed() {
+                // The orbit camera keeps the scale in step with its distance.
+                *projection = if orthographic {
+                    Projection::Orthographic(OrthographicProjection::default_3d())
+                } else {
+                    Projection::Perspective(PerspectiveProjection::default())
+                };
+            }
+            ui.collapsing(strings.get("camera"), |ui| {
+                let limit = OrbitCamera::PITCH_LIMIT;
+                ui.add(
+                    egui::Slider::new(&mut config.min_distance, 0.1..=50.0)
+                        .logarithmic(true)
+                        .suffix(" m")
+                        .text(strings.get("camera.min_distance")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut config.max_distance, 1.0..=2000.0)
+                        .logarithmic(true)
+                        .suffix(" m")
+                        .text(strings.get("camera.max_distance")),
+                );
+                ui.add(egui::Slider::new(&mut config.min_pitch, -limit..=limit).text(strings.get("camera.min_pitch")));
+                ui.add(egui::Slider::new(&mut config.max_pitch, -limit..=limit).text(strings.get("camera.max_pitch")));
+                ui.add(
+                    egui::Slider::new(&mut config.rotate_sensitivity, 0.001..=0.02)
+                        .logarithmic(true)
+                        .text(strings.get("camera.rotate_sensitivity")),
+                );
+                let zoom = egui::Slider::new(&mut config.zoom_sensitivity, 0.1..=4.0);
+                ui.add(zoom.text(strings.get("camera.zoom_sensitivity")));
+                let pan = egui::Slider::new(&mut config.pan_sensitivity, 0.1..=4.0);
+                ui.add(pan.text(strings.get("camera.pan_sensitivity")));
+                ui.checkbox(&mut config.invert_y, strings.get("camera.invert_y"));
+                ui.checkbox(&mut config.block_on_ui, strings.get("camera.block_on_ui"));
+                ui.checkbox(&mut config.ground_limit, strings.get("camera.ground_limit"));
+                ui.add_enabled_ui(config.ground_limit, |ui| {
+                    ui.add(
+                        egui::Slider::new(&mut config.ground_height, -50.0..=50.0)
+                            .suffix(" m")
+                            .text(strings.get("camera.ground_height")),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut config.ground_clearance, 0.0..=5.0)
+                            .suffix(" m")
+                            .text(strings.get("camera.ground_clearance")),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut config.ground_push_rate, 0.5..=30.0)
+                            .logarithmic(true)
+                            .text(strings.get("camera.ground_push_rate")),
+                    );
+                });
+                if ui.button(strings.get("camera.reset")).clicked() {
+                    *config = OrbitCameraConfig::default();
+                }
+            });
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui.button(strings.get("copy_code")).clicked() {
+                match snippet::to_rust_literal(&*settings, Some("CloudSettings::defaults()")) {
+                    Ok(code) => ui.ctx().copy_text(code),
+                    Err(err) => error!("Failed to format settings: {}", err),
+                }
+            }
+            if ui.button(strings.get("copy_ron")).clicked() {
+                match versioning::to_ron(&*settings) {
+                    Ok(text) => ui.ctx().copy_text(text),
+                    Err(err) => error!("Failed to format settings: {}", err),
+                }
+            }
+        });
+
+        if ui.button(strings.get("reset")).clicked() {
+            *settings = CloudSettings {
+                noise_handle: settings.noise_handle.clone(),
+                profile_handle: settings.profile_handle.clone(),
+                wind_offset: settings.wind_offset,
+                wind_offset_upper: settings.wind_offset_upper,
+                ..CloudSettings::defaults()
+            };
+        }
+    });
+    if let Some(code) = language {
+        if let Err(err) = strings.select(&code) {
+            warn!("Failed to load language {}: {}", code, err);
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+fn stats_ui_system(
+    mut contexts: EguiContexts,
+    volumes: Query<&ViewVisibility, With<CloudVolume>>,
+    impostors: Query<&CloudImpostor>,
+    assets: Res<CloudAssetStats>,
+    light_stats: Res<LightVolumeStats>,
+    ui_time: Res<UiFrameTime>,
+) {
+    let total = volumes.iter().count();
+    let drawn = volumes.iter().filter(|visibility| visibility.get()).count();
+    let impostors = impostors.iter().filter(|impostor| impostor.fade > 0.0).count();
+    egui::Window::new("Stats").show(contexts.ctx_mut(), |ui| {
+        ui.label(format!("Volumes drawn: {} / {}", drawn, total));
+        ui.label(format!("Impostors: {}", impostors));
+        ui.label(format!(
+            "GPU memory (est.): {:.1} MiB",
+            assets.gpu_bytes as f64 / (1024.0 * 1024.0)
+        ));
+        ui.label(format!("Cloud assets: {} materials, {} textures", assets.materials, assets.textures));
+        let last_bake = light_stats
+            .last_bake
+            .map_or(String::new(), |time| format!(", last bake {:.1} ms", time.as_secs_f64() * 1000.0));
+        ui.label(format!("Baked lighting: {} / {} volumes{}", light_stats.baked, total, last_bake));
+        let color = if ui_time.average > ui_cache::UI_TARGET {
+            egui::Color32::from_rgb(255, 170, 60)
+        } else {
+            ui.visuals().text_color()
+        };
+        ui.colored_label(
+            color,
+            format!(
+                "UI: {:.2} ms (target {:.2})",
+                ui_time.average.as_secs_f64() * 1000.0,
+                ui_cache::UI_TARGET.as_secs_f64() * 1000.0
+            ),
+        )
+        .on_hover_text("Settings UI systems per frame, smoothed; collapse sections to compare");
+    });
+}
+
+fn wind_system(time: Res<Time>, mut settings: ResMut<CloudSettings>) {
+    if settings.wind_paused {
+        return;
+    }
+    // The weight between the altitudes does not change over time, so
+    // lerping the two scrolls by height gives the scroll of the
+    // interpolated wind at every height.
+    let dt = time.delta_secs();
+    let (wind, wind_upper) = (settings.wind, settings.wind_upper);
+    settings.wind_offset += wind * dt;
+    settings.wind_offset_upper += wind_upper * dt;
+}
+
+/// Sanitizes the settings and, with the `bake` feature, drives the noise
+/// bake. The materials are written later in the frame by
+/// `material_sync::sync_cloud_materials`.
+fn update_material_system(mut settings: ResMut<CloudSettings>, #[cfg(feature = "bake")] mut bake: BakeDriver) {
+    if settings.is_changed() {
+        // Only a clamp counts as a change, or this would keep the settings
+        // changed forever.
+        let clamped = settings.bypass_change_detection().sanitize();
+        if !clamped.is_empty() {
+            settings.set_changed();
+            warn!("Clamped out-of-range cloud settings: {}", clamped.join(", "));
+        }
+    }
+    #[cfg(feature = "bake")]
+    bake.drive(&mut settings);
+}
+
+/// The bake state `update_material_system` advances.
+#[cfg(feature = "bake")]
+#[derive(SystemParam)]
+struct BakeDriver<'w> {
+    stats: ResMut<'w, BakeStats>,
+    scratch: ResMut<'w, BakeScratch>,
+    progress: ResMut<'w, BakeProgress>,
+    budget: Res<'w, BakeBudget>,
+    cache: ResMut<'w, BakeCache>,
+    pending: ResMut<'w, PendingNoise>,
+    images: ResMut<'w, Assets<Image>>,
+}
+
+#[cfg(feature = "bake")]
+impl BakeDriver<'_> {
+    fn drive(&mut self, settings: &mut ResMut<CloudSettings>) {
+        if settings.needs_rebuild {
+            // Supersedes an unfinished bake.
+            if let Some(bake) = self.progress.bake.take() {
+                *self.scratch = bake.into_scratch();
+            }
+            self.progress.cancel_requested = false;
+            settings.needs_rebuild = false;
+            match self.cache.load(settings) {
+                Some((data, stats)) => {
+                    stage_noise(&mut self.pending, &mut self.images, settings.resolution).data = data;
+                    *self.stats = stats;
+                    self.cache.last_hit = true;
+                }
+                None => {
+                    let scratch = std::mem::take(&mut *self.scratch);
+                    self.progress.bake = Some(NoiseBake::new(settings, scratch));
+                    self.cache.last_hit = false;
+                }
+            }
+        }
+        if self.progress.cancel_requested {
+            self.progress.cancel_requested = false;
+            if let Some(bake) = self.progress.bake.take() {
+                // The texture was never touched, so the previous bake stays.
+                *self.scratch = bake.into_scratch();
+            }
+        }
+        if let Some(bake) = self.progress.bake.as_mut() {
+            // Whole slices until the frame budget is used up.
+            let start = std::time::Instant::now();
+            while !bake.step(1) && start.elapsed() < self.budget.per_frame {}
+            let elapsed = start.elapsed();
+            if elapsed > self.budget.warn_after {
+                warn!(
+                    "Noise bake step took {:.1} ms (budget {:.1} ms) and stalled the frame; lower the resolution, \
+                     or bake offline with --bake-only and load the result",
+                    elapsed.as_secs_f64() * 1000.0,
+                    self.budget.warn_after.as_secs_f64() * 1000.0,
+                );
+            }
+            if bake.is_done() {
+                let mut bake = self.progress.bake.take().expect("checked above");
+                let image = stage_noise(&mut self.pending, &mut self.images, bake.size());
+                *self.stats = bake.finish(&mut image.data);
+                self.cache.store(bake.settings(), &image.data, &self.stats);
+                *self.scratch = bake.into_scratch();
+            }
+        }
+    }
+}
+
+/// Solves the threshold of auto threshold mode from the histogram of the
+/// last bake. Runs after `update_material_system`, so a bake finished this
+/// frame is already counted.
+fn auto_threshold_system(stats: Res<BakeStats>, mut settings: ResMut<CloudSettings>) {
+    if !settings.is_changed() && !stats.is_changed() {
+        return;
+    }
+    let Some(threshold) = settings.auto_threshold.and_then(|target| stats.threshold_for_coverage(target)) else {
+        return;
+    };
+    if (settings.threshold - threshold).abs() > 1e-4 {
+        settings.threshold = threshold;
+    }
+}
+
+/// The image a finished bake of `resolution` is written into, staged for
+/// `swap_noise_system`: the spare buffer, or the staged image itself when a
+/// bake finishes before the previous one was swapped in. Its data is reused
+/// unless the resolution changed; only the first bakes allocate an image.
+#[cfg(feature = "bake")]
+fn stage_noise<'a>(pending: &mut PendingNoise, images: &'a mut Assets<Image>, resolution: UVec3) -> &'a mut Image {
+    let target = pending.image.take().or_else(|| pending.spare.take());
+    let handle = match target.filter(|handle| images.contains(handle)) {
+        Some(handle) => handle,
+        None => images.add(noise::noise_image(resolution)),
+    };
+    let image = images.get_mut(&handle).expect("checked or added above");
+    if image.texture_descriptor.size != noise::noise_extent(resolution) {
+        *image = noise::noise_image(resolution);
+    }
+    pending.image = Some(handle);
+    image
+}
+
+/// Points the settings and every lit material sampling the live noise at the
+/// staged bake; `sync_cloud_materials` moves the cloud materials over from the
+/// settings. Runs before `update_material_system`, so the image was added at
+/// least one frame earlier and the render world has uploaded it by the time
+/// the materials bind it. The image swapped out becomes the spare the next
+/// bake is written into; any other old image is freed by the asset cleanup
+/// once nothing references it. Compare snapshots hold their own copy.
+fn swap_noise_system(
+    mut settings: ResMut<CloudSettings>,
+    mut pending: ResMut<PendingNoise>,
+    mut lit_materials: ResMut<Assets<LitCloudMaterial>>,
+    mut baked: EventWriter<NoiseBaked>,
+) {
+    let Some(fresh) = pending.image.take() else {
+        return;
+    };
+    baked.send(NoiseBaked { image: fresh.clone() });
+    if let Some(previous) = pending.live.replace(fresh.clone()) {
+        pending.spare = Some(previous);
+    }
+    // Not a settings edit, so change detection is left alone.
+    let old = std::mem::replace(&mut settings.bypass_change_detection().noise_handle, fresh.clone());
+    let lit_stale: Vec<_> = lit_materials
+        .iter()
+        .filter(|(_, material)| material.extension.noise_texture == old)
+        .map(|(id, _)| id)
+        .collect();
+    for id in lit_stale {
+        if let Some(material) = lit_materials.get_mut(id) {
+            material.extension.noise_texture = fresh.clone();
+        }
+    }
+}
+
+/// Locks and hides the cursor while dragging so long drags don't stop at the
+/// screen edge. Released with the button or when the window loses focus.
+#[cfg(feature = "ui")]
+fn cursor_grab_system(
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut contexts: EguiContexts,
+    ui_state: Res<UiState>,
+    weather_brush: Res<WeatherBrush>,
+    mut grabbed: Local<bool>,
+) {
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+    if *grabbed {
+        if !buttons.pressed(MouseButton::Left) || !window.focused {
+            window.cursor_options.grab_mode = CursorGrabMode::None;
+            window.cursor_options.visible = true;
+            *grabbed = false;
+        }
+    } else if buttons.just_pressed(MouseButton::Left)
+        && window.focused
+        && !weather_brush.captures_pointer
+        && !(ui_state.show_ui && contexts.try_ctx_mut().is_some_and(|ctx| ctx.is_pointer_over_area()))
+    {
+        window.cursor_options.grab_mode = CursorGrabMode::Locked;
+        window.cursor_options.visible = false;
+        *grabbed = true;
+    }
+}
+
+#[cfg(feature = "ui")]
+#[allow(clippy::too_many_arguments)]
+fn camera_control_system(
+    time: Res<Time>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut mouse_motion_events: EventReader<MouseMotion>,
+    gamepads: Query<&Gamepad>,
+    gamepad_settings: Res<GamepadSettings>,
+    touches: Res<Touches>,
+    mut touch_gesture: Local<TouchGesture>,
+    map_view: Res<MapViewSettings>,
+    weather_brush: Res<WeatherBrush>,
+    mut query: Query<(&Camera, &mut OrbitCamera, &OrbitCameraConfig, &mut Transform, &mut Projection)>,
+    windows: Query<&Window>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    mut contexts: EguiContexts,
+    ui_state: Res<UiState>,
+) {
+    let Ok((camera, mut orbit, config, mut transform, mut projection)) = query.get_single_mut() else {
+        return;
+    };
+
+    // Input is shared by every window, so it only steers the camera while
+    // the camera's own window has focus, and the UI it checks is that
+    // window's.
+    let window = camera_window(camera, primary_window.get_single().ok());
+    let focused = window.and_then(|window| windows.get(window).ok()).is_some_and(|window| window.focused);
+    let ctx = window.and_then(|window| contexts.try_ctx_for_entity_mut(window)).cloned();
+    let ui_blocks = config.block_on_ui && ui_state.show_ui;
+    let pointer_over_ui = (ui_blocks && ctx.as_ref().is_some_and(egui::Context::is_pointer_over_area))
+        || map_view.captures_pointer
+        || weather_brush.captures_pointer;
+    let ui_wants_pointer = ui_blocks && ctx.as_ref().is_some_and(egui::Context::wants_pointer_input);
+
+    // Mouse and gamepad deltas are summed so both can drive the camera at once.
+    let mut orbit_delta = Vec2::ZERO;
+    if buttons.pressed(MouseButton::Left) && !pointer_over_ui && focused {
+        for event in mouse_motion_events.read() {
+            orbit_delta += event.delta * config.rotate_sensitivity;
+        }
+    } else {
+        mouse_motion_events.clear();
+    }
+
+    let dt = time.delta_secs();
+    let mut pan = Vec2::ZERO;
+    let mut zoom = 0.0;
+    for gamepad in gamepads.iter().filter(|_| focused) {
+        let look = gamepad_settings.apply_dead_zone(gamepad.right_stick());
+        orbit_delta += Vec2::new(look.x, -look.y) * gamepad_settings.look_sensitivity * dt;
+        pan += gamepad_settings.apply_dead_zone(gamepad.left_stick()) * gamepad_settings.pan_speed * dt;
+        let zoom_in = gamepad.get(GamepadButton::RightTrigger2).unwrap_or(0.0);
+        let zoom_out = gamepad.get(GamepadButton::LeftTrigger2).unwrap_or(0.0);
+        zoom += (zoom_out - zoom_in) * gamepad_settings.zoom_speed * dt;
+    }
+
+    // One finger orbits like the mouse, two fingers pinch-zoom and pan.
+    let touch = touch_gesture.update(&touches, focused && !ui_wants_pointer);
+    orbit_delta += touch.orbit * config.rotate_sensitivity;
+    zoom += touch.pinch.ln();
+    zoom *= config.zoom_sensitivity;
+    pan *= config.pan_sensitivity;
+    if config.invert_y {
+        orbit_delta.y = -orbit_delta.y;
+    }
+
+    orbit.yaw -= orbit_delta.x;
+    let previous_pitch = orbit.pitch;
+    let min_pitch = config.min_pitch.max(-OrbitCamera::PITCH_LIMIT);
+    let max_pitch = config.max_pitch.clamp(min_pitch, OrbitCamera::PITCH_LIMIT);
+    orbit.pitch = (orbit.pitch - orbit_delta.y).clamp(min_pitch, max_pitch);
+    transform.rotation = orbit.rotation();
+
+    if pan != Vec2::ZERO {
+        // Pan in the ground plane, scaled by distance so it feels the same
+        // zoomed in or out.
+        let right = transform.right().with_y(0.0).normalize_or_zero();
+        let forward = transform.forward().with_y(0.0).normalize_or_zero();
+        let distance = orbit.distance;
+        orbit.center += (right * pan.x + forward * pan.y) * distance;
+    }
+    if touch.pan != Vec2::ZERO {
+        // Drag the scene with the fingers in the view plane.
+        let right = transform.right();
+        let up = transform.up();
+        let distance = orbit.distance;
+        orbit.center += (-right * touch.pan.x + up * touch.pan.y) * distance * 0.002 * config.pan_sensitivity;
+    }
+    let min_distance = config.min_distance.max(0.01);
+    let max_distance = config.max_distance.max(min_distance);
+    orbit.distance = (orbit.distance * zoom.exp()).clamp(min_distance, max_distance);
+
+    // Checked after the pan and zoom, which move the camera too.
+    config.limit_to_ground(&mut orbit, previous_pitch, dt);
+    transform.rotation = orbit.rotation();
+
+    let rot_matrix = Mat3::from_quat(transform.rotation);
+    transform.translation = orbit.center + rot_matrix.mul_vec3(Vec3::new(0.0, 0.0, orbit.distance));
+
+    // Zooming an orthographic view scales it; frame what a 45° perspective
+    // camera would see at the orbit center.
+    if let Projection::Orthographic(ortho) = projection.as_ref() {
+        let viewport_height = 2.0 * orbit.distance * std::f32::consts::FRAC_PI_8.tan();
+        let current = matches!(ortho.scaling_mode, ScalingMode::FixedVertical { viewport_height: h } if h == viewport_height);
+        if !current {
+            if let Projection::Orthographic(ortho) = projection.as_mut() {
+                ortho.scaling_mode = ScalingMode::FixedVertical { viewport_height };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The orbit camera's ground limit must win over the pitch limits and bring
+    // the camera back above the ground from any orbit, including ones whose
+    // center is below it.
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn camera_height(orbit: &OrbitCamera) -> f32 {
+        (orbit.center + orbit.rotation() * Vec3::new(0.0, 0.0, orbit.distance)).y
+    }
+
+    /// Runs the limit for `frames` frames without input.
+    fn settle(config: &OrbitCameraConfig, orbit: &mut OrbitCamera, frames: u32) {
+        for _ in 0..frames {
+            let previous_pitch = orbit.pitch;
+            config.limit_to_ground(orbit, previous_pitch, DT);
+        }
+    }
+
+    fn orbit(center: Vec3, distance: f32, pitch: f32) -> OrbitCamera {
+        OrbitCamera {
+            center,
+            distance,
+            yaw: 0.0,
+            pitch,
+        }
+    }
+
+    #[test]
+    fn ground_limit_wins_over_min_pitch() {
+        let config = OrbitCameraConfig {
+            min_pitch: 0.5,
+            ..default()
+        };
+        // Every pitch from 0.5 up puts a camera 10 from a center at 1 underground.
+        let mut orbit = orbit(Vec3::Y, 10.0, 0.5);
+        settle(&config, &mut orbit, 600);
+        let floor = config.ground_height + config.ground_clearance;
+        assert!(camera_height(&orbit) >= floor - 1e-3, "camera at {}", camera_height(&orbit));
+    }
+
+    #[test]
+    fn center_below_ground_is_raised() {
+        let config = OrbitCameraConfig::default();
+        let mut orbit = orbit(Vec3::new(0.0, -20.0, 0.0), 5.0, 0.0);
+        settle(&config, &mut orbit, 600);
+        assert!(orbit.pitch >= -OrbitCamera::PITCH_LIMIT, "pitch {}", orbit.pitch);
+        let floor = config.ground_height + config.ground_clearance;
+        assert!(camera_height(&orbit) >= floor - 1e-3, "camera at {}", camera_height(&orbit));
+    }
+
+    #[test]
+    fn input_cannot_push_below_the_ground() {
+        let config = OrbitCameraConfig::default();
+        let mut orbit = orbit(Vec3::new(0.0, 2.0, 0.0), 10.0, 0.0);
+        let ceiling = config.ground_pitch(orbit.center, orbit.distance).unwrap();
+        let previous_pitch = orbit.pitch;
+        orbit.pitch = OrbitCamera::PITCH_LIMIT;
+        config.limit_to_ground(&mut orbit, previous_pitch, DT);
+        assert!(orbit.pitch <= ceiling + 1e-6, "pitch {} above {}", orbit.pitch, ceiling);
+    }
+
+    #[test]
+    fn sanitize_clamps_nan_to_the_lower_bound() {
+        let mut settings = CloudSettings {
+            needs_rebuild: false,
+            ..CloudSettings::defaults()
+        };
+        settings.density_multiplier = f32::NAN;
+        settings.phase_g = f32::NAN;
+        settings.frequency = f32::NAN;
+        let clamped = settings.sanitize();
+        assert_eq!(clamped, ["density", "phase g", "frequency"]);
+        assert_eq!(settings.density_multiplier, 0.0);
+        assert_eq!(settings.phase_g, -CloudSettings::MAX_PHASE_G);
+        assert_eq!(settings.frequency, CloudSettings::MIN_FREQUENCY);
+        assert!(settings.needs_rebuild);
+    }
+
+    #[test]
+    fn sanitize_leaves_the_defaults_alone() {
+        let mut settings = CloudSettings::defaults();
+        assert!(settings.sanitize().is_empty());
+    }
+
+    /// Random settings baked and marched; the bake needs `bake`.
+    #[cfg(feature = "bake")]
+    mod sanitize_fuzz {
+        use rand::{seq::SliceRandom, Rng, SeedableRng};
+        use rand_chacha::ChaCha8Rng;
+
+        use super::*;
+        use crate::density::{first_hit, march_transmittance, optical_depth, DensityField};
+
+        const CASES: u32 = 200;
+        const RAYS_PER_CASE: u32 = 16;
+
+        /// Values a typed-in field, a hand-edited RON file or a script can produce:
+        /// non-finite, zero, negative and overflowing.
+        const EXTREMES: [f32; 9] = [
+            f32::NAN,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            0.0,
+            -1.0,
+            1.0e-30,
+            1.0e30,
+            -1.0e30,
+            0.5,
+        ];
+        const EXTREME_COUNTS: [u32; 5] = [0, 1, 3, 100_000, u32::MAX];
+
+        /// Fills the settings with random mixes of extreme values, sanitizes them,
+        /// bakes a small volume and marches random rays through volumes of extreme
+        /// size and scale with the CPU mirror of the shader. Every transmittance must
+        /// be finite and in `0..=1`, and every optical depth finite and non-negative.
+        #[test]
+        fn sanitized_settings_stay_finite() {
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let mut failures = Vec::new();
+            for case in 0..CASES {
+                let mut settings = random_settings(&mut rng);
+                let before = ron::to_string(&settings).unwrap_or_default();
+                settings.sanitize();
+                if let Err(err) = check_case(&mut rng, &settings) {
+                    failures.push(format!("case {}: {}\n  settings: {}", case, err, before));
+                }
+            }
+            assert!(failures.is_empty(), "{} of {} cases failed:\n{}", failures.len(), CASES, failures.join("\n"));
+        }
+
+        fn random_settings(rng: &mut ChaCha8Rng) -> CloudSettings {
+            let mut float = |fallback: f32| {
+                if rng.gen_bool(0.5) {
+                    *EXTREMES.choose(rng).unwrap()
+                } else {
+                    fallback
+                }
+            };
+            let mut settings = CloudSettings::defaults();
+            settings.density_multiplier = float(settings.density_multiplier);
+            settings.threshold = float(settings.threshold);
+            settings.auto_threshold = Some(float(0.35));
+            settings.absorption = float(settings.absorption);
+            settings.coverage = float(settings.coverage);
+            settings.phase_g = float(settings.phase_g);
+            settings.silver_intensity = float(settings.silver_intensity);
+            settings.silver_spread = float(settings.silver_spread);
+            settings.sun_intensity = float(settings.sun_intensity);
+            settings.frequency = float(settings.frequency);
+            settings.warp_amplitude = float(settings.warp_amplitude);
+            settings.wind = Vec3::new(float(0.0), float(0.0), float(0.0));
+            settings.wind_upper = Vec3::new(float(0.0), float(0.0), float(0.0));
+            settings.wind_altitudes = Vec2::new(float(settings.wind_altitudes.x), float(settings.wind_altitudes.y));
+            settings.extinction_color = Color::linear_rgb(float(1.0), float(1.0), float(1.0));
+
+            let mut count = |fallback: u32| {
+                if rng.gen_bool(0.5) {
+                    *EXTREME_COUNTS.choose(rng).unwrap()
+                } else {
+                    fallback
+                }
+            };
+            settings.steps = count(settings.steps);
+            settings.light_steps = count(settings.light_steps);
+            settings.cell_count = count(settings.cell_count);
+            settings.octaves = count(settings.octaves);
+            settings.warp_frequency = count(settings.warp_frequency);
+            settings.noise_type = *[noise::NoiseType::Worley, noise::NoiseType::Perlin].choose(rng).unwrap();
+            // Small bakes keep the run quick; the resolution clamp is covered anyway.
+            settings.resolution = UVec3::new(rng.gen_range(0..12), rng.gen_range(0..12), rng.gen_range(0..12));
+            settings
+        }
+
+        fn check_case(rng: &mut ChaCha8Rng, settings: &CloudSettings) -> Result<(), String> {
+            let (data, _) = noise::bake_noise(settings);
+            let mut image = noise::noise_image(settings.resolution);
+            image.data = data;
+            let field = DensityField::from_image(&image).ok_or("bake does not fill its image")?;
+
+            let scale = *[Vec3::ZERO, Vec3::splat(1.0e-6), Vec3::ONE, Vec3::new(-2.0, 0.0, 5.0), Vec3::splat(1.0e6)]
+                .choose(rng)
+                .unwrap();
+            let volume = CloudVolume {
+                density_scale: *EXTREMES.choose(rng).unwrap(),
+                threshold_offset: *EXTREMES.choose(rng).unwrap(),
+                noise_stretch: Vec3::splat(*EXTREMES.choose(rng).unwrap()),
+                noise_offset: Vec3::splat(*EXTREMES.choose(rng).unwrap()),
+                noise_angle: *EXTREMES.choose(rng).unwrap(),
+                cloud_type: *EXTREMES.choose(rng).unwrap(),
+                anvil_amount: *EXTREMES.choose(rng).unwrap(),
+                anvil_start_height: *EXTREMES.choose(rng).unwrap(),
+                auto_scale_params: rng.gen_bool(0.5),
+                ..default()
+            };
+            let transform = GlobalTransform::from(Transform::from_xyz(0.0, 1.0, 0.0).with_scale(scale));
+            let mut uniform = CloudMaterial::new(settings).data;
+            uniform.sync(settings, &volume, &transform);
+
+            for _ in 0..RAYS_PER_CASE {
+                let origin = Vec3::new(
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                );
+                let target = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(0.0..2.0), rng.gen_range(-1.0..1.0));
+                let dir = target - origin;
+                if let Some(march) = march_transmittance(&field, &uniform, origin, dir, settings.steps, 1.0, 0.5) {
+                    if !(0.0..=1.0).contains(&march.transmittance) {
+                        return Err(format!("transmittance {} from {} towards {}", march.transmittance, origin, dir));
+                    }
+                }
+                let depth = optical_depth(&field, &uniform, origin, target, settings.light_steps);
+                if !(depth.is_finite() && depth >= 0.0) {
+                    return Err(format!("optical depth {} from {} to {}", depth, origin, target));
+                }
+                if let Some(hit) = first_hit(&field, &uniform, origin, dir, settings.steps) {
+                    if !hit.is_finite() {
+                        return Err(format!("hit {} from {} towards {}", hit, origin, dir));
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
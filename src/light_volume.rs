@@ -0,0 +1,301 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    image::ImageSampler,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+
+use crate::{
+    cloud_sun::CloudLights,
+    density::{ray_box, sample_volume_density, DensityField},
+    material_sync::{CloudMaterialInputs, CloudSyncSet, SharedMaterialState},
+    weather_map::WeatherMap,
+    CloudMaterial, CloudMaterialUniform, CloudSettings, CloudVolume,
+};
+
+/// Texels per axis of each volume's light texture.
+pub const LIGHT_VOLUME_SIZE: u32 = 32;
+/// Frames a volume's inputs must stay unchanged before it is baked, so
+/// volumes being edited or animated keep the dynamic march.
+const STABLE_FRAMES: u32 = 30;
+
+/// Precomputed sun lighting for static volumes.
+///
+/// Once a volume's density inputs have held still for `STABLE_FRAMES`, the
+/// optical depth towards the clouds' sun (see `CloudSunPlugin`) is baked on
+/// the CPU into a small 3D texture, which `CloudMaterial` samples instead of
+/// running its light march. A volume falls back to the march as soon as anything
+/// that shapes its density changes (wind scroll, animation, edits, a noise
+/// or profile rebake), and rebakes once it is still again or after the light
+/// turns by more than `LightVolumeSettings::max_angle`. At most one volume is
+/// baked per frame. `LitCloudMaterial` and density overrides always march.
+pub struct LightVolumePlugin;
+
+impl Plugin for LightVolumePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LightVolumeSettings>()
+            .init_resource::<LightVolumeStats>()
+            .init_resource::<LightVolumes>()
+            .add_systems(PostUpdate, light_volume_system.in_set(CloudSyncSet::Resolve));
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct LightVolumeSettings {
+    pub enabled: bool,
+    /// Degrees the light may turn before a baked volume is stale.
+    pub max_angle: f32,
+}
+
+impl Default for LightVolumeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_angle: 5.0,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default)]
+pub struct LightVolumeStats {
+    /// Volumes currently drawn from their light texture.
+    pub baked: usize,
+    pub last_bake: Option<Duration>,
+}
+
+#[derive(Resource, Default)]
+struct LightVolumes {
+    volumes: HashMap<Entity, VolumeLight>,
+}
+
+struct VolumeLight {
+    inputs: LightInputs,
+    stable_frames: u32,
+    /// Inputs and light direction of the current texture.
+    baked: Option<(LightInputs, Vec3)>,
+    image: Option<Handle<Image>>,
+}
+
+/// Everything the light march depends on besides the light direction.
+#[derive(Clone, Copy, PartialEq)]
+struct LightInputs {
+    density: Vec2,
+    box_min: Vec4,
+    box_max: Vec4,
+    rotation: Vec4,
+    wind_offset: Vec4,
+    wind_offset_upper: Vec4,
+    shape: Vec4,
+    profile: Vec4,
+    weather_map: Vec4,
+    noise_offset: Vec4,
+    noise_domain: Vec4,
+    fbm_scale: Vec4,
+    fbm_weight: Vec4,
+    light_steps: f32,
+    noise: AssetId<Image>,
+}
+
+impl LightInputs {
+    fn new(material: &CloudMaterial) -> Self {
+        let data = &material.data;
+        Self {
+            density: data.settings.truncate().truncate(),
+            box_min: data.box_min,
+            box_max: data.box_max,
+            rotation: data.rotation,
+            wind_offset: data.wind_offset,
+            wind_offset_upper: data.wind_offset_upper,
+            shape: data.shape,
+            profile: data.profile,
+            weather_map: data.weather_map,
+            noise_offset: data.noise_offset,
+            noise_domain: data.noise_domain,
+            fbm_scale: data.fbm_scale,
+            fbm_weight: data.fbm_weight,
+            light_steps: data.lighting.z,
+            noise: material.noise_texture.id(),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn light_volume_system(
+    light_settings: Res<LightVolumeSettings>,
+    settings: Res<CloudSettings>,
+    weather_map: Res<WeatherMap>,
+    mut state: ResMut<LightVolumes>,
+    mut stats: ResMut<LightVolumeStats>,
+    mut images: ResMut<Assets<Image>>,
+    materials: Res<Assets<CloudMaterial>>,
+    shared: SharedMaterialState,
+    mut image_events: EventReader<AssetEvent<Image>>,
+    cloud_lights: Res<CloudLights>,
+    mut volumes: Query<(
+        Entity,
+        &CloudVolume,
+        &GlobalTransform,
+        &MeshMaterial3d<CloudMaterial>,
+        &mut CloudMaterialInputs,
+    )>,
+) {
+    let modified: Vec<AssetId<Image>> = image_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    let profile_modified = modified.contains(&settings.profile_handle.id());
+    let weather_modified = modified.contains(&weather_map.image.id());
+    // Towards the light, as `direction_to_light` in the shader.
+    let sun = cloud_lights.sun.as_ref().map(|sun| sun.direction);
+    let max_angle = light_settings.max_angle.to_radians();
+
+    let state = &mut *state;
+    state.volumes.retain(|entity, light| {
+        let alive = volumes.contains(*entity);
+        if let (false, Some(image)) = (alive, &light.image) {
+            images.remove(image);
+        }
+        alive
+    });
+
+    let mut baked = 0;
+    let mut candidate = None;
+    for (entity, volume, transform, handle, mut resolved) in &mut volumes {
+        let Some(current) = materials.get(&handle.0) else {
+            continue;
+        };
+        // Judged on what the material sync writes this frame, so the flag
+        // never lags the volume by a frame.
+        let material = shared.compose(current, &settings, volume, transform, Some(&resolved), 1.0);
+        let inputs = LightInputs::new(&material);
+        let light = state.volumes.entry(entity).or_insert_with(|| VolumeLight {
+            inputs,
+            stable_frames: 0,
+            baked: None,
+            image: None,
+        });
+        if light.inputs != inputs || (weather_modified && inputs.weather_map.z > 0.0) {
+            // Painting the weather map is an edit like any other.
+            light.inputs = inputs;
+            light.stable_frames = 0;
+            light.baked = None;
+        } else {
+            light.stable_frames = light.stable_frames.saturating_add(1);
+        }
+        if modified.contains(&inputs.noise) || profile_modified {
+            light.baked = None;
+            light.stable_frames = 0;
+        }
+
+        let eligible = light_settings.enabled && material.density_override.is_none() && sun.is_some();
+        let current = match (sun, light.baked) {
+            (Some(sun), Some((baked_inputs, baked_sun))) => {
+                baked_inputs == inputs && baked_sun.angle_between(sun) <= max_angle
+            }
+            _ => false,
+        };
+        if eligible && !current && light.stable_frames >= STABLE_FRAMES && candidate.is_none() {
+            candidate = Some((entity, material.clone()));
+        }
+
+        let use_baked = eligible && current && light.image.is_some();
+        baked += use_baked as usize;
+        if resolved.light_baked != use_baked || resolved.light_volume != light.image {
+            resolved.light_baked = use_baked;
+            resolved.light_volume = light.image.clone();
+        }
+    }
+    stats.baked = baked;
+
+    // The new texture is picked up next frame.
+    let (Some((entity, material)), Some(sun)) = (candidate, sun) else {
+        return;
+    };
+    let start = Instant::now();
+    let data = {
+        let Some(field) = DensityField::new(&images, &material.noise_texture, &settings.profile_handle) else {
+            return;
+        };
+        let field = field.with_weather_map(images.get(&weather_map.image));
+        let _span = info_span!("bake_light_volume", size = LIGHT_VOLUME_SIZE).entered();
+        bake_depths(&field, &material.data, sun)
+    };
+    let Some(light) = state.volumes.get_mut(&entity) else {
+        return;
+    };
+    match light.image.as_ref().and_then(|image| images.get_mut(image)) {
+        Some(image) => image.data = data,
+        None => light.image = Some(images.add(light_volume_image(data))),
+    }
+    light.baked = Some((light.inputs, sun));
+    stats.last_bake = Some(start.elapsed());
+}
+
+/// Optical depth (without absorption) from each texel center to the box
+/// exit towards `sun`, sampled as `light_march` in `cloud_shader.wgsl` does,
+/// as little-endian `R16Float` texels. The texels span the box in volume
+/// space, as the shader samples them.
+fn bake_depths(field: &DensityField, data: &CloudMaterialUniform, sun: Vec3) -> Vec<u8> {
+    let sun = data.dir_to_volume(sun);
+    let box_max = data.half_extents();
+    let box_min = -box_max;
+    let steps = (data.lighting.z as u32).max(1);
+    let size = LIGHT_VOLUME_SIZE;
+    let mut out = Vec::with_capacity((size * size * size) as usize * 2);
+    for z in 0..size {
+        for y in 0..size {
+            for x in 0..size {
+                let uvw = (UVec3::new(x, y, z).as_vec3() + 0.5) / size as f32;
+                let p = box_min + (box_max - box_min) * uvw;
+                let far = ray_box(p, sun, box_min, box_max).map_or(0.0, |(_, far)| far);
+                let step = far / steps as f32;
+                let depth: f32 = (0..steps)
+                    .map(|i| sample_volume_density(field, data, p + sun * step * (i as f32 + 0.5)) * step)
+                    .sum();
+                out.extend_from_slice(&f32_to_f16(depth).to_le_bytes());
+            }
+        }
+    }
+    out
+}
+
+fn light_volume_image(data: Vec<u8>) -> Image {
+    let mut image = Image::new(
+        Extent3d {
+            width: LIGHT_VOLUME_SIZE,
+            height: LIGHT_VOLUME_SIZE,
+            depth_or_array_layers: LIGHT_VOLUME_SIZE,
+        },
+        TextureDimension::D3,
+        data,
+        TextureFormat::R16Float,
+        RenderAssetUsages::default(),
+    );
+    // Clamped, unlike the noise: the texture spans the box exactly.
+    image.sampler = ImageSampler::linear();
+    image
+}
+
+/// Half-float bits of a non-negative value, truncating the mantissa and
+/// saturating at the largest finite half.
+fn f32_to_f16(value: f32) -> u16 {
+    let value = if value.is_nan() { 0.0 } else { value.clamp(0.0, 65504.0) };
+    if value < 6.103_515_6e-5 {
+        // Subnormal: multiples of 2^-24.
+        return (value * 16_777_216.0) as u16;
+    }
+    let bits = value.to_bits();
+    let exponent = ((bits >> 23) & 0xff) + 15 - 127;
+    let mantissa = (bits >> 13) & 0x3ff;
+    ((exponent << 10) | mantissa) as u16
+}
@@ -0,0 +1,436 @@
+use bevy::{
+    prelude::*,
+    core_pipeline::tonemapping::Tonemapping,
+    pbr::NotShadowCaster,
+    render::{
+        camera::RenderTarget,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        view::RenderLayers,
+    },
+};
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    material_sync::CloudMaterialMirror, volume_visibility::CloudHidden, CloudMaterial, CloudSettings, CloudVolume,
+    OrbitCamera,
+};
+#[cfg(feature = "ui")]
+use crate::CloudUiSet;
+
+/// Swaps distant volumes for camera-facing impostor quads. Each impostor is
+/// rendered by an offscreen camera that only sees a proxy copy of its volume
+/// (isolated on its own render layer) that the material sync keeps in step
+/// with the volume, and is refreshed only when the view angle or the material
+/// parameters change.
+pub struct CloudLodPlugin;
+
+impl Plugin for CloudLodPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ImpostorSettings>()
+            .init_resource::<ImpostorLayers>()
+            .add_systems(
+                Update,
+                (impostor_system.after(crate::update_material_system), impostor_cleanup_system),
+            );
+        #[cfg(feature = "ui")]
+        app.add_systems(Update, lod_ui_system.in_set(CloudUiSet));
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct ImpostorSettings {
+    pub enabled: bool,
+    pub distance: f32,      // camera distance where the crossfade starts
+    pub fade_margin: f32,   // distance over which volume and impostor crossfade
+    pub refresh_angle: f32, // degrees of view change before re-rendering
+    pub resolution: u32,
+}
+
+impl Default for ImpostorSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            distance: 40.0,
+            fade_margin: 8.0,
+            refresh_angle: 5.0,
+            resolution: 128,
+        }
+    }
+}
+
+/// Impostor state attached to a `CloudVolume` once it first goes past the
+/// LOD distance.
+#[derive(Component)]
+pub struct CloudImpostor {
+    /// 0 = fully volumetric, 1 = fully impostor.
+    pub fade: f32,
+    quad: Entity,
+    proxy: Entity,
+    camera: Entity,
+    quad_material: Handle<StandardMaterial>,
+    /// Capture target, resized when the resolution setting changes.
+    image: Handle<Image>,
+    resolution: u32,
+    captured_dir: Option<Vec3>,
+    captured_data: Option<(LinearRgba, Vec4, Vec4, Vec4)>,
+    capturing: bool,
+}
+
+#[cfg(all(test, feature = "ui"))]
+impl CloudImpostor {
+    /// An impostor without parts, faded by `fade`, for driving the material
+    /// sync alone.
+    pub(crate) fn faded(fade: f32) -> Self {
+        Self {
+            fade,
+            quad: Entity::PLACEHOLDER,
+            proxy: Entity::PLACEHOLDER,
+            camera: Entity::PLACEHOLDER,
+            quad_material: default(),
+            image: default(),
+            resolution: 0,
+            captured_dir: None,
+            captured_data: None,
+            capturing: false,
+        }
+    }
+}
+
+/// Helper entity (quad, proxy or capture camera) owned by an impostor.
+#[derive(Component)]
+pub(crate) struct ImpostorPart {
+    owner: Entity,
+}
+
+#[derive(Component)]
+struct ImpostorCamera;
+
+/// Hands out a dedicated render layer per impostor so each capture camera
+/// only sees its own proxy.
+#[derive(Resource, Default)]
+struct ImpostorLayers {
+    next: usize,
+    free: Vec<usize>,
+    quad_mesh: Option<Handle<Mesh>>,
+}
+
+impl ImpostorLayers {
+    fn allocate(&mut self) -> RenderLayers {
+        let layer = self.free.pop().unwrap_or_else(|| {
+            self.next += 1;
+            self.next
+        });
+        RenderLayers::layer(layer)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_impostor(
+    commands: &mut Commands,
+    images: &mut Assets<Image>,
+    cloud_materials: &mut Assets<CloudMaterial>,
+    quad_materials: &mut Assets<StandardMaterial>,
+    layers: &mut ImpostorLayers,
+    quad_mesh: Handle<Mesh>,
+    volume_mesh: Handle<Mesh>,
+    volume_material: &CloudMaterial,
+    owner: Entity,
+    resolution: u32,
+) -> CloudImpostor {
+    let size = Extent3d {
+        width: resolution,
+        height: resolution,
+        ..default()
+    };
+    let mut image = Image::new_fill(
+        size,
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Bgra8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+    let image = images.add(image);
+
+    let layer = layers.allocate();
+    let proxy_material = cloud_materials.add(volume_material.clone());
+    // The capture is blended over transparent black, so it is premultiplied.
+    let quad_material = quad_materials.add(StandardMaterial {
+        base_color: Color::linear_rgba(0.0, 0.0, 0.0, 0.0),
+        base_color_texture: Some(image.clone()),
+        unlit: true,
+        alpha_mode: AlphaMode::Premultiplied,
+        cull_mode: None,
+        ..default()
+    });
+
+    let camera = commands
+        .spawn((
+            Camera3d::default(),
+            Camera {
+                target: RenderTarget::Image(image.clone()),
+                order: -1,
+                is_active: false,
+                clear_color: ClearColorConfig::Custom(Color::NONE),
+                ..default()
+            },
+            Projection::Perspective(PerspectiveProjection::default()),
+            Tonemapping::None,
+            Msaa::Off,
+            layer.clone(),
+            ImpostorCamera,
+            ImpostorPart { owner },
+        ))
+        .id();
+    let proxy = commands
+        .spawn((
+            Mesh3d(volume_mesh),
+            MeshMaterial3d(proxy_material),
+            Transform::default(),
+            layer,
+            CloudMaterialMirror::Copy(owner),
+            ImpostorPart { owner },
+        ))
+        .id();
+    let quad = commands
+        .spawn((
+            Mesh3d(quad_mesh),
+            MeshMaterial3d(quad_material.clone()),
+            Transform::default(),
+            Visibility::Hidden,
+            NotShadowCaster,
+            ImpostorPart { owner },
+        ))
+        .id();
+
+    CloudImpostor {
+        fade: 0.0,
+        quad,
+        proxy,
+        camera,
+        quad_material,
+        image,
+        resolution,
+        captured_dir: None,
+        captured_data: None,
+        capturing: false,
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn impostor_system(
+    mut commands: Commands,
+    lod: Res<ImpostorSettings>,
+    settings: Res<CloudSettings>,
+    mut layers: ResMut<ImpostorLayers>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut cloud_materials: ResMut<Assets<CloudMaterial>>,
+    mut quad_materials: ResMut<Assets<StandardMaterial>>,
+    mut image_events: EventReader<AssetEvent<Image>>,
+    mut last_noise: Local<Option<AssetId<Image>>>,
+    main_camera: Query<&GlobalTransform, With<OrbitCamera>>,
+    mut volumes: Query<
+        (
+            Entity,
+            &CloudVolume,
+            &GlobalTransform,
+            &Mesh3d,
+            &MeshMaterial3d<CloudMaterial>,
+            &mut Visibility,
+            Option<&mut CloudImpostor>,
+            Has<CloudHidden>,
+        ),
+    >,
+    mut parts: Query<(&mut Transform, &mut Visibility), (With<ImpostorPart>, Without<CloudVolume>, Without<ImpostorCamera>)>,
+    mut cameras: Query<(&mut Camera, &mut Transform, &mut Projection), With<ImpostorCamera>>,
+) {
+    // A finished bake replaces the noise image rather than modifying it.
+    let noise_swapped = last_noise.replace(settings.noise_handle.id()) != Some(settings.noise_handle.id());
+    let noise_rebaked = image_events
+        .read()
+        .any(|event| event.is_modified(&settings.noise_handle) || event.is_modified(&settings.profile_handle))
+        || noise_swapped;
+    let Ok(main_camera) = main_camera.get_single() else {
+        return;
+    };
+    let _span = info_span!("impostor_update", volumes = volumes.iter().len()).entered();
+    let camera_pos = main_camera.translation();
+    let quad_mesh = layers
+        .quad_mesh
+        .get_or_insert_with(|| meshes.add(Rectangle::new(2.0, 2.0)))
+        .clone();
+
+    for (entity, volume, transform, mesh, material, mut visibility, impostor, hidden) in &mut volumes {
+        // Hidden volumes take no LOD work, and their impostor hides with them.
+        if hidden {
+            if let Some(mut impostor) = impostor {
+                if let Ok((_, mut quad_visibility)) = parts.get_mut(impostor.quad) {
+                    quad_visibility.set_if_neq(Visibility::Hidden);
+                }
+                if let Ok((mut capture_camera, ..)) = cameras.get_mut(impostor.camera) {
+                    capture_camera.is_active = false;
+                }
+                impostor.capturing = false;
+            }
+            continue;
+        }
+        let (scale, _, center) = transform.to_scale_rotation_translation();
+        let radius = scale.abs().length();
+        let distance = camera_pos.distance(center);
+        // Volumes scaling their own parameters move the crossfade with their
+        // size, so a weather system is not an impostor from inside it.
+        let scale_factor = volume.scale_factor(scale);
+        let fade = if lod.enabled {
+            ((distance - lod.distance * scale_factor) / (lod.fade_margin * scale_factor).max(1e-3)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let Some(mut impostor) = impostor else {
+            if fade > 0.0 {
+                if let Some(volume_material) = cloud_materials.get(&material.0).cloned() {
+                    let impostor = spawn_impostor(
+                        &mut commands,
+                        &mut images,
+                        &mut cloud_materials,
+                        &mut quad_materials,
+                        &mut layers,
+                        quad_mesh.clone(),
+                        mesh.0.clone(),
+                        &volume_material,
+                        entity,
+                        lod.resolution,
+                    );
+                    commands.entity(entity).insert(impostor);
+                }
+            }
+            continue;
+        };
+
+        impostor.fade = fade;
+        // Fully faded volumes skip the raymarch entirely.
+        visibility.set_if_neq(if fade >= 1.0 {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        });
+
+        let view_dir = (center - camera_pos).normalize_or(Vec3::NEG_Z);
+        if let Ok((mut proxy_transform, _)) = parts.get_mut(impostor.proxy) {
+            *proxy_transform = transform.compute_transform();
+        }
+        if let Ok((mut quad_transform, mut quad_visibility)) = parts.get_mut(impostor.quad) {
+            *quad_transform = Transform::from_translation(center)
+                .looking_to(view_dir, Vec3::Y)
+                .with_scale(Vec3::splat(radius));
+            quad_visibility.set_if_neq(if fade > 0.0 {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            });
+        }
+        let quad_color = Color::linear_rgba(fade, fade, fade, fade);
+        if quad_materials
+            .get(&impostor.quad_material)
+            .is_some_and(|quad_material| quad_material.base_color != quad_color)
+        {
+            if let Some(quad_material) = quad_materials.get_mut(&impostor.quad_material) {
+                quad_material.base_color = quad_color;
+            }
+        }
+
+        let Ok((mut capture_camera, mut capture_transform, mut projection)) =
+            cameras.get_mut(impostor.camera)
+        else {
+            continue;
+        };
+        // Captures only need the camera for the single frame they render in.
+        if impostor.capturing {
+            capture_camera.is_active = false;
+            impostor.capturing = false;
+        }
+        if impostor.resolution != lod.resolution {
+            if let Some(image) = images.get_mut(&impostor.image) {
+                image.resize(Extent3d {
+                    width: lod.resolution,
+                    height: lod.resolution,
+                    ..default()
+                });
+            }
+            impostor.resolution = lod.resolution;
+            impostor.captured_dir = None;
+        }
+        if fade <= 0.0 {
+            continue;
+        }
+
+        let Some(data) = cloud_materials.get(&material.0).map(|material| &material.data) else {
+            continue;
+        };
+        // The step count thins out with the fade itself; the proxy the capture
+        // draws marches the full count.
+        let full_steps = data.settings.truncate().extend(settings.steps as f32);
+        let captured_data = (data.color, full_steps, data.shape, data.profile);
+        let angle_changed = impostor
+            .captured_dir
+            .is_none_or(|dir| dir.angle_between(view_dir).to_degrees() > lod.refresh_angle);
+        let data_changed = impostor.captured_data != Some(captured_data);
+        if !angle_changed && !data_changed && !noise_rebaked {
+            continue;
+        }
+
+        let capture_distance = radius * 3.0;
+        *capture_transform = Transform::from_translation(center - view_dir * capture_distance)
+            .looking_to(view_dir, Vec3::Y);
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = 2.0 * (radius / capture_distance).asin();
+            perspective.aspect_ratio = 1.0;
+            perspective.near = (capture_distance - radius).max(0.01);
+            perspective.far = capture_distance + radius;
+        }
+        capture_camera.is_active = true;
+        impostor.capturing = true;
+        impostor.captured_dir = Some(view_dir);
+        impostor.captured_data = Some(captured_data);
+    }
+}
+
+fn impostor_cleanup_system(
+    mut commands: Commands,
+    mut layers: ResMut<ImpostorLayers>,
+    parts: Query<(Entity, &ImpostorPart, Option<&RenderLayers>), With<ImpostorCamera>>,
+    other_parts: Query<(Entity, &ImpostorPart), Without<ImpostorCamera>>,
+    owners: Query<(), With<CloudImpostor>>,
+) {
+    for (entity, part, render_layers) in &parts {
+        if owners.contains(part.owner) {
+            continue;
+        }
+        if let Some(layer) = render_layers.and_then(|render_layers| render_layers.iter().next()) {
+            layers.free.push(layer);
+        }
+        commands.entity(entity).despawn();
+    }
+    for (entity, part) in &other_parts {
+        if !owners.contains(part.owner) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+fn lod_ui_system(mut contexts: EguiContexts, mut lod: ResMut<ImpostorSettings>) {
+    egui::Window::new("Impostor LOD")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.checkbox(&mut lod.enabled, "Enabled");
+            ui.add(egui::Slider::new(&mut lod.distance, 5.0..=200.0).text("Distance"));
+            ui.add(egui::Slider::new(&mut lod.fade_margin, 0.0..=50.0).text("Fade Margin"));
+            ui.add(egui::Slider::new(&mut lod.refresh_angle, 0.5..=45.0).text("Refresh Angle (deg)"));
+            ui.add(egui::Slider::new(&mut lod.resolution, 32..=512).text("Resolution"));
+        });
+}
@@ -0,0 +1,199 @@
+use bevy::{
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+    render::camera::{ScalingMode, Viewport},
+    window::PrimaryWindow,
+};
+use bevy_atmosphere::prelude::AtmosphereCamera;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{shortcuts::UiState, view_overrides::CloudViewOverrides, CloudUiSet};
+
+/// A top-down map in a corner of the window with its own camera: scroll to
+/// zoom, drag to pan over the ground plane.
+pub struct MapViewPlugin;
+
+impl Plugin for MapViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapViewSettings>().add_systems(
+            Update,
+            (map_view_ui_system.in_set(CloudUiSet), map_view_system)
+                .chain()
+                .before(crate::camera_control_system),
+        );
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct MapViewSettings {
+    pub enabled: bool,
+    /// Top-left corner and size of the viewport as fractions of the window.
+    pub position: Vec2,
+    pub size: Vec2,
+    /// Ground-plane point under the map center.
+    pub center: Vec2,
+    /// Camera height above the ground; sets the visible extent when
+    /// orthographic.
+    pub height: f32,
+    pub orthographic: bool,
+    /// Set while the cursor is over the map or dragging it, so the main
+    /// camera ignores the mouse.
+    pub captures_pointer: bool,
+    dragging: bool,
+}
+
+impl Default for MapViewSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            position: Vec2::new(0.7, 0.0),
+            size: Vec2::new(0.3, 0.3),
+            center: Vec2::ZERO,
+            height: 30.0,
+            orthographic: false,
+            captures_pointer: false,
+            dragging: false,
+        }
+    }
+}
+
+impl MapViewSettings {
+    /// Viewport in physical pixels for a window of `window_size`.
+    pub fn viewport(&self, window_size: UVec2) -> (UVec2, UVec2) {
+        let full = window_size.as_vec2();
+        let size = (self.size.clamp(Vec2::splat(0.05), Vec2::ONE) * full).max(Vec2::ONE);
+        let position = (self.position.clamp(Vec2::ZERO, Vec2::ONE) * full).min(full - size);
+        (position.as_uvec2(), size.as_uvec2())
+    }
+}
+
+#[derive(Component)]
+pub struct MapCamera;
+
+#[allow(clippy::too_many_arguments)]
+fn map_view_system(
+    mut commands: Commands,
+    mut settings: ResMut<MapViewSettings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut wheel: EventReader<MouseWheel>,
+    mut cameras: Query<(Entity, &mut Camera, &mut Transform, &mut Projection), With<MapCamera>>,
+    mut contexts: EguiContexts,
+    ui_state: Res<UiState>,
+) {
+    let camera = cameras.get_single_mut().ok();
+    if !settings.enabled {
+        if let Some((entity, ..)) = camera {
+            commands.entity(entity).despawn_recursive();
+        }
+        settings.captures_pointer = false;
+        settings.dragging = false;
+        return;
+    }
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Some((_, mut camera, mut transform, mut projection)) = camera else {
+        commands.spawn((
+            Camera3d::default(),
+            Camera {
+                order: 2,
+                ..default()
+            },
+            Transform::default(),
+            AtmosphereCamera::default(),
+            // The map is small; a coarse march without detail is enough.
+            CloudViewOverrides {
+                steps: Some(8),
+                light_steps: Some(2),
+                detail: Some(false),
+                ..default()
+            },
+            MapCamera,
+        ));
+        return;
+    };
+
+    let (position, size) = settings.viewport(window.physical_size());
+    let over_ui = ui_state.show_ui && contexts.try_ctx_mut().is_some_and(|ctx| ctx.is_pointer_over_area());
+    let hovered = !over_ui
+        && window.physical_cursor_position().is_some_and(|cursor| {
+            let cursor = cursor.as_uvec2();
+            cursor.cmpge(position).all() && cursor.cmplt(position + size).all()
+        });
+
+    if hovered {
+        for event in wheel.read() {
+            let lines = match event.unit {
+                MouseScrollUnit::Line => event.y,
+                MouseScrollUnit::Pixel => event.y / 40.0,
+            };
+            settings.height = (settings.height * (-lines * 0.1).exp()).clamp(2.0, 1000.0);
+        }
+    } else {
+        wheel.clear();
+    }
+
+    if buttons.just_pressed(MouseButton::Left) && hovered {
+        settings.dragging = true;
+    }
+    if !buttons.pressed(MouseButton::Left) {
+        settings.dragging = false;
+    }
+    if settings.dragging {
+        // Pixels to world units at the ground plane.
+        let fov = std::f32::consts::FRAC_PI_4;
+        let world_per_pixel = 2.0 * settings.height * (fov * 0.5).tan() / size.y.max(1) as f32;
+        for event in motion.read() {
+            settings.center -= event.delta * world_per_pixel;
+        }
+    } else {
+        motion.clear();
+    }
+    settings.captures_pointer = hovered || settings.dragging;
+
+    camera.viewport = Some(Viewport {
+        physical_position: position,
+        physical_size: size,
+        ..default()
+    });
+    // Match the extent a 45° perspective camera sees at the ground.
+    let extent = 2.0 * settings.height * std::f32::consts::FRAC_PI_8.tan();
+    match (settings.orthographic, projection.as_mut()) {
+        (true, Projection::Orthographic(ortho)) => {
+            ortho.scaling_mode = ScalingMode::FixedVertical { viewport_height: extent };
+        }
+        (true, _) => {
+            *projection = Projection::Orthographic(OrthographicProjection {
+                scaling_mode: ScalingMode::FixedVertical { viewport_height: extent },
+                ..OrthographicProjection::default_3d()
+            });
+        }
+        (false, Projection::Orthographic(_)) => {
+            *projection = Projection::Perspective(PerspectiveProjection::default());
+        }
+        (false, _) => {}
+    }
+
+    // Screen up is world -Z, screen right is world +X.
+    *transform = Transform::from_xyz(settings.center.x, settings.height, settings.center.y)
+        .looking_to(Vec3::NEG_Y, Vec3::NEG_Z);
+}
+
+fn map_view_ui_system(mut contexts: EguiContexts, mut settings: ResMut<MapViewSettings>) {
+    egui::Window::new("Map View")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.checkbox(&mut settings.enabled, "Show Top-Down Map");
+            ui.checkbox(&mut settings.orthographic, "Orthographic");
+            ui.add(egui::Slider::new(&mut settings.position.x, 0.0..=1.0).text("Left"));
+            ui.add(egui::Slider::new(&mut settings.position.y, 0.0..=1.0).text("Top"));
+            ui.add(egui::Slider::new(&mut settings.size.x, 0.05..=1.0).text("Width"));
+            ui.add(egui::Slider::new(&mut settings.size.y, 0.05..=1.0).text("Height"));
+            ui.add(egui::Slider::new(&mut settings.height, 2.0..=1000.0).logarithmic(true).text("Zoom Height"));
+            if ui.button("Center on Origin").clicked() {
+                settings.center = Vec2::ZERO;
+            }
+        });
+}
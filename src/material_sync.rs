@@ -0,0 +1,469 @@
+use bevy::{ecs::system::SystemParam, prelude::*, transform::TransformSystem};
+
+use crate::{
+    cloud_sun::CloudLights,
+    lod::CloudImpostor,
+    occupancy::OccupancyMaps,
+    portal::CloudMask,
+    shader::DensityOverride,
+    sorting::MAX_MERGED,
+    trail::MAX_TRAIL_POINTS,
+    volume_visibility::CloudHidden,
+    weather_map::WeatherMap,
+    CloudMaterial, CloudSettings, CloudVolume,
+};
+
+/// Writes every volume's `CloudMaterial` once per frame, from one place.
+///
+/// Plugins never touch the materials themselves. What they decide per volume
+/// (its own noise bake, baked lighting, sort bias, merged overlaps, trail
+/// path) goes into its `CloudMaterialInputs`, and what every material shares (portal
+/// mask, weather map, density override, occupancy) stays in their
+/// resources. `sync_cloud_materials` then composes each material in a fixed
+/// order, later steps winning:
+///
+/// 1. `CloudSettings`, already carrying the timeline, time of day and
+///    modulation, which write it during `Update`;
+/// 2. the volume's own overrides, including whatever animation and preset
+///    transitions wrote into its `CloudVolume`;
+/// 3. the cloud lights;
+/// 4. the LOD crossfade, which also cuts the march steps of a fading volume
+///    below the settings' count;
+/// 5. the volume's resolved inputs and the shared lanes.
+///
+/// A material is only written when the result differs from what it holds, so
+/// a changed volume costs exactly one write and an unchanged one none. The
+/// sync runs in `PostUpdate` after transform propagation, so it sees this
+/// frame's transforms and every volume spawned during `Update`.
+pub struct CloudMaterialSyncPlugin;
+
+impl Plugin for CloudMaterialSyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            PostUpdate,
+            (CloudSyncSet::Resolve, CloudSyncSet::Write)
+                .chain()
+                .after(TransformSystem::TransformPropagate),
+        )
+        .add_systems(PostUpdate, sync_cloud_materials.in_set(CloudSyncSet::Write));
+    }
+}
+
+/// Share of its march steps a volume keeps once it has fully faded into its
+/// impostor.
+pub const LOD_MIN_STEP_SHARE: f32 = 0.25;
+
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CloudSyncSet {
+    /// Plugins fill in `CloudMaterialInputs` and the shared lanes.
+    Resolve,
+    /// `sync_cloud_materials`, the only writer of volume materials.
+    Write,
+}
+
+/// Per-volume material state resolved by other plugins, for
+/// `sync_cloud_materials`. Added along with every `CloudVolume`.
+#[derive(Component, Clone, Debug, Default)]
+pub struct CloudMaterialInputs {
+    /// Own bake for a `CloudVolume::noise` override, from
+    /// `VolumeNoisePlugin`; `None` samples the global noise.
+    pub noise_texture: Option<Handle<Image>>,
+    /// Baked light texture from `LightVolumePlugin`, and whether it is
+    /// current enough to draw from.
+    pub light_volume: Option<Handle<Image>>,
+    pub light_baked: bool,
+    /// From `CloudSortingPlugin`.
+    pub depth_bias: f32,
+    /// Overlap regions this volume leaves to another, from
+    /// `CloudSortingPlugin`.
+    pub merge_count: usize,
+    pub merge_min: [Vec4; MAX_MERGED],
+    pub merge_max: [Vec4; MAX_MERGED],
+    /// Path of a `CloudTrail` from `CloudTrailPlugin`; x of the info counts
+    /// the points in use, y is the radius.
+    pub trail_info: Vec4,
+    pub trail: [Vec4; MAX_TRAIL_POINTS],
+}
+
+impl CloudMaterialInputs {
+    /// The noise the volume samples.
+    pub fn noise<'a>(&'a self, settings: &'a CloudSettings) -> &'a Handle<Image> {
+        self.noise_texture.as_ref().unwrap_or(&settings.noise_handle)
+    }
+}
+
+/// Marks an entity drawing a copy of a volume's material, e.g. on another
+/// render layer. `sync_cloud_materials` writes it along with the source.
+#[derive(Component, Clone)]
+pub enum CloudMaterialMirror {
+    /// The source's material, fully opaque: LOD capture proxies.
+    Copy(Entity),
+    /// The source volume under other settings, sampling their noise and none
+    /// of the source's resolved inputs: compare snapshots.
+    #[cfg(feature = "ui")]
+    Snapshot { source: Entity, settings: Box<CloudSettings> },
+}
+
+/// The state every cloud material shares. Plugins that are not added leave
+/// their lanes at the material defaults.
+#[derive(SystemParam)]
+pub struct SharedMaterialState<'w> {
+    cloud_lights: Res<'w, CloudLights>,
+    density_override: Res<'w, DensityOverride>,
+    weather_map: Option<Res<'w, WeatherMap>>,
+    mask: Option<Res<'w, CloudMask>>,
+    occupancy: Option<Res<'w, OccupancyMaps>>,
+}
+
+impl SharedMaterialState<'_> {
+    /// `current` with every lane rewritten, in the order described on
+    /// `CloudMaterialSyncPlugin`. Without `inputs` the volume draws as if no
+    /// plugin had resolved anything for it.
+    pub fn compose(
+        &self,
+        current: &CloudMaterial,
+        settings: &CloudSettings,
+        volume: &CloudVolume,
+        transform: &GlobalTransform,
+        inputs: Option<&CloudMaterialInputs>,
+        opacity: f32,
+    ) -> CloudMaterial {
+        let default_inputs = CloudMaterialInputs::default();
+        let inputs = inputs.unwrap_or(&default_inputs);
+        let mut material = current.clone();
+
+        let data = &mut material.data;
+        data.sync(settings, volume, transform);
+        self.cloud_lights.apply(data, settings.fill_light);
+        data.opacity = opacity;
+        // The impostor covers for the coarser march while the volume fades.
+        data.settings.w = (settings.steps as f32 * LOD_MIN_STEP_SHARE.lerp(1.0, opacity)).round().max(1.0);
+        data.sampling.y = inputs.light_baked as u32 as f32;
+        data.merge_count = Vec4::new(inputs.merge_count as f32, 0.0, 0.0, 0.0);
+        data.merge_min = inputs.merge_min;
+        data.merge_max = inputs.merge_max;
+        data.trail_info = inputs.trail_info;
+        data.trail = inputs.trail;
+        data.weather_map = self.weather_map.as_ref().map_or(Vec4::ZERO, |map| map.uniform());
+        let mask = self.mask.as_deref().cloned().unwrap_or_default();
+        data.mask = mask.uniform;
+
+        material.noise_texture = inputs.noise(settings).clone();
+        material.profile_lut = settings.profile_handle.clone();
+        material.mask_texture = mask.texture;
+        material.density_override = self.density_override.shader.clone();
+        material.depth_bias = inputs.depth_bias;
+        material.blend = volume.blend;
+        material.lighting = volume.lighting;
+        material.light_volume = inputs.light_volume.clone();
+        material.occupancy = self
+            .occupancy
+            .as_ref()
+            .and_then(|maps| maps.get(material.noise_texture.id()).cloned());
+        material.weather_map = self
+            .weather_map
+            .as_ref()
+            .and_then(|map| map.enabled.then(|| map.image.clone()));
+        material
+    }
+}
+
+/// Replaces the material with what `compose` makes of it, if that differs,
+/// so unchanged materials are not re-uploaded.
+fn write(
+    materials: &mut Assets<CloudMaterial>,
+    handle: &Handle<CloudMaterial>,
+    compose: impl FnOnce(&CloudMaterial) -> CloudMaterial,
+) {
+    let Some(current) = materials.get(handle) else {
+        return;
+    };
+    let composed = compose(current);
+    if composed != *current {
+        if let Some(material) = materials.get_mut(handle) {
+            *material = composed;
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn sync_cloud_materials(
+    settings: Res<CloudSettings>,
+    shared: SharedMaterialState,
+    mut materials: ResMut<Assets<CloudMaterial>>,
+    volumes: Query<(
+        &CloudVolume,
+        &GlobalTransform,
+        &CloudMaterialInputs,
+        &MeshMaterial3d<CloudMaterial>,
+        Option<&CloudImpostor>,
+        Has<CloudHidden>,
+    )>,
+    mirrors: Query<(&CloudMaterialMirror, &MeshMaterial3d<CloudMaterial>)>,
+) {
+    let _span = info_span!("cloud_material_sync", volumes = volumes.iter().len()).entered();
+    for (volume, transform, inputs, handle, impostor, hidden) in &volumes {
+        // Picked up again on the first frame it is shown.
+        if hidden {
+            continue;
+        }
+        let opacity = 1.0 - impostor.map_or(0.0, |impostor| impostor.fade);
+        write(&mut materials, &handle.0, |current| {
+            shared.compose(current, &settings, volume, transform, Some(inputs), opacity)
+        });
+    }
+    for (mirror, handle) in &mirrors {
+        let (source, mirror_settings, mirror_inputs) = match mirror {
+            CloudMaterialMirror::Copy(source) => (*source, &*settings, true),
+            #[cfg(feature = "ui")]
+            CloudMaterialMirror::Snapshot { source, settings } => (*source, &**settings, false),
+        };
+        let Ok((volume, transform, inputs, _, _, false)) = volumes.get(source) else {
+            continue;
+        };
+        let inputs = mirror_inputs.then_some(inputs);
+        write(&mut materials, &handle.0, |current| {
+            shared.compose(current, mirror_settings, volume, transform, inputs, 1.0)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bevy::ecs::event::EventCursor;
+
+    use super::*;
+    use crate::testing;
+
+    // Runs `CloudMaterialSyncPlugin` without a renderer and counts material
+    // writes. A changed volume must be written exactly once per frame, an
+    // unchanged one not at all, and the composed material must follow the
+    // precedence documented on the plugin.
+
+    #[test]
+    fn each_changed_volume_is_written_once() {
+        let mut check = SyncCheck::new();
+        let a = check.spawn_volume(Vec3::new(-2.0, 1.0, 0.0));
+        let b = check.spawn_volume(Vec3::new(2.0, 1.0, 0.0));
+        let writes = check.frame();
+        assert_eq!(writes.get(&a.1), Some(&1), "new volumes are written once");
+        assert_eq!(writes.get(&b.1), Some(&1), "new volumes are written once");
+
+        assert!(check.frame().is_empty(), "nothing changed, nothing written");
+
+        check.app.world_mut().get_mut::<Transform>(a.0).unwrap().translation.x -= 1.0;
+        let writes = check.frame();
+        assert_eq!(writes.get(&a.1), Some(&1), "a moved volume is written once");
+        assert!(!writes.contains_key(&b.1), "a still volume is not written");
+
+        // Several sources change in the same frame: still one write each.
+        check.app.world_mut().resource_mut::<CloudSettings>().threshold = 0.4;
+        check.app.world_mut().get_mut::<CloudVolume>(a.0).unwrap().threshold_offset = 0.2;
+        check.app.world_mut().get_mut::<CloudMaterialInputs>(a.0).unwrap().light_baked = true;
+        let writes = check.frame();
+        assert_eq!(writes.get(&a.1), Some(&1), "simultaneous changes are one write");
+        assert_eq!(writes.get(&b.1), Some(&1), "a settings edit writes every volume once");
+    }
+
+    #[test]
+    fn volume_overrides_apply_over_the_settings() {
+        let mut check = SyncCheck::new();
+        let a = check.spawn_volume(Vec3::new(-2.0, 1.0, 0.0));
+        let b = check.spawn_volume(Vec3::new(2.0, 1.0, 0.0));
+        check.app.world_mut().resource_mut::<CloudSettings>().threshold = 0.4;
+        check.app.world_mut().get_mut::<CloudVolume>(a.0).unwrap().threshold_offset = 0.2;
+        check.frame();
+        assert!((check.material(a.1).data.settings.y - 0.6).abs() < 1e-5, "volume offset applies over the settings");
+        assert!((check.material(b.1).data.settings.y - 0.4).abs() < 1e-5, "settings apply without an offset");
+    }
+
+    #[test]
+    fn resolved_inputs_survive_settings_edits() {
+        let mut check = SyncCheck::new();
+        let a = check.spawn_volume(Vec3::new(-2.0, 1.0, 0.0));
+        let b = check.spawn_volume(Vec3::new(2.0, 1.0, 0.0));
+        {
+            let world = check.app.world_mut();
+            let mut inputs = world.get_mut::<CloudMaterialInputs>(a.0).unwrap();
+            inputs.light_baked = true;
+            inputs.merge_count = 1;
+        }
+        check.frame();
+        let (data_a, data_b) = (check.material(a.1).data, check.material(b.1).data);
+        assert!(data_a.sampling.y == 1.0 && data_a.merge_count.x == 1.0, "resolved inputs reach the material");
+        assert!(data_b.sampling.y == 0.0 && data_b.merge_count.x == 0.0, "inputs stay with their volume");
+
+        check.app.world_mut().resource_mut::<CloudSettings>().coverage = 0.3;
+        check.frame();
+        let data_a = check.material(a.1).data;
+        assert!(data_a.sampling.y == 1.0 && data_a.merge_count.x == 1.0, "a settings edit keeps resolved inputs");
+    }
+
+    #[test]
+    fn shared_lanes_reach_every_material() {
+        let mut check = SyncCheck::new();
+        let a = check.spawn_volume(Vec3::new(-2.0, 1.0, 0.0));
+        let b = check.spawn_volume(Vec3::new(2.0, 1.0, 0.0));
+        check.frame();
+        check.app.world_mut().resource_mut::<DensityOverride>().shader = Some(Handle::default());
+        let writes = check.frame();
+        assert!(
+            writes.get(&a.1) == Some(&1) && writes.get(&b.1) == Some(&1),
+            "a shared lane writes every material once"
+        );
+        assert!(check.material(a.1).density_override.is_some() && check.material(b.1).density_override.is_some());
+    }
+
+    #[test]
+    fn mirrors_follow_their_source() {
+        let mut check = SyncCheck::new();
+        let a = check.spawn_volume(Vec3::new(-2.0, 1.0, 0.0));
+        let mirror = check.spawn_mirror(CloudMaterialMirror::Copy(a.0));
+        check.frame();
+        let (source, copy) = (check.material(a.1), check.material(mirror));
+        assert!(copy.data == source.data && copy.noise_texture == source.noise_texture, "a mirror copies its source");
+
+        check.app.world_mut().get_mut::<Transform>(a.0).unwrap().translation.z += 1.0;
+        assert_eq!(check.frame().get(&mirror), Some(&1), "a mirror follows its source");
+    }
+
+    #[test]
+    fn lod_step_reduction_overrides_the_slider() {
+        let mut check = SyncCheck::new();
+        check.app.world_mut().resource_mut::<CloudSettings>().steps = 128;
+        let near = check.spawn_volume(Vec3::new(-2.0, 1.0, 0.0));
+        let fading = check.spawn_volume(Vec3::new(2.0, 1.0, 0.0));
+        let faded = check.spawn_volume(Vec3::new(6.0, 1.0, 0.0));
+        check.app.world_mut().entity_mut(fading.0).insert(CloudImpostor::faded(0.5));
+        check.app.world_mut().entity_mut(faded.0).insert(CloudImpostor::faded(1.0));
+        let proxy = check.spawn_mirror(CloudMaterialMirror::Copy(faded.0));
+        check.frame();
+
+        assert_eq!(check.material(near.1).data.settings.w, 128.0, "without an impostor, the slider's steps");
+        assert_eq!(check.material(fading.1).data.settings.w, (128.0 * LOD_MIN_STEP_SHARE.lerp(1.0, 0.5)).round());
+        assert_eq!(check.material(faded.1).data.settings.w, 128.0 * LOD_MIN_STEP_SHARE);
+        assert_eq!(check.material(proxy).data.settings.w, 128.0, "the impostor capture marches the full count");
+
+        // Raising the slider moves the reduced count with it, but does not undo it.
+        check.app.world_mut().resource_mut::<CloudSettings>().steps = 200;
+        check.frame();
+        assert_eq!(check.material(near.1).data.settings.w, 200.0);
+        assert_eq!(check.material(faded.1).data.settings.w, 200.0 * LOD_MIN_STEP_SHARE);
+    }
+
+    #[cfg(feature = "ui")]
+    #[test]
+    fn animation_overrides_preset() {
+        use bevy::animation::{animated_field, AnimationPlugin};
+
+        use crate::{
+            animation::{play_on_volume, volume_target_id, CloudAnimationPlugin},
+            presets::PRESETS,
+            CloudUiSet,
+        };
+
+        const ANIMATED: f32 = 0.25;
+        let preset = PRESETS
+            .iter()
+            .find(|preset| preset.cloud_type != ANIMATED)
+            .expect("a preset with another cloud type");
+
+        let mut check = SyncCheck::with(|app| {
+            app.add_plugins((AnimationPlugin, CloudAnimationPlugin))
+                // No window, so no egui.
+                .configure_sets(Update, CloudUiSet.run_if(|| false));
+        });
+        let animated = check.spawn_volume(Vec3::new(-2.0, 1.0, 0.0));
+        let still = check.spawn_volume(Vec3::new(2.0, 1.0, 0.0));
+
+        let mut clip = AnimationClip::default();
+        clip.add_curve_to_target(
+            volume_target_id(),
+            AnimatableCurve::new(
+                animated_field!(CloudVolume::cloud_type),
+                AnimatableKeyframeCurve::new([(0.0, ANIMATED), (10.0, ANIMATED)]).expect("keyframes are sorted"),
+            ),
+        );
+        let world = check.app.world_mut();
+        let clip = world.resource_mut::<Assets<AnimationClip>>().add(clip);
+        world.resource_scope(|world, mut graphs: Mut<Assets<AnimationGraph>>| {
+            play_on_volume(&mut world.commands(), animated.0, clip, &mut graphs);
+            world.flush();
+        });
+
+        // The preset writes the volumes every frame, as a transition does.
+        for _ in 0..3 {
+            let world = check.app.world_mut();
+            let mut settings = world.resource::<CloudSettings>().clone();
+            let mut volumes = world.query::<&mut CloudVolume>();
+            preset.apply(&mut settings, volumes.iter_mut(world));
+            world.insert_resource(settings);
+            check.frame();
+        }
+        assert_eq!(check.material(animated.1).data.profile.x, ANIMATED, "the animated value wins");
+        assert_eq!(check.material(still.1).data.profile.x, preset.cloud_type, "the preset applies elsewhere");
+    }
+
+    struct SyncCheck {
+        app: App,
+        events: EventCursor<AssetEvent<CloudMaterial>>,
+    }
+
+    impl SyncCheck {
+        fn new() -> Self {
+            Self::with(|_| {})
+        }
+
+        fn with(setup: impl FnOnce(&mut App)) -> Self {
+            Self {
+                app: testing::headless_app(setup),
+                events: EventCursor::default(),
+            }
+        }
+
+        fn add_material(&mut self) -> Handle<CloudMaterial> {
+            let material = CloudMaterial::new(self.app.world().resource::<CloudSettings>());
+            self.app.world_mut().resource_mut::<Assets<CloudMaterial>>().add(material)
+        }
+
+        fn spawn_volume(&mut self, translation: Vec3) -> (Entity, AssetId<CloudMaterial>) {
+            let material = self.add_material();
+            let id = material.id();
+            let entity = self
+                .app
+                .world_mut()
+                .spawn((CloudVolume::default(), Transform::from_translation(translation), MeshMaterial3d(material)))
+                .id();
+            (entity, id)
+        }
+
+        fn spawn_mirror(&mut self, mirror: CloudMaterialMirror) -> AssetId<CloudMaterial> {
+            let material = self.add_material();
+            let id = material.id();
+            self.app.world_mut().spawn((mirror, MeshMaterial3d(material)));
+            id
+        }
+
+        fn material(&self, id: AssetId<CloudMaterial>) -> CloudMaterial {
+            self.app.world().resource::<Assets<CloudMaterial>>().get(id).cloned().expect("material exists")
+        }
+
+        /// Writes per material caused by one frame. A second, idle frame flushes
+        /// the asset events wherever they run relative to the sync, and must not
+        /// write anything itself.
+        fn frame(&mut self) -> HashMap<AssetId<CloudMaterial>, usize> {
+            self.app.update();
+            self.app.update();
+            let mut writes = HashMap::new();
+            let events = self.app.world().resource::<Events<AssetEvent<CloudMaterial>>>();
+            for event in self.events.read(events) {
+                if let AssetEvent::Modified { id } = event {
+                    *writes.entry(*id).or_insert(0) += 1;
+                }
+            }
+            writes
+        }
+    }
+}
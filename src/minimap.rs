@@ -0,0 +1,218 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    focus::CameraFocus,
+    weather_map::{WeatherBrush, WeatherMap, WEATHER_MAP_SIZE},
+    CloudSettings, CloudUiSet, OrbitCamera,
+};
+
+/// Side of the minimap, in points.
+const MINIMAP_SIZE: f32 = 180.0;
+/// Spacing of the chevrons along the wind arrow, as a fraction of the
+/// arrow's length.
+const CHEVRON_SPACING: f32 = 0.25;
+
+/// A top-down overlay in the bottom-left corner for keeping your bearings
+/// while painting the weather map: the coverage over the map's extent, the
+/// sky holes outlined, the main camera as a wedge of its field of view and
+/// the lower wind as an arrow whose chevrons march with the scroll. With
+/// "Click Moves Camera", a click recenters the orbit camera on that point.
+///
+/// The coverage texture is rebuilt only when the map composes a new image
+/// (see `WeatherMap::generation`); the rest is drawn by the egui painter
+/// each frame.
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Minimap>()
+            .add_systems(Update, minimap_ui_system.in_set(CloudUiSet));
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Minimap {
+    pub enabled: bool,
+    pub click_to_move: bool,
+    texture: Option<egui::TextureHandle>,
+    /// Weather map generation the texture shows.
+    built: Option<u64>,
+}
+
+impl Minimap {
+    /// Copies the composed coverage into the texture, unless it already
+    /// shows this generation.
+    fn update_texture(&mut self, ctx: &egui::Context, map: &WeatherMap, image: &Image) {
+        if self.built == Some(map.generation()) && self.texture.is_some() {
+            return;
+        }
+        let size = WEATHER_MAP_SIZE as usize;
+        if image.data.len() < size * size {
+            return;
+        }
+        // Clear sky in a dark blue, full coverage in white.
+        let pixels = image.data[..size * size]
+            .iter()
+            .map(|&texel| {
+                let t = texel as f32 / 255.0;
+                let channel = |clear: f32, covered: f32| (clear + (covered - clear) * t).round() as u8;
+                egui::Color32::from_rgb(channel(38.0, 235.0), channel(58.0, 240.0), channel(96.0, 245.0))
+            })
+            .collect();
+        let coverage = egui::ColorImage {
+            size: [size, size],
+            pixels,
+        };
+        match &mut self.texture {
+            Some(texture) => texture.set(coverage, egui::TextureOptions::LINEAR),
+            None => self.texture = Some(ctx.load_texture("minimap", coverage, egui::TextureOptions::LINEAR)),
+        }
+        self.built = Some(map.generation());
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn minimap_ui_system(
+    mut contexts: EguiContexts,
+    mut minimap: ResMut<Minimap>,
+    mut focus: ResMut<CameraFocus>,
+    map: Res<WeatherMap>,
+    brush: Res<WeatherBrush>,
+    settings: Res<CloudSettings>,
+    images: Res<Assets<Image>>,
+    cameras: Query<(&OrbitCamera, &GlobalTransform, &Projection)>,
+) {
+    let ctx = contexts.ctx_mut();
+    egui::Window::new("Minimap").default_open(false).show(ctx, |ui| {
+        ui.checkbox(&mut minimap.enabled, "Show Minimap")
+            .on_hover_text("Top-down coverage, sky holes, camera and wind over the weather map's extent");
+        ui.add_enabled(
+            minimap.enabled,
+            egui::Checkbox::new(&mut minimap.click_to_move, "Click Moves Camera"),
+        )
+        .on_hover_text("Clicking the minimap recenters the orbit camera there");
+    });
+    if !minimap.enabled {
+        return;
+    }
+    if let Some(image) = images.get(&map.image) {
+        minimap.update_texture(ctx, &map, image);
+    }
+    let Some(texture) = minimap.texture.as_ref().map(egui::TextureHandle::id) else {
+        return;
+    };
+
+    let half_extent = map.half_extent.max(Vec2::splat(1e-3));
+    let min = map.center - half_extent;
+    egui::Area::new(egui::Id::new("minimap"))
+        .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(8.0, -8.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            let (rect, response) = ui.allocate_exact_size(egui::Vec2::splat(MINIMAP_SIZE), egui::Sense::click());
+            let painter = ui.painter_at(rect);
+            // Image rows run along +Z from the map's minimum, so north (-Z)
+            // is up, as seen from above.
+            let to_screen = |xz: Vec2| {
+                let uv = (xz - min) / (half_extent * 2.0);
+                rect.min + egui::vec2(uv.x, uv.y) * rect.size()
+            };
+            let to_world = |pos: egui::Pos2| {
+                let uv = (pos - rect.min) / rect.size();
+                min + Vec2::new(uv.x, uv.y) * half_extent * 2.0
+            };
+            let scale = rect.width() / (half_extent.x * 2.0);
+
+            let tint = if map.enabled {
+                egui::Color32::WHITE
+            } else {
+                egui::Color32::from_gray(110)
+            };
+            painter.image(
+                texture,
+                rect,
+                egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                tint,
+            );
+            painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, egui::Color32::from_gray(20)));
+
+            let drift = map.drift(settings.wind_offset);
+            for (index, hole) in map.holes.iter().enumerate() {
+                let stroke = if brush.selected_hole == Some(index) {
+                    egui::Stroke::new(2.5, egui::Color32::from_rgb(255, 140, 40))
+                } else {
+                    egui::Stroke::new(1.5, egui::Color32::from_rgb(255, 210, 80))
+                };
+                painter.circle_stroke(to_screen(hole.center + drift), hole.radius * scale, stroke);
+            }
+
+            wind_arrow(&painter, rect, settings.wind, settings.wind_offset, scale);
+
+            if let Ok((_, transform, projection)) = cameras.get_single() {
+                camera_wedge(&painter, to_screen(transform.translation().xz()), transform, projection);
+            }
+
+            if minimap.click_to_move && response.clicked() {
+                if let (Some(pointer), Ok((orbit, ..))) = (response.interact_pointer_pos(), cameras.get_single()) {
+                    let xz = to_world(pointer);
+                    focus.focus_on(orbit, Vec3::new(xz.x, orbit.center.y, xz.y), None);
+                }
+            }
+            response.on_hover_text("Coverage of the weather map from above, north up");
+        });
+}
+
+/// An arrow across the map center along the wind, with chevrons that move
+/// along it as the noise scrolls, at the wind's own speed on the map.
+fn wind_arrow(painter: &egui::Painter, rect: egui::Rect, wind: Vec3, offset: Vec3, scale: f32) {
+    let direction = wind.xz().normalize_or_zero();
+    if direction == Vec2::ZERO {
+        return;
+    }
+    let dir = egui::vec2(direction.x, direction.y);
+    let normal = egui::vec2(-dir.y, dir.x);
+    let length = rect.width() * 0.35;
+    let start = rect.center() - dir * length * 0.5;
+    let stroke = egui::Stroke::new(2.0, egui::Color32::from_rgb(120, 220, 255));
+    painter.arrow(start, dir * length, stroke);
+
+    // Scrolled distance along the wind in minimap points, wrapped to the
+    // chevron spacing.
+    let spacing = length * CHEVRON_SPACING;
+    let phase = (offset.xz().dot(direction) * scale).rem_euclid(spacing);
+    let mut along = phase;
+    while along < length - spacing * 0.5 {
+        let tip = start + dir * along;
+        let size = 5.0;
+        painter.line_segment([tip - dir * size + normal * size, tip], stroke);
+        painter.line_segment([tip - dir * size - normal * size, tip], stroke);
+        along += spacing;
+    }
+}
+
+/// The main camera as a dot with a wedge of its horizontal field of view.
+fn camera_wedge(painter: &egui::Painter, position: egui::Pos2, transform: &GlobalTransform, projection: &Projection) {
+    let forward = transform.forward().xz().normalize_or_zero();
+    let color = egui::Color32::from_rgb(255, 90, 70);
+    painter.circle_filled(position, 4.0, color);
+    if forward == Vec2::ZERO {
+        // Looking straight down: nothing to point with.
+        return;
+    }
+    let half_fov = match projection {
+        Projection::Perspective(perspective) => (perspective.fov * 0.5).tan() * perspective.aspect_ratio,
+        _ => std::f32::consts::FRAC_PI_8.tan(),
+    }
+    .atan();
+    let reach = 36.0;
+    let edge = |angle: f32| {
+        let side = Vec2::from_angle(angle).rotate(forward) * reach;
+        position + egui::vec2(side.x, side.y)
+    };
+    let points = vec![position, edge(-half_fov), edge(half_fov)];
+    painter.add(egui::Shape::convex_polygon(
+        points,
+        color.gamma_multiply(0.3),
+        egui::Stroke::new(1.0, color),
+    ));
+}
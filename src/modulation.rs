@@ -0,0 +1,333 @@
+use std::{collections::BTreeMap, f32::consts::TAU};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use crate::{timeline::TimelineParameter, CloudSettings, CloudUiSet};
+
+/// Input written by the sine demo, in `-1..=1`.
+pub const DEMO_SINE_INPUT: &str = "demo_sine";
+/// Input written by the time demo: a ramp from 0 to 1 every period.
+pub const DEMO_RAMP_INPUT: &str = "demo_ramp";
+
+/// Drives global parameters from named float inputs, e.g. audio band
+/// energies written by the host app every frame.
+///
+/// Each mapping adds `offset + scale * input`, smoothed, to its parameter on
+/// top of the base value. The sum is written into `CloudSettings` after the
+/// timeline and time of day and before the material sync, and taken back out
+/// before the next frame's UI, so the sliders keep editing the base values.
+/// A parameter something else wrote in the meantime keeps that value as its
+/// new base.
+pub struct ModulationPlugin;
+
+impl Plugin for ModulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ParameterModulator>().add_systems(
+            Update,
+            (
+                unmodulate_system.before(CloudUiSet),
+                (modulation_ui_system.in_set(CloudUiSet), demo_inputs_system, modulate_system)
+                    .chain()
+                    .after(crate::timeline::timeline_system)
+                    .after(crate::time_of_day::time_of_day_system)
+                    .before(crate::update_material_system),
+            ),
+        );
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModulationMapping {
+    pub input: String,
+    pub target: TimelineParameter,
+    pub scale: f32,
+    pub offset: f32,
+    /// Time constant of the exponential smoothing in seconds; 0 follows the
+    /// input immediately.
+    pub smoothing: f32,
+    pub enabled: bool,
+    #[serde(skip)]
+    smoothed: Option<f32>,
+}
+
+impl Default for ModulationMapping {
+    fn default() -> Self {
+        Self {
+            input: DEMO_SINE_INPUT.to_string(),
+            target: TimelineParameter::Density,
+            scale: 1.0,
+            offset: 0.0,
+            smoothing: 0.0,
+            enabled: true,
+            smoothed: None,
+        }
+    }
+}
+
+impl ModulationMapping {
+    /// Amount added to the target this frame, if the input exists.
+    pub fn value(&self) -> Option<f32> {
+        self.smoothed.map(|input| self.offset + self.scale * input)
+    }
+}
+
+/// A modulated parameter as last written.
+#[derive(Clone, Copy, Debug)]
+struct Applied {
+    target: TimelineParameter,
+    base: f32,
+    written: f32,
+}
+
+/// The mappings are saved with sessions; inputs and demo state are not.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParameterModulator {
+    pub mappings: Vec<ModulationMapping>,
+    /// Latest value of each input. Inputs nothing writes keep their last
+    /// value.
+    #[serde(skip)]
+    pub inputs: BTreeMap<String, f32>,
+    /// Writes `DEMO_SINE_INPUT` and `DEMO_RAMP_INPUT` from `Time`.
+    #[serde(skip)]
+    pub demo_inputs: bool,
+    #[serde(skip)]
+    pub demo_period: f32,
+    #[serde(skip)]
+    applied: Vec<Applied>,
+}
+
+impl Default for ParameterModulator {
+    fn default() -> Self {
+        Self {
+            mappings: Vec::new(),
+            inputs: BTreeMap::new(),
+            demo_inputs: false,
+            demo_period: 4.0,
+            applied: Vec::new(),
+        }
+    }
+}
+
+impl ParameterModulator {
+    pub fn set_input(&mut self, name: impl Into<String>, value: f32) {
+        self.inputs.insert(name.into(), value);
+    }
+
+    /// Mappings from a saved session. The modulation currently in the
+    /// settings is forgotten, since the settings are replaced as well.
+    pub fn load(&mut self, saved: ParameterModulator) {
+        self.mappings = saved.mappings;
+        self.applied.clear();
+    }
+
+    /// `settings` with the current modulation taken out, for saving.
+    pub fn base_settings(&self, settings: &CloudSettings) -> CloudSettings {
+        let mut base = settings.clone();
+        self.restore(&mut base);
+        base
+    }
+
+    fn restore(&self, settings: &mut CloudSettings) {
+        for applied in &self.applied {
+            if applied.target.get(settings) == applied.written {
+                applied.target.set(settings, applied.base);
+            }
+        }
+    }
+}
+
+fn unmodulate_system(mut modulator: ResMut<ParameterModulator>, mut settings: ResMut<CloudSettings>) {
+    if modulator.applied.is_empty() {
+        return;
+    }
+    // The modulated values already went to the materials; putting the base
+    // back is not a change of its own.
+    modulator.restore(settings.bypass_change_detection());
+    modulator.applied.clear();
+}
+
+fn demo_inputs_system(time: Res<Time>, mut modulator: ResMut<ParameterModulator>) {
+    if !modulator.demo_inputs {
+        return;
+    }
+    let period = modulator.demo_period.max(0.1);
+    let phase = (time.elapsed_secs() / period).fract();
+    modulator.set_input(DEMO_SINE_INPUT, (phase * TAU).sin());
+    modulator.set_input(DEMO_RAMP_INPUT, phase);
+}
+
+fn modulate_system(time: Res<Time>, mut modulator: ResMut<ParameterModulator>, mut settings: ResMut<CloudSettings>) {
+    let modulator = &mut *modulator;
+    let dt = time.delta_secs();
+    let mut offsets: Vec<(TimelineParameter, f32)> = Vec::new();
+    for mapping in &mut modulator.mappings {
+        let Some(&input) = modulator.inputs.get(&mapping.input) else {
+            mapping.smoothed = None;
+            continue;
+        };
+        let smoothed = match mapping.smoothed {
+            Some(previous) if mapping.smoothing > 0.0 => {
+                previous + (input - previous) * (1.0 - (-dt / mapping.smoothing).exp())
+            }
+            _ => input,
+        };
+        mapping.smoothed = Some(smoothed);
+        if !mapping.enabled {
+            continue;
+        }
+        let value = mapping.offset + mapping.scale * smoothed;
+        match offsets.iter_mut().find(|(target, _)| *target == mapping.target) {
+            Some((_, sum)) => *sum += value,
+            None => offsets.push((mapping.target, value)),
+        }
+    }
+    for (target, offset) in offsets {
+        let base = target.get(&settings);
+        let written = base + offset;
+        target.set(&mut settings, written);
+        modulator.applied.push(Applied { target, base, written });
+    }
+}
+
+fn modulation_ui_system(
+    mut contexts: EguiContexts,
+    mut modulator: ResMut<ParameterModulator>,
+    settings: Res<CloudSettings>,
+) {
+    egui::Window::new("Modulation")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let modulator = &mut *modulator;
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut modulator.demo_inputs, "Demo Inputs")
+                    .on_hover_text("Write a sine and a ramp from the clock, for trying mappings out");
+                ui.add_enabled(
+                    modulator.demo_inputs,
+                    egui::DragValue::new(&mut modulator.demo_period)
+                        .speed(0.05)
+                        .range(0.1..=60.0)
+                        .prefix("period ")
+                        .suffix(" s"),
+                );
+            });
+            if modulator.inputs.is_empty() {
+                ui.label("No inputs yet; the app writes them with `ParameterModulator::set_input`.");
+            }
+            egui::Grid::new("modulation_inputs").show(ui, |ui| {
+                for (name, value) in &modulator.inputs {
+                    ui.label(name);
+                    ui.monospace(format!("{:+.3}", value));
+                    ui.end_row();
+                }
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Add Mapping").clicked() {
+                    modulator.mappings.push(ModulationMapping::default());
+                }
+                if ui
+                    .button("Add Demo Mappings")
+                    .on_hover_text("Sine on density and ramp on coverage, driven by the demo inputs")
+                    .clicked()
+                {
+                    modulator.demo_inputs = true;
+                    modulator.mappings.push(ModulationMapping {
+                        scale: 0.5,
+                        smoothing: 0.1,
+                        ..default()
+                    });
+                    modulator.mappings.push(ModulationMapping {
+                        input: DEMO_RAMP_INPUT.to_string(),
+                        target: TimelineParameter::Coverage,
+                        scale: -0.3,
+                        ..default()
+                    });
+                }
+            });
+
+            let input_names: Vec<String> = modulator.inputs.keys().cloned().collect();
+            // The settings hold the base values while the UI runs.
+            let mut totals: Vec<(TimelineParameter, f32)> = Vec::new();
+            for mapping in modulator.mappings.iter().filter(|mapping| mapping.enabled) {
+                let Some(value) = mapping.value() else {
+                    continue;
+                };
+                match totals.iter_mut().find(|(target, _)| *target == mapping.target) {
+                    Some((_, sum)) => *sum += value,
+                    None => totals.push((mapping.target, value)),
+                }
+            }
+            let mut remove = None;
+            for (index, mapping) in modulator.mappings.iter_mut().enumerate() {
+                ui.separator();
+                ui.push_id(index, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut mapping.enabled, "");
+                        egui::ComboBox::from_id_salt("input")
+                            .selected_text(mapping.input.as_str())
+                            .show_ui(ui, |ui| {
+                                for name in &input_names {
+                                    ui.selectable_value(&mut mapping.input, name.clone(), name);
+                                }
+                            });
+                        ui.label("→");
+                        egui::ComboBox::from_id_salt("target")
+                            .selected_text(mapping.target.label())
+                            .show_ui(ui, |ui| {
+                                for target in TimelineParameter::ALL {
+                                    ui.selectable_value(&mut mapping.target, target, target.label());
+                                }
+                            });
+                        if ui.small_button("x").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut mapping.input)
+                            .on_hover_text("Name of the input, also before anything writes it");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut mapping.scale).speed(0.01).prefix("scale "));
+                        ui.add(egui::DragValue::new(&mut mapping.offset).speed(0.01).prefix("offset "));
+                        ui.add(
+                            egui::DragValue::new(&mut mapping.smoothing)
+                                .speed(0.01)
+                                .range(0.0..=10.0)
+                                .prefix("smoothing ")
+                                .suffix(" s"),
+                        );
+                    });
+                    match mapping.value() {
+                        Some(value) if mapping.enabled => {
+                            let base = mapping.target.get(&settings);
+                            let total = totals
+                                .iter()
+                                .find(|(target, _)| *target == mapping.target)
+                                .map_or(0.0, |(_, sum)| *sum);
+                            ui.label(format!(
+                                "{:+.3}: {} {:.3} → {:.3}",
+                                value,
+                                mapping.target.label(),
+                                base,
+                                base + total
+                            ));
+                        }
+                        Some(_) => {
+                            ui.weak("disabled");
+                        }
+                        None => {
+                            ui.weak("input not written");
+                        }
+                    }
+                });
+            }
+            if let Some(index) = remove {
+                modulator.mappings.remove(index);
+            }
+        });
+}
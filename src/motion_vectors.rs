@@ -0,0 +1,306 @@
+use bevy::{
+    asset::load_internal_asset,
+    core_pipeline::{motion_blur::MotionBlur, prepass::MotionVectorPrepass},
+    pbr::{MaterialPipeline, MaterialPipelineKey, NotShadowCaster},
+    prelude::*,
+    render::{
+        mesh::MeshVertexBufferLayoutRef,
+        render_resource::{
+            AsBindGroup, BlendComponent, BlendFactor, BlendOperation, BlendState, ColorWrites, Face,
+            RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError,
+        },
+    },
+};
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{material_sync::CloudSyncSet, CloudMaterial, CloudMaterialUniform, CloudVolume, OrbitCamera};
+#[cfg(feature = "ui")]
+use crate::CloudUiSet;
+
+const CLOUD_VELOCITY_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(0x0b6d_93e2_5f17_4ac8_b2e4_71c9_d3a0_8e56);
+
+/// Writes the motion of the wind-blown clouds into the motion-vector
+/// prepass, so TAA and motion blur treat them like moving geometry.
+///
+/// Blended materials get no prepass, so every volume gets an invisible
+/// companion drawing an alpha-masked copy of its box. Its main pass discards
+/// everything. Its prepass marches the density coarsely to find where the
+/// cloud sits along the pixel, projects the wind's movement over the last
+/// frame from that depth, and blends the result over the motion vectors
+/// already written by geometry, weighted by the coverage: geometry seen
+/// through thin cloud keeps most of its own motion. Views without a
+/// `MotionVectorPrepass` are left alone.
+///
+/// A single depth per pixel cannot describe a volume, so the vectors are an
+/// approximation, which is why they are off by default.
+pub struct CloudMotionVectorsPlugin;
+
+impl Plugin for CloudMotionVectorsPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            CLOUD_VELOCITY_SHADER_HANDLE,
+            "../assets/shaders/cloud_velocity.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugins(MaterialPlugin::<CloudVelocityMaterial> {
+            shadows_enabled: false,
+            ..default()
+        })
+        .init_resource::<CloudMotionVectors>()
+        .add_systems(Update, motion_blur_camera_system)
+        .add_systems(
+            PostUpdate,
+            (velocity_proxy_system, sync_velocity_materials_system)
+                .chain()
+                .after(CloudSyncSet::Write),
+        );
+        #[cfg(feature = "ui")]
+        app.add_systems(
+            Update,
+            motion_vectors_ui_system
+                .in_set(CloudUiSet)
+                .before(motion_blur_camera_system),
+        );
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default)]
+pub struct CloudMotionVectors {
+    /// Write cloud motion into views with a motion-vector prepass.
+    pub enabled: bool,
+    /// Add a motion-vector prepass and Bevy's motion blur to the main camera,
+    /// to see the vectors at work.
+    pub motion_blur: bool,
+}
+
+/// Draws a volume's box into the motion-vector prepass; see
+/// `CloudMotionVectorsPlugin`.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone, PartialEq)]
+#[bind_group_data(CloudVelocityKey)]
+pub struct CloudVelocityMaterial {
+    /// Copied from the volume's `CloudMaterial`, with the same bindings, so
+    /// the density modules apply unchanged.
+    #[uniform(0)]
+    pub data: CloudMaterialUniform,
+    #[texture(1, dimension = "3d")]
+    #[sampler(2)]
+    pub noise_texture: Handle<Image>,
+    #[texture(3)]
+    #[sampler(4)]
+    pub profile_lut: Handle<Image>,
+    #[texture(9, dimension = "3d")]
+    pub occupancy: Option<Handle<Image>>,
+    #[texture(10)]
+    #[sampler(11)]
+    pub weather_map: Option<Handle<Image>>,
+    /// xyz: volume-space wind scroll since the previous frame, at the lower
+    /// wind altitude.
+    #[uniform(12)]
+    pub motion: Vec4,
+    /// The same at the upper wind altitude.
+    #[uniform(13)]
+    pub motion_upper: Vec4,
+    pub density_override: bool,
+}
+
+impl CloudVelocityMaterial {
+    fn from_cloud(cloud: &CloudMaterial, motion: Vec4, motion_upper: Vec4) -> Self {
+        Self {
+            data: cloud.data.clone(),
+            noise_texture: cloud.noise_texture.clone(),
+            profile_lut: cloud.profile_lut.clone(),
+            occupancy: cloud.occupancy.clone(),
+            weather_map: cloud.weather_map.clone(),
+            motion,
+            motion_upper,
+            density_override: cloud.density_override.is_some(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CloudVelocityKey {
+    density_override: bool,
+    ground_fog: bool,
+}
+
+impl From<&CloudVelocityMaterial> for CloudVelocityKey {
+    fn from(material: &CloudVelocityMaterial) -> Self {
+        Self {
+            density_override: material.density_override,
+            ground_fog: material.data.ground_fog.y > 0.0,
+        }
+    }
+}
+
+impl Material for CloudVelocityMaterial {
+    fn fragment_shader() -> ShaderRef {
+        CLOUD_VELOCITY_SHADER_HANDLE.into()
+    }
+
+    fn prepass_fragment_shader() -> ShaderRef {
+        CLOUD_VELOCITY_SHADER_HANDLE.into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        // Blended materials are skipped by the prepass.
+        AlphaMode::Mask(0.5)
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // Back faces, so the box still draws with the camera inside it.
+        descriptor.primitive.cull_mode = Some(Face::Front);
+        if let Some(depth_stencil) = descriptor.depth_stencil.as_mut() {
+            depth_stencil.depth_write_enabled = false;
+        }
+        let Some(fragment) = descriptor.fragment.as_mut() else {
+            return Ok(());
+        };
+        // The density falls back to its run-time checks for these.
+        for def in ["CLOUD_DETAIL_NOISE", "CLOUD_WEATHER_MAP", "CLOUD_TRAIL"] {
+            fragment.shader_defs.push(def.into());
+        }
+        if key.bind_group_data.density_override {
+            fragment.shader_defs.push("CLOUD_DENSITY_OVERRIDE".into());
+        }
+        if key.bind_group_data.ground_fog {
+            fragment.shader_defs.push("CLOUD_GROUND_FOG".into());
+        }
+        if !fragment.shader_defs.contains(&"PREPASS_PIPELINE".into()) {
+            return Ok(());
+        }
+        // Prepass targets sit at fixed indices: normals, motion vectors, then
+        // the deferred buffers. Only the motion vectors are written, blended
+        // by the coverage in alpha.
+        for (index, target) in fragment.targets.iter_mut().enumerate() {
+            let Some(target) = target else {
+                continue;
+            };
+            if index == 1 {
+                let over = BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::OneMinusSrcAlpha,
+                    operation: BlendOperation::Add,
+                };
+                target.blend = Some(BlendState { color: over, alpha: over });
+            } else {
+                target.write_mask = ColorWrites::empty();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The companion of a volume, spawned as its child so it follows the box.
+#[derive(Component)]
+struct VelocityProxy {
+    /// The parent's wind scroll last frame, in volume space.
+    last_wind_offsets: Option<[Vec3; 2]>,
+}
+
+/// Spawns a companion for every volume while enabled and removes them all
+/// when disabled.
+#[allow(clippy::type_complexity)]
+fn velocity_proxy_system(
+    mut commands: Commands,
+    motion_vectors: Res<CloudMotionVectors>,
+    clouds: Res<Assets<CloudMaterial>>,
+    mut materials: ResMut<Assets<CloudVelocityMaterial>>,
+    volumes: Query<(Entity, &Mesh3d, &MeshMaterial3d<CloudMaterial>, Option<&Children>), With<CloudVolume>>,
+    proxies: Query<Entity, With<VelocityProxy>>,
+) {
+    if !motion_vectors.enabled {
+        for entity in &proxies {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+    for (entity, mesh, cloud, children) in &volumes {
+        if children.is_some_and(|children| children.iter().any(|child| proxies.contains(*child))) {
+            continue;
+        }
+        let Some(cloud) = clouds.get(&cloud.0) else {
+            continue;
+        };
+        let proxy = commands
+            .spawn((
+                Mesh3d(mesh.0.clone()),
+                MeshMaterial3d(materials.add(CloudVelocityMaterial::from_cloud(cloud, Vec4::ZERO, Vec4::ZERO))),
+                Transform::default(),
+                NotShadowCaster,
+                VelocityProxy { last_wind_offsets: None },
+            ))
+            .id();
+        commands.entity(entity).add_child(proxy);
+    }
+}
+
+/// Copies each parent's freshly synced material, with the wind's movement
+/// since the previous frame.
+fn sync_velocity_materials_system(
+    clouds: Res<Assets<CloudMaterial>>,
+    mut materials: ResMut<Assets<CloudVelocityMaterial>>,
+    volumes: Query<&MeshMaterial3d<CloudMaterial>, With<CloudVolume>>,
+    mut proxies: Query<(&Parent, &mut VelocityProxy, &MeshMaterial3d<CloudVelocityMaterial>)>,
+) {
+    for (parent, mut proxy, handle) in &mut proxies {
+        let Some(cloud) = volumes.get(parent.get()).ok().and_then(|cloud| clouds.get(&cloud.0)) else {
+            continue;
+        };
+        let wind_offsets = [cloud.data.wind_offset.truncate(), cloud.data.wind_offset_upper.truncate()];
+        let [motion, motion_upper] = proxy
+            .last_wind_offsets
+            .map_or([Vec3::ZERO; 2], |last| [wind_offsets[0] - last[0], wind_offsets[1] - last[1]]);
+        proxy.last_wind_offsets = Some(wind_offsets);
+        let composed = CloudVelocityMaterial::from_cloud(cloud, motion.extend(0.0), motion_upper.extend(0.0));
+        if materials.get(&handle.0) != Some(&composed) {
+            if let Some(material) = materials.get_mut(&handle.0) {
+                *material = composed;
+            }
+        }
+    }
+}
+
+/// Adds or removes the main camera's motion-vector prepass and motion blur.
+fn motion_blur_camera_system(
+    mut commands: Commands,
+    motion_vectors: Res<CloudMotionVectors>,
+    cameras: Query<(Entity, Has<MotionBlur>), With<OrbitCamera>>,
+) {
+    for (entity, has_blur) in &cameras {
+        match (motion_vectors.motion_blur, has_blur) {
+            (true, false) => {
+                commands.entity(entity).insert((MotionVectorPrepass, MotionBlur::default()));
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<(MotionVectorPrepass, MotionBlur)>();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+fn motion_vectors_ui_system(
+    mut contexts: EguiContexts,
+    mut motion_vectors: ResMut<CloudMotionVectors>,
+    cameras: Query<Has<MotionVectorPrepass>, With<Camera3d>>,
+) {
+    egui::Window::new("Motion Vectors")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.checkbox(&mut motion_vectors.enabled, "Write Cloud Motion Vectors")
+                .on_hover_text("Approximate: one depth per pixel, weighted by the cloud's coverage");
+            ui.checkbox(&mut motion_vectors.motion_blur, "Motion Blur on Main Camera");
+            if motion_vectors.enabled && !cameras.iter().any(|has_prepass| has_prepass) {
+                ui.colored_label(egui::Color32::YELLOW, "No camera has a motion-vector prepass.");
+            }
+        });
+}
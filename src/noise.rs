@@ -0,0 +1,860 @@
+//! CPU bake of the tileable 3D noise volume sampled by the cloud shader.
+
+use std::time::Duration;
+#[cfg(feature = "bake")]
+use std::time::Instant;
+
+use bevy::{
+    image::{ImageAddressMode, ImageLoaderSettings, ImageSampler, ImageSamplerDescriptor},
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+#[cfg(feature = "bake")]
+use rand::Rng;
+#[cfg(feature = "bake")]
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::CloudSettings;
+#[cfg(feature = "bake")]
+use crate::seed_streams::{SeedStream, SeedStreams};
+
+/// Default edge length of the baked volume in texels.
+pub const DEFAULT_RESOLUTION: u32 = 32;
+/// Bounds for each axis of `CloudSettings::resolution`.
+pub const MIN_RESOLUTION: u32 = 4;
+pub const MAX_RESOLUTION: u32 = 256;
+/// Edge length of the volume baked at startup while the real bake runs.
+pub(crate) const PLACEHOLDER_RESOLUTION: u32 = 8;
+/// Bytes per texel: R is the noise, G the ambient visibility from the noise
+/// above (see `OCCLUSION_OFFSETS`).
+pub(crate) const NOISE_CHANNELS: usize = 2;
+/// Texels above a voxel, along +Y and wrapping, whose mean noise occludes
+/// its ambient light.
+#[cfg(feature = "bake")]
+const OCCLUSION_OFFSETS: [u32; 4] = [1, 2, 4, 8];
+
+/// `resolution` with every axis clamped to the supported range.
+pub fn clamp_resolution(resolution: UVec3) -> UVec3 {
+    resolution.clamp(UVec3::splat(MIN_RESOLUTION), UVec3::splat(MAX_RESOLUTION))
+}
+
+/// Empty noise volume of `resolution` (clamped) with the repeating sampler
+/// the shader expects.
+pub(crate) fn noise_image(resolution: UVec3) -> Image {
+    let mut image = Image::new_fill(
+        noise_extent(resolution),
+        TextureDimension::D3,
+        &[0, 255],
+        TextureFormat::Rg8Unorm,
+        RenderAssetUsages::default(),
+    );
+    // The bake is tileable, so the wind can scroll it indefinitely.
+    image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+        address_mode_u: ImageAddressMode::Repeat,
+        address_mode_v: ImageAddressMode::Repeat,
+        address_mode_w: ImageAddressMode::Repeat,
+        ..ImageSamplerDescriptor::linear()
+    });
+    image
+}
+
+/// Texture size of a noise volume baked at `resolution`.
+pub(crate) fn noise_extent(resolution: UVec3) -> Extent3d {
+    let size = clamp_resolution(resolution);
+    Extent3d {
+        width: size.x,
+        height: size.y,
+        depth_or_array_layers: size.z,
+    }
+}
+
+/// Loads a prebaked noise volume, such as a `--bake-only` KTX2, with the
+/// repeating sampler `noise_image` gives a fresh bake. Builds without the
+/// `bake` feature have no other way to get noise.
+pub fn load_noise(asset_server: &AssetServer, path: impl Into<String>) -> Handle<Image> {
+    asset_server.load_with_settings(path.into(), |settings: &mut ImageLoaderSettings| {
+        settings.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+            address_mode_u: ImageAddressMode::Repeat,
+            address_mode_v: ImageAddressMode::Repeat,
+            address_mode_w: ImageAddressMode::Repeat,
+            ..ImageSamplerDescriptor::linear()
+        });
+    })
+}
+
+/// Counts the histogram of a noise volume loaded from disk once it arrives,
+/// so auto threshold mode works on volumes baked ahead of time. Volumes baked
+/// at runtime come with their own stats. Block-compressed exports can't be
+/// read on the CPU and leave the stats alone.
+pub(crate) fn loaded_noise_stats_system(
+    settings: Res<CloudSettings>,
+    images: Res<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+    mut events: EventReader<AssetEvent<Image>>,
+    mut counted: Local<Option<AssetId<Image>>>,
+    mut stats: ResMut<BakeStats>,
+) {
+    let id = settings.noise_handle.id();
+    let modified = events.read().any(|event| event.is_modified(id));
+    if *counted == Some(id) && !modified {
+        return;
+    }
+    if asset_server.get_path(id).is_none() {
+        return;
+    }
+    let Some(image) = images.get(id) else {
+        return;
+    };
+    *counted = Some(id);
+    let channels = match image.texture_descriptor.format {
+        TextureFormat::R8Unorm => 1,
+        TextureFormat::Rg8Unorm => 2,
+        TextureFormat::Rgba8Unorm => 4,
+        _ => return,
+    };
+    stats.count_texels(image.data.iter().step_by(channels).copied());
+}
+
+/// Tileable 2D Perlin FBM of `size` texels per side, as `R8Unorm` with a
+/// repeating sampler, for the flat sheets of `cirrus::CirrusLayer`.
+#[cfg(feature = "ui")]
+pub(crate) fn sheet_noise_image(seed: u32, size: u32) -> Image {
+    let size = size.max(MIN_RESOLUTION);
+    let perlin = Perlin::new(&mut SeedStreams::new(seed as u64).rng(SeedStream::CirrusSheet));
+    let values: Vec<f32> = (0..size * size)
+        .map(|index| {
+            let uv = Vec2::new((index % size) as f32, (index / size) as f32) / size as f32;
+            perlin.fbm(uv.extend(0.0), 4.0, 5, FractalType::Standard)
+        })
+        .collect();
+    // FBM bunches up around the middle; stretch it so coverage spans 0..=1.
+    let (min, max) = values
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), &value| (min.min(value), max.max(value)));
+    let range = (max - min).max(1e-6);
+    let data = values
+        .iter()
+        .map(|value| ((value - min) / range * 255.0).round() as u8)
+        .collect();
+    let mut image = Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::R8Unorm,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+        address_mode_u: ImageAddressMode::Repeat,
+        address_mode_v: ImageAddressMode::Repeat,
+        ..ImageSamplerDescriptor::linear()
+    });
+    image
+}
+
+/// Base function of the bake.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoiseType {
+    /// Inverted cellular distance; round, billowy cells.
+    #[default]
+    Worley,
+    /// Perlin gradient FBM shaped by `FractalType`.
+    Perlin,
+}
+
+/// How each Perlin octave is shaped before it is accumulated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FractalType {
+    #[default]
+    Standard,
+    /// `abs(noise)`: puffy forms.
+    Billow,
+    /// `1 - abs(noise)`: sharp ridges, good for wispy cirrus.
+    Ridged,
+}
+
+impl NoiseType {
+    pub const ALL: [NoiseType; 2] = [NoiseType::Worley, NoiseType::Perlin];
+}
+
+impl FractalType {
+    pub const ALL: [FractalType; 3] = [FractalType::Standard, FractalType::Billow, FractalType::Ridged];
+}
+
+/// Buckets in `BakeStats::histogram`.
+pub const HISTOGRAM_BUCKETS: usize = 64;
+
+/// Value distribution of the last bake, or of the last noise volume loaded
+/// from disk (see `loaded_noise_stats_system`).
+#[derive(Resource, Clone, Debug)]
+pub struct BakeStats {
+    /// Range of the raw field before normalization, in `0..=1`.
+    pub min: f32,
+    pub max: f32,
+    /// Texel counts of the stored (post-normalization) values over `0..=1`.
+    pub histogram: [u32; HISTOGRAM_BUCKETS],
+    /// Time spent baking, summed over the frames of a chunked bake.
+    pub duration: Duration,
+}
+
+impl Default for BakeStats {
+    fn default() -> Self {
+        Self {
+            min: 0.0,
+            max: 1.0,
+            histogram: [0; HISTOGRAM_BUCKETS],
+            duration: Duration::ZERO,
+        }
+    }
+}
+
+impl BakeStats {
+    /// Replaces the histogram with the distribution of 8-bit texels.
+    pub fn count_texels(&mut self, texels: impl Iterator<Item = u8>) {
+        self.histogram = [0; HISTOGRAM_BUCKETS];
+        for texel in texels {
+            let bucket = (texel as usize * HISTOGRAM_BUCKETS / 256).min(HISTOGRAM_BUCKETS - 1);
+            self.histogram[bucket] += 1;
+        }
+    }
+
+    /// Share of texels above `threshold`, spreading each bucket evenly over
+    /// its range. 0 before the first bake.
+    pub fn coverage_above(&self, threshold: f32) -> f32 {
+        let total: u32 = self.histogram.iter().sum();
+        if total == 0 {
+            return 0.0;
+        }
+        let position = threshold.clamp(0.0, 1.0) * HISTOGRAM_BUCKETS as f32;
+        let bucket = (position as usize).min(HISTOGRAM_BUCKETS - 1);
+        let above: u32 = self.histogram[bucket + 1..].iter().sum();
+        let inside = self.histogram[bucket] as f32 * (bucket as f32 + 1.0 - position).clamp(0.0, 1.0);
+        (above as f32 + inside) / total as f32
+    }
+
+    /// Threshold leaving `fraction` of the texels above it; the inverse of
+    /// `coverage_above`. `None` before the first bake.
+    pub fn threshold_for_coverage(&self, fraction: f32) -> Option<f32> {
+        let total: u32 = self.histogram.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let target = fraction.clamp(0.0, 1.0) * total as f32;
+        let mut above = 0.0;
+        for bucket in (0..HISTOGRAM_BUCKETS).rev() {
+            let count = self.histogram[bucket] as f32;
+            if above + count >= target && count > 0.0 {
+                let inside = (target - above) / count;
+                return Some((bucket as f32 + 1.0 - inside) / HISTOGRAM_BUCKETS as f32);
+            }
+            above += count;
+        }
+        Some(0.0)
+    }
+}
+
+/// Frame time given to the chunked bake of the global noise. Frames whose
+/// bake step runs past `warn_after` log a warning, since they stall.
+#[cfg(feature = "bake")]
+#[derive(Resource, Clone, Debug)]
+pub struct BakeBudget {
+    pub per_frame: Duration,
+    pub warn_after: Duration,
+}
+
+#[cfg(feature = "bake")]
+impl Default for BakeBudget {
+    fn default() -> Self {
+        Self {
+            per_frame: Duration::from_millis(8),
+            warn_after: Duration::from_millis(16),
+        }
+    }
+}
+
+/// Buffers reused between bakes so frequent rebuilds don't reallocate.
+#[cfg(feature = "bake")]
+#[derive(Resource, Default)]
+pub struct BakeScratch {
+    points: Vec<Vec3>,
+    tiled: TiledPoints,
+    raw: Vec<f32>,
+}
+
+/// Bakes the noise volume for `settings` as RG8 data, X varying fastest.
+/// With `settings.normalize` the raw range is stretched to the full `0..=255`.
+#[cfg(feature = "bake")]
+pub fn bake_noise(settings: &CloudSettings) -> (Vec<u8>, BakeStats) {
+    let mut data = Vec::new();
+    let stats = bake_noise_into(settings, &mut BakeScratch::default(), &mut data);
+    (data, stats)
+}
+
+/// Like `bake_noise`, writing into `out` and reusing `scratch`. `out` keeps
+/// its allocation when the size is unchanged.
+#[cfg(feature = "bake")]
+pub fn bake_noise_into(settings: &CloudSettings, scratch: &mut BakeScratch, out: &mut Vec<u8>) -> BakeStats {
+    let mut bake = NoiseBake::new(settings, std::mem::take(scratch));
+    bake.step(u32::MAX);
+    let stats = bake.finish(out);
+    *scratch = bake.into_scratch();
+    stats
+}
+
+/// A bake advanced a few Z slices at a time, so large volumes don't stall
+/// the frame. Nothing is written to the output until `finish`, so dropping an
+/// unfinished bake leaves the previous texture intact.
+#[cfg(feature = "bake")]
+pub(crate) struct NoiseBake {
+    settings: CloudSettings,
+    size: UVec3,
+    next_slice: u32,
+    warp: Option<WarpField>,
+    perlin: Option<Perlin>,
+    scratch: BakeScratch,
+    /// Time spent in `step`, excluding the frames in between.
+    busy: Duration,
+}
+
+#[cfg(feature = "bake")]
+impl NoiseBake {
+    pub fn new(settings: &CloudSettings, mut scratch: BakeScratch) -> Self {
+        let start = Instant::now();
+        let size = clamp_resolution(settings.resolution);
+        scratch.raw.clear();
+        scratch.raw.reserve((size.x * size.y * size.z) as usize);
+
+        let streams = SeedStreams::new(settings.seed as u64);
+        let mut rng = streams.rng(SeedStream::WorleyPoints);
+        scratch.points.clear();
+        for _ in 0..settings.cell_count {
+            scratch.points.push(Vec3::new(
+                rng.gen_range(0.0..1.0),
+                rng.gen_range(0.0..1.0),
+                rng.gen_range(0.0..1.0),
+            ));
+        }
+        let warp = (settings.warp_amplitude > 0.0)
+            .then(|| WarpField::new(&mut streams.rng(SeedStream::Warp), settings.warp_frequency));
+        let perlin =
+            (settings.noise_type == NoiseType::Perlin).then(|| Perlin::new(&mut streams.rng(SeedStream::Perlin)));
+        if perlin.is_none() {
+            scratch.tiled.rebuild(&scratch.points, settings.frequency);
+        }
+        Self {
+            settings: settings.clone(),
+            size,
+            next_slice: 0,
+            warp,
+            perlin,
+            scratch,
+            busy: start.elapsed(),
+        }
+    }
+
+    /// Bakes up to `slices` more Z slices; returns true once all are done.
+    pub fn step(&mut self, slices: u32) -> bool {
+        let start = Instant::now();
+        let size = self.size;
+        let _span = info_span!(
+            "bake_noise",
+            resolution = ?size,
+            points = self.settings.cell_count,
+            octaves = self.settings.octaves,
+            noise_type = ?self.settings.noise_type,
+        )
+        .entered();
+        let settings = &self.settings;
+        let freq = settings.frequency;
+        let end = self.next_slice.saturating_add(slices).min(size.z);
+        for z in self.next_slice..end {
+            let fz = z as f32 / size.z as f32;
+            for y in 0..size.y {
+                let fy = y as f32 / size.y as f32;
+                for x in 0..size.x {
+                    let fx = x as f32 / size.x as f32;
+                    let mut uvw = Vec3::new(fx, fy, fz);
+                    if let Some(warp) = &self.warp {
+                        // Wrap back into the unit cube the cell search covers.
+                        uvw = (uvw + warp.sample(uvw) * settings.warp_amplitude).rem_euclid(Vec3::ONE);
+                    }
+                    let value = match &self.perlin {
+                        Some(perlin) => perlin.fbm(uvw, freq, settings.octaves, settings.fractal_type),
+                        None => worley(&self.scratch.tiled, uvw, freq),
+                    };
+                    self.scratch.raw.push(value.clamp(0.0, 1.0));
+                }
+            }
+        }
+        self.next_slice = end;
+        self.busy += start.elapsed();
+        self.is_done()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next_slice >= self.size.z
+    }
+
+    /// Fraction of slices baked, `0..=1`.
+    #[cfg(feature = "ui")]
+    pub fn progress(&self) -> f32 {
+        self.next_slice as f32 / self.size.z as f32
+    }
+
+    pub fn settings(&self) -> &CloudSettings {
+        &self.settings
+    }
+
+    /// Size of the finished volume.
+    pub fn size(&self) -> UVec3 {
+        self.size
+    }
+
+    /// Quantizes the finished bake into `out` and derives the occlusion
+    /// channel. Call only once `step` has returned true.
+    pub fn finish(&mut self, out: &mut Vec<u8>) -> BakeStats {
+        debug_assert!(self.is_done());
+        let start = Instant::now();
+        let raw = &self.scratch.raw;
+        let mut stats = BakeStats {
+            min: raw.iter().copied().fold(f32::MAX, f32::min),
+            max: raw.iter().copied().fold(f32::MIN, f32::max),
+            ..default()
+        };
+        let range = stats.max - stats.min;
+        let stretch = self.settings.normalize && range > 1e-5;
+        let value = |index: usize| {
+            if stretch {
+                (raw[index] - stats.min) / range
+            } else {
+                raw[index]
+            }
+        };
+        out.resize(raw.len() * NOISE_CHANNELS, 0);
+        let size = self.size;
+        let mut index = 0;
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let noise = value(index);
+                    let bucket = ((noise * HISTOGRAM_BUCKETS as f32) as usize).min(HISTOGRAM_BUCKETS - 1);
+                    stats.histogram[bucket] += 1;
+                    let occlusion = OCCLUSION_OFFSETS
+                        .iter()
+                        .map(|offset| value((((z * size.y + (y + offset) % size.y) * size.x) + x) as usize))
+                        .sum::<f32>()
+                        / OCCLUSION_OFFSETS.len() as f32;
+                    out[index * NOISE_CHANNELS] = (noise * 255.0) as u8;
+                    out[index * NOISE_CHANNELS + 1] = ((1.0 - occlusion) * 255.0) as u8;
+                    index += 1;
+                }
+            }
+        }
+        self.busy += start.elapsed();
+        stats.duration = self.busy;
+        stats
+    }
+
+    /// Hands the buffers back for the next bake.
+    pub fn into_scratch(self) -> BakeScratch {
+        self.scratch
+    }
+}
+
+/// Noise texels per side of an occupancy block.
+pub(crate) const OCCUPANCY_BLOCK: u32 = 8;
+
+/// Block counts of the occupancy volume of a `size` noise volume.
+pub(crate) fn occupancy_size(size: UVec3) -> UVec3 {
+    (size / OCCUPANCY_BLOCK).max(UVec3::ONE)
+}
+
+/// Coarse R8 volume of the highest noise value in each block of
+/// `OCCUPANCY_BLOCK`³ texels of an RG8 bake, widened by one texel on every
+/// side (wrapping) so linear filtering at block edges is covered. The shader
+/// skips blocks whose maximum cannot clear the threshold.
+pub(crate) fn occupancy(data: &[u8], size: UVec3) -> Vec<u8> {
+    let blocks = occupancy_size(size);
+    let texel = |x: i32, y: i32, z: i32| {
+        let p = IVec3::new(x, y, z).rem_euclid(size.as_ivec3()).as_uvec3();
+        data[((p.z * size.y + p.y) * size.x + p.x) as usize * NOISE_CHANNELS]
+    };
+    // Texel range of block `b` along one axis, before widening.
+    let range = |block: u32, count: u32, texels: u32| {
+        let start = (block * texels / count) as i32;
+        let end = ((block + 1) * texels).div_ceil(count) as i32;
+        start - 1..end + 1
+    };
+    let mut out = Vec::with_capacity((blocks.x * blocks.y * blocks.z) as usize);
+    for bz in 0..blocks.z {
+        for by in 0..blocks.y {
+            for bx in 0..blocks.x {
+                let mut max = 0;
+                for z in range(bz, blocks.z, size.z) {
+                    for y in range(by, blocks.y, size.y) {
+                        for x in range(bx, blocks.x, size.x) {
+                            max = max.max(texel(x, y, z));
+                        }
+                    }
+                }
+                out.push(max);
+            }
+        }
+    }
+    out
+}
+
+/// A finished bake of the global noise in its own image, waiting for the
+/// render world to upload it before `swap_noise_system` points the settings
+/// and materials at it. Writing into the live image instead could show a
+/// frame of half-old, half-new texels.
+///
+/// Bakes alternate between two images: the one swapped in last, and a spare
+/// the materials have moved off, which the next bake is written into.
+#[derive(Resource, Default)]
+pub(crate) struct PendingNoise {
+    pub image: Option<Handle<Image>>,
+    pub live: Option<Handle<Image>>,
+    pub spare: Option<Handle<Image>>,
+}
+
+/// The chunked bake of the global noise, if one is running.
+#[cfg(feature = "bake")]
+#[derive(Resource, Default)]
+pub(crate) struct BakeProgress {
+    pub bake: Option<NoiseBake>,
+    /// Set by the UI; the bake is dropped on the next update.
+    pub cancel_requested: bool,
+}
+
+#[cfg(feature = "ui")]
+impl BakeProgress {
+    pub fn fraction(&self) -> Option<f32> {
+        self.bake.as_ref().map(NoiseBake::progress)
+    }
+}
+
+/// A new global noise has replaced the old one, sent by `swap_noise_system`
+/// once the settings point at it.
+#[derive(Event, Clone, Debug)]
+pub struct NoiseBaked {
+    pub image: Handle<Image>,
+}
+
+/// Bar heights of `stats.histogram` relative to its peak, for `histogram_ui`.
+#[cfg(feature = "ui")]
+pub(crate) fn histogram_bars(stats: &BakeStats) -> Vec<f32> {
+    let peak = stats.histogram.iter().copied().max().unwrap_or(0).max(1) as f32;
+    stats.histogram.iter().map(|&count| count as f32 / peak).collect()
+}
+
+/// Bar chart of `histogram_bars` with `threshold` marked as a vertical line.
+#[cfg(feature = "ui")]
+pub(crate) fn histogram_ui(ui: &mut bevy_egui::egui::Ui, bars: &[f32], threshold: f32) {
+    use bevy_egui::egui;
+
+    let size = egui::vec2(ui.available_width().min(240.0), 60.0);
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    // One mesh rather than a shape per bar.
+    let bar_width = rect.width() / bars.len().max(1) as f32;
+    let color = ui.visuals().widgets.inactive.fg_stroke.color;
+    let mut mesh = egui::Mesh::default();
+    for (i, height) in bars.iter().enumerate() {
+        let left = rect.left() + i as f32 * bar_width;
+        let bar = egui::Rect::from_min_max(
+            egui::pos2(left, rect.bottom() - height * rect.height()),
+            egui::pos2(left + bar_width, rect.bottom()),
+        );
+        mesh.add_colored_rect(bar, color);
+    }
+    painter.add(mesh);
+
+    let x = rect.left() + threshold.clamp(0.0, 1.0) * rect.width();
+    painter.vline(x, rect.y_range(), egui::Stroke::new(1.5, egui::Color32::RED));
+}
+
+/// State of `slice_preview_ui`. The texture is written by
+/// `ui_cache::slice_preview_system`, not by the UI.
+#[cfg(feature = "ui")]
+#[derive(Default)]
+pub(crate) struct SlicePreview {
+    pub z: u32,
+    /// Index into the texel, `0..NOISE_CHANNELS`.
+    pub channel: usize,
+    /// Set by the UI every frame the preview is shown.
+    pub open: bool,
+    texture: Option<bevy_egui::egui::TextureHandle>,
+    /// Size of the noise the texture was cut from.
+    size: UVec3,
+    /// Noise generation, slice and channel the texture shows.
+    built: Option<(u64, u32, usize)>,
+}
+
+#[cfg(feature = "ui")]
+impl SlicePreview {
+    /// Cuts the selected slice out of `image` into the preview texture, unless
+    /// it already shows that slice of noise `generation`.
+    pub fn update(&mut self, ctx: &bevy_egui::egui::Context, image: &Image, generation: u64) {
+        use bevy_egui::egui;
+
+        let extent = image.texture_descriptor.size;
+        let size = UVec3::new(extent.width, extent.height, extent.depth_or_array_layers);
+        let texels = (size.x * size.y) as usize;
+        if image.data.len() < texels * size.z as usize * NOISE_CHANNELS {
+            return;
+        }
+        self.z = self.z.min(size.z - 1);
+        let key = (generation, self.z, self.channel);
+        if self.built == Some(key) {
+            return;
+        }
+        let start = texels * self.z as usize * NOISE_CHANNELS;
+        let pixels = image.data[start..start + texels * NOISE_CHANNELS]
+            .chunks_exact(NOISE_CHANNELS)
+            .map(|texel| egui::Color32::from_gray(texel[self.channel]))
+            .collect();
+        let slice = egui::ColorImage {
+            size: [size.x as usize, size.y as usize],
+            pixels,
+        };
+        match &mut self.texture {
+            Some(texture) => texture.set(slice, egui::TextureOptions::NEAREST),
+            None => self.texture = Some(ctx.load_texture("noise_slice", slice, egui::TextureOptions::NEAREST)),
+        }
+        self.size = size;
+        self.built = Some(key);
+    }
+}
+
+/// One Z slice of the baked noise as a grayscale image, with the channel
+/// selectable: the noise itself or its ambient visibility.
+#[cfg(feature = "ui")]
+pub(crate) fn slice_preview_ui(ui: &mut bevy_egui::egui::Ui, preview: &mut SlicePreview) {
+    use bevy_egui::egui;
+
+    preview.open = true;
+    let Some(texture) = &preview.texture else {
+        ui.spinner();
+        return;
+    };
+    let (texture, size) = (texture.id(), preview.size);
+    ui.horizontal(|ui| {
+        ui.add(egui::Slider::new(&mut preview.z, 0..=size.z - 1).text("Z"));
+        ui.selectable_value(&mut preview.channel, 0, "Noise");
+        ui.selectable_value(&mut preview.channel, 1, "Occlusion");
+    });
+    let width = ui.available_width().min(240.0);
+    // Rows run bottom to top in the volume, so +Y is up in the preview.
+    ui.add(
+        egui::Image::new((texture, egui::vec2(width, width * size.y as f32 / size.x as f32)))
+            .uv(egui::Rect::from_min_max(egui::pos2(0.0, 1.0), egui::pos2(1.0, 0.0))),
+    );
+}
+
+/// SIMD-friendly width of the Worley distance loop.
+#[cfg(feature = "bake")]
+const LANES: usize = 8;
+
+/// Feature points repeated over the 27 neighbouring tiles and pre-scaled by
+/// the frequency, stored as separate coordinate arrays padded to a multiple
+/// of `LANES` so the distance loop autovectorizes.
+#[cfg(feature = "bake")]
+#[derive(Default)]
+struct TiledPoints {
+    x: Vec<f32>,
+    y: Vec<f32>,
+    z: Vec<f32>,
+}
+
+#[cfg(feature = "bake")]
+impl TiledPoints {
+    fn rebuild(&mut self, points: &[Vec3], freq: f32) {
+        self.x.clear();
+        self.y.clear();
+        self.z.clear();
+        for point in points {
+            for oz in -1..=1 {
+                for oy in -1..=1 {
+                    for ox in -1..=1 {
+                        let offset = Vec3::new(ox as f32, oy as f32, oz as f32);
+                        let tiled = (*point + offset) * freq;
+                        self.x.push(tiled.x);
+                        self.y.push(tiled.y);
+                        self.z.push(tiled.z);
+                    }
+                }
+            }
+        }
+        // Padding points are infinitely far away and never win.
+        let padded = self.x.len().next_multiple_of(LANES);
+        self.x.resize(padded, f32::INFINITY);
+        self.y.resize(padded, f32::INFINITY);
+        self.z.resize(padded, f32::INFINITY);
+    }
+}
+
+/// Inverted distance to the nearest feature point, searching the 27
+/// neighbouring tiles so the result wraps. Compares squared distances per
+/// lane and takes a single square root, which matches the per-point
+/// distances exactly since the square root is monotonic.
+#[cfg(feature = "bake")]
+fn worley(points: &TiledPoints, uvw: Vec3, freq: f32) -> f32 {
+    let p = uvw * freq;
+    let mut best = [f32::INFINITY; LANES];
+    let chunks = points
+        .x
+        .chunks_exact(LANES)
+        .zip(points.y.chunks_exact(LANES))
+        .zip(points.z.chunks_exact(LANES));
+    for ((xs, ys), zs) in chunks {
+        for lane in 0..LANES {
+            let dx = p.x - xs[lane];
+            let dy = p.y - ys[lane];
+            let dz = p.z - zs[lane];
+            // A plain compare, not `f32::min`: its NaN handling keeps the
+            // loop from compiling to packed minimums.
+            let dist = dx * dx + dy * dy + dz * dz;
+            if dist < best[lane] {
+                best[lane] = dist;
+            }
+        }
+    }
+    let min_dist = best.into_iter().fold(100.0, f32::min).sqrt();
+    1.0 - min_dist.min(1.0)
+}
+
+/// Classic gradient noise with a configurable integer period per call, so
+/// every octave tiles across the unit cube.
+#[cfg(feature = "bake")]
+struct Perlin {
+    perm: [u8; 256],
+}
+
+#[cfg(feature = "bake")]
+impl Perlin {
+    const GRADIENTS: [Vec3; 12] = [
+        Vec3::new(1.0, 1.0, 0.0),
+        Vec3::new(-1.0, 1.0, 0.0),
+        Vec3::new(1.0, -1.0, 0.0),
+        Vec3::new(-1.0, -1.0, 0.0),
+        Vec3::new(1.0, 0.0, 1.0),
+        Vec3::new(-1.0, 0.0, 1.0),
+        Vec3::new(1.0, 0.0, -1.0),
+        Vec3::new(-1.0, 0.0, -1.0),
+        Vec3::new(0.0, 1.0, 1.0),
+        Vec3::new(0.0, -1.0, 1.0),
+        Vec3::new(0.0, 1.0, -1.0),
+        Vec3::new(0.0, -1.0, -1.0),
+    ];
+
+    fn new(rng: &mut ChaCha8Rng) -> Self {
+        let mut perm = [0u8; 256];
+        for (i, value) in perm.iter_mut().enumerate() {
+            *value = i as u8;
+        }
+        for i in (1..perm.len()).rev() {
+            perm.swap(i, rng.gen_range(0..=i));
+        }
+        Self { perm }
+    }
+
+    fn gradient(&self, cell: IVec3, period: i32) -> Vec3 {
+        let c = cell.rem_euclid(IVec3::splat(period));
+        let hash = |value: usize, coord: i32| self.perm[(value + coord as usize) & 255] as usize;
+        let index = hash(hash(hash(0, c.x), c.y), c.z);
+        Self::GRADIENTS[index % Self::GRADIENTS.len()]
+    }
+
+    /// Gradient noise in roughly `-1..=1`, repeating every `period` units.
+    fn sample(&self, p: Vec3, period: i32) -> f32 {
+        let base = p.floor();
+        let f = p - base;
+        let b = base.as_ivec3();
+        let corner = |offset: IVec3| self.gradient(b + offset, period).dot(f - offset.as_vec3());
+        let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+
+        let x00 = corner(IVec3::ZERO).lerp(corner(IVec3::X), u.x);
+        let x10 = corner(IVec3::Y).lerp(corner(IVec3::new(1, 1, 0)), u.x);
+        let x01 = corner(IVec3::Z).lerp(corner(IVec3::new(1, 0, 1)), u.x);
+        let x11 = corner(IVec3::new(0, 1, 1)).lerp(corner(IVec3::ONE), u.x);
+        x00.lerp(x10, u.y).lerp(x01.lerp(x11, u.y), u.z)
+    }
+
+    /// Octaves double the period from `frequency` (rounded so it tiles) and
+    /// halve the amplitude. Each octave is mapped into `0..=1` before summing
+    /// and the sum is divided by the total amplitude, so every fractal type
+    /// stays in range.
+    fn fbm(&self, uvw: Vec3, frequency: f32, octaves: u32, fractal: FractalType) -> f32 {
+        let mut period = frequency.round().max(1.0) as i32;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut sum = 0.0;
+        for _ in 0..octaves.max(1) {
+            let n = self.sample(uvw * period as f32, period).clamp(-1.0, 1.0);
+            let shaped = match fractal {
+                FractalType::Standard => n * 0.5 + 0.5,
+                FractalType::Billow => n.abs(),
+                FractalType::Ridged => 1.0 - n.abs(),
+            };
+            sum += shaped * amplitude;
+            total += amplitude;
+            amplitude *= 0.5;
+            period *= 2;
+        }
+        sum / total
+    }
+}
+
+/// Low-frequency vector value noise on a lattice that wraps every unit, so
+/// warping by it keeps the bake tileable.
+#[cfg(feature = "bake")]
+struct WarpField {
+    cells: u32,
+    lattice: Vec<Vec3>,
+}
+
+#[cfg(feature = "bake")]
+impl WarpField {
+    fn new(rng: &mut ChaCha8Rng, frequency: u32) -> Self {
+        let cells = frequency.max(1);
+        let lattice = (0..cells * cells * cells)
+            .map(|_| {
+                Vec3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+            })
+            .collect();
+        Self { cells, lattice }
+    }
+
+    fn at(&self, cell: IVec3) -> Vec3 {
+        let c = cell.rem_euclid(IVec3::splat(self.cells as i32)).as_uvec3();
+        self.lattice[((c.z * self.cells + c.y) * self.cells + c.x) as usize]
+    }
+
+    fn sample(&self, uvw: Vec3) -> Vec3 {
+        let p = uvw * self.cells as f32;
+        let base = p.floor();
+        let f = p - base;
+        let f = f * f * (3.0 - 2.0 * f);
+        let b = base.as_ivec3();
+
+        let x00 = self.at(b).lerp(self.at(b + IVec3::X), f.x);
+        let x10 = self.at(b + IVec3::Y).lerp(self.at(b + IVec3::new(1, 1, 0)), f.x);
+        let x01 = self.at(b + IVec3::Z).lerp(self.at(b + IVec3::new(1, 0, 1)), f.x);
+        let x11 = self.at(b + IVec3::new(0, 1, 1)).lerp(self.at(b + IVec3::ONE), f.x);
+        x00.lerp(x10, f.y).lerp(x01.lerp(x11, f.y), f.z)
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+
+use crate::{extension::LitCloudMaterial, noise};
+
+/// Empty-space skipping for the cloud march.
+///
+/// Every baked noise volume (the global one, per-volume overrides, thumbnail
+/// bakes) gets a coarse occupancy volume from `noise::occupancy`, rebuilt
+/// whenever the noise is rebaked and bound to every material sampling that
+/// noise (by `sync_cloud_materials` for the cloud materials). The threshold is compared in the shader, so threshold and coverage
+/// edits need no rebuild. Materials without one bind the white fallback
+/// texture, which never skips.
+pub struct OccupancyPlugin;
+
+impl Plugin for OccupancyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OccupancyMaps>()
+            .add_systems(Update, occupancy_system.after(crate::update_material_system));
+    }
+}
+
+/// Occupancy volume of each noise volume.
+#[derive(Resource, Default)]
+pub struct OccupancyMaps {
+    maps: HashMap<AssetId<Image>, Handle<Image>>,
+}
+
+impl OccupancyMaps {
+    pub fn get(&self, noise: AssetId<Image>) -> Option<&Handle<Image>> {
+        self.maps.get(&noise)
+    }
+}
+
+fn is_noise_volume(image: &Image) -> bool {
+    image.texture_descriptor.dimension == TextureDimension::D3
+        && image.texture_descriptor.format == TextureFormat::Rg8Unorm
+}
+
+fn occupancy_image(noise: &Image) -> Option<Image> {
+    let extent = noise.texture_descriptor.size;
+    let size = UVec3::new(extent.width, extent.height, extent.depth_or_array_layers);
+    if size.min_element() == 0 || noise.data.len() < (size.x * size.y * size.z) as usize * noise::NOISE_CHANNELS {
+        return None;
+    }
+    let blocks = noise::occupancy_size(size);
+    Some(Image::new(
+        Extent3d {
+            width: blocks.x,
+            height: blocks.y,
+            depth_or_array_layers: blocks.z,
+        },
+        TextureDimension::D3,
+        noise::occupancy(&noise.data, size),
+        TextureFormat::R8Unorm,
+        RenderAssetUsages::default(),
+    ))
+}
+
+fn occupancy_system(
+    mut maps: ResMut<OccupancyMaps>,
+    mut images: ResMut<Assets<Image>>,
+    mut image_events: EventReader<AssetEvent<Image>>,
+    mut lit_materials: ResMut<Assets<LitCloudMaterial>>,
+) {
+    for event in image_events.read() {
+        match *event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => {
+                let Some(occupancy) = images.get(id).filter(|image| is_noise_volume(image)).and_then(occupancy_image)
+                else {
+                    continue;
+                };
+                let _span = info_span!("bake_occupancy").entered();
+                match maps.maps.get(&id).cloned() {
+                    Some(handle) => {
+                        images.insert(&handle, occupancy);
+                    }
+                    None => {
+                        let handle = images.add(occupancy);
+                        maps.maps.insert(id, handle);
+                    }
+                }
+            }
+            AssetEvent::Removed { id } => {
+                if let Some(handle) = maps.maps.remove(&id) {
+                    images.remove(&handle);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let wanted = |noise: &Handle<Image>| maps.get(noise.id()).cloned();
+    let lit_stale: Vec<_> = lit_materials
+        .iter()
+        .filter(|(_, material)| material.extension.occupancy != wanted(&material.extension.noise_texture))
+        .map(|(id, _)| id)
+        .collect();
+    for id in lit_stale {
+        if let Some(material) = lit_materials.get_mut(id) {
+            material.extension.occupancy = wanted(&material.extension.noise_texture);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    density::{march_transmittance, volume_ray_box, DensityField},
+    shortcuts::UiState,
+    volume_visibility::CloudHidden,
+    weather_map::WeatherMap,
+    CloudMaterial, CloudMaterialUniform, CloudSettings, CloudUiSet, CloudVolume, OrbitCamera,
+};
+
+/// Finds where the cursor ray "hits" the clouds by marching the CPU density
+/// field, for tools that place things on the cloud surface.
+pub struct CloudPickPlugin;
+
+impl Plugin for CloudPickPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CloudPickSettings>()
+            .init_resource::<CloudPickResult>()
+            .add_event::<CloudSurfaceClicked>()
+            .add_systems(
+                Update,
+                (
+                    pick_ui_system.in_set(CloudUiSet),
+                    cursor_pick_system.after(crate::update_material_system),
+                    pick_gizmo_system,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct CloudPickSettings {
+    pub enabled: bool,
+    /// The surface is where accumulated transmittance first drops to this.
+    pub threshold: f32,
+    pub steps: u32, // march steps per volume
+    pub show_gizmo: bool,
+    /// Whether volumes hidden with `CloudHidden` can be hit, here and by the
+    /// double-click focus. Off, a hidden volume is as absent as it looks.
+    pub include_hidden: bool,
+}
+
+impl Default for CloudPickSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 0.5,
+            steps: 64,
+            show_gizmo: false,
+            include_hidden: false,
+        }
+    }
+}
+
+/// What the cursor ray hit this frame.
+#[derive(Resource, Clone, Debug)]
+pub struct CloudPickResult {
+    /// Volume containing the surface point, if the threshold was crossed.
+    pub entity: Option<Entity>,
+    pub position: Option<Vec3>,
+    /// Transmittance along the whole ray through every volume.
+    pub transmittance: f32,
+}
+
+impl Default for CloudPickResult {
+    fn default() -> Self {
+        Self {
+            entity: None,
+            position: None,
+            transmittance: 1.0,
+        }
+    }
+}
+
+/// Sent when the cloud surface is clicked outside the UI. Tools that place
+/// objects on clouds listen for this instead of doing their own picking.
+#[derive(Event, Clone, Debug)]
+pub struct CloudSurfaceClicked {
+    pub entity: Entity,
+    pub position: Vec3,
+}
+
+/// Picks along an arbitrary ray. Volumes are marched front to back by entry
+/// distance, carrying transmittance from one into the next.
+pub fn pick_cloud<'a>(
+    field: &DensityField,
+    volumes: impl IntoIterator<Item = (Entity, &'a CloudMaterialUniform)>,
+    ray: Ray3d,
+    settings: &CloudPickSettings,
+) -> CloudPickResult {
+    let mut hits: Vec<_> = volumes
+        .into_iter()
+        .filter_map(|(entity, data)| {
+            let (near, _) = volume_ray_box(data, ray.origin, *ray.direction)?;
+            Some((near, entity, data))
+        })
+        .collect();
+    hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut result = CloudPickResult::default();
+    for (_, entity, data) in hits {
+        let Some(march) = march_transmittance(
+            field,
+            data,
+            ray.origin,
+            *ray.direction,
+            settings.steps,
+            result.transmittance,
+            settings.threshold,
+        ) else {
+            continue;
+        };
+        result.transmittance = march.transmittance;
+        if result.position.is_none() {
+            if let Some(t) = march.crossing {
+                result.entity = Some(entity);
+                result.position = Some(ray.get_point(t));
+            }
+        }
+    }
+    result
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn cursor_pick_system(
+    settings: Res<CloudPickSettings>,
+    cloud_settings: Res<CloudSettings>,
+    weather_map: Res<WeatherMap>,
+    images: Res<Assets<Image>>,
+    materials: Res<Assets<CloudMaterial>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<OrbitCamera>>,
+    volumes: Query<(Entity, &MeshMaterial3d<CloudMaterial>, Has<CloudHidden>), With<CloudVolume>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut contexts: EguiContexts,
+    ui_state: Res<UiState>,
+    mut result: ResMut<CloudPickResult>,
+    mut clicked: EventWriter<CloudSurfaceClicked>,
+) {
+    *result = CloudPickResult::default();
+    if !settings.enabled {
+        return;
+    }
+    let Some(cursor) = windows.get_single().ok().and_then(|window| window.cursor_position()) else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Ok(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+    let Some(field) = DensityField::from_settings(&images, &cloud_settings) else {
+        return;
+    };
+    let field = field.with_weather_map(images.get(&weather_map.image));
+
+    *result = pick_cloud(
+        &field,
+        volumes
+            .iter()
+            .filter(|(_, _, hidden)| settings.include_hidden || !hidden)
+            .filter_map(|(entity, handle, _)| Some((entity, &materials.get(&handle.0)?.data))),
+        ray,
+        &settings,
+    );
+
+    let over_ui = ui_state.show_ui && contexts.try_ctx_mut().is_some_and(|ctx| ctx.is_pointer_over_area());
+    if buttons.just_pressed(MouseButton::Left) && !over_ui {
+        if let (Some(entity), Some(position)) = (result.entity, result.position) {
+            clicked.send(CloudSurfaceClicked { entity, position });
+        }
+    }
+}
+
+fn pick_gizmo_system(settings: Res<CloudPickSettings>, result: Res<CloudPickResult>, mut gizmos: Gizmos) {
+    if !settings.show_gizmo {
+        return;
+    }
+    if let Some(position) = result.position {
+        gizmos.sphere(Isometry3d::from_translation(position), 0.1, Color::srgb(1.0, 0.3, 0.2));
+    }
+}
+
+fn pick_ui_system(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<CloudPickSettings>,
+    result: Res<CloudPickResult>,
+    mut clicks: EventReader<CloudSurfaceClicked>,
+    mut last_click: Local<Option<CloudSurfaceClicked>>,
+) {
+    if let Some(click) = clicks.read().last() {
+        *last_click = Some(click.clone());
+    }
+    egui::Window::new("Picking")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.checkbox(&mut settings.enabled, "Enabled");
+            ui.checkbox(&mut settings.show_gizmo, "Show Hit Point");
+            ui.checkbox(&mut settings.include_hidden, "Hit Hidden Volumes")
+                .on_hover_text("Also pick and double-click focus volumes hidden in the volume list");
+            ui.add(egui::Slider::new(&mut settings.threshold, 0.01..=0.99).text("Transmittance Threshold"));
+            ui.add(egui::Slider::new(&mut settings.steps, 8..=256).text("Steps"));
+
+            match result.position {
+                Some(position) => ui.label(format!(
+                    "Hit: ({:.2}, {:.2}, {:.2})",
+                    position.x, position.y, position.z
+                )),
+                None => ui.label("Hit: none"),
+            };
+            ui.label(format!("Transmittance: {:.3}", result.transmittance));
+            if let Some(click) = last_click.as_ref() {
+                ui.label(format!(
+                    "Last click: ({:.2}, {:.2}, {:.2}) on {}",
+                    click.position.x, click.position.y, click.position.z, click.entity
+                ));
+            }
+        });
+}
@@ -0,0 +1,242 @@
+use bevy::{
+    core_pipeline::tonemapping::Tonemapping,
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        view::RenderLayers,
+    },
+};
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    material_sync::CloudSyncSet,
+    resize::{CloudTargetSize, CloudTargetsResized},
+    OrbitCamera,
+};
+#[cfg(feature = "ui")]
+use crate::CloudUiSet;
+
+/// Layer holding the portal meshes; only the mask camera sees it.
+const PORTAL_LAYER: usize = 30;
+
+/// Restricts the clouds to the screen area covered by `CloudPortal` meshes.
+///
+/// An offscreen camera that follows the main camera draws the portals into a
+/// window-sized mask, and the cloud shader discards fragments whose mask alpha
+/// is below one half before marching. The depth buffer has no stencil aspect,
+/// so the mask is a texture rather than a stencil test.
+pub struct CloudPortalPlugin;
+
+impl Plugin for CloudPortalPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CloudMaskSettings>()
+            .init_resource::<CloudMask>()
+            .add_systems(Update, (portal_demo_system, portal_layer_system).chain())
+            .add_systems(PostUpdate, mask_system.in_set(CloudSyncSet::Resolve));
+        #[cfg(feature = "ui")]
+        app.add_systems(Update, portal_ui_system.in_set(CloudUiSet).before(portal_demo_system));
+    }
+}
+
+/// Marks a mesh as a window the clouds are visible through. Portals are moved
+/// to their own render layer, so they are invisible in the main view; any
+/// opaque material works.
+#[derive(Component, Default)]
+pub struct CloudPortal;
+
+#[derive(Resource, Clone, Debug, Default)]
+pub struct CloudMaskSettings {
+    pub enabled: bool,
+    /// Spawns a window frame between the default camera and the cloud.
+    pub demo: bool,
+}
+
+/// The mask as every cloud material binds it.
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub struct CloudMask {
+    pub uniform: Vec4,
+    pub texture: Handle<Image>,
+}
+
+#[derive(Component)]
+struct MaskCamera;
+
+/// Frame and portal spawned by the demo toggle.
+#[derive(Component)]
+struct PortalDemo;
+
+fn portal_layer_system(mut commands: Commands, portals: Query<Entity, Added<CloudPortal>>) {
+    for entity in &portals {
+        commands.entity(entity).insert(RenderLayers::layer(PORTAL_LAYER));
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn mask_system(
+    mut commands: Commands,
+    settings: Res<CloudMaskSettings>,
+    mut images: ResMut<Assets<Image>>,
+    mut mask: ResMut<CloudMask>,
+    target_size: Res<CloudTargetSize>,
+    mut resized: EventReader<CloudTargetsResized>,
+    main_camera: Query<(&Transform, &Projection), (With<OrbitCamera>, Without<MaskCamera>)>,
+    mut mask_camera: Query<(Entity, &Camera, &mut Transform, &mut Projection), With<MaskCamera>>,
+) {
+    let size = target_size.physical;
+    let camera = mask_camera.get_single_mut().ok();
+    let main_view = main_camera.get_single().ok();
+
+    let mask_image = match (settings.enabled, camera) {
+        (false, camera) => {
+            if let Some((entity, camera, ..)) = camera {
+                if let RenderTarget::Image(image) = &camera.target {
+                    images.remove(image);
+                }
+                commands.entity(entity).despawn_recursive();
+            }
+            None
+        }
+        (true, Some((_, camera, mut transform, mut projection))) => {
+            let RenderTarget::Image(image) = &camera.target else {
+                return;
+            };
+            // Resized in place, so the camera keeps its target and the
+            // clouds stay masked through the resize.
+            if let Some(resized) = resized.read().last() {
+                if let Some(image) = images.get_mut(image) {
+                    image.resize(Extent3d {
+                        width: resized.size.physical.x,
+                        height: resized.size.physical.y,
+                        ..default()
+                    });
+                }
+            }
+            if let Some((main_transform, main_projection)) = main_view {
+                *transform = *main_transform;
+                *projection = main_projection.clone();
+            }
+            Some(image.clone())
+        }
+        (true, None) => {
+            let mut image = Image::new_fill(
+                Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    ..default()
+                },
+                TextureDimension::D2,
+                &[0, 0, 0, 0],
+                TextureFormat::Bgra8UnormSrgb,
+                RenderAssetUsages::default(),
+            );
+            image.texture_descriptor.usage =
+                TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+            let image = images.add(image);
+            commands.spawn((
+                Camera3d::default(),
+                Camera {
+                    target: RenderTarget::Image(image.clone()),
+                    order: -2,
+                    clear_color: ClearColorConfig::Custom(Color::NONE),
+                    ..default()
+                },
+                Tonemapping::None,
+                Msaa::Off,
+                RenderLayers::layer(PORTAL_LAYER),
+                MaskCamera,
+            ));
+            Some(image)
+        }
+    };
+
+    // Until the mask exists the clouds stay unmasked. The shader applies it
+    // only in the view at the main camera's position, so other windows and
+    // cameras draw the clouds whole. That position makes every material
+    // re-upload while the camera moves, but only with the mask on.
+    let view_position = main_view.map_or(Vec3::ZERO, |(transform, _)| transform.translation);
+    mask.set_if_neq(match mask_image {
+        Some(texture) => CloudMask {
+            uniform: Vec4::new(1.0, view_position.x, view_position.y, view_position.z),
+            texture,
+        },
+        None => CloudMask::default(),
+    });
+}
+
+fn portal_demo_system(
+    mut commands: Commands,
+    settings: Res<CloudMaskSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    demos: Query<Entity, With<PortalDemo>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    match (settings.demo, demos.is_empty()) {
+        (true, true) => {
+            let width = 1.6;
+            let height = 1.2;
+            let bar = 0.08;
+            let frame_material = materials.add(StandardMaterial {
+                base_color: Color::srgb(0.35, 0.22, 0.12),
+                perceptual_roughness: 0.8,
+                ..default()
+            });
+            let portal_material = materials.add(StandardMaterial {
+                base_color: Color::WHITE,
+                unlit: true,
+                cull_mode: None,
+                ..default()
+            });
+            let horizontal = meshes.add(Cuboid::new(width + 2.0 * bar, bar, bar));
+            let vertical = meshes.add(Cuboid::new(bar, height, bar));
+            // Halfway between the default camera and the cloud, facing the camera.
+            let transform = Transform::from_xyz(-1.5, 2.0, 3.0).looking_at(Vec3::new(-3.0, 3.0, 6.0), Vec3::Y);
+            commands
+                .spawn((transform, Visibility::default(), PortalDemo))
+                .with_children(|frame| {
+                    frame.spawn((
+                        Mesh3d(meshes.add(Rectangle::new(width, height))),
+                        MeshMaterial3d(portal_material),
+                        CloudPortal,
+                    ));
+                    for y in [-1.0, 1.0] {
+                        frame.spawn((
+                            Mesh3d(horizontal.clone()),
+                            MeshMaterial3d(frame_material.clone()),
+                            Transform::from_xyz(0.0, y * (height + bar) * 0.5, 0.0),
+                        ));
+                    }
+                    for x in [-1.0, 1.0] {
+                        frame.spawn((
+                            Mesh3d(vertical.clone()),
+                            MeshMaterial3d(frame_material.clone()),
+                            Transform::from_xyz(x * (width + bar) * 0.5, 0.0, 0.0),
+                        ));
+                    }
+                });
+        }
+        (false, false) => {
+            for entity in &demos {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(feature = "ui")]
+fn portal_ui_system(mut contexts: EguiContexts, mut settings: ResMut<CloudMaskSettings>) {
+    egui::Window::new("Portal Mask")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.checkbox(&mut settings.enabled, "Only Draw Clouds Through Portals");
+            if ui.checkbox(&mut settings.demo, "Window Frame Demo").changed() && settings.demo {
+                settings.enabled = true;
+            }
+        });
+}
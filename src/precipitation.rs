@@ -0,0 +1,293 @@
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    density::{optical_depth, DensityField},
+    volume_visibility::CloudHidden,
+    weather_map::WeatherMap,
+    CloudMaterial, CloudSettings, CloudVolume,
+};
+#[cfg(feature = "ui")]
+use crate::{CloudUiSet, OrbitCamera};
+
+/// Integrates cloud density vertically on the CPU and publishes where
+/// precipitation should fall. Games read `PrecipitationMap` or listen for
+/// `PrecipitationCell` events to drive their own particle systems.
+pub struct PrecipitationPlugin;
+
+impl Plugin for PrecipitationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PrecipitationSettings>()
+            .init_resource::<PrecipitationMap>()
+            .add_event::<PrecipitationCell>()
+            .add_systems(
+                Update,
+                (
+                    precipitation_system.after(crate::update_material_system),
+                    precipitation_gizmo_system,
+                )
+                    .chain(),
+            );
+        #[cfg(feature = "ui")]
+        app.add_systems(
+            Update,
+            precipitation_ui_system
+                .in_set(CloudUiSet)
+                .before(precipitation_system),
+        );
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct PrecipitationSettings {
+    pub enabled: bool,
+    pub interval: f32,   // seconds between map updates
+    pub resolution: u32, // cells per side of the grid
+    pub threshold: f32,  // minimum intensity for a `PrecipitationCell` event
+    pub column_steps: u32,
+    pub show_gizmos: bool,
+    /// Whether volumes hidden with `CloudHidden` still rain. On by default:
+    /// hiding is a view tool, and gameplay keeps its weather.
+    pub include_hidden: bool,
+}
+
+impl Default for PrecipitationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: 0.5,
+            resolution: 32,
+            threshold: 0.5,
+            column_steps: 16,
+            show_gizmos: true,
+            include_hidden: true,
+        }
+    }
+}
+
+/// Precipitation intensity over a regular XZ grid covering every cloud
+/// volume.
+///
+/// Layout (stable):
+/// - `intensity` and `cloud_base` have `resolution.x * resolution.y` entries,
+///   row-major with X varying fastest: `index = z * resolution.x + x`.
+/// - Cell `(x, z)` covers the world XZ rectangle starting at
+///   `min + (x, z) * cell_size()` with size `cell_size()`.
+/// - `intensity` is `1 - exp(-optical_depth)` of the vertical column through
+///   all volumes over the cell center, in `0..=1`.
+/// - `cloud_base` is the lowest world Y of the volumes covering the cell, or
+///   `f32::NAN` where no volume covers it.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct PrecipitationMap {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub resolution: UVec2,
+    pub intensity: Vec<f32>,
+    pub cloud_base: Vec<f32>,
+}
+
+impl PrecipitationMap {
+    pub fn cell_size(&self) -> Vec2 {
+        (self.max - self.min) / self.resolution.max(UVec2::ONE).as_vec2()
+    }
+
+    pub fn cell_center(&self, x: u32, z: u32) -> Vec2 {
+        self.min + (UVec2::new(x, z).as_vec2() + 0.5) * self.cell_size()
+    }
+
+    /// Intensity at a world XZ position, zero outside the map.
+    pub fn intensity_at(&self, position: Vec2) -> f32 {
+        if self.resolution.min_element() == 0 {
+            return 0.0;
+        }
+        let cell = ((position - self.min) / self.cell_size()).floor();
+        if cell.cmplt(Vec2::ZERO).any() || cell.cmpge(self.resolution.as_vec2()).any() {
+            return 0.0;
+        }
+        let cell = cell.as_uvec2();
+        self.intensity[(cell.y * self.resolution.x + cell.x) as usize]
+    }
+}
+
+/// Fired on every map update for each cell at or above
+/// `PrecipitationSettings::threshold`.
+#[derive(Event, Clone, Debug)]
+pub struct PrecipitationCell {
+    /// Cell center at the cloud base.
+    pub position: Vec3,
+    pub intensity: f32,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn precipitation_system(
+    time: Res<Time>,
+    mut timer: Local<f32>,
+    settings: Res<PrecipitationSettings>,
+    cloud_settings: Res<CloudSettings>,
+    weather_map: Res<WeatherMap>,
+    images: Res<Assets<Image>>,
+    materials: Res<Assets<CloudMaterial>>,
+    volumes: Query<(&MeshMaterial3d<CloudMaterial>, Has<CloudHidden>), With<CloudVolume>>,
+    mut map: ResMut<PrecipitationMap>,
+    mut cells: EventWriter<PrecipitationCell>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    *timer += time.delta_secs();
+    if *timer < settings.interval {
+        return;
+    }
+    *timer = 0.0;
+
+    let Some(field) = DensityField::from_settings(&images, &cloud_settings) else {
+        return;
+    };
+    let field = field.with_weather_map(images.get(&weather_map.image));
+    let volumes: Vec<_> = volumes
+        .iter()
+        .filter(|(_, hidden)| settings.include_hidden || !hidden)
+        .filter_map(|(handle, _)| materials.get(&handle.0))
+        .map(|material| &material.data)
+        .collect();
+    if volumes.is_empty() {
+        *map = PrecipitationMap::default();
+        return;
+    }
+
+    let mut min = Vec2::splat(f32::MAX);
+    let mut max = Vec2::splat(f32::MIN);
+    for data in &volumes {
+        let (box_min, box_max) = data.world_bounds();
+        min = min.min(box_min.xz());
+        max = max.max(box_max.xz());
+    }
+
+    let resolution = UVec2::splat(settings.resolution.max(1));
+    let _span = info_span!("precipitation_readback", cells = resolution.x * resolution.y, steps = settings.column_steps).entered();
+    let cell_count = (resolution.x * resolution.y) as usize;
+    map.min = min;
+    map.max = max;
+    map.resolution = resolution;
+    map.intensity.clear();
+    map.intensity.resize(cell_count, 0.0);
+    map.cloud_base.clear();
+    map.cloud_base.resize(cell_count, f32::NAN);
+
+    for z in 0..resolution.y {
+        for x in 0..resolution.x {
+            let center = map.cell_center(x, z);
+            let mut depth = 0.0;
+            let mut base = f32::NAN;
+            for data in &volumes {
+                let (box_min, box_max) = data.world_bounds();
+                if center.cmplt(box_min.xz()).any() || center.cmpgt(box_max.xz()).any() {
+                    continue;
+                }
+                depth += optical_depth(
+                    &field,
+                    data,
+                    Vec3::new(center.x, box_min.y, center.y),
+                    Vec3::new(center.x, box_max.y, center.y),
+                    settings.column_steps,
+                );
+                base = if base.is_nan() { box_min.y } else { base.min(box_min.y) };
+            }
+
+            let index = (z * resolution.x + x) as usize;
+            let intensity = 1.0 - (-depth).exp();
+            map.intensity[index] = intensity;
+            map.cloud_base[index] = base;
+            if intensity >= settings.threshold {
+                cells.send(PrecipitationCell {
+                    position: Vec3::new(center.x, base, center.y),
+                    intensity,
+                });
+            }
+        }
+    }
+}
+
+/// Demo visualization: falling line streaks under dense columns.
+fn precipitation_gizmo_system(
+    time: Res<Time>,
+    settings: Res<PrecipitationSettings>,
+    map: Res<PrecipitationMap>,
+    mut gizmos: Gizmos,
+) {
+    if !settings.enabled || !settings.show_gizmos {
+        return;
+    }
+    let fall_height = 6.0;
+    let streak = 0.4;
+    let color = Color::srgba(0.6, 0.7, 0.9, 0.6);
+    for z in 0..map.resolution.y {
+        for x in 0..map.resolution.x {
+            let index = (z * map.resolution.x + x) as usize;
+            let intensity = map.intensity[index];
+            if intensity < settings.threshold {
+                continue;
+            }
+            let center = map.cell_center(x, z);
+            let base = map.cloud_base[index];
+            // Offset each cell's phase so the streaks don't fall in lockstep.
+            let phase = ((x * 7 + z * 13) % 17) as f32 / 17.0;
+            let drops = (intensity * 4.0).ceil() as u32;
+            for drop in 0..drops {
+                let t = (time.elapsed_secs() * 4.0 / fall_height + phase + drop as f32 / drops as f32).fract();
+                let top = base - t * fall_height;
+                let start = Vec3::new(center.x, top, center.y);
+                gizmos.line(start, start - Vec3::Y * streak, color);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+fn precipitation_ui_system(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<PrecipitationSettings>,
+    map: Res<PrecipitationMap>,
+    mut cells: EventReader<PrecipitationCell>,
+    mut heaviest: Local<Option<PrecipitationCell>>,
+    camera: Query<&GlobalTransform, With<OrbitCamera>>,
+) {
+    // Listens like a game would: the heaviest cell of the latest update.
+    if let Some(cell) = cells.read().max_by(|a, b| a.intensity.total_cmp(&b.intensity)) {
+        *heaviest = Some(cell.clone());
+    }
+
+    egui::Window::new("Precipitation")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.checkbox(&mut settings.enabled, "Enabled");
+            ui.checkbox(&mut settings.show_gizmos, "Show Rain Gizmos");
+            ui.checkbox(&mut settings.include_hidden, "Hidden Volumes Rain")
+                .on_hover_text("Count volumes hidden in the volume list in the precipitation map");
+            ui.add(egui::Slider::new(&mut settings.interval, 0.0..=5.0).text("Interval (s)"));
+            ui.add(egui::Slider::new(&mut settings.resolution, 4..=128).text("Resolution"));
+            ui.add(egui::Slider::new(&mut settings.threshold, 0.0..=1.0).text("Threshold"));
+            ui.add(egui::Slider::new(&mut settings.column_steps, 4..=64).text("Column Steps"));
+
+            let raining = map
+                .intensity
+                .iter()
+                .filter(|intensity| **intensity >= settings.threshold)
+                .count();
+            ui.label(format!("Raining cells: {} / {}", raining, map.intensity.len()));
+            if raining == 0 {
+                *heaviest = None;
+            }
+            if let Some(cell) = heaviest.as_ref() {
+                ui.label(format!(
+                    "Heaviest: {:.2} at ({:.1}, {:.1}), base {:.1}",
+                    cell.intensity, cell.position.x, cell.position.z, cell.position.y
+                ));
+            }
+            if let Ok(camera) = camera.get_single() {
+                let position = camera.translation().xz();
+                ui.label(format!("Under camera: {:.2}", map.intensity_at(position)));
+            }
+        });
+}
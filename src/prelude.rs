@@ -0,0 +1,30 @@
+//! The stable surface of the crate: `use bevy_clouds::prelude::*;`.
+//!
+//! Everything re-exported here only changes in a semver-breaking release.
+//! Other public paths (`noise`, the crate root, `#[doc(hidden)]` types) are
+//! public for the editor or for Bevy's derives and may move between any two
+//! releases. `examples/prelude.rs` builds against this module alone, so
+//! dropping or renaming an item here fails CI.
+//!
+//! The noise parameters are fields of `CloudSettings`, and `NoiseOverride`
+//! swaps the cell layout for one volume; there is no separate parameter type.
+
+pub use crate::{
+    cloud_sun::CloudSun,
+    extension::{CloudExtension, LitCloudMaterial},
+    ground_fog::GroundFog,
+    motion_vectors::{CloudMotionVectors, CloudMotionVectorsPlugin},
+    noise::{load_noise, BakeStats, FractalType, NoiseBaked, NoiseType},
+    precipitation::{PrecipitationCell, PrecipitationMap, PrecipitationPlugin, PrecipitationSettings},
+    spawn::{CloudVolumeParams, SpawnCloudExt},
+    time_of_day::{Sun, TimeOfDay, TimeOfDayPlugin},
+    trail::CloudTrail,
+    versioning,
+    volume_noise::NoiseOverride,
+    weather::{SetWeather, WeatherController, WeatherPlugin},
+    CloudBlendMode, CloudLightingMode, CloudMaterial, CloudPlugin, CloudSettings, CloudVolume, OrbitCamera,
+    OrbitCameraConfig, QualityPreset,
+};
+
+#[cfg(feature = "bake")]
+pub use crate::noise::{bake_noise, BakeBudget};
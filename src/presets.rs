@@ -0,0 +1,466 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    ground_fog::GroundFog,
+    noise::{FractalType, NoiseType},
+    thumbnails::PresetThumbnails,
+    CloudSettings, CloudUiSet, CloudVolume,
+};
+
+/// Edge length of the thumbnails in the preset picker, in points.
+const PICKER_THUMBNAIL_SIZE: f32 = 80.0;
+const PICKER_COLUMNS: usize = 3;
+
+/// Shipped looks combining global settings with per-volume noise shaping.
+///
+/// With transitions enabled a preset eases the continuous parameters over
+/// `PresetTransitionSettings::duration`; bake parameters snap at the start,
+/// so the rebake runs while the look is still blending.
+pub struct CloudPresetsPlugin;
+
+impl Plugin for CloudPresetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PresetTransitionSettings>()
+            .init_resource::<PresetTransition>()
+            .add_systems(
+                Update,
+                (presets_ui_system.in_set(CloudUiSet), preset_transition_system)
+                    .chain()
+                    .before(crate::update_material_system),
+            );
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct PresetTransitionSettings {
+    pub enabled: bool,
+    pub duration: f32, // seconds
+}
+
+impl Default for PresetTransitionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duration: 2.0,
+        }
+    }
+}
+
+/// The continuous global parameters a preset sets.
+#[derive(Clone, Copy, Debug)]
+struct PresetLook {
+    coverage: f32,
+    density_multiplier: f32,
+    threshold: f32,
+    absorption: f32,
+    phase_g: f32,
+    silver_intensity: f32,
+    silver_spread: f32,
+}
+
+impl PresetLook {
+    fn from_settings(settings: &CloudSettings) -> Self {
+        Self {
+            coverage: settings.coverage,
+            density_multiplier: settings.density_multiplier,
+            threshold: settings.threshold,
+            absorption: settings.absorption,
+            phase_g: settings.phase_g,
+            silver_intensity: settings.silver_intensity,
+            silver_spread: settings.silver_spread,
+        }
+    }
+
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        Self {
+            coverage: self.coverage.lerp(to.coverage, t),
+            density_multiplier: self.density_multiplier.lerp(to.density_multiplier, t),
+            threshold: self.threshold.lerp(to.threshold, t),
+            absorption: self.absorption.lerp(to.absorption, t),
+            phase_g: self.phase_g.lerp(to.phase_g, t),
+            silver_intensity: self.silver_intensity.lerp(to.silver_intensity, t),
+            silver_spread: self.silver_spread.lerp(to.silver_spread, t),
+        }
+    }
+
+    fn write(&self, settings: &mut CloudSettings) {
+        settings.coverage = self.coverage;
+        settings.density_multiplier = self.density_multiplier;
+        settings.threshold = self.threshold;
+        settings.auto_threshold = None;
+        settings.absorption = self.absorption;
+        settings.phase_g = self.phase_g;
+        settings.silver_intensity = self.silver_intensity;
+        settings.silver_spread = self.silver_spread;
+    }
+}
+
+/// The preset being eased towards.
+#[derive(Resource, Default)]
+pub struct PresetTransition {
+    active: Option<Transition>,
+}
+
+struct Transition {
+    target: &'static CloudPreset,
+    from: PresetLook,
+    /// The volumes present when the transition began, as they were then.
+    volumes: Vec<(Entity, CloudVolume)>,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl PresetTransition {
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Starts easing towards `target` from the current values. A transition
+    /// already in flight is retargeted from wherever it got to.
+    pub fn start(
+        &mut self,
+        target: &'static CloudPreset,
+        duration: f32,
+        settings: &mut CloudSettings,
+        volumes: impl IntoIterator<Item = (Entity, CloudVolume)>,
+    ) {
+        target.apply_bake(settings);
+        self.active = Some(Transition {
+            target,
+            from: PresetLook::from_settings(settings),
+            volumes: volumes.into_iter().collect(),
+            elapsed: 0.0,
+            duration,
+        });
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CloudPreset {
+    pub name: &'static str,
+    pub coverage: f32,
+    pub density_multiplier: f32,
+    pub threshold: f32,
+    pub absorption: f32,
+    pub phase_g: f32,
+    pub silver_intensity: f32,
+    pub silver_spread: f32,
+    /// Applied to every volume's `CloudVolume::noise_stretch`.
+    pub noise_stretch: Vec3,
+    pub cloud_type: f32,
+    /// Applied to every volume's `CloudVolume::anvil_amount` and
+    /// `anvil_start_height`.
+    pub anvil_amount: f32,
+    pub anvil_start_height: f32,
+    /// Bake parameters; changing any of them triggers a rebake.
+    pub warp_amplitude: f32,
+    pub warp_frequency: u32,
+    pub noise_type: NoiseType,
+    pub octaves: u32,
+    pub fractal_type: FractalType,
+    /// Applied to every volume's `CloudVolume::ground_fog`; switches at the
+    /// end of a transition.
+    pub ground_fog: Option<GroundFog>,
+}
+
+pub const PRESETS: &[CloudPreset] = &[
+    CloudPreset {
+        name: "Cumulus",
+        coverage: 1.0,
+        density_multiplier: 2.0,
+        threshold: 0.2,
+        absorption: 3.0,
+        phase_g: 0.2,
+        silver_intensity: 0.0,
+        silver_spread: 0.2,
+        noise_stretch: Vec3::ONE,
+        cloud_type: 0.5,
+        anvil_amount: 0.0,
+        anvil_start_height: 0.75,
+        warp_amplitude: 0.0,
+        warp_frequency: 2,
+        noise_type: NoiseType::Worley,
+        octaves: 4,
+        fractal_type: FractalType::Standard,
+        ground_fog: None,
+    },
+    CloudPreset {
+        name: "Stratus",
+        coverage: 1.0,
+        density_multiplier: 1.2,
+        threshold: 0.1,
+        absorption: 2.0,
+        phase_g: 0.1,
+        silver_intensity: 0.0,
+        silver_spread: 0.2,
+        noise_stretch: Vec3::new(4.0, 1.0, 4.0),
+        cloud_type: 0.0,
+        anvil_amount: 0.0,
+        anvil_start_height: 0.75,
+        warp_amplitude: 0.0,
+        warp_frequency: 2,
+        noise_type: NoiseType::Worley,
+        octaves: 4,
+        fractal_type: FractalType::Standard,
+        ground_fog: None,
+    },
+    CloudPreset {
+        name: "Warped Cumulus",
+        coverage: 1.0,
+        density_multiplier: 2.0,
+        threshold: 0.2,
+        absorption: 3.0,
+        phase_g: 0.2,
+        silver_intensity: 0.0,
+        silver_spread: 0.2,
+        noise_stretch: Vec3::ONE,
+        cloud_type: 0.5,
+        anvil_amount: 0.0,
+        anvil_start_height: 0.75,
+        warp_amplitude: 0.15,
+        warp_frequency: 2,
+        noise_type: NoiseType::Worley,
+        octaves: 4,
+        fractal_type: FractalType::Standard,
+        ground_fog: None,
+    },
+    CloudPreset {
+        name: "Cirrus",
+        coverage: 1.0,
+        density_multiplier: 0.8,
+        threshold: 0.55,
+        absorption: 1.5,
+        phase_g: 0.4,
+        silver_intensity: 0.0,
+        silver_spread: 0.2,
+        noise_stretch: Vec3::new(8.0, 1.0, 2.0),
+        cloud_type: 0.0,
+        anvil_amount: 0.0,
+        anvil_start_height: 0.75,
+        warp_amplitude: 0.1,
+        warp_frequency: 2,
+        noise_type: NoiseType::Perlin,
+        octaves: 4,
+        fractal_type: FractalType::Ridged,
+        ground_fog: None,
+    },
+    CloudPreset {
+        name: "Storm",
+        coverage: 1.0,
+        density_multiplier: 3.5,
+        threshold: 0.15,
+        absorption: 5.0,
+        phase_g: 0.3,
+        silver_intensity: 0.8,
+        silver_spread: 0.1,
+        noise_stretch: Vec3::new(1.5, 1.0, 1.5),
+        cloud_type: 1.0,
+        anvil_amount: 0.6,
+        anvil_start_height: 0.7,
+        warp_amplitude: 0.1,
+        warp_frequency: 2,
+        noise_type: NoiseType::Worley,
+        octaves: 4,
+        fractal_type: FractalType::Standard,
+        ground_fog: None,
+    },
+    CloudPreset {
+        name: "Sunset",
+        coverage: 1.0,
+        density_multiplier: 1.8,
+        threshold: 0.25,
+        absorption: 2.5,
+        phase_g: 0.5,
+        silver_intensity: 1.5,
+        silver_spread: 0.3,
+        noise_stretch: Vec3::new(2.0, 1.0, 2.0),
+        cloud_type: 0.5,
+        anvil_amount: 0.0,
+        anvil_start_height: 0.75,
+        warp_amplitude: 0.0,
+        warp_frequency: 2,
+        noise_type: NoiseType::Worley,
+        octaves: 4,
+        fractal_type: FractalType::Standard,
+        ground_fog: None,
+    },
+    CloudPreset {
+        name: "Ground Fog",
+        coverage: 1.0,
+        density_multiplier: 1.5,
+        threshold: 0.0,
+        absorption: 1.0,
+        phase_g: 0.1,
+        silver_intensity: 0.0,
+        silver_spread: 0.2,
+        noise_stretch: Vec3::new(2.0, 1.0, 2.0),
+        cloud_type: 0.0,
+        anvil_amount: 0.0,
+        anvil_start_height: 0.75,
+        warp_amplitude: 0.0,
+        warp_frequency: 2,
+        noise_type: NoiseType::Perlin,
+        octaves: 4,
+        fractal_type: FractalType::Billow,
+        ground_fog: Some(GroundFog::DEFAULT),
+    },
+];
+
+impl CloudPreset {
+    fn look(&self) -> PresetLook {
+        PresetLook {
+            coverage: self.coverage,
+            density_multiplier: self.density_multiplier,
+            threshold: self.threshold,
+            absorption: self.absorption,
+            phase_g: self.phase_g,
+            silver_intensity: self.silver_intensity,
+            silver_spread: self.silver_spread,
+        }
+    }
+
+    pub fn apply<'a>(&self, settings: &mut CloudSettings, volumes: impl IntoIterator<Item = Mut<'a, CloudVolume>>) {
+        self.look().write(settings);
+        self.apply_bake(settings);
+        for mut volume in volumes {
+            volume.noise_stretch = self.noise_stretch;
+            volume.cloud_type = self.cloud_type;
+            volume.anvil_amount = self.anvil_amount;
+            volume.anvil_start_height = self.anvil_start_height;
+            volume.ground_fog = self.ground_fog;
+        }
+    }
+
+    /// Sets the bake parameters, requesting a rebake only if they differ.
+    fn apply_bake(&self, settings: &mut CloudSettings) {
+        let bake = (
+            self.warp_amplitude,
+            self.warp_frequency,
+            self.noise_type,
+            self.octaves,
+            self.fractal_type,
+        );
+        let current = (
+            settings.warp_amplitude,
+            settings.warp_frequency,
+            settings.noise_type,
+            settings.octaves,
+            settings.fractal_type,
+        );
+        if bake != current {
+            settings.warp_amplitude = self.warp_amplitude;
+            settings.warp_frequency = self.warp_frequency;
+            settings.noise_type = self.noise_type;
+            settings.octaves = self.octaves;
+            settings.fractal_type = self.fractal_type;
+            settings.needs_rebuild = true;
+        }
+    }
+}
+
+fn preset_transition_system(
+    time: Res<Time>,
+    mut transition: ResMut<PresetTransition>,
+    mut settings: ResMut<CloudSettings>,
+    mut volumes: Query<(Entity, &mut CloudVolume)>,
+) {
+    let Some(active) = transition.active.as_mut() else {
+        return;
+    };
+    active.elapsed += time.delta_secs();
+    let t = (active.elapsed / active.duration.max(1e-3)).min(1.0);
+    if t >= 1.0 {
+        // Exact end values, including volumes spawned meanwhile.
+        let target = active.target;
+        transition.active = None;
+        target.apply(&mut settings, volumes.iter_mut().map(|(_, volume)| volume));
+        return;
+    }
+
+    let t = t * t * (3.0 - 2.0 * t);
+    active.from.lerp(&active.target.look(), t).write(&mut settings);
+    let target = active.target;
+    for (entity, from) in &active.volumes {
+        if let Ok((_, mut volume)) = volumes.get_mut(*entity) {
+            volume.noise_stretch = from.noise_stretch.lerp(target.noise_stretch, t);
+            volume.cloud_type = from.cloud_type.lerp(target.cloud_type, t);
+            volume.anvil_amount = from.anvil_amount.lerp(target.anvil_amount, t);
+            volume.anvil_start_height = from.anvil_start_height.lerp(target.anvil_start_height, t);
+        }
+    }
+}
+
+fn presets_ui_system(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<CloudSettings>,
+    mut transition_settings: ResMut<PresetTransitionSettings>,
+    mut transition: ResMut<PresetTransition>,
+    mut thumbnails: ResMut<PresetThumbnails>,
+    mut volumes: Query<(Entity, &mut CloudVolume)>,
+) {
+    let textures: Vec<_> = (0..PRESETS.len())
+        .map(|index| thumbnails.get(index).cloned().map(|image| contexts.add_image(image)))
+        .collect();
+    egui::Window::new("Presets")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("preset_picker").show(ui, |ui| {
+                for (index, preset) in PRESETS.iter().enumerate() {
+                    let clicked = ui
+                        .vertical_centered(|ui| {
+                            let clicked = match textures[index] {
+                                Some(texture) => ui
+                                    .add(egui::ImageButton::new(egui::load::SizedTexture::new(
+                                        texture,
+                                        [PICKER_THUMBNAIL_SIZE; 2],
+                                    )))
+                                    .on_hover_text(preset.name)
+                                    .clicked(),
+                                None => ui
+                                    .add_sized([PICKER_THUMBNAIL_SIZE; 2], egui::Button::new("..."))
+                                    .clicked(),
+                            };
+                            ui.label(preset.name);
+                            clicked
+                        })
+                        .inner;
+                    if (index + 1) % PICKER_COLUMNS == 0 {
+                        ui.end_row();
+                    }
+                    if !clicked {
+                        continue;
+                    }
+                    if transition_settings.enabled {
+                        let starts = volumes.iter().map(|(entity, volume)| (entity, volume.clone()));
+                        transition.start(preset, transition_settings.duration, &mut settings, starts);
+                    } else {
+                        transition.active = None;
+                        preset.apply(&mut settings, volumes.iter_mut().map(|(_, volume)| volume));
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut transition_settings.enabled, "Transition");
+                ui.add_enabled(
+                    transition_settings.enabled,
+                    egui::DragValue::new(&mut transition_settings.duration)
+                        .speed(0.1)
+                        .range(0.1..=30.0)
+                        .suffix(" s"),
+                );
+                if transition.is_active() {
+                    ui.label("blending...");
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Regenerate Thumbnails").clicked() {
+                    thumbnails.regenerate();
+                }
+                let pending = thumbnails.pending();
+                if pending > 0 {
+                    ui.label(format!("rendering {}...", pending));
+                }
+            });
+        });
+}
@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::CloudUiSet;
+
+/// A white diffuse sphere next to the default cloud, for judging cloud
+/// brightness against lit geometry under the same sun.
+pub struct ReferenceSpherePlugin;
+
+impl Plugin for ReferenceSpherePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReferenceSphereSettings>().add_systems(
+            Update,
+            (reference_ui_system.in_set(CloudUiSet), reference_sphere_system).chain(),
+        );
+    }
+}
+
+#[derive(Resource, Default)]
+struct ReferenceSphereSettings {
+    enabled: bool,
+}
+
+#[derive(Component)]
+struct ReferenceSphere;
+
+fn reference_sphere_system(
+    mut commands: Commands,
+    reference: Res<ReferenceSphereSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    spheres: Query<Entity, With<ReferenceSphere>>,
+) {
+    if !reference.is_changed() {
+        return;
+    }
+    match (reference.enabled, spheres.is_empty()) {
+        (true, true) => {
+            commands.spawn((
+                Mesh3d(meshes.add(Sphere::new(0.6).mesh().uv(32, 18))),
+                MeshMaterial3d(materials.add(StandardMaterial {
+                    base_color: Color::WHITE,
+                    perceptual_roughness: 1.0,
+                    reflectance: 0.0,
+                    ..default()
+                })),
+                Transform::from_xyz(2.2, 1.0, 0.0),
+                ReferenceSphere,
+            ));
+        }
+        (false, false) => {
+            for entity in &spheres {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn reference_ui_system(mut contexts: EguiContexts, mut reference: ResMut<ReferenceSphereSettings>) {
+    egui::Window::new("Reference Sphere")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("A white diffuse sphere under the same sun as the clouds.");
+            ui.label("Compare with \"Physical\" next to the sun intensity enabled.");
+            ui.checkbox(&mut reference.enabled, "Show Sphere");
+        });
+}
@@ -0,0 +1,301 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::{prelude::*, render::camera::CameraUpdateSystem};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    accumulation::CloudAccumulation, material_sync::CloudSyncSet, view_overrides::CloudViewOverrides, CloudMaterial,
+    CloudMaterialUniform, CloudUiSet, CloudVolume, OrbitCamera,
+};
+
+/// Progressive refinement of the main view while nothing changes.
+///
+/// Once the camera and every volume's material have held still for `delay`
+/// seconds, refinement runs through three stages on the main camera, one
+/// `stage_time` apart:
+///
+/// 1. `CloudViewOverrides::step_scale` doubles every stage up to
+///    `max_scale`, multiplying the main and light steps of every volume. The
+///    march weighs each step by its length, so the image sharpens without
+///    getting brighter or darker.
+/// 2. With `multi_scatter`, `CloudViewOverrides::multi_scatter` fades the
+///    extra octaves of multiple scattering into single-scatter volumes over
+///    one stage. Fading rather than switching keeps the brighter cores from
+///    popping in.
+/// 3. A `CloudAccumulation` averages the frames, each with its rays and step
+///    offsets jittered, until `max_samples` are in. The average weighs every
+///    frame the same, so it keeps the brightness of the last stage while the
+///    noise and the aliased edges settle.
+///
+/// Anything moving drops the view back to the interactive counts the same
+/// frame. Stillness is judged on the composed materials, so slider drags,
+/// moving volumes, the time of day and the weather map all count. The wind
+/// scroll only stops the accumulation: frames of drifting clouds cannot be
+/// averaged without reprojection, so the view then stays at the second
+/// stage, drawn fresh each frame.
+pub struct ProgressiveRefinementPlugin;
+
+impl Plugin for ProgressiveRefinementPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ProgressiveRefinement>()
+            .add_systems(Update, refine_ui_system.in_set(CloudUiSet))
+            // After this frame's materials and camera, so that the frame
+            // something changes in is neither refined nor averaged in.
+            .add_systems(
+                PostUpdate,
+                refine_system.after(CloudSyncSet::Write).after(CameraUpdateSystem),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct ProgressiveRefinement {
+    pub enabled: bool,
+    /// Seconds of stillness before refining starts.
+    pub delay: f32,
+    /// Seconds each stage lasts.
+    pub stage_time: f32,
+    /// Largest multiple of the interactive step counts, up to `MAX_SCALE`.
+    pub max_scale: u32,
+    /// Fade multiple scattering into single-scatter volumes once the steps
+    /// are refined.
+    pub multi_scatter: bool,
+    /// Frames averaged before the view counts as converged, up to
+    /// `MAX_SAMPLES`.
+    pub max_samples: u32,
+    /// The "Refining..." label in the corner.
+    pub show_indicator: bool,
+    still_for: f32,
+    scale: u32,
+    scatter: f32,
+    samples: u32,
+    /// The wind scroll moved this frame.
+    drifting: bool,
+    view: Option<(Mat4, Mat4)>,
+    materials: HashMap<AssetId<CloudMaterial>, CloudMaterialUniform>,
+}
+
+impl Default for ProgressiveRefinement {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            delay: 0.3,
+            stage_time: 0.15,
+            max_scale: 8,
+            multi_scatter: true,
+            max_samples: 64,
+            show_indicator: true,
+            still_for: 0.0,
+            scale: 1,
+            scatter: 0.0,
+            samples: 0,
+            drifting: false,
+            view: None,
+            materials: HashMap::new(),
+        }
+    }
+}
+
+impl ProgressiveRefinement {
+    /// Cap on `max_scale`. Past it the shader's bound of 256 steps takes
+    /// over for most settings, and a frame costs too much to stay
+    /// interactive while the next move is awaited.
+    pub const MAX_SCALE: u32 = 8;
+    /// Cap on `max_samples`.
+    pub const MAX_SAMPLES: u32 = 1024;
+
+    /// Whether the view is as refined as it gets, or refinement is off and
+    /// there is nothing to wait for. With the wind blowing that is the last
+    /// stage before the accumulation.
+    pub fn converged(&self) -> bool {
+        !self.enabled
+            || (self.scale >= self.capped_max()
+                && (!self.multi_scatter || self.scatter >= 1.0)
+                && (self.drifting || self.samples >= self.capped_samples()))
+    }
+
+    /// Frames averaged into the main view so far.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    fn capped_max(&self) -> u32 {
+        self.max_scale.clamp(1, Self::MAX_SCALE)
+    }
+
+    fn capped_samples(&self) -> u32 {
+        self.max_samples.clamp(1, Self::MAX_SAMPLES)
+    }
+}
+
+/// A material's uniform without the lanes that change with nothing moving:
+/// the wind scroll.
+fn still_key(data: &CloudMaterialUniform) -> CloudMaterialUniform {
+    let mut key = data.clone();
+    key.wind_offset = Vec3::ZERO.extend(key.wind_offset.w);
+    key.wind_offset_upper = Vec3::ZERO.extend(key.wind_offset_upper.w);
+    key
+}
+
+type RefinedCameras<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Camera,
+        &'static GlobalTransform,
+        Option<&'static mut CloudViewOverrides>,
+        Option<&'static CloudAccumulation>,
+    ),
+    With<OrbitCamera>,
+>;
+
+fn refine_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut refine: ResMut<ProgressiveRefinement>,
+    materials: Res<Assets<CloudMaterial>>,
+    volumes: Query<&MeshMaterial3d<CloudMaterial>, With<CloudVolume>>,
+    mut cameras: RefinedCameras,
+) {
+    let Ok((entity, camera, transform, overrides, accumulation)) = cameras.get_single_mut() else {
+        return;
+    };
+    let refine = refine.as_mut();
+
+    let view = (transform.compute_matrix(), camera.clip_from_view());
+    let mut moved = refine.view.replace(view) != Some(view);
+    let mut drifting = false;
+    // Volumes can share a material, so count handles rather than volumes.
+    let mut seen = HashSet::new();
+    for handle in &volumes {
+        let Some(material) = materials.get(&handle.0) else {
+            continue;
+        };
+        seen.insert(handle.id());
+        let previous = refine.materials.insert(handle.id(), material.data.clone());
+        match previous {
+            Some(previous) if previous == material.data => {}
+            Some(previous) if still_key(&previous) == still_key(&material.data) => drifting = true,
+            _ => moved = true,
+        }
+    }
+    // Removed volumes change the image too.
+    if refine.materials.len() != seen.len() {
+        refine.materials.retain(|id, _| seen.contains(id));
+        moved = true;
+    }
+    refine.drifting = drifting;
+
+    if moved || !refine.enabled {
+        refine.still_for = 0.0;
+    } else {
+        refine.still_for += time.delta_secs();
+    }
+    let stage_time = refine.stage_time.max(1e-3);
+    let elapsed = refine.still_for - refine.delay;
+    let stages = (elapsed / stage_time).floor();
+    refine.scale = if stages < 0.0 {
+        1
+    } else {
+        2u32.saturating_pow(stages as u32 + 1).min(refine.capped_max())
+    };
+    // Each scale is held for a stage before the scattering fades in.
+    let scatter_start = refine.capped_max().ilog2() as f32 * stage_time;
+    let mut accumulate_start = scatter_start;
+    refine.scatter = 0.0;
+    if refine.multi_scatter {
+        refine.scatter = ((elapsed - scatter_start) / stage_time).clamp(0.0, 1.0);
+        accumulate_start += stage_time;
+    }
+
+    let next_accumulation = if elapsed >= accumulate_start && !refine.drifting {
+        // Once full, the average is shown without adding to it.
+        let hold = refine.samples >= refine.capped_samples();
+        let sample = refine.samples;
+        if !hold {
+            refine.samples += 1;
+        }
+        Some(CloudAccumulation { sample, hold })
+    } else {
+        refine.samples = 0;
+        None
+    };
+    match (accumulation, next_accumulation) {
+        (Some(accumulation), Some(next)) if *accumulation == next => {}
+        (_, Some(next)) => {
+            commands.entity(entity).insert(next);
+        }
+        (Some(_), None) => {
+            commands.entity(entity).remove::<CloudAccumulation>();
+        }
+        (None, None) => {}
+    }
+
+    let step_scale = (refine.scale > 1).then_some(refine.scale);
+    let multi_scatter = (refine.scatter > 0.0).then_some(refine.scatter);
+    match overrides {
+        Some(mut overrides) => {
+            if overrides.step_scale != step_scale {
+                overrides.step_scale = step_scale;
+            }
+            if overrides.multi_scatter != multi_scatter {
+                overrides.multi_scatter = multi_scatter;
+            }
+        }
+        None if step_scale.is_some() || multi_scatter.is_some() => {
+            commands.entity(entity).insert(CloudViewOverrides {
+                step_scale,
+                multi_scatter,
+                ..default()
+            });
+        }
+        None => {}
+    }
+}
+
+fn refine_ui_system(mut contexts: EguiContexts, mut refine: ResMut<ProgressiveRefinement>) {
+    let ctx = contexts.ctx_mut();
+    egui::Window::new("Progressive Refinement")
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut refine.enabled, "Enabled").on_hover_text(
+                "Refine the main view while nothing moves: more march steps, then multiple scattering, then \
+                 jittered frames averaged together. Pause the wind to let the frames average",
+            );
+            ui.add(egui::Slider::new(&mut refine.delay, 0.0..=2.0).text("Delay (s)"));
+            ui.add(egui::Slider::new(&mut refine.stage_time, 0.05..=1.0).text("Stage Time (s)"));
+            ui.add(egui::Slider::new(&mut refine.max_scale, 1..=ProgressiveRefinement::MAX_SCALE).text("Max Step Scale"));
+            ui.checkbox(&mut refine.multi_scatter, "Multiple Scattering")
+                .on_hover_text("Fade multiple scattering into single-scatter volumes once the steps are refined");
+            ui.add(
+                egui::Slider::new(&mut refine.max_samples, 1..=ProgressiveRefinement::MAX_SAMPLES)
+                    .logarithmic(true)
+                    .text("Max Samples"),
+            );
+            ui.checkbox(&mut refine.show_indicator, "Show Indicator");
+        });
+
+    if !refine.enabled || !refine.show_indicator || refine.still_for <= refine.delay {
+        return;
+    }
+    let text = if refine.converged() {
+        if refine.samples > 0 {
+            format!("Refined: {} samples", refine.samples)
+        } else {
+            format!("Refined: {}x steps", refine.scale)
+        }
+    } else if refine.samples > 0 {
+        format!("Refining... {} samples", refine.samples)
+    } else if refine.scatter > 0.0 {
+        "Refining... multiple scattering".to_string()
+    } else {
+        format!("Refining... {}x steps", refine.scale)
+    };
+    egui::Area::new(egui::Id::new("refine_indicator"))
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -8.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            ui.label(egui::RichText::new(text).small().weak());
+        });
+}
@@ -0,0 +1,138 @@
+use bevy::{prelude::*, render::camera::RenderTarget};
+
+use crate::{
+    bake_cache::BakeCache,
+    capture::{self, Captured},
+    extension::{CloudExtension, LitCloudMaterial},
+    golden,
+    noise::{BakeProgress, PendingNoise},
+    spawn::{CloudVolumeParams, SpawnCloudExt},
+    CloudBlendMode, CloudSettings, CloudVolume,
+};
+
+/// Frames rendered by every camera before the readback.
+const FRAMES: u32 = 8;
+
+/// The view configurations a host app may render the clouds with.
+const VARIANTS: [(Msaa, bool); 4] = [
+    (Msaa::Off, false),
+    (Msaa::Off, true),
+    (Msaa::Sample4, false),
+    (Msaa::Sample4, true),
+];
+
+/// Headless compatibility run: `--render-matrix`.
+///
+/// Renders the same scene through one camera per MSAA × HDR combination,
+/// with both cloud materials and a non-default blend mode, then reads every
+/// target back. The materials take sample count and target format from the
+/// view's pipeline key, so each camera specializes its own pipelines; a
+/// mismatch is a wgpu validation error, which panics and fails the run.
+pub fn run() -> AppExit {
+    let mut app = golden::headless_app();
+    // Each run starts from a fresh bake rather than whatever is cached.
+    app.world_mut().resource_mut::<BakeCache>().enabled = false;
+    app.init_resource::<MatrixRun>()
+        .add_systems(Startup, matrix_setup)
+        .add_systems(
+            Update,
+            (
+                matrix_system,
+                crate::wind_system,
+                crate::swap_noise_system,
+                crate::update_material_system,
+            )
+                .chain(),
+        );
+    app.run()
+}
+
+#[derive(Resource, Default)]
+struct MatrixRun {
+    frame: u32,
+    targets: Vec<(String, Handle<Image>)>,
+    /// Readbacks still in flight, with their labels.
+    pending: Vec<(Entity, String)>,
+    read_back: bool,
+}
+
+fn matrix_setup(
+    mut commands: Commands,
+    mut run: ResMut<MatrixRun>,
+    settings: Res<CloudSettings>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut lit_materials: ResMut<Assets<LitCloudMaterial>>,
+) {
+    commands.spawn((
+        DirectionalLight {
+            shadows_enabled: true,
+            ..default()
+        },
+        Transform::from_xyz(4.0, 8.0, 4.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+    commands.spawn_cloud(CloudVolumeParams::default().at(Vec3::new(-1.2, 1.0, 0.0)));
+    let mut premultiplied = CloudVolumeParams::default().at(Vec3::new(0.0, 1.0, -1.5));
+    premultiplied.volume.blend = CloudBlendMode::Premultiplied;
+    commands.spawn_cloud(premultiplied);
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::new(2.0, 2.0, 2.0))),
+        MeshMaterial3d(lit_materials.add(CloudExtension::material(&settings))),
+        Transform::from_xyz(1.2, 1.0, 0.0),
+        CloudVolume::default(),
+    ));
+
+    for (order, (msaa, hdr)) in VARIANTS.into_iter().enumerate() {
+        let target = images.add(golden::target_image());
+        commands.spawn((
+            Camera3d::default(),
+            Camera {
+                target: RenderTarget::Image(target.clone()),
+                order: order as isize,
+                hdr,
+                clear_color: ClearColorConfig::Custom(Color::srgb(0.35, 0.5, 0.75)),
+                ..default()
+            },
+            msaa,
+            golden::outside(),
+        ));
+        run.targets.push((format!("msaa {} hdr {}", msaa.samples(), hdr), target));
+    }
+}
+
+fn matrix_system(
+    mut commands: Commands,
+    mut run: ResMut<MatrixRun>,
+    settings: Res<CloudSettings>,
+    bake_progress: Res<BakeProgress>,
+    pending_noise: Res<PendingNoise>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // Count frames only once the real noise is bound.
+    if settings.needs_rebuild || bake_progress.bake.is_some() || pending_noise.image.is_some() {
+        return;
+    }
+    if run.read_back {
+        if run.pending.is_empty() {
+            println!("All {} view configurations rendered", VARIANTS.len());
+            exit.send(AppExit::Success);
+        }
+        return;
+    }
+    run.frame += 1;
+    if run.frame > FRAMES {
+        run.read_back = true;
+        for (label, target) in run.targets.clone() {
+            let readback = capture::read_back(&mut commands, target, golden::SIZE, 4, finish_readback);
+            run.pending.push((readback, label));
+        }
+    }
+}
+
+fn finish_readback(trigger: Trigger<Captured>, mut run: ResMut<MatrixRun>) {
+    let Some(index) = run.pending.iter().position(|(entity, _)| *entity == trigger.entity()) else {
+        return;
+    };
+    let (_, label) = run.pending.swap_remove(index);
+    println!("{}: ok", label);
+}
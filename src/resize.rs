@@ -0,0 +1,62 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+/// Single source of the primary window's render size for every cloud texture
+/// that follows it.
+///
+/// Window-sized targets (currently the portal mask) read `CloudTargetSize`
+/// and reallocate on `CloudTargetsResized` instead of polling the window
+/// themselves. The window is compared every frame rather than trusting
+/// `WindowResized`, which reports logical sizes: dragging the window to a
+/// monitor with another scale factor changes its physical size while the
+/// logical one may stay put. Impostor captures have their own fixed
+/// resolution and are unaffected.
+pub struct CloudResizePlugin;
+
+impl Plugin for CloudResizePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CloudTargetSize>()
+            .add_event::<CloudTargetsResized>()
+            .add_systems(PreUpdate, target_size_system);
+    }
+}
+
+/// Physical size and scale factor of the primary window, at least 1x1.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct CloudTargetSize {
+    pub physical: UVec2,
+    pub scale_factor: f32,
+}
+
+impl Default for CloudTargetSize {
+    fn default() -> Self {
+        Self {
+            physical: UVec2::ONE,
+            scale_factor: 1.0,
+        }
+    }
+}
+
+/// Sent the frame `CloudTargetSize` changes; intermediate textures are stale
+/// from then on and anything accumulated in them should be discarded.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct CloudTargetsResized {
+    pub size: CloudTargetSize,
+}
+
+fn target_size_system(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut size: ResMut<CloudTargetSize>,
+    mut events: EventWriter<CloudTargetsResized>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let current = CloudTargetSize {
+        physical: window.physical_size().max(UVec2::ONE),
+        scale_factor: window.scale_factor(),
+    };
+    if current != *size {
+        *size = current;
+        events.send(CloudTargetsResized { size: current });
+    }
+}
@@ -0,0 +1,154 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::bake_cache::fnv1a;
+
+/// Independent random streams derived from one master seed.
+///
+/// Every feature that draws random numbers takes its generator from here,
+/// under its own `SeedStream`, instead of seeding `ChaCha8Rng` with the
+/// master seed itself. The sub-seed is FNV-1a over the stream's tag and the
+/// master seed, so it doesn't depend on how many numbers other features
+/// drew or in what order they ran, and it is the same on every platform and
+/// toolchain. Adding a feature, or turning one on, leaves the others'
+/// results for a seed unchanged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeedStreams {
+    master: u64,
+}
+
+/// The random consumers. The tags are part of the baked results: renaming
+/// one reshuffles that feature for every seed, reordering the variants
+/// doesn't.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeedStream {
+    /// Feature points of the Worley bake.
+    WorleyPoints,
+    /// Lattice of the domain warp.
+    Warp,
+    /// Permutation table of the Perlin bake.
+    Perlin,
+    /// Permutation table of the cirrus sheet noise.
+    #[cfg(feature = "ui")]
+    CirrusSheet,
+    /// Positions, sizes and jitter of a cloud field.
+    #[cfg(feature = "ui")]
+    FieldLayout,
+    /// Per-volume noise variation of a cloud field.
+    #[cfg(feature = "ui")]
+    FieldVariation,
+}
+
+impl SeedStream {
+    #[cfg(all(test, feature = "ui"))]
+    pub const ALL: [SeedStream; 6] = [
+        SeedStream::WorleyPoints,
+        SeedStream::Warp,
+        SeedStream::Perlin,
+        SeedStream::CirrusSheet,
+        SeedStream::FieldLayout,
+        SeedStream::FieldVariation,
+    ];
+
+    fn tag(self) -> &'static str {
+        match self {
+            SeedStream::WorleyPoints => "worley_points",
+            SeedStream::Warp => "warp",
+            SeedStream::Perlin => "perlin",
+            #[cfg(feature = "ui")]
+            SeedStream::CirrusSheet => "cirrus_sheet",
+            #[cfg(feature = "ui")]
+            SeedStream::FieldLayout => "field_layout",
+            #[cfg(feature = "ui")]
+            SeedStream::FieldVariation => "field_variation",
+        }
+    }
+}
+
+impl SeedStreams {
+    pub fn new(master: u64) -> Self {
+        Self { master }
+    }
+
+    /// The sub-seed of `stream`.
+    pub fn seed(&self, stream: SeedStream) -> u64 {
+        let mut bytes = stream.tag().as_bytes().to_vec();
+        bytes.push(0);
+        bytes.extend_from_slice(&self.master.to_le_bytes());
+        fnv1a(&bytes)
+    }
+
+    /// A generator for `stream`, starting from the beginning every call.
+    pub fn rng(&self, stream: SeedStream) -> ChaCha8Rng {
+        ChaCha8Rng::seed_from_u64(self.seed(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::{
+        noise::{self, NoiseType},
+        CloudSettings,
+    };
+
+    // For a fixed master seed, every stream must get its own sub-seed, and
+    // turning one random consumer of the bake on or off must not change
+    // another one's output.
+
+    /// Sub-seeds of master seed 1, pinned so a change to the derivation, or a
+    /// platform where it differs, fails here before it reshuffles every bake.
+    const PINNED: [(SeedStream, u64); 2] = [
+        (SeedStream::WorleyPoints, 0xa39b_6397_0e4e_f498),
+        (SeedStream::FieldLayout, 0xa481_dfbe_4514_c33b),
+    ];
+
+    #[test]
+    fn sub_seeds_are_pinned() {
+        for (stream, seed) in PINNED {
+            assert_eq!(SeedStreams::new(1).seed(stream), seed, "{stream:?} moved off its pinned sub-seed");
+        }
+    }
+
+    #[test]
+    fn every_stream_has_its_own_sub_seed() {
+        let seeds: Vec<u64> = SeedStream::ALL
+            .iter()
+            .map(|&stream| SeedStreams::new(1).seed(stream))
+            .collect();
+        assert!(seeds.iter().enumerate().all(|(i, seed)| !seeds[..i].contains(seed)));
+    }
+
+    #[test]
+    fn another_master_seed_moves_every_stream() {
+        for stream in SeedStream::ALL {
+            assert_ne!(SeedStreams::new(1).seed(stream), SeedStreams::new(2).seed(stream), "{stream:?}");
+        }
+    }
+
+    #[test]
+    fn a_seed_bakes_the_same_noise_twice() {
+        let settings = warped_perlin();
+        assert!(noise::bake_noise(&settings).0 == noise::bake_noise(&settings).0);
+    }
+
+    #[test]
+    fn the_worley_point_count_leaves_the_perlin_and_warp_streams_alone() {
+        let mut settings = warped_perlin();
+        let (reference, _) = noise::bake_noise(&settings);
+        settings.cell_count = settings.cell_count * 2 + 1;
+        assert!(noise::bake_noise(&settings).0 == reference);
+    }
+
+    /// Warped, so both streams that used to be drawn after the points are covered.
+    fn warped_perlin() -> CloudSettings {
+        CloudSettings {
+            noise_type: NoiseType::Perlin,
+            resolution: UVec3::splat(8),
+            warp_amplitude: 0.3,
+            ..CloudSettings::defaults()
+        }
+    }
+}
@@ -0,0 +1,436 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    field::{CloudField, CloudFieldMember},
+    modulation::ParameterModulator,
+    spawn::{CloudVolumeParams, SpawnCloudExt},
+    time_of_day::{Sun, TimeOfDay},
+    timeline::ParameterTimeline,
+    versioning::{self, FORMAT_VERSION},
+    volume_visibility::CloudHidden,
+    weather_map::{SkyHole, WeatherMap},
+    CloudMaterial, CloudSettings, CloudUiSet, CloudVolume, OrbitCamera, OrbitCameraConfig,
+};
+
+const SESSION_DIR: &str = "sessions";
+const MAX_RECENT: usize = 10;
+
+/// Saves and restores the whole scene (camera, sun, time of day, global
+/// settings, parameter timeline, modulation mappings, weather map and every
+/// volume) as RON. The
+/// painted weather map is written next to the session as a greyscale PNG.
+pub struct SessionPlugin;
+
+impl Plugin for SessionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SessionState>()
+            .add_event::<SaveSession>()
+            .add_event::<LoadSession>()
+            .add_systems(
+                Update,
+                (session_ui_system.in_set(CloudUiSet), save_session_system, load_session_system).chain(),
+            );
+    }
+}
+
+/// Everything needed to reproduce a shot. All fields fall back to defaults,
+/// so files written by older versions keep loading; see `versioning`.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloudSession {
+    /// `versioning::FORMAT_VERSION` the session was written with; 0 before
+    /// versioning.
+    #[serde(default)]
+    pub version: u32,
+    pub camera: CameraSession,
+    pub light: Option<LightSession>,
+    pub time_of_day: TimeOfDay,
+    pub settings: CloudSettings,
+    pub volumes: Vec<VolumeSession>,
+    pub timeline: ParameterTimeline,
+    pub weather_map: Option<WeatherMapSession>,
+    pub modulation: ParameterModulator,
+}
+
+impl Default for CloudSession {
+    fn default() -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            camera: default(),
+            light: None,
+            time_of_day: default(),
+            settings: CloudSettings::defaults(),
+            volumes: Vec::new(),
+            timeline: default(),
+            weather_map: None,
+            modulation: default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct CameraSession {
+    pub transform: Transform,
+    pub center: Vec3,
+    pub distance: f32,
+    pub config: OrbitCameraConfig,
+}
+
+impl Default for CameraSession {
+    fn default() -> Self {
+        Self {
+            transform: Transform::from_xyz(-3.0, 3.0, 6.0),
+            center: Vec3::new(0.0, 1.0, 0.0),
+            distance: 7.0,
+            config: OrbitCameraConfig::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct LightSession {
+    pub transform: Transform,
+    pub color: Color,
+    pub illuminance: f32,
+    pub shadows_enabled: bool,
+}
+
+impl Default for LightSession {
+    fn default() -> Self {
+        let light = DirectionalLight::default();
+        Self {
+            transform: Transform::default(),
+            color: light.color,
+            illuminance: light.illuminance,
+            shadows_enabled: light.shadows_enabled,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct VolumeSession {
+    pub name: Option<String>,
+    pub transform: Transform,
+    pub volume: CloudVolume,
+    pub field_member: bool,
+    pub hidden: bool,
+}
+
+/// Placement and sky holes of the weather map; its painted texels are in
+/// `<session>.weather.png`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct WeatherMapSession {
+    pub enabled: bool,
+    pub center: Vec2,
+    pub half_extent: Vec2,
+    pub holes: Vec<SkyHole>,
+    pub holes_drift: bool,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct SaveSession(pub PathBuf);
+
+#[derive(Event, Clone, Debug)]
+pub struct LoadSession(pub PathBuf);
+
+#[derive(Resource)]
+pub struct SessionState {
+    pub name: String,
+    pub recent: Vec<PathBuf>,
+    pub status: Option<String>,
+}
+
+impl Default for SessionState {
+    fn default() -> Self {
+        Self {
+            name: "session".to_string(),
+            recent: recent_sessions(),
+            status: None,
+        }
+    }
+}
+
+impl SessionState {
+    pub fn path_for(name: &str) -> PathBuf {
+        Path::new(SESSION_DIR).join(format!("{}.ron", name))
+    }
+
+    /// The weather map PNG saved alongside a session file.
+    pub fn weather_map_path(session: &Path) -> PathBuf {
+        session.with_extension("weather.png")
+    }
+}
+
+/// Session files in `SESSION_DIR`, most recently modified first.
+fn recent_sessions() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(SESSION_DIR) else {
+        return Vec::new();
+    };
+    let mut files: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .filter_map(|path| {
+            let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok()?;
+            Some((modified, path))
+        })
+        .collect();
+    files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    files.into_iter().take(MAX_RECENT).map(|(_, path)| path).collect()
+}
+
+pub fn write_session(path: &Path, session: &CloudSession) -> Result<(), String> {
+    let text = versioning::to_ron(session)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    fs::write(path, text).map_err(|err| err.to_string())
+}
+
+/// Reads a session written by any version so far, migrated to the current
+/// format.
+pub fn read_session(path: &Path) -> Result<CloudSession, String> {
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let (session, warnings) = versioning::from_ron(&text)?;
+    for warning in warnings {
+        warn!("{}: {}", path.display(), warning);
+    }
+    Ok(session)
+}
+
+/// Everything a session is captured from, shared by explicit saves and the
+/// autosave.
+#[derive(SystemParam)]
+#[allow(clippy::type_complexity)]
+pub struct SessionSource<'w, 's> {
+    settings: Res<'w, CloudSettings>,
+    time_of_day: Res<'w, TimeOfDay>,
+    timeline: Res<'w, ParameterTimeline>,
+    modulator: Res<'w, ParameterModulator>,
+    weather_map: Res<'w, WeatherMap>,
+    camera: Query<'w, 's, (&'static Transform, &'static OrbitCamera, &'static OrbitCameraConfig)>,
+    light: Query<'w, 's, (&'static Transform, &'static DirectionalLight), With<Sun>>,
+    volumes: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static CloudVolume,
+            Option<&'static Name>,
+            Has<CloudFieldMember>,
+            Has<CloudHidden>,
+        ),
+    >,
+}
+
+impl SessionSource<'_, '_> {
+    pub fn capture(&self) -> CloudSession {
+        let mut session = CloudSession {
+            light: self.light.get_single().ok().map(|(transform, light)| LightSession {
+                transform: *transform,
+                color: light.color,
+                illuminance: light.illuminance,
+                shadows_enabled: light.shadows_enabled,
+            }),
+            time_of_day: self.time_of_day.clone(),
+            // Without the modulation, which the inputs recreate.
+            settings: self.modulator.base_settings(&self.settings),
+            timeline: self.timeline.clone(),
+            modulation: self.modulator.clone(),
+            weather_map: Some(WeatherMapSession {
+                enabled: self.weather_map.enabled,
+                center: self.weather_map.center,
+                half_extent: self.weather_map.half_extent,
+                holes: self.weather_map.holes.clone(),
+                holes_drift: self.weather_map.holes_drift,
+            }),
+            volumes: self
+                .volumes
+                .iter()
+                .map(|(transform, volume, name, field_member, hidden)| VolumeSession {
+                    name: name.map(|name| name.to_string()),
+                    transform: *transform,
+                    volume: volume.clone(),
+                    field_member,
+                    hidden,
+                })
+                .collect(),
+            ..default()
+        };
+        if let Ok((transform, orbit, config)) = self.camera.get_single() {
+            session.camera = CameraSession {
+                transform: *transform,
+                center: orbit.center,
+                distance: orbit.distance,
+                config: config.clone(),
+            };
+        }
+        session
+    }
+
+    /// Writes the session and its weather map PNG.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        write_session(path, &self.capture())
+            .and_then(|()| self.weather_map.save_png(&SessionState::weather_map_path(path)))
+    }
+}
+
+fn save_session_system(mut events: EventReader<SaveSession>, mut state: ResMut<SessionState>, source: SessionSource) {
+    for SaveSession(path) in events.read() {
+        state.status = Some(match source.save(path) {
+            Ok(()) => format!("Saved {}", path.display()),
+            Err(err) => {
+                error!("Failed to save session {}: {}", path.display(), err);
+                format!("Save failed: {}", err)
+            }
+        });
+        state.recent = recent_sessions();
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn load_session_system(
+    mut commands: Commands,
+    mut events: EventReader<LoadSession>,
+    mut state: ResMut<SessionState>,
+    mut settings: ResMut<CloudSettings>,
+    mut time_of_day: ResMut<TimeOfDay>,
+    mut timeline: ResMut<ParameterTimeline>,
+    mut modulator: ResMut<ParameterModulator>,
+    mut field: ResMut<CloudField>,
+    mut weather_map: ResMut<WeatherMap>,
+    mut materials: ResMut<Assets<CloudMaterial>>,
+    volumes: Query<(Entity, &MeshMaterial3d<CloudMaterial>), With<CloudVolume>>,
+    mut camera: Query<(&mut Transform, &mut OrbitCamera, &mut OrbitCameraConfig)>,
+    mut light: Query<(&mut Transform, &mut DirectionalLight), (With<Sun>, Without<OrbitCamera>)>,
+) {
+    let Some(LoadSession(path)) = events.read().last() else {
+        return;
+    };
+    let session = match read_session(path) {
+        Ok(session) => session,
+        Err(err) => {
+            error!("Failed to load session {}: {}", path.display(), err);
+            state.status = Some(format!("Load failed: {}", err));
+            return;
+        }
+    };
+
+    let noise_handle = settings.noise_handle.clone();
+    let profile_handle = settings.profile_handle.clone();
+    let wind_offsets = (settings.wind_offset, settings.wind_offset_upper);
+    *settings = session.settings;
+    settings.noise_handle = noise_handle;
+    settings.profile_handle = profile_handle;
+    (settings.wind_offset, settings.wind_offset_upper) = wind_offsets;
+    settings.needs_rebuild = true;
+    *time_of_day = session.time_of_day;
+    *timeline = session.timeline;
+    modulator.load(session.modulation);
+
+    // Sessions from before the weather map leave it off.
+    let saved_map = session.weather_map.unwrap_or_default();
+    weather_map.enabled = saved_map.enabled;
+    if saved_map.half_extent.min_element() > 0.0 {
+        weather_map.center = saved_map.center;
+        weather_map.half_extent = saved_map.half_extent;
+    }
+    weather_map.holes = saved_map.holes;
+    weather_map.holes_drift = saved_map.holes_drift;
+    let map_path = SessionState::weather_map_path(path);
+    if map_path.exists() {
+        if let Err(err) = weather_map.load_png(&map_path) {
+            warn!("Failed to load weather map {}: {}", map_path.display(), err);
+        }
+    }
+
+    if let Ok((mut transform, mut orbit, mut config)) = camera.get_single_mut() {
+        *transform = session.camera.transform;
+        orbit.center = session.camera.center;
+        orbit.distance = session.camera.distance;
+        *config = session.camera.config;
+        orbit.set_rotation(transform.rotation);
+    }
+    if let (Some(saved), Ok((mut transform, mut light))) = (session.light, light.get_single_mut()) {
+        *transform = saved.transform;
+        light.color = saved.color;
+        light.illuminance = saved.illuminance;
+        light.shadows_enabled = saved.shadows_enabled;
+    }
+
+    for (entity, material) in &volumes {
+        materials.remove(&material.0);
+        commands.entity(entity).despawn_recursive();
+    }
+    field.active = false;
+    for saved in session.volumes {
+        let mut entity = commands.spawn_cloud(CloudVolumeParams {
+            transform: saved.transform,
+            volume: saved.volume,
+        });
+        if let Some(name) = saved.name {
+            entity.insert(Name::new(name));
+        }
+        if saved.field_member {
+            entity.insert(CloudFieldMember);
+            field.active = true;
+        }
+        if saved.hidden {
+            entity.insert(CloudHidden);
+        }
+    }
+
+    state.status = Some(format!("Loaded {}", path.display()));
+    state.recent = recent_sessions();
+}
+
+fn session_ui_system(
+    mut contexts: EguiContexts,
+    mut state: ResMut<SessionState>,
+    mut save: EventWriter<SaveSession>,
+    mut load: EventWriter<LoadSession>,
+) {
+    egui::Window::new("Session")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Name");
+                ui.text_edit_singleline(&mut state.name);
+            });
+            ui.horizontal(|ui| {
+                let path = SessionState::path_for(&state.name);
+                if ui.button("Save session").clicked() {
+                    save.send(SaveSession(path.clone()));
+                }
+                if ui.button("Load session").clicked() {
+                    load.send(LoadSession(path));
+                }
+            });
+            if let Some(status) = &state.status {
+                ui.label(status);
+            }
+
+            ui.separator();
+            ui.label("Recent sessions");
+            for path in &state.recent {
+                let label = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.display().to_string());
+                if ui.button(label).clicked() {
+                    load.send(LoadSession(path.clone()));
+                }
+            }
+        });
+}
@@ -0,0 +1,309 @@
+//! The cloud shader ships inside the binary; `--shader <asset path>` swaps in
+//! a file from the assets folder instead, which hot-reloads with the
+//! `hot_reload` feature. Load and compile errors are shown in a window.
+//!
+//! The shader is split into import modules: `intersection`, `density`,
+//! `lighting`, `compositing`, `debug` and the per-view `view`, all under
+//! `bevy_clouds::`. Sampling
+//! the density stays in the main shader, the only place the override may be
+//! imported conditionally.
+//! Optional features are compiled in by shader defs from `CloudMaterialKey`,
+//! and the common variants are built at startup so that toggling one does not
+//! stall a frame.
+//!
+//! Density evaluation lives in the `bevy_clouds::density` module. Setting
+//! `DensityOverride` to a shader that declares
+//! `#define_import_path bevy_clouds::density_override` and defines
+//! `fn sample_density(p: vec3<f32>) -> f32`, with `p` in world space,
+//! replaces it for every cloud material, keeping the raymarch and lighting.
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use bevy::{
+    asset::{load_internal_asset, AssetLoadFailedEvent, AssetPath},
+    prelude::*,
+    render::{
+        render_resource::{CachedPipelineState, PipelineCache, PipelineDescriptor},
+        view::NoFrustumCulling,
+        Render, RenderApp, RenderSet,
+    },
+};
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{CloudLightingMode, CloudMaterial, CloudSettings};
+#[cfg(feature = "ui")]
+use crate::CloudUiSet;
+
+/// The copy of `assets/shaders/cloud_shader.wgsl` compiled into the binary.
+pub const CLOUD_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(0x5c1f_8a52_93d4_4e0b_a7c6_2f1e_9b30_d841);
+/// Import modules shared by the cloud shader and density overrides.
+const BINDINGS_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(0x2b7e_4c19_d06a_4f53_8e21_c94a_17f6_0b3d);
+const DENSITY_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(0x91d3_6f0e_2a84_4b7c_b5e9_03c2_de58_7a16);
+const COMMON_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(0x3fa8_0d6c_7b21_49e5_8c03_f61b_a29d_54e7);
+const INTERSECTION_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(0x7d42_e9a1_0c5b_4f86_93e7_5a2c_b81f_6d09);
+const LIGHTING_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(0x1e93_c57a_4b0d_42f8_a6e1_d03f_9c28_75b4);
+const COMPOSITING_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(0xc8b5_2f47_9e6a_4130_8d2c_6b7f_e0a9_1d53);
+const DEBUG_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(0x54f1_a80d_6c3e_4b29_9a75_e2d8_0f6b_c317);
+const VIEW_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(0xa6d0_3b8e_51f7_4c92_8e4d_07b9_c2a1_5f68);
+
+/// Example override shipped in the assets folder.
+#[cfg(feature = "ui")]
+const EXAMPLE_DENSITY_OVERRIDE: &str = "shaders/density_spheres.wgsl";
+
+static SHADER_OVERRIDE: OnceLock<String> = OnceLock::new();
+
+/// Reads `--shader <asset path>`. Has to run before the app is built, since
+/// the material pipeline asks for its shader once.
+#[cfg(feature = "ui")]
+pub fn parse_args(args: &[String]) {
+    if let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--shader")
+        .and_then(|index| args.get(index + 1))
+    {
+        let _ = SHADER_OVERRIDE.set(path.clone());
+    }
+}
+
+/// Shader for `CloudMaterial`.
+pub fn fragment_shader() -> bevy::render::render_resource::ShaderRef {
+    match SHADER_OVERRIDE.get() {
+        Some(path) => path.as_str().into(),
+        None => CLOUD_SHADER_HANDLE.into(),
+    }
+}
+
+pub struct CloudShaderPlugin;
+
+impl Plugin for CloudShaderPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(app, BINDINGS_SHADER_HANDLE, "../assets/shaders/cloud_bindings.wgsl", Shader::from_wgsl);
+        load_internal_asset!(app, COMMON_SHADER_HANDLE, "../assets/shaders/cloud_common.wgsl", Shader::from_wgsl);
+        load_internal_asset!(
+            app,
+            INTERSECTION_SHADER_HANDLE,
+            "../assets/shaders/cloud_intersection.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(app, DENSITY_SHADER_HANDLE, "../assets/shaders/cloud_density.wgsl", Shader::from_wgsl);
+        load_internal_asset!(app, LIGHTING_SHADER_HANDLE, "../assets/shaders/cloud_lighting.wgsl", Shader::from_wgsl);
+        load_internal_asset!(
+            app,
+            COMPOSITING_SHADER_HANDLE,
+            "../assets/shaders/cloud_compositing.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(app, DEBUG_SHADER_HANDLE, "../assets/shaders/cloud_debug.wgsl", Shader::from_wgsl);
+        load_internal_asset!(app, VIEW_SHADER_HANDLE, "../assets/shaders/cloud_view.wgsl", Shader::from_wgsl);
+        load_internal_asset!(app, CLOUD_SHADER_HANDLE, "../assets/shaders/cloud_shader.wgsl", Shader::from_wgsl);
+
+        let errors = ShaderErrors::default();
+        app.insert_resource(errors.clone())
+            .init_resource::<DensityOverride>()
+            .add_systems(Startup, spawn_warm_up_system)
+            .add_systems(Update, (despawn_warm_up_system, shader_load_error_system));
+        #[cfg(feature = "ui")]
+        app.add_systems(
+            Update,
+            (shader_error_ui_system, shader_ui_system)
+                .chain()
+                .in_set(CloudUiSet)
+                .after(shader_load_error_system),
+        );
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(errors)
+                .add_systems(Render, pipeline_error_system.in_set(RenderSet::Cleanup));
+        }
+    }
+}
+
+/// Replacement density function for all cloud materials. Only one override
+/// can be active at a time, since they share an import path.
+#[derive(Resource, Clone, Default)]
+pub struct DensityOverride {
+    pub shader: Option<Handle<Shader>>,
+}
+
+/// Frames the warm-up proxies stay, long enough for their pipelines to be
+/// specialized and queued for compiling.
+const WARM_UP_FRAMES: u32 = 10;
+
+/// Invisible cube drawn only to build one pipeline variant. Not a
+/// `CloudVolume`, so the material sync leaves it alone.
+#[derive(Component)]
+struct WarmUpProxy {
+    frames_left: u32,
+}
+
+/// Spawns a proxy per common variant: each lighting mode with and without the
+/// light march, and each remaining feature on its own. The proxies have zero
+/// opacity, so their fragments discard immediately.
+fn spawn_warm_up_system(
+    mut commands: Commands,
+    settings: Res<CloudSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<CloudMaterial>>,
+) {
+    let mesh = meshes.add(Cuboid::from_length(0.01));
+    let mut base = CloudMaterial::new(&settings);
+    base.data.opacity = 0.0;
+    base.data.profile.z = 0.0;
+    base.data.weather_map = Vec4::ZERO;
+    base.data.lighting.y = 0.0;
+    base.data.sampling.x = 0.0;
+    base.data.sampling.z = 0.0;
+
+    let mut variants = Vec::new();
+    for lighting in [CloudLightingMode::Unlit, CloudLightingMode::SingleScatter, CloudLightingMode::MultiScatter] {
+        for light_march in [false, true] {
+            let mut material = base.clone();
+            material.lighting = lighting;
+            if light_march {
+                material.data.lighting.y = 1.0;
+                material.data.sun_direction.w = 1.0;
+            }
+            variants.push(material);
+        }
+    }
+    let flips: [fn(&mut CloudMaterial); 4] = [
+        |material| material.data.profile.z = 1.0,
+        |material| material.data.weather_map = Vec4::new(0.0, 0.0, 1.0, 1.0),
+        |material| material.data.sampling.x = 1.0,
+        |material| material.data.sampling.z = 1.0,
+    ];
+    for flip in flips {
+        let mut material = base.clone();
+        flip(&mut material);
+        variants.push(material);
+    }
+
+    for material in variants {
+        commands.spawn((
+            Mesh3d(mesh.clone()),
+            MeshMaterial3d(materials.add(material)),
+            NoFrustumCulling,
+            WarmUpProxy {
+                frames_left: WARM_UP_FRAMES,
+            },
+        ));
+    }
+}
+
+fn despawn_warm_up_system(mut commands: Commands, mut proxies: Query<(Entity, &mut WarmUpProxy)>) {
+    for (entity, mut proxy) in &mut proxies {
+        proxy.frames_left = proxy.frames_left.saturating_sub(1);
+        if proxy.frames_left == 0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+fn shader_ui_system(
+    mut contexts: EguiContexts,
+    mut density_override: ResMut<DensityOverride>,
+    asset_server: Res<AssetServer>,
+) {
+    egui::Window::new("Shader")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(match SHADER_OVERRIDE.get() {
+                Some(path) => format!("Cloud shader: {}", path),
+                None => "Cloud shader: built in".to_string(),
+            });
+            let mut example = density_override.shader.is_some();
+            if ui.checkbox(&mut example, "Sphere Checkerboard Density (example)").changed() {
+                density_override.shader = example.then(|| asset_server.load(EXAMPLE_DENSITY_OVERRIDE));
+            }
+        });
+}
+
+/// Current errors, shared between the main and render worlds.
+#[derive(Resource, Clone, Default)]
+struct ShaderErrors {
+    /// Failure to load an override file.
+    load: Arc<Mutex<Option<String>>>,
+    /// Pipeline compile errors for the cloud shader.
+    compile: Arc<Mutex<Vec<String>>>,
+}
+
+fn shader_load_error_system(
+    errors: Res<ShaderErrors>,
+    density_override: Res<DensityOverride>,
+    mut failed: EventReader<AssetLoadFailedEvent<Shader>>,
+    mut events: EventReader<AssetEvent<Shader>>,
+    asset_server: Res<AssetServer>,
+) {
+    let density_path = density_override
+        .shader
+        .as_ref()
+        .and_then(|shader| asset_server.get_path(shader.id()));
+    let watched = |path: &AssetPath| {
+        SHADER_OVERRIDE.get().is_some_and(|cloud| path.path().to_str() == Some(cloud.as_str()))
+            || density_path.as_ref().is_some_and(|density| density == path)
+    };
+    let mut load = errors.load.lock().unwrap();
+    for event in failed.read() {
+        if watched(&event.path) {
+            *load = Some(format!("Could not load {}: {}", event.path, event.error));
+        }
+    }
+    for event in events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } = event {
+            if asset_server.get_path(*id).is_some_and(|loaded| watched(&loaded)) {
+                *load = None;
+            }
+        }
+    }
+    if density_override.is_changed() && density_path.is_none() && SHADER_OVERRIDE.get().is_none() {
+        *load = None;
+    }
+}
+
+/// Collects the errors of pipelines whose fragment stage is the cloud shader,
+/// which includes failures in an imported density override.
+/// Hot reloads re-queue the pipeline, so fixed errors disappear on their own.
+fn pipeline_error_system(pipeline_cache: Res<PipelineCache>, errors: Res<ShaderErrors>, asset_server: Res<AssetServer>) {
+    let is_cloud_shader = |shader: &Handle<Shader>| match SHADER_OVERRIDE.get() {
+        Some(path) => asset_server
+            .get_path(shader.id())
+            .is_some_and(|loaded| loaded.path().to_str() == Some(path.as_str())),
+        None => shader.id() == CLOUD_SHADER_HANDLE.id(),
+    };
+    let mut messages: Vec<String> = pipeline_cache
+        .pipelines()
+        .filter_map(|pipeline| {
+            let PipelineDescriptor::RenderPipelineDescriptor(descriptor) = &pipeline.descriptor else {
+                return None;
+            };
+            let fragment = descriptor.fragment.as_ref()?;
+            let CachedPipelineState::Err(err) = &pipeline.state else {
+                return None;
+            };
+            is_cloud_shader(&fragment.shader).then(|| err.to_string())
+        })
+        .collect();
+    messages.dedup();
+    *errors.compile.lock().unwrap() = messages;
+}
+
+#[cfg(feature = "ui")]
+fn shader_error_ui_system(mut contexts: EguiContexts, errors: Res<ShaderErrors>) {
+    let load = errors.load.lock().unwrap().clone();
+    let compile = errors.compile.lock().unwrap().clone();
+    if load.is_none() && compile.is_empty() {
+        return;
+    }
+    egui::Window::new("Shader Error")
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 8.0))
+        .collapsible(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let color = ui.visuals().error_fg_color;
+            for message in load.iter().chain(&compile) {
+                ui.colored_label(color, egui::RichText::new(message).monospace());
+            }
+            ui.label("Clouds are not drawn until the shader compiles; saving a fix reloads it.");
+        });
+}
@@ -0,0 +1,214 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{windows::ui_window_open, CloudSettings, CloudUiSet, QualityPreset};
+
+/// Keyboard shortcuts and gamepad tuning for the viewer, plus the visibility
+/// state of its UI.
+pub struct ShortcutsPlugin;
+
+impl Plugin for ShortcutsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KeyBindings>()
+            .init_resource::<GamepadSettings>()
+            .init_resource::<UiState>()
+            .configure_sets(
+                Update,
+                CloudUiSet.run_if(|state: Res<UiState>| state.show_ui).run_if(ui_window_open),
+            )
+            .add_systems(
+                Update,
+                (shortcut_system, help_ui_system.run_if(ui_window_open))
+                    .chain()
+                    .before(CloudUiSet),
+            );
+    }
+}
+
+/// Remappable key bindings. `entries` feeds the help popup, so it always
+/// shows the bindings actually in use.
+#[derive(Resource, Clone, Debug)]
+pub struct KeyBindings {
+    pub toggle_ui: KeyCode,
+    pub toggle_stats: KeyCode,
+    pub toggle_help: KeyCode,
+    pub rebuild_noise: KeyCode,
+    pub toggle_wind: KeyCode,
+    pub frame_selected: KeyCode,
+    pub quality: [KeyCode; 4], // Low, Medium, High, Ultra
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            toggle_ui: KeyCode::F1,
+            toggle_stats: KeyCode::F2,
+            toggle_help: KeyCode::KeyH,
+            rebuild_noise: KeyCode::KeyR,
+            toggle_wind: KeyCode::Space,
+            frame_selected: KeyCode::KeyF,
+            quality: [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4],
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn entries(&self) -> Vec<(String, KeyCode)> {
+        let mut entries = vec![
+            ("Toggle UI".to_string(), self.toggle_ui),
+            ("Toggle stats".to_string(), self.toggle_stats),
+            ("Toggle help".to_string(), self.toggle_help),
+            ("Rebuild noise".to_string(), self.rebuild_noise),
+            ("Toggle wind".to_string(), self.toggle_wind),
+            ("Frame selected volume".to_string(), self.frame_selected),
+        ];
+        for (preset, key) in QualityPreset::ALL.iter().zip(self.quality) {
+            entries.push((format!("Quality: {:?}", preset), key));
+        }
+        entries
+    }
+}
+
+/// Gamepad camera tuning: right stick orbits, left stick pans the orbit
+/// center, triggers zoom.
+#[derive(Resource, Clone, Debug)]
+pub struct GamepadSettings {
+    pub dead_zone: f32,
+    pub look_sensitivity: f32, // radians per second at full deflection
+    pub pan_speed: f32,        // fraction of the orbit distance per second
+    pub zoom_speed: f32,       // log-distance per second
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.15,
+            look_sensitivity: 2.0,
+            pan_speed: 0.5,
+            zoom_speed: 1.5,
+        }
+    }
+}
+
+impl GamepadSettings {
+    /// Zeroes small deflections and rescales the rest to start from zero at
+    /// the dead-zone edge.
+    pub fn apply_dead_zone(&self, stick: Vec2) -> Vec2 {
+        let length = stick.length();
+        if length <= self.dead_zone {
+            return Vec2::ZERO;
+        }
+        let scaled = ((length - self.dead_zone) / (1.0 - self.dead_zone).max(1e-3)).min(1.0);
+        stick / length * scaled
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct UiState {
+    pub show_ui: bool,
+    pub show_stats: bool,
+    pub show_help: bool,
+}
+
+impl Default for UiState {
+    fn default() -> Self {
+        Self {
+            show_ui: true,
+            show_stats: true,
+            show_help: false,
+        }
+    }
+}
+
+fn shortcut_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut ui_state: ResMut<UiState>,
+    mut settings: ResMut<CloudSettings>,
+    mut contexts: EguiContexts,
+) {
+    // Don't steal keys while typing into a text field.
+    if ui_state.show_ui && contexts.try_ctx_mut().is_some_and(|ctx| ctx.wants_keyboard_input()) {
+        return;
+    }
+
+    if keys.just_pressed(bindings.toggle_ui) {
+        ui_state.show_ui = !ui_state.show_ui;
+    }
+    if keys.just_pressed(bindings.toggle_stats) {
+        ui_state.show_stats = !ui_state.show_stats;
+    }
+    if keys.just_pressed(bindings.toggle_help) {
+        ui_state.show_help = !ui_state.show_help;
+    }
+    if keys.just_pressed(bindings.rebuild_noise) {
+        settings.needs_rebuild = true;
+    }
+    if keys.just_pressed(bindings.toggle_wind) {
+        settings.wind_paused = !settings.wind_paused;
+    }
+    for (preset, key) in QualityPreset::ALL.iter().zip(bindings.quality) {
+        if keys.just_pressed(key) {
+            preset.apply(&mut settings);
+        }
+    }
+}
+
+fn help_ui_system(
+    mut contexts: EguiContexts,
+    bindings: Res<KeyBindings>,
+    mut ui_state: ResMut<UiState>,
+) {
+    if !ui_state.show_help {
+        return;
+    }
+    let mut open = true;
+    egui::Window::new("Shortcuts")
+        .open(&mut open)
+        .collapsible(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("shortcuts").striped(true).show(ui, |ui| {
+                for (action, key) in bindings.entries() {
+                    ui.label(action);
+                    ui.label(format!("{:?}", key));
+                    ui.end_row();
+                }
+            });
+        });
+    ui_state.show_help = open;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        gilrs::GilrsPlugin,
+        log::LogPlugin,
+        prelude::*,
+        render::{settings::WgpuSettings, RenderPlugin},
+        winit::WinitPlugin,
+    };
+
+    // The editor reads `Gamepad` entities, which only exist when gilrs is
+    // connecting controllers. A feature set without `bevy/bevy_gilrs` still
+    // builds but leaves the camera's gamepad query empty, so the `ui` build
+    // must bring the plugin in through `DefaultPlugins`.
+
+    #[test]
+    fn editor_plugins_register_gilrs() {
+        let mut app = App::new();
+        app.add_plugins(
+            DefaultPlugins
+                .set(RenderPlugin {
+                    render_creation: WgpuSettings {
+                        backends: None,
+                        ..default()
+                    }
+                    .into(),
+                    ..default()
+                })
+                .disable::<WinitPlugin>()
+                .disable::<LogPlugin>(),
+        );
+        assert!(app.is_plugin_added::<GilrsPlugin>(), "DefaultPlugins has no GilrsPlugin");
+    }
+}
@@ -0,0 +1,552 @@
+use std::{
+    f32::consts::{FRAC_PI_2, PI, TAU},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    core_pipeline::tonemapping::Tonemapping,
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+    },
+};
+use bevy_atmosphere::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    capture::{self, Captured},
+    cirrus::{CirrusLayer, CirrusPlugin},
+    exr_capture, export, golden,
+    lod::ImpostorPart,
+    noise::{BakeProgress, PendingNoise},
+    session::read_session,
+    sorting,
+    spawn::{CloudVolumeParams, SpawnCloudExt},
+    CloudSettings, CloudUiSet, CloudVolume, OrbitCamera,
+};
+
+const CAPTURE_DIR: &str = "captures";
+/// Frames the face cameras render before the readback, so the targets exist
+/// on the GPU and the atmosphere has drawn its sky.
+const WARMUP_FRAMES: u32 = 3;
+const FACE_SIZES: [u32; 4] = [256, 512, 1024, 2048];
+/// View direction and up of each face's camera, in KTX2 face order (+X, -X,
+/// +Y, -Y, +Z, -Z). Bevy's `Skybox` flips Z when sampling the cubemap, which
+/// makes every face the picture of an ordinary camera looking along its
+/// axis, except that the Z faces swap.
+const FACES: [(Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::Y),
+    (Vec3::NEG_X, Vec3::Y),
+    (Vec3::Y, Vec3::Z),
+    (Vec3::NEG_Y, Vec3::NEG_Z),
+    (Vec3::NEG_Z, Vec3::Y),
+    (Vec3::Z, Vec3::Y),
+];
+
+/// Bakes the sky into a static skybox for platforms that can't afford the
+/// march.
+///
+/// Six temporary 90° HDR cameras at the capture point render the clouds,
+/// cirrus sheets and atmosphere with the current sun and ambient settings
+/// into `Rgba16Float` faces, without tonemapping. The faces are written as
+/// a KTX2 cubemap that `Skybox` loads as is, or resampled into an
+/// equirectangular OpenEXR. While the cameras render, volumes whose base is
+/// below the minimum altitude and other `StandardMaterial` geometry are
+/// hidden, so hero clouds and props near the capture point stay out of the
+/// sky.
+pub struct SkyboxCapturePlugin;
+
+impl Plugin for SkyboxCapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SkyboxCaptureSettings>()
+            .init_resource::<SkyboxCapture>()
+            .add_systems(Update, (skybox_ui_system.in_set(CloudUiSet), skybox_capture_system).chain());
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkyboxFormat {
+    /// KTX2 cubemap for `Skybox`.
+    Cubemap,
+    /// 2:1 equirectangular OpenEXR, four face widths across.
+    Equirect,
+}
+
+impl SkyboxFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Cubemap => "ktx2",
+            Self::Equirect => "exr",
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct SkyboxCaptureSettings {
+    /// Where the sky is seen from.
+    pub position: Vec3,
+    /// Texels per face edge.
+    pub face_size: u32,
+    /// Volumes whose box reaches below this height are left out.
+    pub min_altitude: f32,
+    pub format: SkyboxFormat,
+}
+
+impl Default for SkyboxCaptureSettings {
+    fn default() -> Self {
+        Self {
+            position: Vec3::new(0.0, 1.7, 0.0),
+            face_size: 512,
+            min_altitude: 5.0,
+            format: SkyboxFormat::Cubemap,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct SkyboxCapture {
+    requested: Option<PathBuf>,
+    in_flight: Option<InFlight>,
+    pub status: Option<String>,
+    /// Outcome of the last capture: the file written or the error.
+    pub finished: Option<Result<PathBuf, String>>,
+}
+
+impl SkyboxCapture {
+    /// Captures into `path`, which should carry the format's extension.
+    pub fn request(&mut self, path: PathBuf) {
+        self.requested = Some(path);
+        self.finished = None;
+    }
+
+    pub fn busy(&self) -> bool {
+        self.requested.is_some() || self.in_flight.is_some()
+    }
+}
+
+struct InFlight {
+    path: PathBuf,
+    format: SkyboxFormat,
+    size: u32,
+    cameras: Vec<Entity>,
+    targets: Vec<Handle<Image>>,
+    frames: u32,
+    readbacks: [Option<Entity>; 6],
+    /// Unpadded `Rgba16Float` rows per face, as they arrive.
+    faces: [Option<Vec<u8>>; 6],
+    /// Entities hidden for the capture, with the visibility to restore.
+    hidden: Vec<(Entity, Visibility)>,
+}
+
+fn face_image(size: u32) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size,
+            height: size,
+            ..default()
+        },
+        TextureDimension::D2,
+        &[0; 8],
+        TextureFormat::Rgba16Float,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT;
+    image
+}
+
+#[allow(clippy::type_complexity)]
+fn skybox_capture_system(
+    mut commands: Commands,
+    mut capture: ResMut<SkyboxCapture>,
+    settings: Res<SkyboxCaptureSettings>,
+    mut images: ResMut<Assets<Image>>,
+    foreground: Query<
+        (Entity, &Visibility, &GlobalTransform, Has<CloudVolume>),
+        (
+            Or<(With<CloudVolume>, With<MeshMaterial3d<StandardMaterial>>)>,
+            // The LOD toggles its quads itself; they stand in for far
+            // volumes, which belong in the sky.
+            Without<ImpostorPart>,
+        ),
+    >,
+) {
+    if capture.in_flight.is_none() {
+        let Some(path) = capture.requested.take() else {
+            return;
+        };
+        let hidden: Vec<(Entity, Visibility)> = foreground
+            .iter()
+            .filter(|(_, visibility, transform, is_cloud)| {
+                **visibility != Visibility::Hidden
+                    && (!is_cloud || sorting::bounds(transform).0.y < settings.min_altitude)
+            })
+            .map(|(entity, visibility, ..)| (entity, *visibility))
+            .collect();
+        for &(entity, _) in &hidden {
+            commands.entity(entity).insert(Visibility::Hidden);
+        }
+
+        let size = settings.face_size.max(1);
+        let mut cameras = Vec::new();
+        let mut targets = Vec::new();
+        for (face, (forward, up)) in FACES.into_iter().enumerate() {
+            let target = images.add(face_image(size));
+            cameras.push(
+                commands
+                    .spawn((
+                        Camera3d::default(),
+                        Camera {
+                            target: RenderTarget::Image(target.clone()),
+                            order: -10 - face as isize,
+                            hdr: true,
+                            ..default()
+                        },
+                        Tonemapping::None,
+                        Projection::Perspective(PerspectiveProjection {
+                            fov: FRAC_PI_2,
+                            aspect_ratio: 1.0,
+                            ..default()
+                        }),
+                        Transform::from_translation(settings.position).looking_to(forward, up),
+                        AtmosphereCamera::default(),
+                    ))
+                    .id(),
+            );
+            targets.push(target);
+        }
+        capture.in_flight = Some(InFlight {
+            path,
+            format: settings.format,
+            size,
+            cameras,
+            targets,
+            frames: 0,
+            readbacks: [None; 6],
+            faces: Default::default(),
+            hidden,
+        });
+        capture.status = Some("Capturing...".to_string());
+        return;
+    }
+
+    let Some(in_flight) = capture.in_flight.as_mut() else {
+        return;
+    };
+    in_flight.frames += 1;
+    if in_flight.frames == WARMUP_FRAMES {
+        let size = UVec2::splat(in_flight.size);
+        for (face, target) in in_flight.targets.iter().enumerate() {
+            in_flight.readbacks[face] = Some(capture::read_back(&mut commands, target.clone(), size, 8, finish_face));
+        }
+    }
+}
+
+fn finish_face(
+    trigger: Trigger<Captured>,
+    mut commands: Commands,
+    mut capture: ResMut<SkyboxCapture>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Some(in_flight) = capture.in_flight.as_mut() else {
+        return;
+    };
+    let Some(face) = in_flight.readbacks.iter().position(|readback| *readback == Some(trigger.entity())) else {
+        return;
+    };
+    in_flight.faces[face] = Some(trigger.event().0.clone());
+    if in_flight.faces.iter().any(Option::is_none) {
+        return;
+    }
+
+    let Some(in_flight) = capture.in_flight.take() else {
+        return;
+    };
+    for camera in in_flight.cameras {
+        commands.entity(camera).despawn_recursive();
+    }
+    for target in &in_flight.targets {
+        images.remove(target);
+    }
+    for (entity, visibility) in in_flight.hidden {
+        if let Some(mut entity) = commands.get_entity(entity) {
+            entity.insert(visibility);
+        }
+    }
+
+    let faces = in_flight.faces.map(Option::unwrap_or_default);
+    let result = match in_flight.format {
+        SkyboxFormat::Cubemap => export::write_ktx2_cubemap(&in_flight.path, &faces, in_flight.size),
+        SkyboxFormat::Equirect => {
+            let faces = faces.map(|face| exr_capture::decode_rgba16f(&face));
+            let (pixels, extent) = equirect(&faces, in_flight.size);
+            exr_capture::write_exr(&in_flight.path, &pixels, extent)
+        }
+    };
+    capture.status = Some(match &result {
+        Ok(()) => format!("Wrote {}", in_flight.path.display()),
+        Err(err) => {
+            error!("Failed to write {}: {}", in_flight.path.display(), err);
+            format!("Export failed: {}", err)
+        }
+    });
+    capture.finished = Some(result.map(|()| in_flight.path));
+}
+
+/// Resamples RGBA float faces into an equirectangular image, nearest
+/// texel. Longitude 0 looks down -Z and grows towards +X.
+fn equirect(faces: &[Vec<f32>; 6], size: u32) -> (Vec<f32>, UVec2) {
+    let extent = UVec2::new(size * 4, size * 2);
+    let mut pixels = Vec::with_capacity((extent.x * extent.y * 4) as usize);
+    for y in 0..extent.y {
+        let latitude = (0.5 - (y as f32 + 0.5) / extent.y as f32) * PI;
+        for x in 0..extent.x {
+            let longitude = ((x as f32 + 0.5) / extent.x as f32 - 0.5) * TAU;
+            let dir = Vec3::new(
+                latitude.cos() * longitude.sin(),
+                latitude.sin(),
+                -latitude.cos() * longitude.cos(),
+            );
+            let (face, (forward, up)) = FACES
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1 .0.dot(dir).total_cmp(&b.1 .0.dot(dir)))
+                .expect("six faces");
+            // Onto the face's image plane, one unit in front of the camera.
+            let local = dir / forward.dot(dir);
+            let uv = Vec2::new(local.dot(forward.cross(*up)) + 1.0, 1.0 - local.dot(*up)) * 0.5;
+            let texel = (uv * size as f32).as_uvec2().min(UVec2::splat(size - 1));
+            let index = ((texel.y * size + texel.x) * 4) as usize;
+            pixels.extend_from_slice(&faces[face][index..index + 4]);
+        }
+    }
+    (pixels, extent)
+}
+
+fn capture_path(format: SkyboxFormat) -> PathBuf {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+    Path::new(CAPTURE_DIR).join(format!("skybox_{}.{}", stamp, format.extension()))
+}
+
+fn skybox_ui_system(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<SkyboxCaptureSettings>,
+    mut capture: ResMut<SkyboxCapture>,
+    cameras: Query<&GlobalTransform, With<OrbitCamera>>,
+) {
+    egui::Window::new("Skybox Capture")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("Bakes the sky into a static skybox.");
+            ui.horizontal(|ui| {
+                ui.label("Position");
+                ui.add(egui::DragValue::new(&mut settings.position.x).speed(0.1).prefix("x "));
+                ui.add(egui::DragValue::new(&mut settings.position.y).speed(0.1).prefix("y "));
+                ui.add(egui::DragValue::new(&mut settings.position.z).speed(0.1).prefix("z "));
+            });
+            if let Ok(camera) = cameras.get_single() {
+                if ui.button("Use Camera Position").clicked() {
+                    settings.position = camera.translation();
+                }
+            }
+            egui::ComboBox::from_label("Face Size")
+                .selected_text(format!("{}²", settings.face_size))
+                .show_ui(ui, |ui| {
+                    for size in FACE_SIZES {
+                        ui.selectable_value(&mut settings.face_size, size, format!("{}²", size));
+                    }
+                });
+            ui.add(
+                egui::Slider::new(&mut settings.min_altitude, -10.0..=100.0)
+                    .suffix(" m")
+                    .text("Min Cloud Altitude"),
+            )
+            .on_hover_text("Volumes reaching below this height are left out of the sky");
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut settings.format, SkyboxFormat::Cubemap, "KTX2 Cubemap");
+                ui.radio_value(&mut settings.format, SkyboxFormat::Equirect, "Equirect EXR");
+            });
+            if ui.add_enabled(!capture.busy(), egui::Button::new("Capture Skybox")).clicked() {
+                capture.request(capture_path(settings.format));
+            }
+            if let Some(status) = &capture.status {
+                ui.label(status);
+            }
+        });
+}
+
+/// Headless capture: `--capture-skybox [out.ktx2|out.exr] [--session file.ron]
+/// [--skybox-size N] [--skybox-at x,y,z] [--skybox-min-altitude m] [--cirrus]`.
+///
+/// Renders the volumes, settings and sun of a saved session, or the default
+/// scene, once the noise is baked, and writes the skybox. An `.exr` output
+/// is equirectangular, anything else a KTX2 cubemap. `--cirrus` adds a
+/// default cirrus sheet, which sessions don't store.
+pub fn run(args: &[String]) -> AppExit {
+    let value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|index| args.get(index + 1))
+            .filter(|value| !value.starts_with("--"))
+    };
+    let mut settings = SkyboxCaptureSettings::default();
+    let path = PathBuf::from(value("--capture-skybox").map_or("skybox.ktx2", String::as_str));
+    if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("exr")) {
+        settings.format = SkyboxFormat::Equirect;
+    }
+    let parsed = (|| -> Result<(), String> {
+        if let Some(size) = value("--skybox-size") {
+            settings.face_size = size
+                .parse()
+                .ok()
+                .filter(|&size| size > 0)
+                .ok_or_else(|| format!("--skybox-size: expected a face size in texels, got {}", size))?;
+        }
+        if let Some(at) = value("--skybox-at") {
+            let coords: Vec<f32> = at.split(',').filter_map(|coord| coord.trim().parse().ok()).collect();
+            let &[x, y, z] = coords.as_slice() else {
+                return Err(format!("--skybox-at: expected x,y,z, got {}", at));
+            };
+            settings.position = Vec3::new(x, y, z);
+        }
+        if let Some(altitude) = value("--skybox-min-altitude") {
+            settings.min_altitude = altitude
+                .parse()
+                .map_err(|_| format!("--skybox-min-altitude: expected a height, got {}", altitude))?;
+        }
+        Ok(())
+    })();
+    if let Err(err) = parsed {
+        eprintln!("{}", err);
+        return AppExit::error();
+    }
+    let session = match value("--session").map(|path| read_session(Path::new(path))) {
+        Some(Ok(session)) => Some(session),
+        Some(Err(err)) => {
+            eprintln!("Failed to load session: {}", err);
+            return AppExit::error();
+        }
+        None => None,
+    };
+
+    let mut app = golden::cloud_app(None, Duration::from_secs_f64(1.0 / 60.0));
+    app.add_plugins(AtmospherePlugin)
+        .add_plugins(CirrusPlugin)
+        .add_plugins(SkyboxCapturePlugin)
+        .insert_resource(settings)
+        .insert_resource(HeadlessSkybox { path, frames: 0 })
+        .add_systems(
+            Update,
+            (
+                headless_skybox_system,
+                crate::wind_system,
+                crate::swap_noise_system,
+                crate::update_material_system,
+            )
+                .chain(),
+        );
+
+    let world = app.world_mut();
+    let (light, volumes) = match session {
+        Some(session) => {
+            let mut settings = world.resource_mut::<CloudSettings>();
+            *settings = CloudSettings {
+                noise_handle: settings.noise_handle.clone(),
+                profile_handle: settings.profile_handle.clone(),
+                needs_rebuild: true,
+                ..session.settings
+            };
+            let light = session
+                .light
+                .map(|light| (light.transform, light.color, light.illuminance))
+                .unwrap_or_else(default_light);
+            let volumes = session
+                .volumes
+                .into_iter()
+                .map(|volume| CloudVolumeParams {
+                    transform: volume.transform,
+                    volume: volume.volume,
+                })
+                .collect();
+            (light, volumes)
+        }
+        None => (default_light(), vec![CloudVolumeParams::default().at(Vec3::new(0.0, 1.0, 0.0))]),
+    };
+    let (transform, color, illuminance) = light;
+    // The sky's sun where the light comes from.
+    world.insert_resource(AtmosphereModel::new(Nishita {
+        sun_position: -transform.forward().as_vec3(),
+        ..default()
+    }));
+    world.spawn((
+        DirectionalLight {
+            color,
+            illuminance,
+            shadows_enabled: true,
+            ..default()
+        },
+        transform,
+    ));
+    if args.iter().any(|arg| arg == "--cirrus") {
+        world.spawn(CirrusLayer {
+            follow_camera: false,
+            ..default()
+        });
+    }
+    let mut commands = world.commands();
+    for params in volumes {
+        commands.spawn_cloud(params);
+    }
+    world.flush();
+    app.run()
+}
+
+fn default_light() -> (Transform, Color, f32) {
+    let light = DirectionalLight::default();
+    (
+        Transform::from_xyz(4.0, 8.0, 4.0).looking_at(Vec3::ZERO, Vec3::Y),
+        light.color,
+        light.illuminance,
+    )
+}
+
+#[derive(Resource)]
+struct HeadlessSkybox {
+    path: PathBuf,
+    frames: u32,
+}
+
+/// Waits for the bake, captures once and exits with the outcome.
+fn headless_skybox_system(
+    mut run: ResMut<HeadlessSkybox>,
+    settings: Res<CloudSettings>,
+    bake_progress: Res<BakeProgress>,
+    pending_noise: Res<PendingNoise>,
+    mut capture: ResMut<SkyboxCapture>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if let Some(finished) = capture.finished.take() {
+        exit.send(match finished {
+            Ok(path) => {
+                println!("Wrote {}", path.display());
+                AppExit::Success
+            }
+            Err(err) => {
+                eprintln!("Skybox capture failed: {}", err);
+                AppExit::error()
+            }
+        });
+        return;
+    }
+    if settings.needs_rebuild || bake_progress.bake.is_some() || pending_noise.image.is_some() {
+        return;
+    }
+    // A few frames for the materials to pick up the bake.
+    run.frames += 1;
+    if run.frames == WARMUP_FRAMES {
+        capture.request(run.path.clone());
+    }
+}
@@ -0,0 +1,441 @@
+//! Formats any `Serialize` value as a Rust expression.
+//!
+//! Driving this from serde means the "Copy as code" output always names the
+//! same fields as saved sessions, and fields marked `#[serde(skip)]` are
+//! covered by a struct-update `..rest` expression instead.
+
+use std::fmt::{self, Display, Write};
+
+use serde::{ser, Serialize};
+
+#[derive(Debug)]
+pub struct SnippetError(String);
+
+impl Display for SnippetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SnippetError {}
+
+impl ser::Error for SnippetError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl From<fmt::Error> for SnippetError {
+    fn from(err: fmt::Error) -> Self {
+        Self(err.to_string())
+    }
+}
+
+/// Formats `value` as a Rust literal. `rest` is appended as `..rest` to the
+/// outermost struct, e.g. `Some("CloudSettings::defaults()")`.
+pub fn to_rust_literal<T: Serialize + ?Sized>(value: &T, rest: Option<&str>) -> Result<String, SnippetError> {
+    let mut serializer = RustLiteral {
+        out: String::new(),
+        indent: 0,
+        rest: rest.map(str::to_string),
+    };
+    value.serialize(&mut serializer)?;
+    Ok(serializer.out)
+}
+
+struct RustLiteral {
+    out: String,
+    indent: usize,
+    rest: Option<String>,
+}
+
+impl RustLiteral {
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+    }
+
+    fn float(&mut self, v: f64, ty: &str) -> Result<(), SnippetError> {
+        if v.is_nan() {
+            write!(self.out, "{}::NAN", ty)?;
+        } else if v.is_infinite() {
+            let sign = if v > 0.0 { "" } else { "NEG_" };
+            write!(self.out, "{}::{}INFINITY", ty, sign)?;
+        } else {
+            write!(self.out, "{:?}", v)?;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, kind: CompoundKind, opening: String) -> Compound<'_> {
+        self.out.push_str(&opening);
+        let rest = if matches!(kind, CompoundKind::Struct) && self.indent == 0 {
+            self.rest.take()
+        } else {
+            None
+        };
+        if matches!(kind, CompoundKind::Struct) {
+            self.indent += 1;
+        }
+        Compound {
+            ser: self,
+            kind,
+            first: true,
+            rest,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum CompoundKind {
+    /// Comma separated on one line, closed with the given delimiter.
+    Inline(&'static str),
+    /// Comma separated on one line. Serde writes fixed-size arrays as tuples,
+    /// so this becomes `[..]` when every element has the same type, which is
+    /// what the settings hold, and stays `(..)` otherwise.
+    Tuple {
+        start: usize,
+        element: Option<&'static str>,
+        uniform: bool,
+    },
+    /// One `field: value,` per line.
+    Struct,
+}
+
+struct Compound<'a> {
+    ser: &'a mut RustLiteral,
+    kind: CompoundKind,
+    first: bool,
+    rest: Option<String>,
+}
+
+impl Compound<'_> {
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SnippetError> {
+        if !self.first {
+            self.ser.out.push_str(", ");
+        }
+        self.first = false;
+        value.serialize(&mut *self.ser)
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), SnippetError> {
+        self.first = false;
+        self.ser.newline();
+        write!(self.ser.out, "{}: ", key)?;
+        value.serialize(&mut *self.ser)?;
+        self.ser.out.push(',');
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), SnippetError> {
+        match self.kind {
+            CompoundKind::Inline(close) => self.ser.out.push_str(close),
+            CompoundKind::Tuple {
+                start,
+                element: Some(_),
+                uniform: true,
+            } => {
+                self.ser.out.replace_range(start..start + 1, "[");
+                self.ser.out.push(']');
+            }
+            CompoundKind::Tuple { .. } => self.ser.out.push(')'),
+            CompoundKind::Struct => {
+                if let Some(rest) = self.rest {
+                    self.ser.newline();
+                    write!(self.ser.out, "..{}", rest)?;
+                }
+                self.ser.indent -= 1;
+                self.ser.newline();
+                self.ser.out.push('}');
+            }
+        }
+        Ok(())
+    }
+}
+
+macro_rules! display_int {
+    ($($method:ident: $ty:ty),*) => {
+        $(fn $method(self, v: $ty) -> Result<(), SnippetError> {
+            write!(self.out, "{}", v)?;
+            Ok(())
+        })*
+    };
+}
+
+impl<'a> ser::Serializer for &'a mut RustLiteral {
+    type Ok = ();
+    type Error = SnippetError;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    display_int!(
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64
+    );
+
+    fn serialize_f32(self, v: f32) -> Result<(), SnippetError> {
+        // Round-trip through the f32 Debug output to avoid f64 noise digits.
+        if v.is_finite() {
+            write!(self.out, "{:?}", v)?;
+            Ok(())
+        } else {
+            self.float(v as f64, "f32")
+        }
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), SnippetError> {
+        self.float(v, "f64")
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), SnippetError> {
+        write!(self.out, "{:?}", v)?;
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), SnippetError> {
+        write!(self.out, "String::from({:?})", v)?;
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), SnippetError> {
+        write!(self.out, "vec!{:?}", v)?;
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), SnippetError> {
+        self.out.push_str("None");
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SnippetError> {
+        self.out.push_str("Some(");
+        value.serialize(&mut *self)?;
+        self.out.push(')');
+        Ok(())
+    }
+
+    fn serialize_unit(self) -> Result<(), SnippetError> {
+        self.out.push_str("()");
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<(), SnippetError> {
+        self.out.push_str(name);
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), SnippetError> {
+        write!(self.out, "{}::{}", name, variant)?;
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), SnippetError> {
+        write!(self.out, "{}(", name)?;
+        value.serialize(&mut *self)?;
+        self.out.push(')');
+        Ok(())
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), SnippetError> {
+        write!(self.out, "{}::{}(", name, variant)?;
+        value.serialize(&mut *self)?;
+        self.out.push(')');
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>, SnippetError> {
+        Ok(self.open(CompoundKind::Inline("]"), "vec![".to_string()))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>, SnippetError> {
+        let kind = CompoundKind::Tuple {
+            start: self.out.len(),
+            element: None,
+            uniform: true,
+        };
+        Ok(self.open(kind, "(".to_string()))
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, _len: usize) -> Result<Compound<'a>, SnippetError> {
+        // glam's math types serialize as tuple structs; rebuild them through
+        // their constructors.
+        let constructor = match name {
+            "Quat" => "from_xyzw",
+            _ => "new",
+        };
+        Ok(self.open(CompoundKind::Inline(")"), format!("{}::{}(", name, constructor)))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, SnippetError> {
+        Ok(self.open(CompoundKind::Inline(")"), format!("{}::{}(", name, variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a>, SnippetError> {
+        Err(SnippetError("maps have no Rust literal syntax".to_string()))
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Compound<'a>, SnippetError> {
+        Ok(self.open(CompoundKind::Struct, format!("{} {{", name)))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, SnippetError> {
+        Ok(self.open(CompoundKind::Struct, format!("{}::{} {{", name, variant)))
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_> {
+    type Ok = ();
+    type Error = SnippetError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SnippetError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), SnippetError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_> {
+    type Ok = ();
+    type Error = SnippetError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SnippetError> {
+        if let CompoundKind::Tuple { element, uniform, .. } = &mut self.kind {
+            let name = std::any::type_name::<T>();
+            *uniform &= element.is_none_or(|element| element == name);
+            *element = Some(name);
+        }
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), SnippetError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_> {
+    type Ok = ();
+    type Error = SnippetError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SnippetError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), SnippetError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_> {
+    type Ok = ();
+    type Error = SnippetError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SnippetError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), SnippetError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeMap for Compound<'_> {
+    type Ok = ();
+    type Error = SnippetError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, _key: &T) -> Result<(), SnippetError> {
+        unreachable!("serialize_map always fails")
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, _value: &T) -> Result<(), SnippetError> {
+        unreachable!("serialize_map always fails")
+    }
+
+    fn end(self) -> Result<(), SnippetError> {
+        unreachable!("serialize_map always fails")
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_> {
+    type Ok = ();
+    type Error = SnippetError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SnippetError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), SnippetError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_> {
+    type Ok = ();
+    type Error = SnippetError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SnippetError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), SnippetError> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CloudSettings;
+
+    // "Copy as code" output has to compile as Rust, so fixed-size arrays are
+    // written as array literals rather than the tuples serde describes them as.
+
+    #[test]
+    fn snippet_writes_arrays_as_arrays() {
+        let snippet = to_rust_literal(&CloudSettings::defaults(), Some("CloudSettings::defaults()")).unwrap();
+        assert!(snippet.contains("fbm_frequencies: [1.0, 2.0, 4.0, 8.0],"), "{snippet}");
+        assert!(snippet.contains("fbm_weights: ["), "{snippet}");
+    }
+}
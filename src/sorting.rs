@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+
+use crate::{
+    material_sync::{CloudMaterialInputs, CloudSyncSet},
+    CloudSettings, CloudVolume, OrbitCamera,
+};
+
+/// Most overlapping volumes a material can hand its overlap regions to.
+pub const MAX_MERGED: usize = 4;
+
+/// Blending of overlapping volumes.
+///
+/// Volumes are alpha blended back to front. Bevy sorts transparent meshes by
+/// the view depth of their origin, which flips the order of two intersecting
+/// boxes as the camera orbits; each material's depth bias moves its sort
+/// point to the box's nearest point to the main camera instead. Both are
+/// resolved into `CloudMaterialInputs` for the material sync.
+///
+/// With `CloudSettings::merge_overlaps`, the region where two volumes sharing
+/// a noise texture intersect is marched only by the older of the two (the
+/// lower entity); the other skips it, so the overlap is not darkened twice.
+pub struct CloudSortingPlugin;
+
+impl Plugin for CloudSortingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            (depth_bias_system, merge_overlaps_system).in_set(CloudSyncSet::Resolve),
+        );
+    }
+}
+
+/// Bias changes smaller than this don't rewrite the material.
+const BIAS_EPSILON: f32 = 0.01;
+
+/// World-space bounds of a volume's box, rotated or not.
+pub(crate) fn bounds(transform: &GlobalTransform) -> (Vec3, Vec3) {
+    let (scale, rotation, center) = transform.to_scale_rotation_translation();
+    let rotation = Mat3::from_quat(rotation);
+    let half_extents = scale.abs();
+    let reach = rotation.x_axis.abs() * half_extents.x
+        + rotation.y_axis.abs() * half_extents.y
+        + rotation.z_axis.abs() * half_extents.z;
+    (center - reach, center + reach)
+}
+
+fn depth_bias_system(
+    cameras: Query<&GlobalTransform, With<OrbitCamera>>,
+    mut volumes: Query<(&GlobalTransform, &mut CloudMaterialInputs), With<CloudVolume>>,
+) {
+    let Ok(camera) = cameras.get_single() else {
+        return;
+    };
+    let view_from_world = camera.compute_matrix().inverse();
+    let camera_pos = camera.translation();
+    for (transform, mut inputs) in &mut volumes {
+        let (min, max) = bounds(transform);
+        let nearest = camera_pos.clamp(min, max);
+        // View-space z grows towards the camera, as the sort distance does.
+        let bias = view_from_world.transform_point3(nearest).z - view_from_world.transform_point3(transform.translation()).z;
+        if (inputs.depth_bias - bias).abs() > BIAS_EPSILON {
+            inputs.depth_bias = bias;
+        }
+    }
+}
+
+fn merge_overlaps_system(
+    settings: Res<CloudSettings>,
+    mut volumes: Query<(Entity, &GlobalTransform, &mut CloudMaterialInputs), With<CloudVolume>>,
+) {
+    let boxes: Vec<_> = volumes
+        .iter()
+        .filter_map(|(entity, transform, inputs)| {
+            // Regions are world-space boxes, which only match unrotated volumes.
+            if !transform.rotation().is_near_identity() {
+                return None;
+            }
+            let (min, max) = bounds(transform);
+            Some((entity, min, max, inputs.noise(&settings).id()))
+        })
+        .collect();
+
+    for (entity, _, mut inputs) in &mut volumes {
+        let Some(&(_, min, max, noise)) = boxes.iter().find(|other| other.0 == entity) else {
+            if inputs.merge_count != 0 {
+                inputs.merge_count = 0;
+            }
+            continue;
+        };
+        let mut count = 0;
+        let mut merge_min = [Vec4::ZERO; MAX_MERGED];
+        let mut merge_max = [Vec4::ZERO; MAX_MERGED];
+        if settings.merge_overlaps {
+            let owners = boxes
+                .iter()
+                .filter(|other| other.0 < entity && other.3 == noise)
+                .map(|other| (other.1.max(min), other.2.min(max)))
+                .filter(|(overlap_min, overlap_max)| overlap_min.cmplt(*overlap_max).all());
+            for (overlap_min, overlap_max) in owners.take(MAX_MERGED) {
+                merge_min[count] = overlap_min.extend(0.0);
+                merge_max[count] = overlap_max.extend(0.0);
+                count += 1;
+            }
+        }
+        if inputs.merge_count != count || inputs.merge_min != merge_min || inputs.merge_max != merge_max {
+            inputs.merge_count = count;
+            inputs.merge_min = merge_min;
+            inputs.merge_max = merge_max;
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+
+use crate::{CloudMaterial, CloudSettings, CloudVolume};
+
+/// What `spawn_cloud` needs to place one volume.
+#[derive(Clone, Debug)]
+pub struct CloudVolumeParams {
+    /// The scale is the half-extent of the raymarched box.
+    pub transform: Transform,
+    pub volume: CloudVolume,
+}
+
+impl Default for CloudVolumeParams {
+    fn default() -> Self {
+        Self::new(Vec3::ONE)
+    }
+}
+
+impl CloudVolumeParams {
+    /// A default volume at the origin with the given half-extents.
+    pub fn new(half_extents: Vec3) -> Self {
+        Self {
+            transform: Transform::from_scale(half_extents),
+            volume: CloudVolume::default(),
+        }
+    }
+
+    pub fn at(mut self, translation: Vec3) -> Self {
+        self.transform.translation = translation;
+        self
+    }
+}
+
+/// Spawns cloud volumes with their mesh and a fresh material wired to the
+/// shared noise and profile textures.
+pub trait SpawnCloudExt {
+    fn spawn_cloud(&mut self, params: CloudVolumeParams) -> EntityCommands<'_>;
+}
+
+impl SpawnCloudExt for Commands<'_, '_> {
+    fn spawn_cloud(&mut self, params: CloudVolumeParams) -> EntityCommands<'_> {
+        let entity = self.spawn((params.transform, params.volume)).id();
+        // The material copies the settings' texture handles, so it is built
+        // when the command is applied. Until the first bake the noise is all
+        // zeros and the volume draws nothing.
+        self.queue(move |world: &mut World| {
+            if !world.entities().contains(entity) {
+                return;
+            }
+            let mesh = match world.get_resource::<CloudVolumeMesh>() {
+                Some(mesh) => mesh.0.clone(),
+                None => {
+                    let mesh = world.resource_mut::<Assets<Mesh>>().add(Cuboid::new(2.0, 2.0, 2.0));
+                    world.insert_resource(CloudVolumeMesh(mesh.clone()));
+                    mesh
+                }
+            };
+            let material = CloudMaterial::new(world.resource::<CloudSettings>());
+            let material = world.resource_mut::<Assets<CloudMaterial>>().add(material);
+            world.entity_mut(entity).insert((Mesh3d(mesh), MeshMaterial3d(material)));
+        });
+        self.entity(entity)
+    }
+}
+
+/// The 2x2x2 cuboid shared by every spawned volume.
+#[derive(Resource)]
+struct CloudVolumeMesh(Handle<Mesh>);
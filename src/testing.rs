@@ -0,0 +1,26 @@
+//! Fixtures shared by the unit tests of several modules.
+
+use bevy::{asset::AssetPlugin, prelude::*, transform::TransformPlugin};
+
+use crate::{
+    cloud_sun::CloudLights, material_sync::CloudMaterialSyncPlugin, shader::DensityOverride, CloudMaterial,
+    CloudSettings,
+};
+
+/// An app running `CloudMaterialSyncPlugin` without a renderer or a window,
+/// with the default settings. `setup` adds whatever else the test needs
+/// before the app is finished.
+pub(crate) fn headless_app(setup: impl FnOnce(&mut App)) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), TransformPlugin, CloudMaterialSyncPlugin))
+        .init_asset::<Image>()
+        .init_asset::<Shader>()
+        .init_asset::<CloudMaterial>()
+        .init_resource::<CloudLights>()
+        .init_resource::<DensityOverride>()
+        .insert_resource(CloudSettings::defaults());
+    setup(&mut app);
+    app.finish();
+    app.cleanup();
+    app
+}
@@ -0,0 +1,167 @@
+//! Checks the noise resolution against what the GPU and a memory budget
+//! allow before anything is baked, so an oversized volume ends in a readable
+//! message instead of an error deep in wgpu.
+
+use bevy::{
+    prelude::*,
+    render::{renderer::RenderDevice, settings::WgpuLimits},
+};
+
+use crate::{noise, CloudSettings, CloudUiSet};
+
+/// Budget for one noise volume unless `--texture-budget` says otherwise.
+pub const DEFAULT_BUDGET_MB: u32 = 128;
+
+pub struct TextureLimitsPlugin;
+
+impl Plugin for TextureLimitsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TextureLimits>()
+            .add_systems(Startup, adapter_limits_system)
+            .add_systems(
+                Update,
+                texture_limits_system
+                    .after(CloudUiSet)
+                    .before(crate::update_material_system),
+            );
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct TextureLimits {
+    /// Largest 3D texture edge the adapter supports; wgpu's default limit
+    /// until the adapter has been queried, and in the headless bake.
+    pub max_dimension_3d: u32,
+    /// Memory one noise volume may take, in megabytes.
+    pub budget_mb: u32,
+    /// Why the last requested resolution was reduced, for the settings
+    /// window.
+    pub error: Option<String>,
+    /// Resolution and limits last checked, so a reduction's message stays
+    /// until the resolution or the limits change again.
+    checked: Option<(UVec3, u32, u32)>,
+}
+
+impl Default for TextureLimits {
+    fn default() -> Self {
+        Self {
+            max_dimension_3d: WgpuLimits::default().max_texture_dimension_3d,
+            budget_mb: DEFAULT_BUDGET_MB,
+            error: None,
+            checked: None,
+        }
+    }
+}
+
+/// `256³`, or `256×128×256` when the axes differ.
+pub fn format_resolution(resolution: UVec3) -> String {
+    if resolution.x == resolution.y && resolution.y == resolution.z {
+        format!("{}³", resolution.x)
+    } else {
+        format!("{}×{}×{}", resolution.x, resolution.y, resolution.z)
+    }
+}
+
+/// Bytes of one noise volume at `resolution`.
+pub fn noise_bytes(resolution: UVec3) -> u64 {
+    resolution.as_u64vec3().element_product() * noise::NOISE_CHANNELS as u64
+}
+
+impl TextureLimits {
+    /// Reads `--texture-budget <MB>`.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut limits = Self::default();
+        if let Some(index) = args.iter().position(|arg| arg == "--texture-budget") {
+            let value = args.get(index + 1).ok_or("--texture-budget needs a size in MB")?;
+            limits.budget_mb = value
+                .parse()
+                .ok()
+                .filter(|&mb| mb > 0)
+                .ok_or_else(|| format!("--texture-budget: {:?} is not a size in MB", value))?;
+        }
+        Ok(limits)
+    }
+
+    fn budget_bytes(&self) -> u64 {
+        u64::from(self.budget_mb) << 20
+    }
+
+    /// Largest edge the resolution sliders offer.
+    pub fn max_resolution(&self) -> u32 {
+        noise::MAX_RESOLUTION.min(self.max_dimension_3d)
+    }
+
+    /// Whether a noise volume of `resolution` fits the adapter and the
+    /// budget, with the reason if not.
+    pub fn check(&self, resolution: UVec3) -> Result<(), String> {
+        if resolution.max_element() > self.max_dimension_3d {
+            return Err(format!(
+                "{} exceeds this GPU's max 3D texture size of {}",
+                format_resolution(resolution),
+                self.max_dimension_3d
+            ));
+        }
+        let bytes = noise_bytes(resolution);
+        if bytes > self.budget_bytes() {
+            return Err(format!(
+                "{} ({} MB) exceeds the texture budget of {} MB",
+                format_resolution(resolution),
+                bytes.div_ceil(1 << 20),
+                self.budget_mb
+            ));
+        }
+        Ok(())
+    }
+
+    /// `resolution` cut down until it passes `check`: clamped to the
+    /// adapter, then the longest axis halved until it is within budget.
+    pub fn fit(&self, resolution: UVec3) -> UVec3 {
+        let mut fitted = resolution.min(UVec3::splat(self.max_resolution()));
+        while noise_bytes(fitted) > self.budget_bytes() && fitted.max_element() > noise::MIN_RESOLUTION {
+            let longest = fitted.max_element();
+            fitted = UVec3::from_array(fitted.to_array().map(|axis| {
+                if axis == longest {
+                    (axis / 2).max(noise::MIN_RESOLUTION)
+                } else {
+                    axis
+                }
+            }));
+        }
+        fitted
+    }
+}
+
+/// The main world gets the render device once the renderer has started.
+fn adapter_limits_system(device: Option<Res<RenderDevice>>, mut limits: ResMut<TextureLimits>) {
+    let Some(device) = device else {
+        return;
+    };
+    limits.max_dimension_3d = device.limits().max_texture_dimension_3d;
+    info!(
+        "Max 3D texture size {}, noise budget {} MB",
+        limits.max_dimension_3d, limits.budget_mb
+    );
+}
+
+/// Cuts an oversized resolution down before the bake or any per-volume
+/// texture sees it, however it was set: sliders, a session, a preset.
+fn texture_limits_system(mut settings: ResMut<CloudSettings>, mut limits: ResMut<TextureLimits>) {
+    let resolution = noise::clamp_resolution(settings.resolution);
+    let (budget_mb, max_dimension_3d) = (limits.budget_mb, limits.max_dimension_3d);
+    let key = |resolution| Some((resolution, budget_mb, max_dimension_3d));
+    if limits.checked == key(resolution) {
+        return;
+    }
+    let Err(err) = limits.check(resolution) else {
+        limits.checked = key(resolution);
+        limits.error = None;
+        return;
+    };
+    let fitted = limits.fit(resolution);
+    let message = format!("{}; baking {} instead", err, format_resolution(fitted));
+    warn!("{}", message);
+    limits.checked = key(fitted);
+    limits.error = Some(message);
+    settings.resolution = fitted;
+    settings.needs_rebuild = true;
+}
@@ -0,0 +1,283 @@
+use std::{collections::VecDeque, fs, path::PathBuf};
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        view::RenderLayers,
+    },
+};
+
+use crate::{
+    bake_cache,
+    capture::{self, Captured},
+    noise,
+    presets::{CloudPreset, PRESETS},
+    CloudMaterial, CloudSettings, CloudVolume,
+};
+
+/// Layer holding the thumbnail volume and its light; only the thumbnail
+/// camera sees it.
+const THUMBNAIL_LAYER: usize = 29;
+pub const THUMBNAIL_SIZE: u32 = 128;
+/// Bump when the canonical scene changes, so cached thumbnails re-render.
+const THUMBNAIL_VERSION: u32 = 1;
+const WARMUP_FRAMES: u32 = 3;
+/// Position of the thumbnail light, which shines towards the origin.
+const THUMBNAIL_SUN: Vec3 = Vec3::new(4.0, 8.0, 4.0);
+
+/// Renders a small picture of every preset for the preset picker.
+///
+/// Each preset is applied to a copy of the default settings and drawn on a
+/// hidden volume with its own small noise bake, from a fixed camera, so the
+/// user's settings, volumes and camera are never touched. Thumbnails are
+/// cached as PNGs keyed by a hash of the preset, and re-render when a preset
+/// changes.
+pub struct PresetThumbnailPlugin;
+
+impl Plugin for PresetThumbnailPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PresetThumbnails>()
+            .add_systems(Update, thumbnail_system.after(crate::update_material_system));
+    }
+}
+
+#[derive(Resource)]
+pub struct PresetThumbnails {
+    /// By index into `PRESETS`.
+    images: Vec<Option<Handle<Image>>>,
+    queue: VecDeque<usize>,
+    loaded: bool,
+    rig: Option<ThumbnailRig>,
+    job: Option<ThumbnailJob>,
+    dir: Option<PathBuf>,
+}
+
+impl Default for PresetThumbnails {
+    fn default() -> Self {
+        Self {
+            images: vec![None; PRESETS.len()],
+            queue: VecDeque::new(),
+            loaded: false,
+            rig: None,
+            job: None,
+            dir: directories::ProjectDirs::from("", "", "bevy-clouds").map(|dirs| dirs.cache_dir().join("thumbnails")),
+        }
+    }
+}
+
+impl PresetThumbnails {
+    pub fn get(&self, index: usize) -> Option<&Handle<Image>> {
+        self.images.get(index)?.as_ref()
+    }
+
+    /// Re-renders every thumbnail, ignoring the cache.
+    pub fn regenerate(&mut self) {
+        self.queue = (0..PRESETS.len()).collect();
+    }
+
+    pub fn pending(&self) -> usize {
+        self.queue.len() + self.job.is_some() as usize
+    }
+
+    fn path(&self, preset: &CloudPreset) -> Option<PathBuf> {
+        let key = format!("{}:{}:{:?}", THUMBNAIL_VERSION, THUMBNAIL_SIZE, preset);
+        Some(self.dir.as_ref()?.join(format!("{:016x}.png", bake_cache::fnv1a(key.as_bytes()))))
+    }
+}
+
+/// Camera, light and volume on the thumbnail layer, kept between jobs.
+struct ThumbnailRig {
+    camera: Entity,
+    volume: Entity,
+    target: Handle<Image>,
+}
+
+struct ThumbnailJob {
+    index: usize,
+    frames: u32,
+    noise: Handle<Image>,
+    material: Handle<CloudMaterial>,
+    readback: Option<Entity>,
+}
+
+fn thumbnail_image(data: Vec<u8>) -> Image {
+    Image::new(
+        Extent3d {
+            width: THUMBNAIL_SIZE,
+            height: THUMBNAIL_SIZE,
+            ..default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    )
+}
+
+fn load_png(path: &PathBuf) -> Option<Vec<u8>> {
+    let image = image::open(path).ok()?.to_rgba8();
+    (image.dimensions() == (THUMBNAIL_SIZE, THUMBNAIL_SIZE)).then(|| image.into_raw())
+}
+
+fn spawn_rig(commands: &mut Commands, images: &mut Assets<Image>, meshes: &mut Assets<Mesh>) -> ThumbnailRig {
+    let mut target = thumbnail_image(vec![0; (THUMBNAIL_SIZE * THUMBNAIL_SIZE * 4) as usize]);
+    target.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT;
+    let target = images.add(target);
+    let layer = RenderLayers::layer(THUMBNAIL_LAYER);
+    let camera = commands
+        .spawn((
+            Camera3d::default(),
+            Camera {
+                target: RenderTarget::Image(target.clone()),
+                order: -4,
+                is_active: false,
+                clear_color: ClearColorConfig::Custom(Color::srgb(0.35, 0.5, 0.75)),
+                ..default()
+            },
+            Msaa::Off,
+            Transform::from_xyz(-2.2, 1.2, 3.6).looking_at(Vec3::ZERO, Vec3::Y),
+            layer.clone(),
+        ))
+        .id();
+    commands.spawn((
+        DirectionalLight::default(),
+        Transform::from_translation(THUMBNAIL_SUN).looking_at(Vec3::ZERO, Vec3::Y),
+        layer.clone(),
+    ));
+    // Not a `CloudVolume`, so the volume systems leave it alone.
+    let volume = commands
+        .spawn((
+            Mesh3d(meshes.add(Cuboid::new(2.0, 2.0, 2.0))),
+            Transform::default(),
+            Visibility::Hidden,
+            layer,
+        ))
+        .id();
+    ThumbnailRig { camera, volume, target }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn thumbnail_system(
+    mut commands: Commands,
+    mut thumbnails: ResMut<PresetThumbnails>,
+    settings: Res<CloudSettings>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<CloudMaterial>>,
+    mut cameras: Query<&mut Camera>,
+) {
+    let thumbnails = &mut *thumbnails;
+    if !thumbnails.loaded {
+        thumbnails.loaded = true;
+        for (index, preset) in PRESETS.iter().enumerate() {
+            match thumbnails.path(preset).as_ref().and_then(load_png) {
+                Some(data) => thumbnails.images[index] = Some(images.add(thumbnail_image(data))),
+                None => thumbnails.queue.push_back(index),
+            }
+        }
+    }
+
+    if let Some(job) = thumbnails.job.as_mut() {
+        job.frames += 1;
+        if job.frames == WARMUP_FRAMES {
+            if let Some(rig) = &thumbnails.rig {
+                let size = UVec2::splat(THUMBNAIL_SIZE);
+                job.readback = Some(capture::read_back(&mut commands, rig.target.clone(), size, 4, finish_thumbnail));
+            }
+        }
+        return;
+    }
+    let Some(index) = thumbnails.queue.pop_front() else {
+        return;
+    };
+    let rig = thumbnails
+        .rig
+        .get_or_insert_with(|| spawn_rig(&mut commands, &mut images, &mut meshes));
+
+    let preset = &PRESETS[index];
+    let mut thumbnail_settings = CloudSettings {
+        profile_handle: settings.profile_handle.clone(),
+        resolution: UVec3::splat(noise::DEFAULT_RESOLUTION),
+        ..CloudSettings::defaults()
+    };
+    preset.apply(&mut thumbnail_settings, std::iter::empty());
+    let (data, _) = noise::bake_noise(&thumbnail_settings);
+    let mut noise_image = noise::noise_image(thumbnail_settings.resolution);
+    noise_image.data = data;
+    thumbnail_settings.noise_handle = images.add(noise_image);
+
+    let volume = CloudVolume {
+        noise_stretch: preset.noise_stretch,
+        cloud_type: preset.cloud_type,
+        anvil_amount: preset.anvil_amount,
+        anvil_start_height: preset.anvil_start_height,
+        ground_fog: preset.ground_fog,
+        ..default()
+    };
+    let mut material = CloudMaterial::new(&thumbnail_settings);
+    material.data.sync(&thumbnail_settings, &volume, &GlobalTransform::IDENTITY);
+    // The scene's sun selection leaves the thumbnail layer out.
+    let light = DirectionalLight::default();
+    material
+        .data
+        .set_sun(Some((THUMBNAIL_SUN.normalize(), light.color.to_linear() * light.illuminance)));
+    let material = materials.add(material);
+    commands
+        .entity(rig.volume)
+        .insert((MeshMaterial3d(material.clone()), Visibility::Visible));
+    if let Ok(mut camera) = cameras.get_mut(rig.camera) {
+        camera.is_active = true;
+    }
+    thumbnails.job = Some(ThumbnailJob {
+        index,
+        frames: 0,
+        noise: thumbnail_settings.noise_handle,
+        material,
+        readback: None,
+    });
+}
+
+fn finish_thumbnail(
+    trigger: Trigger<Captured>,
+    mut commands: Commands,
+    mut thumbnails: ResMut<PresetThumbnails>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<CloudMaterial>>,
+    mut cameras: Query<&mut Camera>,
+) {
+    let thumbnails = &mut *thumbnails;
+    let Some(job) = thumbnails.job.take_if(|job| job.readback == Some(trigger.entity())) else {
+        return;
+    };
+    images.remove(&job.noise);
+    materials.remove(&job.material);
+    if let Some(rig) = &thumbnails.rig {
+        commands.entity(rig.volume).insert(Visibility::Hidden);
+        if let Ok(mut camera) = cameras.get_mut(rig.camera) {
+            camera.is_active = false;
+        }
+    }
+
+    let data = trigger.event().0.clone();
+    let preset = &PRESETS[job.index];
+    if let Some(path) = thumbnails.path(preset) {
+        let saved = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .map_err(|err| err.to_string())
+            .and_then(|()| {
+                image::save_buffer(&path, &data, THUMBNAIL_SIZE, THUMBNAIL_SIZE, image::ExtendedColorType::Rgba8)
+                    .map_err(|err| err.to_string())
+            });
+        if let Err(err) = saved {
+            warn!("Failed to cache thumbnail {}: {}", path.display(), err);
+        }
+    }
+    if let Some(old) = thumbnails.images[job.index].replace(images.add(thumbnail_image(data))) {
+        images.remove(&old);
+    }
+}
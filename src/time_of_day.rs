@@ -0,0 +1,174 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_atmosphere::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use crate::CloudSettings;
+#[cfg(feature = "ui")]
+use crate::CloudUiSet;
+
+/// Drives the sun direction, light color, cloud ambient and, in the editor,
+/// the sky from a single clock. While paused nothing is written, so the manual controls
+/// take over again.
+pub struct TimeOfDayPlugin;
+
+impl Plugin for TimeOfDayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeOfDay>()
+            .add_systems(Update, time_of_day_system.before(crate::update_material_system));
+        #[cfg(feature = "ui")]
+        app.add_systems(
+            Update,
+            time_of_day_ui_system
+                .in_set(CloudUiSet)
+                .before(time_of_day_system),
+        );
+    }
+}
+
+/// Marks the directional light moved by the day/night cycle.
+#[derive(Component)]
+pub struct Sun;
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeOfDay {
+    pub hour: f32,  // 0..24
+    pub speed: f32, // in-game hours per second
+    pub paused: bool,
+    pub sun_azimuth: f32,   // degrees, rotates the sun path around +Y
+    pub max_elevation: f32, // degrees above the horizon at noon
+    pub day_illuminance: f32,
+    pub moon_floor: f32, // fraction of daylight kept at night
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            hour: 10.0,
+            speed: 0.1,
+            paused: true,
+            sun_azimuth: 30.0,
+            max_elevation: 60.0,
+            day_illuminance: bevy::pbr::light_consts::lux::AMBIENT_DAYLIGHT,
+            moon_floor: 0.05,
+        }
+    }
+}
+
+impl TimeOfDay {
+    /// Unit vector pointing towards the sun. Rises in +X at 06:00, peaks at
+    /// `max_elevation` at noon and sets in -X at 18:00, before the azimuth
+    /// rotation is applied.
+    pub fn sun_direction(&self) -> Vec3 {
+        let hour_angle = (self.hour / 24.0) * TAU - PI;
+        let tilt = self.max_elevation.to_radians();
+        let local = Vec3::new(
+            -hour_angle.sin(),
+            hour_angle.cos() * tilt.sin(),
+            hour_angle.cos() * tilt.cos(),
+        );
+        Quat::from_rotation_y(self.sun_azimuth.to_radians()) * local
+    }
+
+    /// Sine of the sun's elevation; negative below the horizon.
+    pub fn sun_height(&self) -> f32 {
+        self.sun_direction().y
+    }
+}
+
+/// Light color for a given sun height: warm near the horizon, neutral white
+/// once the sun is well up.
+fn sun_color(height: f32) -> LinearRgba {
+    let warm = LinearRgba::rgb(1.0, 0.45, 0.2);
+    let noon = LinearRgba::rgb(1.0, 0.96, 0.9);
+    let t = (height / 0.4).clamp(0.0, 1.0);
+    warm.mix(&noon, t * t * (3.0 - 2.0 * t))
+}
+
+pub(crate) fn time_of_day_system(
+    time: Res<Time>,
+    mut time_of_day: ResMut<TimeOfDay>,
+    mut last_hour: Local<Option<f32>>,
+    mut settings: ResMut<CloudSettings>,
+    #[cfg(feature = "ui")] mut atmosphere: AtmosphereMut<Nishita>,
+    mut suns: Query<(&mut Transform, &mut DirectionalLight), With<Sun>>,
+) {
+    if !time_of_day.paused {
+        let hour = time_of_day.hour + time_of_day.speed * time.delta_secs();
+        time_of_day.hour = hour.rem_euclid(24.0);
+    } else if last_hour.is_none_or(|hour| hour == time_of_day.hour) {
+        // Paused and not scrubbed: leave the manual controls alone.
+        *last_hour = Some(time_of_day.hour);
+        return;
+    }
+    *last_hour = Some(time_of_day.hour);
+
+    let sun_dir = time_of_day.sun_direction();
+    let height = sun_dir.y;
+    let daylight = height.max(0.0).sqrt();
+    let moon_floor = time_of_day.moon_floor;
+
+    // Below the horizon the moon takes over from the opposite direction.
+    let (light_dir, color, illuminance) = if height > 0.0 {
+        (
+            sun_dir,
+            sun_color(height),
+            time_of_day.day_illuminance * daylight.max(moon_floor),
+        )
+    } else {
+        (
+            -sun_dir,
+            LinearRgba::rgb(0.6, 0.7, 1.0),
+            time_of_day.day_illuminance * moon_floor,
+        )
+    };
+    for (mut transform, mut light) in &mut suns {
+        *transform = Transform::default().looking_to(-light_dir, Vec3::Y);
+        light.color = color.into();
+        light.illuminance = illuminance;
+    }
+    #[cfg(feature = "ui")]
+    {
+        atmosphere.sun_position = sun_dir;
+    }
+
+    // Ambient: the defaults at full daylight, tinted at dusk and dimmed to the
+    // moon floor at night, never fully black.
+    let tint = sun_color(height.max(0.0));
+    let brightness = daylight.max(moon_floor);
+    let ambient = |base: f32| {
+        let day = LinearRgba::rgb(tint.red * base, tint.green * base, tint.blue * base) * brightness;
+        let night = LinearRgba::rgb(0.5, 0.6, 0.9) * (moon_floor * base);
+        Color::from(day.mix(&night, 1.0 - daylight))
+    };
+    settings.ambient_top = ambient(1.0);
+    settings.ambient_bottom = ambient(0.6);
+}
+
+#[cfg(feature = "ui")]
+fn time_of_day_ui_system(mut contexts: EguiContexts, mut time_of_day: ResMut<TimeOfDay>) {
+    egui::Window::new("Time of Day")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                let label = if time_of_day.paused { "Play" } else { "Pause" };
+                if ui.button(label).clicked() {
+                    time_of_day.paused = !time_of_day.paused;
+                }
+                ui.add(egui::Slider::new(&mut time_of_day.hour, 0.0..=24.0).text("Hour"));
+            });
+            ui.add(egui::Slider::new(&mut time_of_day.speed, 0.0..=2.0).text("Speed (h/s)"));
+            ui.add(egui::Slider::new(&mut time_of_day.sun_azimuth, -180.0..=180.0).text("Sun Azimuth"));
+            ui.add(egui::Slider::new(&mut time_of_day.max_elevation, 5.0..=90.0).text("Max Elevation"));
+            ui.add(egui::Slider::new(&mut time_of_day.moon_floor, 0.0..=0.5).text("Moon Floor"));
+            ui.label(format!(
+                "Sun elevation: {:.1}°",
+                time_of_day.sun_height().asin().to_degrees()
+            ));
+        });
+}
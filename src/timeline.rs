@@ -0,0 +1,295 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use crate::{CloudSettings, CloudUiSet};
+
+/// Keyframed global parameters, e.g. "threshold 0.6 → 0.2 over 8 s while the
+/// wind picks up". While playing or scrubbing the animated values are written
+/// into `CloudSettings`, so the settings sliders follow them.
+pub struct TimelinePlugin;
+
+impl Plugin for TimelinePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ParameterTimeline>().add_systems(
+            Update,
+            (timeline_ui_system.in_set(CloudUiSet), timeline_system)
+                .chain()
+                .before(crate::update_material_system),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimelineParameter {
+    Density,
+    Threshold,
+    Absorption,
+    Coverage,
+    SunIntensity,
+    PhaseG,
+    WindX,
+    WindZ,
+}
+
+impl TimelineParameter {
+    pub const ALL: [TimelineParameter; 8] = [
+        Self::Density,
+        Self::Threshold,
+        Self::Absorption,
+        Self::Coverage,
+        Self::SunIntensity,
+        Self::PhaseG,
+        Self::WindX,
+        Self::WindZ,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Density => "Density",
+            Self::Threshold => "Threshold",
+            Self::Absorption => "Absorption",
+            Self::Coverage => "Coverage",
+            Self::SunIntensity => "Sun Intensity",
+            Self::PhaseG => "Phase G",
+            Self::WindX => "Wind X",
+            Self::WindZ => "Wind Z",
+        }
+    }
+
+    pub fn get(self, settings: &CloudSettings) -> f32 {
+        match self {
+            Self::Density => settings.density_multiplier,
+            Self::Threshold => settings.threshold,
+            Self::Absorption => settings.absorption,
+            Self::Coverage => settings.coverage,
+            Self::SunIntensity => settings.sun_intensity,
+            Self::PhaseG => settings.phase_g,
+            Self::WindX => settings.wind.x,
+            Self::WindZ => settings.wind.z,
+        }
+    }
+
+    pub fn set(self, settings: &mut CloudSettings, value: f32) {
+        match self {
+            Self::Density => settings.density_multiplier = value,
+            // A keyframed threshold overrides auto threshold mode, as the
+            // slider does.
+            Self::Threshold => {
+                settings.threshold = value;
+                settings.auto_threshold = None;
+            }
+            Self::Absorption => settings.absorption = value,
+            Self::Coverage => settings.coverage = value,
+            Self::SunIntensity => settings.sun_intensity = value,
+            Self::PhaseG => settings.phase_g = value,
+            Self::WindX => settings.wind.x = value,
+            Self::WindZ => settings.wind.z = value,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Smoothstep between keys, easing in and out of each.
+    Smooth,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f32, // seconds
+    pub value: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimelineTrack {
+    pub parameter: TimelineParameter,
+    pub interpolation: Interpolation,
+    /// Sorted by time.
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl TimelineTrack {
+    /// Value at `time`, holding the first and last keys outside their range.
+    pub fn sample(&self, time: f32) -> Option<f32> {
+        let first = self.keyframes.first()?;
+        if time <= first.time {
+            return Some(first.value);
+        }
+        for pair in self.keyframes.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if time <= b.time {
+                let span = (b.time - a.time).max(1e-6);
+                let t = ((time - a.time) / span).clamp(0.0, 1.0);
+                let t = match self.interpolation {
+                    Interpolation::Linear => t,
+                    Interpolation::Smooth => t * t * (3.0 - 2.0 * t),
+                };
+                return Some(a.value.lerp(b.value, t));
+            }
+        }
+        self.keyframes.last().map(|key| key.value)
+    }
+}
+
+/// Saved with sessions; the playhead and transport state are not.
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParameterTimeline {
+    pub tracks: Vec<TimelineTrack>,
+    pub looping: bool,
+    #[serde(skip)]
+    pub time: f32,
+    #[serde(skip)]
+    pub playing: bool,
+    /// The playhead was moved while paused; apply once.
+    #[serde(skip)]
+    scrubbed: bool,
+}
+
+impl ParameterTimeline {
+    /// Time of the last keyframe over all tracks.
+    pub fn duration(&self) -> f32 {
+        self.tracks
+            .iter()
+            .filter_map(|track| track.keyframes.last())
+            .map(|key| key.time)
+            .fold(0.0, f32::max)
+    }
+
+    pub fn apply(&self, settings: &mut CloudSettings) {
+        for track in &self.tracks {
+            if let Some(value) = track.sample(self.time) {
+                track.parameter.set(settings, value);
+            }
+        }
+    }
+}
+
+pub(crate) fn timeline_system(time: Res<Time>, mut timeline: ResMut<ParameterTimeline>, mut settings: ResMut<CloudSettings>) {
+    if timeline.playing {
+        let duration = timeline.duration();
+        timeline.time += time.delta_secs();
+        if timeline.time > duration {
+            if timeline.looping && duration > 0.0 {
+                timeline.time %= duration;
+            } else {
+                timeline.time = duration;
+                timeline.playing = false;
+            }
+        }
+    } else if !timeline.scrubbed {
+        return;
+    }
+    timeline.scrubbed = false;
+    timeline.apply(&mut settings);
+}
+
+fn timeline_ui_system(
+    mut contexts: EguiContexts,
+    mut timeline: ResMut<ParameterTimeline>,
+    settings: Res<CloudSettings>,
+    mut new_parameter: Local<Option<TimelineParameter>>,
+) {
+    egui::Window::new("Timeline")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let duration = timeline.duration();
+            ui.horizontal(|ui| {
+                let label = if timeline.playing { "Pause" } else { "Play" };
+                if ui.button(label).clicked() {
+                    if !timeline.playing && timeline.time >= duration {
+                        timeline.time = 0.0;
+                    }
+                    timeline.playing = !timeline.playing;
+                }
+                if ui.button("Rewind").clicked() {
+                    timeline.time = 0.0;
+                    timeline.scrubbed = true;
+                }
+                ui.checkbox(&mut timeline.looping, "Loop");
+            });
+            let mut playhead = timeline.time;
+            if ui
+                .add(egui::Slider::new(&mut playhead, 0.0..=duration.max(1.0)).text("Time (s)"))
+                .changed()
+            {
+                timeline.time = playhead;
+                timeline.scrubbed = true;
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                let parameter = new_parameter.get_or_insert(TimelineParameter::Threshold);
+                egui::ComboBox::from_id_salt("timeline_parameter")
+                    .selected_text(parameter.label())
+                    .show_ui(ui, |ui| {
+                        for option in TimelineParameter::ALL {
+                            ui.selectable_value(parameter, option, option.label());
+                        }
+                    });
+                if ui.button("Add Track").clicked() {
+                    let parameter = *parameter;
+                    let value = parameter.get(&settings);
+                    let time = timeline.time;
+                    timeline.tracks.push(TimelineTrack {
+                        parameter,
+                        interpolation: Interpolation::Linear,
+                        keyframes: vec![Keyframe { time, value }],
+                    });
+                }
+            });
+
+            let now = timeline.time;
+            let mut remove_track = None;
+            let mut edited = false;
+            for (index, track) in timeline.tracks.iter_mut().enumerate() {
+                ui.separator();
+                ui.push_id(index, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.strong(track.parameter.label());
+                        egui::ComboBox::from_id_salt("interpolation")
+                            .selected_text(format!("{:?}", track.interpolation))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut track.interpolation, Interpolation::Linear, "Linear");
+                                ui.selectable_value(&mut track.interpolation, Interpolation::Smooth, "Smooth");
+                            });
+                        if ui.button("Add Key").on_hover_text("Current value at the playhead").clicked() {
+                            let value = track.parameter.get(&settings);
+                            track.keyframes.retain(|key| key.time != now);
+                            track.keyframes.push(Keyframe { time: now, value });
+                            edited = true;
+                        }
+                        if ui.button("Remove Track").clicked() {
+                            remove_track = Some(index);
+                        }
+                    });
+                    let mut remove_key = None;
+                    for (key_index, key) in track.keyframes.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            edited |= ui
+                                .add(egui::DragValue::new(&mut key.time).speed(0.05).range(0.0..=3600.0).suffix(" s"))
+                                .changed();
+                            edited |= ui.add(egui::DragValue::new(&mut key.value).speed(0.01)).changed();
+                            if ui.small_button("x").clicked() {
+                                remove_key = Some(key_index);
+                            }
+                        });
+                    }
+                    if let Some(key_index) = remove_key {
+                        track.keyframes.remove(key_index);
+                        edited = true;
+                    }
+                    track.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+                });
+            }
+            if let Some(index) = remove_track {
+                timeline.tracks.remove(index);
+            }
+            if edited && !timeline.playing {
+                timeline.scrubbed = true;
+            }
+        });
+}
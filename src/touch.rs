@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+
+/// Camera deltas produced by touch gestures for one frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TouchDeltas {
+    /// One-finger drag, in pixels.
+    pub orbit: Vec2,
+    /// Two-finger drag of the midpoint, in pixels.
+    pub pan: Vec2,
+    /// Ratio of the previous finger spread to the current one; multiply the
+    /// orbit distance by it.
+    pub pinch: f32,
+}
+
+/// Tracks up to two touches across frames. Fingers are identified by touch
+/// id, so lifting them in either order only drops the lifted one; whenever the
+/// tracked set changes the gesture re-baselines instead of producing a jump.
+#[derive(Default)]
+pub struct TouchGesture {
+    fingers: Vec<(u64, Vec2)>,
+}
+
+impl TouchGesture {
+    /// Follows the active touches and returns this frame's deltas.
+    /// `accept_new` is false while the pointer belongs to the UI, so touches
+    /// starting on a window never join the gesture.
+    pub fn update(&mut self, touches: &Touches, accept_new: bool) -> TouchDeltas {
+        let previous = self.fingers.clone();
+
+        self.fingers.retain(|(id, _)| touches.get_pressed(*id).is_some());
+        if accept_new {
+            for touch in touches.iter() {
+                if self.fingers.len() >= 2 {
+                    break;
+                }
+                if !self.fingers.iter().any(|(id, _)| *id == touch.id()) {
+                    self.fingers.push((touch.id(), touch.position()));
+                }
+            }
+        }
+        for (id, position) in &mut self.fingers {
+            if let Some(touch) = touches.get_pressed(*id) {
+                *position = touch.position();
+            }
+        }
+
+        let mut deltas = TouchDeltas {
+            pinch: 1.0,
+            ..default()
+        };
+        let same_fingers = previous.len() == self.fingers.len()
+            && previous
+                .iter()
+                .zip(&self.fingers)
+                .all(|((a, _), (b, _))| a == b);
+        if !same_fingers {
+            return deltas;
+        }
+
+        match (previous.as_slice(), self.fingers.as_slice()) {
+            ([(_, before)], [(_, after)]) => {
+                deltas.orbit = *after - *before;
+            }
+            ([(_, a0), (_, b0)], [(_, a1), (_, b1)]) => {
+                deltas.pan = (*a1 + *b1) * 0.5 - (*a0 + *b0) * 0.5;
+                let before = a0.distance(*b0);
+                let after = a1.distance(*b1);
+                if before > 1.0 && after > 1.0 {
+                    deltas.pinch = before / after;
+                }
+            }
+            _ => {}
+        }
+        deltas
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, transform::TransformSystem};
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts};
+
+#[cfg(feature = "ui")]
+use crate::CloudUiSet;
+use crate::{
+    material_sync::{CloudMaterialInputs, CloudSyncSet},
+    CloudVolume, CloudVolumeParams, SpawnCloudExt,
+};
+
+/// Most path points a trail hands the shader; matches the array length in
+/// `cloud_bindings.wgsl`.
+pub const MAX_TRAIL_POINTS: usize = 16;
+
+/// Positions a trail records, spread evenly over its length.
+const HISTORY_LEN: usize = 64;
+
+/// Cloud volumes that follow an entity and stretch out behind it: exhaust,
+/// contrails, dust behind a vehicle.
+///
+/// A `CloudTrail` records where its target has been over the last `length`
+/// seconds. Each frame, after transform propagation, its volume's box is
+/// fitted around that path plus the radius, and the path, thinned to
+/// `MAX_TRAIL_POINTS`, goes into `CloudMaterialInputs` as a chain of
+/// capsules the shader scales the coverage by (CLOUD_TRAIL). The noise keeps
+/// a fixed world scale while the box grows and moves, so the shapes do not
+/// swim.
+///
+/// When the target despawns, nothing more is recorded; the path it left
+/// ages out as before and the trail entity despawns once it is gone.
+pub struct CloudTrailPlugin;
+
+impl Plugin for CloudTrailPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrailDemo>()
+            .add_systems(Update, trail_demo_system)
+            .add_systems(
+                PostUpdate,
+                trail_system
+                    .after(TransformSystem::TransformPropagate)
+                    .before(CloudSyncSet::Resolve),
+            );
+        #[cfg(feature = "ui")]
+        app.add_systems(Update, trail_demo_ui_system.in_set(CloudUiSet).before(trail_demo_system));
+    }
+}
+
+/// Makes a cloud volume trail behind `target`. The volume's transform, noise
+/// stretch and noise offset are rewritten every frame; the rest of its
+/// `CloudVolume` applies as usual. Trail volumes must not have a parent.
+#[derive(Component, Clone, Debug)]
+pub struct CloudTrail {
+    pub target: Entity,
+    /// Seconds of history the trail covers.
+    pub length: f32,
+    /// World radius of the trail around the path.
+    pub radius: f32,
+    /// Exponent on the coverage fading towards the tail; 0 keeps full
+    /// coverage up to the end.
+    pub falloff: f32,
+    /// Coverage lost per second of age, on top of the falloff.
+    pub dissipation: f32,
+    /// World size of one noise tile.
+    pub noise_tile: f32,
+    /// Recorded seconds and world positions, oldest first.
+    history: VecDeque<(f32, Vec3)>,
+}
+
+impl CloudTrail {
+    pub fn new(target: Entity) -> Self {
+        Self {
+            target,
+            length: 3.0,
+            radius: 0.6,
+            falloff: 1.0,
+            dissipation: 0.3,
+            noise_tile: 4.0,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+
+    /// Drops what has aged past the length, then adds the target's position
+    /// if a sample is due.
+    fn record(&mut self, now: f32, target: Option<Vec3>) {
+        let length = self.length.max(0.01);
+        while self.history.front().is_some_and(|&(time, _)| now - time > length) {
+            self.history.pop_front();
+        }
+        let Some(position) = target else {
+            return;
+        };
+        let due = match self.history.back() {
+            Some(&(time, _)) => now - time >= length / HISTORY_LEN as f32,
+            None => true,
+        };
+        if due {
+            if self.history.len() == HISTORY_LEN {
+                self.history.pop_front();
+            }
+            self.history.push_back((now, position));
+        }
+    }
+
+    /// Coverage of the path after `age` seconds.
+    fn strength(&self, age: f32) -> f32 {
+        let left = (1.0 - age / self.length.max(0.01)).clamp(0.0, 1.0);
+        left.powf(self.falloff.max(0.0)) * (-self.dissipation.max(0.0) * age).exp()
+    }
+
+    /// The path newest first, starting at the target's current position when
+    /// it is alive: world position and coverage, at most `MAX_TRAIL_POINTS`
+    /// evenly picked from the history.
+    fn path(&self, now: f32, head: Option<Vec3>) -> Vec<Vec4> {
+        let points: Vec<Vec4> = head
+            .map(|position| (now, position))
+            .into_iter()
+            .chain(self.history.iter().rev().copied())
+            .map(|(time, position)| position.extend(self.strength(now - time)))
+            .collect();
+        if points.len() <= MAX_TRAIL_POINTS {
+            return points;
+        }
+        let last = (points.len() - 1) as f32;
+        (0..MAX_TRAIL_POINTS)
+            .map(|i| points[(i as f32 * last / (MAX_TRAIL_POINTS - 1) as f32).round() as usize])
+            .collect()
+    }
+}
+
+fn trail_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    targets: Query<&GlobalTransform, Without<CloudTrail>>,
+    mut trails: Query<(
+        Entity,
+        &mut CloudTrail,
+        &mut Transform,
+        &mut GlobalTransform,
+        &mut CloudVolume,
+        &mut CloudMaterialInputs,
+    )>,
+) {
+    let now = time.elapsed_secs();
+    for (entity, mut trail, mut transform, mut global, mut volume, mut inputs) in &mut trails {
+        let target = targets.get(trail.target).ok().map(GlobalTransform::translation);
+        trail.record(now, target);
+        let path = trail.path(now, target);
+        // Only a lost target leaves the path empty.
+        if path.is_empty() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let radius = trail.radius.max(0.01);
+        let (min, max) = path.iter().fold((Vec3::MAX, Vec3::MIN), |(min, max), point| {
+            (min.min(point.truncate()), max.max(point.truncate()))
+        });
+        let center = (min + max) * 0.5;
+        let half_extents = (max - min) * 0.5 + radius;
+        *transform = Transform::from_translation(center).with_scale(half_extents);
+        // Propagation has already run this frame; the material sync reads
+        // the global transform.
+        *global = GlobalTransform::from(*transform);
+
+        // The shader divides by the box size and the stretch; stretching by
+        // the size over the tile and offsetting by the center cancel both.
+        let stretch = half_extents * 2.0 / trail.noise_tile.max(0.01);
+        volume.noise_stretch = stretch;
+        volume.noise_offset = center / trail.noise_tile.max(0.01) - 0.5 / stretch;
+
+        inputs.trail_info = Vec4::new(path.len() as f32, radius, 0.0, 0.0);
+        inputs.trail = [Vec4::ZERO; MAX_TRAIL_POINTS];
+        inputs.trail[..path.len()].copy_from_slice(&path);
+    }
+}
+
+#[derive(Resource, Default)]
+struct TrailDemo {
+    enabled: bool,
+}
+
+/// The sphere the demo trail follows.
+#[derive(Component)]
+struct TrailDemoMover;
+
+/// Circle the demo mover flies, and its angular speed in radians per second.
+const DEMO_CENTER: Vec3 = Vec3::new(0.0, 2.5, 0.0);
+const DEMO_RADIUS: f32 = 4.0;
+const DEMO_SPEED: f32 = 1.2;
+
+fn demo_position(seconds: f32) -> Vec3 {
+    let (sin, cos) = (seconds * DEMO_SPEED).sin_cos();
+    DEMO_CENTER + Vec3::new(cos, 0.0, sin) * DEMO_RADIUS
+}
+
+/// Spawns the mover with a trail behind it. Turning the demo off despawns
+/// only the mover, so its trail dissipates on its own.
+fn trail_demo_system(
+    mut commands: Commands,
+    demo: Res<TrailDemo>,
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut movers: Query<(Entity, &mut Transform), With<TrailDemoMover>>,
+) {
+    let position = demo_position(time.elapsed_secs());
+    if demo.is_changed() {
+        match (demo.enabled, movers.is_empty()) {
+            (true, true) => {
+                let mover = commands
+                    .spawn((
+                        Mesh3d(meshes.add(Sphere::new(0.15))),
+                        MeshMaterial3d(materials.add(Color::srgb(0.9, 0.45, 0.2))),
+                        Transform::from_translation(position),
+                        TrailDemoMover,
+                    ))
+                    .id();
+                commands
+                    .spawn_cloud(CloudVolumeParams::default().at(position))
+                    .insert(CloudTrail::new(mover));
+            }
+            (false, false) => {
+                for (entity, _) in &movers {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            _ => {}
+        }
+    }
+    for (_, mut transform) in &mut movers {
+        transform.translation = position;
+    }
+}
+
+#[cfg(feature = "ui")]
+fn trail_demo_ui_system(mut contexts: EguiContexts, mut demo: ResMut<TrailDemo>, mut trails: Query<&mut CloudTrail>) {
+    egui::Window::new("Cloud Trail")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("A sphere circling with a cloud trail behind it; turning it off leaves the trail to dissipate.");
+            ui.checkbox(&mut demo.enabled, "Spawn Mover");
+            for mut trail in &mut trails {
+                ui.separator();
+                ui.add(egui::Slider::new(&mut trail.length, 0.5..=10.0).text("Length (s)"));
+                ui.add(egui::Slider::new(&mut trail.radius, 0.1..=3.0).text("Radius"));
+                ui.add(egui::Slider::new(&mut trail.falloff, 0.0..=4.0).text("Falloff"));
+                ui.add(egui::Slider::new(&mut trail.dissipation, 0.0..=3.0).text("Dissipation"));
+                ui.add(egui::Slider::new(&mut trail.noise_tile, 0.5..=16.0).text("Noise Tile"));
+            }
+        });
+}
@@ -0,0 +1,99 @@
+//! Keeps the settings window cheap when nothing changes: the histogram and
+//! slice preview are prepared when a new noise arrives instead of every
+//! frame, and the UI's own cost is measured for the stats window.
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+
+use crate::{
+    noise::{self, BakeStats, NoiseBaked, SlicePreview},
+    CloudUiSet,
+};
+
+/// Frame time the settings UI should stay under with its sections collapsed.
+pub const UI_TARGET: Duration = Duration::from_micros(200);
+
+pub struct UiCachePlugin;
+
+impl Plugin for UiCachePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NoisePreviews>()
+            .init_resource::<UiFrameTime>()
+            .add_systems(
+                Update,
+                (
+                    ui_time_begin_system.before(CloudUiSet),
+                    ui_time_end_system.after(CloudUiSet),
+                    (noise_baked_system, slice_preview_system)
+                        .chain()
+                        .after(CloudUiSet)
+                        .after(crate::swap_noise_system),
+                ),
+            );
+    }
+}
+
+/// What the noise widgets draw, prepared outside the UI.
+#[derive(Resource, Default)]
+pub struct NoisePreviews {
+    /// Counts `NoiseBaked`; cached widgets rebuild when it moves on.
+    pub generation: u64,
+    /// The noise of the latest `NoiseBaked`; empty until the first bake.
+    pub noise: Handle<Image>,
+    /// From `noise::histogram_bars`.
+    pub histogram: Vec<f32>,
+    pub slice: SlicePreview,
+}
+
+/// Wall time from the first to the last settings UI system. Systems running
+/// alongside them are included, so it is an upper bound.
+#[derive(Resource, Default)]
+pub struct UiFrameTime {
+    started: Option<Instant>,
+    pub last: Duration,
+    /// Smoothed over roughly the last second.
+    pub average: Duration,
+}
+
+fn ui_time_begin_system(mut time: ResMut<UiFrameTime>) {
+    time.started = Some(Instant::now());
+}
+
+fn ui_time_end_system(mut time: ResMut<UiFrameTime>) {
+    let Some(started) = time.started.take() else {
+        return;
+    };
+    time.last = started.elapsed();
+    time.average = time.average.mul_f32(0.95) + time.last.mul_f32(0.05);
+}
+
+fn noise_baked_system(
+    mut baked: EventReader<NoiseBaked>,
+    stats: Res<BakeStats>,
+    mut previews: ResMut<NoisePreviews>,
+) {
+    let Some(baked) = baked.read().last() else {
+        return;
+    };
+    previews.generation += 1;
+    previews.noise = baked.image.clone();
+    previews.histogram = noise::histogram_bars(&stats);
+}
+
+/// Uploads the slice preview while it is shown and its noise, slice or
+/// channel changed.
+fn slice_preview_system(
+    mut contexts: EguiContexts,
+    images: Res<Assets<Image>>,
+    mut previews: ResMut<NoisePreviews>,
+) {
+    let previews = &mut *previews;
+    if !std::mem::take(&mut previews.slice.open) {
+        return;
+    }
+    if let (Some(image), Some(ctx)) = (images.get(&previews.noise), contexts.try_ctx_mut()) {
+        previews.slice.update(ctx, image, previews.generation);
+    }
+}
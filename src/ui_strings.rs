@@ -0,0 +1,107 @@
+//! Translatable labels and tooltips for the settings window.
+
+use std::{collections::HashMap, fmt::Display, path::PathBuf};
+
+use bevy::{asset::io::file::FileAssetReader, prelude::*};
+
+/// Language used when neither `--lang` nor the window picks one.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Built in, so every key has text even without the assets folder.
+const ENGLISH: &str = include_str!("../assets/lang/en.ron");
+
+/// Text of the settings window in the selected language.
+///
+/// Each language is a RON map from key to text in `assets/lang/<code>.ron`.
+/// A key the selected language lacks falls back to English, and one English
+/// lacks shows the key itself, so a partial translation stays usable. Units
+/// are appended by the widgets and are not translated.
+#[derive(Resource, Clone, Debug)]
+pub struct UiStrings {
+    pub language: String,
+    /// Codes of the languages in `assets/lang`, for the window's dropdown.
+    pub available: Vec<String>,
+    english: HashMap<String, String>,
+    selected: HashMap<String, String>,
+}
+
+impl Default for UiStrings {
+    fn default() -> Self {
+        let english = ron::from_str(ENGLISH).unwrap_or_else(|err| {
+            error!("Built-in English strings are malformed: {}", err);
+            HashMap::new()
+        });
+        Self {
+            language: DEFAULT_LANGUAGE.to_string(),
+            available: available_languages(),
+            english,
+            selected: HashMap::new(),
+        }
+    }
+}
+
+fn language_dir() -> PathBuf {
+    FileAssetReader::get_base_path().join("assets").join("lang")
+}
+
+fn available_languages() -> Vec<String> {
+    let mut languages: Vec<String> = std::fs::read_dir(language_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .collect();
+    if !languages.iter().any(|code| code == DEFAULT_LANGUAGE) {
+        languages.push(DEFAULT_LANGUAGE.to_string());
+    }
+    languages.sort();
+    languages
+}
+
+impl UiStrings {
+    /// Reads `--lang <code>`.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut strings = Self::default();
+        if let Some(index) = args.iter().position(|arg| arg == "--lang") {
+            let code = args.get(index + 1).ok_or("--lang needs a language code")?;
+            strings.select(code)?;
+        }
+        Ok(strings)
+    }
+
+    /// Switches to the language in `assets/lang/<code>.ron`. English needs
+    /// no file.
+    pub fn select(&mut self, code: &str) -> Result<(), String> {
+        if code == DEFAULT_LANGUAGE {
+            self.selected.clear();
+        } else {
+            let path = language_dir().join(format!("{}.ron", code));
+            let text = std::fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            self.selected = ron::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+        }
+        self.language = code.to_string();
+        Ok(())
+    }
+
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.selected
+            .get(key)
+            .or_else(|| self.english.get(key))
+            .map_or(key, String::as_str)
+    }
+
+    /// `get` with each `{}` replaced by the next of `values`.
+    pub fn fill(&self, key: &str, values: &[&dyn Display]) -> String {
+        let mut parts = self.get(key).split("{}");
+        let mut text = parts.next().unwrap_or_default().to_string();
+        for (part, value) in parts.zip(values.iter().map(Some).chain(std::iter::repeat(None))) {
+            if let Some(value) = value {
+                text.push_str(&value.to_string());
+            }
+            text.push_str(part);
+        }
+        text
+    }
+}
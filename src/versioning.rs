@@ -0,0 +1,288 @@
+//! Versioned RON for settings dumps and sessions, including autosaves.
+//!
+//! Every document carries the `FORMAT_VERSION` it was written with; files
+//! from before versioning have none and read as version 0. Loading checks the
+//! version before parsing, so files from a newer build fail with a clear
+//! error instead of mis-parsed values, then parses with the current structs
+//! (fields added since fall back to their defaults), warns about fields this
+//! build no longer has, and runs the migrations from the file's version up.
+//!
+//! Changing what a saved field means, renaming one or dropping one bumps
+//! `FORMAT_VERSION` and adds a step to the `migrate_from` of the type, plus a
+//! fixture of the old format in `fixtures/` for `tests/migrations.rs`.
+
+use std::fmt;
+
+use serde::{
+    de::{self, DeserializeOwned, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+
+use crate::CloudSettings;
+#[cfg(feature = "ui")]
+use crate::session::CloudSession;
+
+/// Format written by this build.
+///
+/// - 0: unversioned; contact with geometry was a hard cut.
+/// - 1: `version` field; `contact_fade_distance` added.
+/// - 2: `wind_upper` and `wind_altitudes` added; `wind` is the lower wind.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// A document that knows how to upgrade itself from older formats.
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// A value whose serialized fields are exactly the ones this build reads.
+    fn template() -> Self;
+
+    fn version_mut(&mut self) -> &mut u32;
+
+    /// Upgrades values parsed from format `from` to `from + 1`.
+    fn migrate_from(&mut self, from: u32);
+
+    /// Upgrades documents nested inside this one, which carry their own
+    /// version.
+    fn migrate_nested(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl Versioned for CloudSettings {
+    fn template() -> Self {
+        Self::defaults()
+    }
+
+    fn version_mut(&mut self) -> &mut u32 {
+        &mut self.version
+    }
+
+    fn migrate_from(&mut self, from: u32) {
+        if from == 0 {
+            // Keep the hard cut the file was tuned with.
+            self.contact_fade_distance = 0.0;
+        }
+        if from == 1 {
+            // One wind blew at every height.
+            self.wind_upper = self.wind;
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+impl Versioned for CloudSession {
+    fn template() -> Self {
+        Self::default()
+    }
+
+    fn version_mut(&mut self) -> &mut u32 {
+        &mut self.version
+    }
+
+    fn migrate_from(&mut self, _from: u32) {}
+
+    fn migrate_nested(&mut self) -> Result<(), String> {
+        migrate(&mut self.settings)
+    }
+}
+
+/// Parses a document written by this or any older build. Returns warnings
+/// about fields that were dropped since, which are ignored.
+pub fn from_ron<T: Versioned>(text: &str) -> Result<(T, Vec<String>), String> {
+    let probe: VersionProbe = ron::from_str(text).map_err(|err| err.to_string())?;
+    check_version(probe.version)?;
+
+    let fields: Fields = ron::from_str(text).map_err(|err| err.to_string())?;
+    let template = ron::to_string(&T::template()).map_err(|err| err.to_string())?;
+    let known: Fields = ron::from_str(&template).map_err(|err| err.to_string())?;
+    let mut warnings = Vec::new();
+    fields.removed(&known, "", &mut warnings);
+
+    let mut value: T = ron::from_str(text).map_err(|err| err.to_string())?;
+    migrate(&mut value)?;
+    Ok((value, warnings))
+}
+
+/// Pretty RON. Values from `template` or `from_ron` already carry the
+/// current format.
+pub fn to_ron<T: Versioned>(value: &T) -> Result<String, String> {
+    ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()).map_err(|err| err.to_string())
+}
+
+fn check_version(version: u32) -> Result<(), String> {
+    if version > FORMAT_VERSION {
+        return Err(format!(
+            "written in format {} by a newer version of bevy-clouds; this one reads up to format {}",
+            version, FORMAT_VERSION
+        ));
+    }
+    Ok(())
+}
+
+fn migrate<T: Versioned>(value: &mut T) -> Result<(), String> {
+    let version = *value.version_mut();
+    check_version(version)?;
+    for from in version..FORMAT_VERSION {
+        value.migrate_from(from);
+    }
+    *value.version_mut() = FORMAT_VERSION;
+    value.migrate_nested()
+}
+
+/// Reads only the version of a document, skipping everything else.
+#[derive(Deserialize)]
+struct VersionProbe {
+    #[serde(default)]
+    version: u32,
+}
+
+/// Field names of a RON struct, with the fields of nested structs.
+#[derive(Default, Debug)]
+struct Fields(Vec<(String, Option<Fields>)>);
+
+impl Fields {
+    /// Pushes a warning for every field, at any depth, that `known` lacks.
+    /// Fields whose known value is not a struct, such as lists and `None`,
+    /// are not looked into, and neither are empty ones: RON maps read like
+    /// structs, and the template's maps are empty.
+    fn removed(&self, known: &Fields, prefix: &str, warnings: &mut Vec<String>) {
+        for (name, nested) in &self.0 {
+            let path = format!("{}{}", prefix, name);
+            match known.0.iter().find(|(known_name, _)| known_name == name) {
+                None => warnings.push(format!("ignoring `{}`, which this version no longer has", path)),
+                Some((_, Some(known_nested))) if !known_nested.0.is_empty() => {
+                    if let Some(nested) = nested {
+                        nested.removed(known_nested, &format!("{}.", path), warnings);
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Fields {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Node::deserialize(deserializer)?.0.unwrap_or_default())
+    }
+}
+
+/// Any RON value; keeps the field names when it is a struct.
+struct Node(Option<Fields>);
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NodeVisitor).map(Node)
+    }
+}
+
+struct NodeVisitor;
+
+impl<'de> Visitor<'de> for NodeVisitor {
+    type Value = Option<Fields>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a RON value")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut fields = Vec::new();
+        while let Some(key) = map.next_key::<Key>()? {
+            let value: Node = map.next_value()?;
+            if let Some(name) = key.0 {
+                fields.push((name, value.0));
+            }
+        }
+        Ok(Some(Fields(fields)))
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        Node::deserialize(deserializer).map(|node| node.0)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        Node::deserialize(deserializer).map(|node| node.0)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(None)
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_char<E: de::Error>(self, _: char) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_bytes<E: de::Error>(self, _: &[u8]) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+}
+
+/// A struct field name, or `None` for the keys of maps that are not strings.
+struct Key(Option<String>);
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(KeyVisitor).map(Key)
+    }
+}
+
+struct KeyVisitor;
+
+impl<'de> Visitor<'de> for KeyVisitor {
+    type Value = Option<String>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a field name or map key")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+        Ok(Some(name.to_string()))
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_char<E: de::Error>(self, _: char) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+}
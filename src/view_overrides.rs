@@ -0,0 +1,319 @@
+use std::sync::OnceLock;
+
+use bevy::{
+    core_pipeline::core_3d::Transparent3d,
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    pbr::{DrawMesh, RenderMaterialInstances, SetMaterialBindGroup, SetMeshBindGroup, SetMeshViewBindGroup},
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        render_phase::{
+            AddRenderCommand, DrawFunctions, PhaseItem, RenderCommand, RenderCommandResult, SetItemPipeline,
+            TrackedRenderPass, ViewSortedRenderPhases,
+        },
+        render_resource::{
+            binding_types::uniform_buffer, BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries,
+            DynamicUniformBuffer, RenderPipelineDescriptor, ShaderDefVal, ShaderStages, ShaderType,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        view::ExtractedView,
+        Render, RenderApp, RenderSet,
+    },
+};
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{accumulation::AccumulationSample, extension::LitCloudMaterial, CloudMaterial, CloudSettings};
+#[cfg(feature = "ui")]
+use crate::{CloudUiSet, OrbitCamera};
+
+/// Bind group index of the per-view data, after the material's.
+pub const VIEW_BIND_GROUP: usize = 3;
+
+/// Shader def set on the cloud pipelines that bind the per-view data, so the
+/// modules they share with other pipelines can read it.
+pub const VIEW_BINDING_DEF: &str = "CLOUD_VIEW_BINDING";
+
+/// Smallest render scale a view can ask for.
+pub const MIN_RENDER_SCALE: f32 = 0.0625;
+
+/// Created once the render device exists. `Material::specialize` has no world
+/// to fetch it from, and the pipelines that use it are only specialized after.
+static VIEW_LAYOUT: OnceLock<BindGroupLayout> = OnceLock::new();
+
+/// Lets cameras draw the clouds with their own march quality, so a minimap or
+/// split view can be cheaper than the main view without touching materials.
+///
+/// Every view gets a `CloudViewUniform`, from its `CloudViewOverrides` or the
+/// defaults, in one dynamic uniform buffer. The cloud materials draw with
+/// `DrawCloud`, which binds the view's entry at `VIEW_BIND_GROUP`, so the
+/// same material resolves its step counts per view.
+pub struct ViewOverridesPlugin;
+
+impl Plugin for ViewOverridesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<CloudViewOverrides>::default());
+        #[cfg(feature = "ui")]
+        app.add_systems(Update, view_overrides_ui_system.in_set(CloudUiSet));
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<CloudViewUniforms>()
+            .add_render_command::<Transparent3d, DrawCloud<CloudMaterial>>()
+            .add_render_command::<Transparent3d, DrawCloud<LitCloudMaterial>>()
+            .add_systems(
+                Render,
+                (
+                    draw_cloud_system.in_set(RenderSet::PhaseSort),
+                    prepare_view_uniforms_system.in_set(RenderSet::PrepareResources),
+                    prepare_view_bind_group_system.in_set(RenderSet::PrepareBindGroups),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        let render_device = render_app.world().resource::<RenderDevice>();
+        VIEW_LAYOUT.get_or_init(|| {
+            render_device.create_bind_group_layout(
+                "cloud_view_layout",
+                &BindGroupLayoutEntries::single(ShaderStages::FRAGMENT, uniform_buffer::<CloudViewUniform>(true)),
+            )
+        });
+    }
+}
+
+/// Per-camera replacements for the material's march and lighting parameters.
+/// `None` keeps the value from `CloudSettings`. `step_scale` and
+/// `render_scale` multiply both counts after that, so every volume keeps its
+/// own count relative to the others.
+#[derive(Component, ExtractComponent, Clone, Copy, Debug, Default, PartialEq)]
+pub struct CloudViewOverrides {
+    pub steps: Option<u32>,
+    pub light_steps: Option<u32>,
+    /// Whole multiple of both counts, for views that can afford more.
+    pub step_scale: Option<u32>,
+    /// Share of both counts, down to `MIN_RENDER_SCALE`, for views drawn
+    /// small: a 200px inset at 0.25 marches a quarter of the steps.
+    pub render_scale: Option<f32>,
+    /// `Some(false)` skips the detail noise in this view. It cannot add
+    /// detail to a material compiled without it.
+    pub detail: Option<bool>,
+    /// Weight, from 0 to 1, of multiple scattering's extra octaves on volumes
+    /// lit with single scattering. Volumes set to multiple scattering keep
+    /// all of theirs, and the other modes have no octaves to add.
+    pub multi_scatter: Option<f32>,
+}
+
+impl CloudViewOverrides {
+    fn uniform(&self, jitter: Vec3) -> CloudViewUniform {
+        let count = |value: Option<u32>| value.map_or(-1.0, |value| value.max(1) as f32);
+        CloudViewUniform {
+            steps: Vec4::new(
+                count(self.steps),
+                count(self.light_steps),
+                self.step_scale.unwrap_or(1).max(1) as f32,
+                // A NaN scale counts as unset rather than reaching `clamp`.
+                self.render_scale.filter(|scale| !scale.is_nan()).unwrap_or(1.0).clamp(MIN_RENDER_SCALE, 1.0),
+            ),
+            detail: Vec4::new(
+                self.detail.unwrap_or(true) as u32 as f32,
+                self.multi_scatter.filter(|weight| !weight.is_nan()).unwrap_or(0.0).clamp(0.0, 1.0),
+                0.0,
+                0.0,
+            ),
+            sample: jitter.extend(0.0),
+        }
+    }
+}
+
+/// `cloud_view` in `cloud_view.wgsl`.
+#[derive(ShaderType, Clone, Copy, Debug, PartialEq)]
+pub struct CloudViewUniform {
+    pub steps: Vec4, // x: steps, y: light steps, negative keeps the material value; z: multiple of both; w: render scale
+    pub detail: Vec4, // x: 0 skips the detail noise; y: weight of the extra scattering octaves
+    pub sample: Vec4, // xy: ray offset within the pixel; z: step jitter rotation; see `AccumulationSample::jitter`
+}
+
+/// Adds the per-view bind group to a cloud pipeline, with the step bounds of
+/// `CloudSettings` that `cloud_view.wgsl` resolves the counts under. Called
+/// from the materials' `specialize`, after the material's own group is in
+/// place.
+pub fn specialize_view(descriptor: &mut RenderPipelineDescriptor) {
+    if let Some(layout) = VIEW_LAYOUT.get() {
+        descriptor.layout.push(layout.clone());
+    }
+    if let Some(fragment) = descriptor.fragment.as_mut() {
+        fragment.shader_defs.extend([
+            VIEW_BINDING_DEF.into(),
+            ShaderDefVal::Int("CLOUD_MAX_STEPS".into(), CloudSettings::MAX_STEPS as i32),
+            ShaderDefVal::Int("CLOUD_MAX_LIGHT_STEPS".into(), CloudSettings::MAX_LIGHT_STEPS as i32),
+        ]);
+    }
+}
+
+/// `DrawMaterial` with the view's `CloudViewUniform` bound after the
+/// material.
+type DrawCloud<M> = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    SetMaterialBindGroup<M, 2>,
+    SetCloudViewBindGroup<VIEW_BIND_GROUP>,
+    DrawMesh,
+);
+
+#[derive(Resource, Default)]
+struct CloudViewUniforms {
+    buffer: DynamicUniformBuffer<CloudViewUniform>,
+}
+
+#[derive(Resource)]
+struct CloudViewBindGroup(BindGroup);
+
+/// Offset of a view's entry in `CloudViewUniforms`.
+#[derive(Component)]
+struct CloudViewOffset(u32);
+
+/// Swaps the material draw function for `DrawCloud` on every cloud item the
+/// material plugins queued.
+fn draw_cloud_system(
+    draw_functions: Res<DrawFunctions<Transparent3d>>,
+    clouds: Res<RenderMaterialInstances<CloudMaterial>>,
+    lit_clouds: Res<RenderMaterialInstances<LitCloudMaterial>>,
+    mut phases: ResMut<ViewSortedRenderPhases<Transparent3d>>,
+) {
+    let draw_functions = draw_functions.read();
+    let (Some(draw_cloud), Some(draw_lit_cloud)) = (
+        draw_functions.get_id::<DrawCloud<CloudMaterial>>(),
+        draw_functions.get_id::<DrawCloud<LitCloudMaterial>>(),
+    ) else {
+        return;
+    };
+    for phase in phases.values_mut() {
+        for item in &mut phase.items {
+            let entity = item.main_entity();
+            if clouds.0.contains_key(&entity) {
+                item.draw_function = draw_cloud;
+            } else if lit_clouds.0.contains_key(&entity) {
+                item.draw_function = draw_lit_cloud;
+            }
+        }
+    }
+}
+
+type ViewUniformSources<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, Option<&'static CloudViewOverrides>, Option<&'static AccumulationSample>),
+    With<ExtractedView>,
+>;
+
+fn prepare_view_uniforms_system(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut uniforms: ResMut<CloudViewUniforms>,
+    views: ViewUniformSources,
+) {
+    let Some(mut writer) = uniforms.buffer.get_writer(views.iter().len(), &render_device, &render_queue) else {
+        return;
+    };
+    for (entity, overrides, sample) in &views {
+        let jitter = sample.map_or(Vec3::ZERO, |sample| sample.jitter());
+        let offset = writer.write(&overrides.copied().unwrap_or_default().uniform(jitter));
+        commands.entity(entity).insert(CloudViewOffset(offset));
+    }
+}
+
+fn prepare_view_bind_group_system(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    uniforms: Res<CloudViewUniforms>,
+) {
+    let (Some(layout), Some(binding)) = (VIEW_LAYOUT.get(), uniforms.buffer.binding()) else {
+        return;
+    };
+    commands.insert_resource(CloudViewBindGroup(render_device.create_bind_group(
+        "cloud_view_bind_group",
+        layout,
+        &BindGroupEntries::single(binding),
+    )));
+}
+
+struct SetCloudViewBindGroup<const I: usize>;
+
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetCloudViewBindGroup<I> {
+    type Param = Option<SRes<CloudViewBindGroup>>;
+    type ViewQuery = &'static CloudViewOffset;
+    type ItemQuery = ();
+
+    fn render<'w>(
+        _item: &P,
+        offset: &'w CloudViewOffset,
+        _entity: Option<()>,
+        bind_group: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(bind_group) = bind_group else {
+            return RenderCommandResult::Skip;
+        };
+        pass.set_bind_group(I, &bind_group.into_inner().0, &[offset.0]);
+        RenderCommandResult::Success
+    }
+}
+
+#[cfg(feature = "ui")]
+fn view_overrides_ui_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut cameras: Query<(Entity, Option<&mut CloudViewOverrides>), With<OrbitCamera>>,
+) {
+    let Ok((entity, view_overrides)) = cameras.get_single_mut() else {
+        return;
+    };
+    egui::Window::new("View Overrides")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let mut edited = view_overrides.as_deref().copied().unwrap_or_default();
+            for (label, value, default) in [
+                ("Steps", &mut edited.steps, 16),
+                ("Light Steps", &mut edited.light_steps, 4),
+            ] {
+                ui.horizontal(|ui| {
+                    let mut enabled = value.is_some();
+                    if ui.checkbox(&mut enabled, label).changed() {
+                        *value = enabled.then_some(default);
+                    }
+                    if let Some(value) = value {
+                        ui.add(egui::Slider::new(value, 1..=128));
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                let mut enabled = edited.render_scale.is_some();
+                if ui.checkbox(&mut enabled, "Render Scale").changed() {
+                    edited.render_scale = enabled.then_some(0.5);
+                }
+                if let Some(scale) = edited.render_scale.as_mut() {
+                    ui.add(egui::Slider::new(scale, MIN_RENDER_SCALE..=1.0));
+                }
+            });
+            let mut detail = edited.detail.unwrap_or(true);
+            if ui.checkbox(&mut detail, "Detail Noise").changed() {
+                edited.detail = (!detail).then_some(false);
+            }
+            match view_overrides {
+                Some(mut view_overrides) => {
+                    view_overrides.set_if_neq(edited);
+                }
+                None if edited != CloudViewOverrides::default() => {
+                    commands.entity(entity).insert(edited);
+                }
+                None => {}
+            }
+        });
+}
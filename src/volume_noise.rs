@@ -0,0 +1,166 @@
+#[cfg(feature = "bake")]
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::CloudSettings;
+#[cfg(feature = "bake")]
+use crate::{
+    material_sync::CloudMaterialInputs,
+    noise::{self, BakeScratch, FractalType, NoiseType},
+    CloudVolume,
+};
+
+/// Gives volumes with a `CloudVolume::noise` override their own bake.
+/// Volumes with identical overrides share one texture, which is freed when
+/// the last of them goes away.
+#[cfg(feature = "bake")]
+pub struct VolumeNoisePlugin;
+
+#[cfg(feature = "bake")]
+impl Plugin for VolumeNoisePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VolumeNoiseTextures>()
+            .add_systems(Update, volume_noise_system.after(crate::update_material_system));
+    }
+}
+
+/// Per-volume replacement for the cell layout of the global bake. Every
+/// other bake parameter (resolution, noise type, warp, ...) still comes from
+/// `CloudSettings`. Builds without the `bake` feature cannot bake it, and
+/// draw such volumes with the global noise.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect, Serialize, Deserialize)]
+pub struct NoiseOverride {
+    pub seed: u32,
+    pub frequency: f32,
+    pub cell_count: u32,
+}
+
+impl NoiseOverride {
+    pub fn from_settings(settings: &CloudSettings) -> Self {
+        Self {
+            seed: settings.seed,
+            frequency: settings.frequency,
+            cell_count: settings.cell_count,
+        }
+    }
+
+    #[cfg(feature = "bake")]
+    fn key(&self) -> OverrideKey {
+        (self.seed, self.frequency.to_bits(), self.cell_count)
+    }
+}
+
+#[cfg(feature = "bake")]
+type OverrideKey = (u32, u32, u32);
+
+/// The global parameters an override bake inherits; a change rebakes every
+/// override texture.
+#[cfg(feature = "bake")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SharedBakeParams {
+    resolution: UVec3,
+    noise_type: NoiseType,
+    octaves: u32,
+    fractal_type: FractalType,
+    warp_amplitude: f32,
+    warp_frequency: u32,
+    normalize: bool,
+}
+
+#[cfg(feature = "bake")]
+impl SharedBakeParams {
+    fn from_settings(settings: &CloudSettings) -> Self {
+        Self {
+            resolution: noise::clamp_resolution(settings.resolution),
+            noise_type: settings.noise_type,
+            octaves: settings.octaves,
+            fractal_type: settings.fractal_type,
+            warp_amplitude: settings.warp_amplitude,
+            warp_frequency: settings.warp_frequency,
+            normalize: settings.normalize,
+        }
+    }
+}
+
+/// Override textures by parameters.
+#[cfg(feature = "bake")]
+#[derive(Resource, Default)]
+pub struct VolumeNoiseTextures {
+    textures: HashMap<OverrideKey, Handle<Image>>,
+    shared: Option<SharedBakeParams>,
+}
+
+#[cfg(feature = "bake")]
+fn volume_noise_system(
+    settings: Res<CloudSettings>,
+    mut textures: ResMut<VolumeNoiseTextures>,
+    mut scratch: ResMut<BakeScratch>,
+    mut images: ResMut<Assets<Image>>,
+    mut volumes: Query<(&CloudVolume, &mut CloudMaterialInputs)>,
+) {
+    let shared = SharedBakeParams::from_settings(&settings);
+    let stale = textures.shared != Some(shared);
+    textures.shared = Some(shared);
+
+    // Rebake existing textures when the shared parameters changed.
+    if stale {
+        for (&(seed, frequency, cell_count), handle) in &textures.textures {
+            let noise_override = NoiseOverride {
+                seed,
+                frequency: f32::from_bits(frequency),
+                cell_count,
+            };
+            if let Some(image) = images.get_mut(handle) {
+                let size = shared.resolution;
+                noise::bake_noise_into(&override_settings(&settings, &noise_override), &mut scratch, &mut image.data);
+                image.texture_descriptor.size = bevy::render::render_resource::Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: size.z,
+                };
+            }
+        }
+    }
+
+    let mut used = Vec::new();
+    for (volume, mut inputs) in &mut volumes {
+        let texture = match &volume.noise {
+            None => None,
+            Some(noise_override) => {
+                let key = noise_override.key();
+                used.push(key);
+                let texture = textures.textures.entry(key).or_insert_with(|| {
+                    let _span = info_span!("bake_volume_noise", seed = key.0, cells = key.2).entered();
+                    let mut image = noise::noise_image(shared.resolution);
+                    noise::bake_noise_into(&override_settings(&settings, noise_override), &mut scratch, &mut image.data);
+                    images.add(image)
+                });
+                Some(texture.clone())
+            }
+        };
+        if inputs.noise_texture != texture {
+            inputs.noise_texture = texture;
+        }
+    }
+
+    // Free textures no volume uses any more.
+    textures.textures.retain(|key, handle| {
+        let keep = used.contains(key);
+        if !keep {
+            images.remove(handle.id());
+        }
+        keep
+    });
+}
+
+#[cfg(feature = "bake")]
+fn override_settings(settings: &CloudSettings, noise_override: &NoiseOverride) -> CloudSettings {
+    CloudSettings {
+        seed: noise_override.seed,
+        frequency: noise_override.frequency,
+        cell_count: noise_override.cell_count,
+        ..settings.clone()
+    }
+}
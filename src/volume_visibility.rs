@@ -0,0 +1,40 @@
+use bevy::prelude::*;
+
+use crate::CloudVolume;
+
+/// Hides volumes marked `CloudHidden` without despawning them.
+///
+/// The marker, not `Visibility`, is the switch: the impostor LOD writes
+/// `Visibility` on the volumes it fades out, so this plugin keeps a hidden
+/// volume `Hidden` and hands it back as `Inherited` once the marker goes.
+/// Hidden volumes also skip the material sync and the LOD, so hiding the rest
+/// of a field isolates one volume's cost as well as its look. CPU queries
+/// over the volumes (picking, precipitation) decide for themselves whether
+/// hidden volumes count, through an `include_hidden` setting.
+pub struct VolumeVisibilityPlugin;
+
+impl Plugin for VolumeVisibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, hidden_volume_system);
+    }
+}
+
+/// Hides its `CloudVolume` until removed. Saved with sessions.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct CloudHidden;
+
+fn hidden_volume_system(
+    mut volumes: Query<(&mut Visibility, Has<CloudHidden>), With<CloudVolume>>,
+    mut shown: RemovedComponents<CloudHidden>,
+) {
+    for (mut visibility, hidden) in &mut volumes {
+        if hidden {
+            visibility.set_if_neq(Visibility::Hidden);
+        }
+    }
+    for entity in shown.read() {
+        if let Ok((mut visibility, false)) = volumes.get_mut(entity) {
+            visibility.set_if_neq(Visibility::Inherited);
+        }
+    }
+}
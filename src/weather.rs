@@ -0,0 +1,323 @@
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{noise::BakeStats, CloudSettings};
+#[cfg(feature = "ui")]
+use crate::CloudUiSet;
+
+/// Evolves the global cloud parameters through a sequence of named weather
+/// states. Editing any driven parameter by hand pauses the controller until
+/// it is resumed.
+///
+/// States set the share of the noise above the threshold rather than the
+/// threshold itself: the controller runs the settings in auto threshold
+/// mode (`CloudSettings::auto_threshold`), so a state looks the same after
+/// the noise is rebaked with other parameters.
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WeatherController>()
+            .add_event::<SetWeather>()
+            .add_systems(Update, weather_system.before(crate::update_material_system));
+        #[cfg(feature = "ui")]
+        app.add_systems(Update, weather_ui_system.in_set(CloudUiSet).before(weather_system));
+    }
+}
+
+/// Parameter targets for one weather state.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeatherState {
+    pub name: String,
+    pub coverage: f32,
+    pub density_multiplier: f32,
+    pub coverage_fraction: f32, // share of the noise above the threshold
+    pub wind_speed: f32, // at the lower wind altitude
+    pub wind_speed_upper: f32,
+    pub phase_g: f32,
+    pub transition: f32, // seconds to blend in from the previous state
+    pub hold: f32,       // seconds to stay before auto-advancing
+}
+
+/// The subset of `CloudSettings` the controller drives.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct WeatherValues {
+    coverage: f32,
+    density_multiplier: f32,
+    coverage_fraction: f32,
+    wind_speed: f32,
+    wind_speed_upper: f32,
+    phase_g: f32,
+}
+
+impl WeatherValues {
+    /// Outside auto threshold mode the fraction is what the threshold gives
+    /// on the last bake.
+    fn from_settings(settings: &CloudSettings, stats: &BakeStats) -> Self {
+        Self {
+            coverage: settings.coverage,
+            density_multiplier: settings.density_multiplier,
+            coverage_fraction: settings
+                .auto_threshold
+                .unwrap_or_else(|| stats.coverage_above(settings.threshold)),
+            wind_speed: settings.wind.length(),
+            wind_speed_upper: settings.wind_upper.length(),
+            phase_g: settings.phase_g,
+        }
+    }
+
+    fn from_state(state: &WeatherState) -> Self {
+        Self {
+            coverage: state.coverage,
+            density_multiplier: state.density_multiplier,
+            coverage_fraction: state.coverage_fraction,
+            wind_speed: state.wind_speed,
+            wind_speed_upper: state.wind_speed_upper,
+            phase_g: state.phase_g,
+        }
+    }
+
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            coverage: self.coverage.lerp(other.coverage, t),
+            density_multiplier: self.density_multiplier.lerp(other.density_multiplier, t),
+            coverage_fraction: self.coverage_fraction.lerp(other.coverage_fraction, t),
+            wind_speed: self.wind_speed.lerp(other.wind_speed, t),
+            wind_speed_upper: self.wind_speed_upper.lerp(other.wind_speed_upper, t),
+            phase_g: self.phase_g.lerp(other.phase_g, t),
+        }
+    }
+
+    /// Writes the values, pointing the winds along their headings: the states
+    /// set only their speeds, so the shear keeps its directions, and a calm
+    /// has no direction of its own to keep.
+    fn apply(&self, settings: &mut CloudSettings, heading: Vec3, heading_upper: Vec3) {
+        settings.coverage = self.coverage;
+        settings.density_multiplier = self.density_multiplier;
+        settings.auto_threshold = Some(self.coverage_fraction);
+        settings.wind = heading * self.wind_speed;
+        settings.wind_upper = heading_upper * self.wind_speed_upper;
+        settings.phase_g = self.phase_g;
+    }
+
+    fn approx_eq(&self, other: &Self) -> bool {
+        let eps = 1e-4;
+        (self.coverage - other.coverage).abs() < eps
+            && (self.density_multiplier - other.density_multiplier).abs() < eps
+            && (self.coverage_fraction - other.coverage_fraction).abs() < eps
+            && (self.wind_speed - other.wind_speed).abs() < eps
+            && (self.wind_speed_upper - other.wind_speed_upper).abs() < eps
+            && (self.phase_g - other.phase_g).abs() < eps
+    }
+}
+
+#[derive(Resource)]
+pub struct WeatherController {
+    pub enabled: bool,
+    pub states: Vec<WeatherState>,
+    pub current: usize,
+    /// Advance to the next state once `hold` has elapsed.
+    pub auto_advance: bool,
+    /// Set when a driven parameter was edited by hand.
+    pub paused: bool,
+    elapsed: f32,
+    from: Option<WeatherValues>,
+    last_written: Option<WeatherValues>,
+    /// Last directions of a nonzero lower and upper wind, kept through calms.
+    heading: Vec3,
+    heading_upper: Vec3,
+}
+
+impl Default for WeatherController {
+    fn default() -> Self {
+        let state = |name: &str, coverage, density_multiplier, coverage_fraction, (wind_speed, wind_speed_upper), phase_g| WeatherState {
+            name: name.to_string(),
+            coverage,
+            density_multiplier,
+            coverage_fraction,
+            wind_speed,
+            wind_speed_upper,
+            phase_g,
+            transition: 20.0,
+            hold: 40.0,
+        };
+        Self {
+            enabled: false,
+            states: vec![
+                state("Clear", 0.15, 1.0, 0.2, (0.1, 0.2), 0.3),
+                state("Scattered", 0.6, 2.0, 0.4, (0.3, 0.5), 0.2),
+                state("Overcast", 1.0, 3.0, 0.85, (0.6, 0.8), 0.1),
+                state("Storm", 1.0, 6.0, 0.95, (1.5, 3.0), 0.0),
+            ],
+            current: 0,
+            auto_advance: true,
+            paused: false,
+            elapsed: 0.0,
+            from: None,
+            last_written: None,
+            heading: Vec3::X,
+            heading_upper: Vec3::X,
+        }
+    }
+}
+
+impl WeatherController {
+    pub fn current_state(&self) -> Option<&WeatherState> {
+        self.states.get(self.current)
+    }
+
+    /// Begins transitioning from the current values towards `index`.
+    pub fn transition_to(&mut self, index: usize) {
+        if index < self.states.len() {
+            self.current = index;
+            self.elapsed = 0.0;
+            self.from = None;
+        }
+    }
+
+    /// Progress of the active transition in `0..=1`.
+    pub fn transition_progress(&self) -> f32 {
+        match self.current_state() {
+            Some(state) if state.transition > 0.0 => (self.elapsed / state.transition).min(1.0),
+            _ => 1.0,
+        }
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        // Blend from wherever the manual edits left things.
+        self.from = None;
+        self.elapsed = 0.0;
+    }
+}
+
+/// Requests a transition to the named weather state.
+#[derive(Event, Clone, Debug)]
+pub struct SetWeather(pub String);
+
+fn weather_system(
+    time: Res<Time>,
+    stats: Res<BakeStats>,
+    mut controller: ResMut<WeatherController>,
+    mut settings: ResMut<CloudSettings>,
+    mut events: EventReader<SetWeather>,
+) {
+    for SetWeather(name) in events.read() {
+        match controller.states.iter().position(|state| &state.name == name) {
+            Some(index) => {
+                controller.transition_to(index);
+                controller.paused = false;
+            }
+            None => warn!("Unknown weather state {:?}", name),
+        }
+    }
+
+    if !controller.enabled {
+        controller.last_written = None;
+        return;
+    }
+
+    if let Some(heading) = settings.wind.try_normalize() {
+        controller.heading = heading;
+    }
+    if let Some(heading) = settings.wind_upper.try_normalize() {
+        controller.heading_upper = heading;
+    }
+    let current = WeatherValues::from_settings(&settings, &stats);
+    if let Some(last_written) = controller.last_written {
+        if !last_written.approx_eq(&current) {
+            controller.paused = true;
+        }
+    }
+    if controller.paused {
+        controller.last_written = None;
+        return;
+    }
+
+    let Some(state) = controller.current_state().cloned() else {
+        return;
+    };
+    let from = *controller.from.get_or_insert(current);
+    controller.elapsed += time.delta_secs();
+
+    let t = controller.transition_progress();
+    let values = from.lerp(WeatherValues::from_state(&state), t * t * (3.0 - 2.0 * t));
+    values.apply(&mut settings, controller.heading, controller.heading_upper);
+    controller.last_written = Some(WeatherValues::from_settings(&settings, &stats));
+
+    if controller.auto_advance && controller.elapsed >= state.transition + state.hold {
+        let next = (controller.current + 1) % controller.states.len();
+        controller.transition_to(next);
+    }
+}
+
+#[cfg(feature = "ui")]
+fn weather_ui_system(
+    mut contexts: EguiContexts,
+    mut controller: ResMut<WeatherController>,
+    mut set_weather: EventWriter<SetWeather>,
+) {
+    egui::Window::new("Weather")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.checkbox(&mut controller.enabled, "Enabled");
+            ui.checkbox(&mut controller.auto_advance, "Auto Advance");
+
+            let selected = controller
+                .current_state()
+                .map(|state| state.name.clone())
+                .unwrap_or_default();
+            egui::ComboBox::from_label("State")
+                .selected_text(selected.clone())
+                .show_ui(ui, |ui| {
+                    for state in &controller.states {
+                        if ui.selectable_label(state.name == selected, &state.name).clicked() {
+                            set_weather.send(SetWeather(state.name.clone()));
+                        }
+                    }
+                });
+
+            let current = controller.current;
+            if let Some(state) = controller.states.get_mut(current) {
+                let mut percent = state.coverage_fraction * 100.0;
+                if ui
+                    .add(egui::Slider::new(&mut percent, 0.0..=100.0).suffix("%").text("Coverage"))
+                    .on_hover_text("Share of the noise above the threshold in this state")
+                    .changed()
+                {
+                    state.coverage_fraction = percent / 100.0;
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Wind");
+                    ui.add(
+                        egui::DragValue::new(&mut state.wind_speed)
+                            .speed(0.01)
+                            .range(0.0..=50.0)
+                            .prefix("lower: ")
+                            .suffix(" m/s"),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut state.wind_speed_upper)
+                            .speed(0.01)
+                            .range(0.0..=50.0)
+                            .prefix("upper: ")
+                            .suffix(" m/s"),
+                    );
+                })
+                .response
+                .on_hover_text("Wind speeds of this state at the lower and upper wind altitudes");
+            }
+
+            ui.add(egui::ProgressBar::new(controller.transition_progress()).text("Transition"));
+
+            if controller.paused {
+                ui.horizontal(|ui| {
+                    ui.label("Paused by manual edit");
+                    if ui.button("Resume").clicked() {
+                        controller.resume();
+                    }
+                });
+            }
+        });
+}
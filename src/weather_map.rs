@@ -0,0 +1,592 @@
+#[cfg(feature = "ui")]
+use std::path::Path;
+
+use bevy::{
+    image::ImageSampler,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+#[cfg(feature = "ui")]
+use bevy::window::PrimaryWindow;
+#[cfg(feature = "ui")]
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use crate::{extension::LitCloudMaterial, CloudSettings};
+#[cfg(feature = "ui")]
+use crate::{shortcuts::UiState, CloudUiSet, OrbitCamera};
+
+/// Texels per side of the coverage map.
+pub const WEATHER_MAP_SIZE: u32 = 256;
+/// Strokes kept for undo.
+#[cfg(feature = "ui")]
+const MAX_UNDO: usize = 32;
+
+/// A coverage map over the ground plane, painted in the viewport.
+///
+/// The map scales `CloudSettings::coverage` under each point of every volume
+/// it spans, so skies can be laid out by hand instead of by noise alone.
+/// With "Paint" on, dragging in the viewport paints into the map where the
+/// cursor ray meets the brush plane (the orbit camera ignores those drags),
+/// and each stroke can be undone.
+///
+/// Sky holes are cut out of the painted coverage on top: discs on the ground
+/// plane that clear the sky inside them, placed by clicking with "Place
+/// Holes" on and optionally drifting with the wind. The volumes sample the
+/// result, so CPU density queries see the holes too.
+pub struct WeatherMapPlugin;
+
+impl Plugin for WeatherMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WeatherMap>().add_systems(
+            Update,
+            (compose_system, sync_weather_map_system.after(crate::update_material_system)),
+        );
+        #[cfg(feature = "ui")]
+        app.init_resource::<WeatherBrush>().add_systems(
+            Update,
+            (
+                (weather_map_ui_system.in_set(CloudUiSet), paint_system)
+                    .chain()
+                    .before(compose_system),
+                brush_gizmo_system.after(compose_system),
+            ),
+        );
+    }
+}
+
+#[derive(Resource)]
+pub struct WeatherMap {
+    pub enabled: bool,
+    /// World XZ center and half-size of the area the map spans; the edge
+    /// texels extend beyond it.
+    pub center: Vec2,
+    pub half_extent: Vec2,
+    /// `R8Unorm` coverage the volumes sample, 1 where the sky keeps the
+    /// global coverage: the painted layer with the holes cut out.
+    pub image: Handle<Image>,
+    pub holes: Vec<SkyHole>,
+    /// Move the holes along with the noise as the wind scrolls it.
+    pub holes_drift: bool,
+    /// Painted coverage, row-major like the image.
+    painted: Vec<u8>,
+    /// Painted layer before each stroke, newest last.
+    #[cfg(feature = "ui")]
+    undo: Vec<Vec<u8>>,
+    /// What `image` was last composed from; `None` after painting.
+    composed: Option<Composed>,
+    /// Counts the compositions into `image`.
+    generation: u64,
+}
+
+/// A clear patch in the coverage, on the ground plane.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SkyHole {
+    /// World XZ, before any drift.
+    pub center: Vec2,
+    /// In world units.
+    pub radius: f32,
+    /// Fraction of the radius over which the coverage returns; 0 is a hard
+    /// edge.
+    pub softness: f32,
+}
+
+impl SkyHole {
+    /// How much of the coverage the hole clears at `xz`, 1 inside.
+    fn cut(&self, center: Vec2, xz: Vec2) -> f32 {
+        let radius = self.radius.max(1e-3);
+        let inner = radius * (1.0 - self.softness.clamp(0.0, 1.0));
+        let distance = xz.distance(center);
+        if distance >= radius {
+            0.0
+        } else if distance <= inner {
+            1.0
+        } else {
+            let t = (radius - distance) / (radius - inner);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Composed {
+    center: Vec2,
+    half_extent: Vec2,
+    drift: Vec2,
+    holes: Vec<SkyHole>,
+}
+
+impl FromWorld for WeatherMap {
+    fn from_world(world: &mut World) -> Self {
+        let mut image = Image::new_fill(
+            Extent3d {
+                width: WEATHER_MAP_SIZE,
+                height: WEATHER_MAP_SIZE,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[255],
+            TextureFormat::R8Unorm,
+            RenderAssetUsages::default(),
+        );
+        image.sampler = ImageSampler::linear();
+        Self {
+            enabled: false,
+            center: Vec2::ZERO,
+            half_extent: Vec2::splat(20.0),
+            image: world.resource_mut::<Assets<Image>>().add(image),
+            holes: Vec::new(),
+            holes_drift: true,
+            painted: vec![255; (WEATHER_MAP_SIZE * WEATHER_MAP_SIZE) as usize],
+            #[cfg(feature = "ui")]
+            undo: Vec::new(),
+            composed: None,
+            generation: 0,
+        }
+    }
+}
+
+impl WeatherMap {
+    /// The `weather_map` lane of `CloudMaterialUniform`.
+    pub fn uniform(&self) -> Vec4 {
+        if !self.enabled {
+            return Vec4::ZERO;
+        }
+        let half_extent = self.half_extent.max(Vec2::splat(1e-3));
+        let min = self.center - half_extent;
+        Vec4::new(min.x, min.y, 0.5 / half_extent.x, 0.5 / half_extent.y)
+    }
+
+    /// How far the holes have moved from where they were placed.
+    pub fn drift(&self, wind_offset: Vec3) -> Vec2 {
+        if self.holes_drift {
+            wind_offset.xz()
+        } else {
+            Vec2::ZERO
+        }
+    }
+
+    /// The painted layer times what the holes leave of it.
+    fn compose_into(&self, data: &mut [u8], drift: Vec2) {
+        if self.holes.is_empty() {
+            data.copy_from_slice(&self.painted);
+            return;
+        }
+        let half_extent = self.half_extent.max(Vec2::splat(1e-3));
+        let texel_size = half_extent * 2.0 / WEATHER_MAP_SIZE as f32;
+        let min = self.center - half_extent;
+        for (i, (out, painted)) in data.iter_mut().zip(&self.painted).enumerate() {
+            let texel = UVec2::new(i as u32 % WEATHER_MAP_SIZE, i as u32 / WEATHER_MAP_SIZE);
+            let xz = min + (texel.as_vec2() + 0.5) * texel_size;
+            let cut = self
+                .holes
+                .iter()
+                .map(|hole| hole.cut(hole.center + drift, xz))
+                .fold(0.0, f32::max);
+            *out = (*painted as f32 * (1.0 - cut)).round() as u8;
+        }
+    }
+}
+
+/// Editing, from the window and the viewport brush.
+#[cfg(feature = "ui")]
+impl WeatherMap {
+    /// Map UV of a world XZ position.
+    pub fn uv(&self, xz: Vec2) -> Vec2 {
+        (xz - self.center) / self.half_extent.max(Vec2::splat(1e-3)) * 0.5 + 0.5
+    }
+
+    /// Index of the topmost hole covering `xz`, with the holes drifted by
+    /// `drift`.
+    pub fn hole_at(&self, xz: Vec2, drift: Vec2) -> Option<usize> {
+        self.holes
+            .iter()
+            .rposition(|hole| xz.distance(hole.center + drift) < hole.radius)
+    }
+
+    /// Moves on every time `image` is composed again, so views of it can
+    /// rebuild only then.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    fn push_undo(&mut self) {
+        if self.undo.len() == MAX_UNDO {
+            self.undo.remove(0);
+        }
+        self.undo.push(self.painted.clone());
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(data) = self.undo.pop() {
+            self.painted = data;
+            self.composed = None;
+        }
+    }
+
+    /// Resets the painting to full coverage, as one undoable step. The holes
+    /// stay.
+    pub fn clear(&mut self) {
+        self.push_undo();
+        self.painted.fill(255);
+        self.composed = None;
+    }
+
+    /// Writes the painted layer as a greyscale PNG; the holes are saved with
+    /// the session.
+    pub fn save_png(&self, path: &Path) -> Result<(), String> {
+        image::save_buffer(path, &self.painted, WEATHER_MAP_SIZE, WEATHER_MAP_SIZE, image::ExtendedColorType::L8)
+            .map_err(|err| err.to_string())
+    }
+
+    /// Replaces the painted layer with a greyscale PNG of the same size, as
+    /// one undoable step.
+    pub fn load_png(&mut self, path: &Path) -> Result<(), String> {
+        let loaded = image::open(path).map_err(|err| err.to_string())?.to_luma8();
+        if loaded.dimensions() != (WEATHER_MAP_SIZE, WEATHER_MAP_SIZE) {
+            return Err(format!(
+                "{} is {}x{}, expected {}x{}",
+                path.display(),
+                loaded.width(),
+                loaded.height(),
+                WEATHER_MAP_SIZE,
+                WEATHER_MAP_SIZE
+            ));
+        }
+        self.push_undo();
+        self.painted = loaded.into_raw();
+        self.composed = None;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ui")]
+#[derive(Resource, Clone, Debug)]
+pub struct WeatherBrush {
+    /// Paint mode: left drags paint instead of orbiting.
+    pub painting: bool,
+    /// In world units.
+    pub radius: f32,
+    /// Fraction of the radius painted at full strength before the falloff.
+    pub hardness: f32,
+    /// Coverage added per second at the brush center.
+    pub strength: f32,
+    pub erase: bool,
+    /// Height of the plane the cursor ray is projected onto; set it to the
+    /// cloud layer's altitude so the brush lands under the cursor.
+    pub altitude: f32,
+    /// Where the cursor ray meets the brush plane.
+    pub cursor: Option<Vec3>,
+    /// Set while painting, so the main camera ignores left drags.
+    pub captures_pointer: bool,
+    /// Hole mode: clicks add, drag and remove sky holes instead of painting.
+    pub placing_holes: bool,
+    /// Hole last clicked, highlighted in the viewport and the window.
+    pub selected_hole: Option<usize>,
+    /// Cursor offset from the center of the hole being dragged.
+    grab: Option<Vec2>,
+}
+
+#[cfg(feature = "ui")]
+impl Default for WeatherBrush {
+    fn default() -> Self {
+        Self {
+            painting: false,
+            radius: 3.0,
+            hardness: 0.5,
+            strength: 2.0,
+            erase: true,
+            altitude: 1.0,
+            cursor: None,
+            captures_pointer: false,
+            placing_holes: false,
+            selected_hole: None,
+            grab: None,
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+impl WeatherBrush {
+    /// Weight of the brush at `distance` from its center.
+    fn falloff(&self, distance: f32) -> f32 {
+        let t = distance / self.radius.max(1e-3);
+        let hardness = self.hardness.clamp(0.0, 0.999);
+        (1.0 - (t - hardness) / (1.0 - hardness)).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(feature = "ui")]
+#[allow(clippy::too_many_arguments)]
+fn paint_system(
+    time: Res<Time>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    ui_state: Res<UiState>,
+    settings: Res<CloudSettings>,
+    mut contexts: EguiContexts,
+    mut map: ResMut<WeatherMap>,
+    mut brush: ResMut<WeatherBrush>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<OrbitCamera>>,
+) {
+    brush.cursor = None;
+    brush.captures_pointer = (brush.painting || brush.placing_holes) && map.enabled;
+    if !brush.captures_pointer {
+        return;
+    }
+    let Some(cursor) = windows.get_single().ok().and_then(|window| window.cursor_position()) else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Ok(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+    let Some(distance) = ray.intersect_plane(Vec3::Y * brush.altitude, InfinitePlane3d::new(Vec3::Y)) else {
+        return;
+    };
+    let point = ray.get_point(distance);
+    brush.cursor = Some(point);
+
+    if ui_state.show_ui && contexts.try_ctx_mut().is_some_and(|ctx| ctx.is_pointer_over_area()) {
+        return;
+    }
+    if brush.placing_holes {
+        let remove = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+        edit_holes(&mut map, &mut brush, &buttons, remove, point.xz(), settings.wind_offset);
+        return;
+    }
+    if buttons.just_pressed(MouseButton::Left) {
+        map.push_undo();
+    }
+    if !buttons.pressed(MouseButton::Left) {
+        return;
+    }
+
+    let amount = brush.strength * time.delta_secs() * if brush.erase { -255.0 } else { 255.0 };
+    let texel_size = map.half_extent.max(Vec2::splat(1e-3)) * 2.0 / WEATHER_MAP_SIZE as f32;
+    let center = map.uv(point.xz()) * WEATHER_MAP_SIZE as f32;
+    let reach = (Vec2::splat(brush.radius) / texel_size).ceil();
+    let min = (center - reach).floor().max(Vec2::ZERO).as_uvec2();
+    let max = (center + reach).ceil().min(Vec2::splat(WEATHER_MAP_SIZE as f32)).as_uvec2();
+    // Only the texels under the brush change, but the whole image is
+    // composed and uploaded again; at 64 KiB that is cheap.
+    map.composed = None;
+    for y in min.y..max.y {
+        for x in min.x..max.x {
+            let texel_center = (UVec2::new(x, y).as_vec2() + 0.5 - center) * texel_size;
+            let weight = brush.falloff(texel_center.length());
+            if weight <= 0.0 {
+                continue;
+            }
+            let texel = &mut map.painted[(y * WEATHER_MAP_SIZE + x) as usize];
+            *texel = (*texel as f32 + amount * weight).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// A click on a hole selects and grabs it, or removes it with `remove`; a
+/// click elsewhere adds a hole the size of the brush. Dragging moves the
+/// grabbed hole.
+#[cfg(feature = "ui")]
+fn edit_holes(
+    map: &mut WeatherMap,
+    brush: &mut WeatherBrush,
+    buttons: &ButtonInput<MouseButton>,
+    remove: bool,
+    cursor: Vec2,
+    wind_offset: Vec3,
+) {
+    let drift = map.drift(wind_offset);
+    if buttons.just_pressed(MouseButton::Left) {
+        match (map.hole_at(cursor, drift), remove) {
+            (Some(index), true) => {
+                map.holes.remove(index);
+                brush.selected_hole = None;
+            }
+            (Some(index), false) => {
+                brush.selected_hole = Some(index);
+                brush.grab = Some(cursor - drift - map.holes[index].center);
+            }
+            (None, true) => {}
+            (None, false) => {
+                map.holes.push(SkyHole {
+                    center: cursor - drift,
+                    radius: brush.radius,
+                    softness: 1.0 - brush.hardness,
+                });
+                brush.selected_hole = Some(map.holes.len() - 1);
+                brush.grab = Some(Vec2::ZERO);
+            }
+        }
+    }
+    if !buttons.pressed(MouseButton::Left) {
+        brush.grab = None;
+    }
+    if let (Some(grab), Some(hole)) = (brush.grab, brush.selected_hole.and_then(|index| map.holes.get_mut(index))) {
+        hole.center = cursor - drift - grab;
+    }
+}
+
+/// Cuts the holes out of the painted coverage into the sampled image when
+/// either changed, the map moved or the holes drifted.
+fn compose_system(settings: Res<CloudSettings>, mut map: ResMut<WeatherMap>, mut images: ResMut<Assets<Image>>) {
+    let drift = if map.holes.is_empty() { Vec2::ZERO } else { map.drift(settings.wind_offset) };
+    let key = Composed {
+        center: map.center,
+        half_extent: map.half_extent,
+        drift,
+        holes: map.holes.clone(),
+    };
+    if map.composed.as_ref() == Some(&key) {
+        return;
+    }
+    let Some(image) = images.get_mut(&map.image) else {
+        return;
+    };
+    map.compose_into(&mut image.data, drift);
+    map.composed = Some(key);
+    map.generation += 1;
+}
+
+#[cfg(feature = "ui")]
+fn brush_gizmo_system(
+    map: Res<WeatherMap>,
+    brush: Res<WeatherBrush>,
+    settings: Res<CloudSettings>,
+    mut gizmos: Gizmos,
+) {
+    if !map.enabled || !(brush.painting || brush.placing_holes) {
+        return;
+    }
+    // Gizmo circles and rectangles lie in XY; turn them onto the ground.
+    let flat = Quat::from_rotation_arc(Vec3::Z, Vec3::Y);
+    let bounds = Vec3::new(map.center.x, brush.altitude, map.center.y);
+    gizmos.rect(Isometry3d::new(bounds, flat), map.half_extent * 2.0, Color::srgb(0.4, 0.6, 1.0));
+    let drift = map.drift(settings.wind_offset);
+    for (index, hole) in map.holes.iter().enumerate() {
+        let center = hole.center + drift;
+        let center = Vec3::new(center.x, brush.altitude, center.y);
+        let color = if brush.selected_hole == Some(index) {
+            Color::srgb(1.0, 0.9, 0.3)
+        } else {
+            Color::srgb(0.9, 0.9, 1.0)
+        };
+        gizmos.circle(Isometry3d::new(center, flat), hole.radius, color);
+        let inner = hole.radius * (1.0 - hole.softness.clamp(0.0, 1.0));
+        gizmos.circle(Isometry3d::new(center, flat), inner, color.with_alpha(0.5));
+    }
+    if brush.placing_holes {
+        return;
+    }
+    if let Some(point) = brush.cursor {
+        let color = if brush.erase { Color::srgb(1.0, 0.4, 0.3) } else { Color::srgb(0.3, 1.0, 0.5) };
+        gizmos.circle(Isometry3d::new(point, flat), brush.radius, color);
+        gizmos.circle(Isometry3d::new(point, flat), brush.radius * brush.hardness, color.with_alpha(0.5));
+    }
+}
+
+/// Binds the map to every cloud material, or unbinds it when disabled.
+/// Binds the map to the lit materials; `sync_cloud_materials` does the same
+/// for the cloud materials.
+fn sync_weather_map_system(map: Res<WeatherMap>, mut lit_materials: ResMut<Assets<LitCloudMaterial>>) {
+    let lane = map.uniform();
+    let image = map.enabled.then(|| map.image.clone());
+    let lit_stale: Vec<_> = lit_materials
+        .iter()
+        .filter(|(_, material)| material.extension.data.weather_map != lane || material.extension.weather_map != image)
+        .map(|(id, _)| id)
+        .collect();
+    for id in lit_stale {
+        if let Some(material) = lit_materials.get_mut(id) {
+            material.extension.data.weather_map = lane;
+            material.extension.weather_map = image.clone();
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+fn weather_map_ui_system(mut contexts: EguiContexts, mut map: ResMut<WeatherMap>, mut brush: ResMut<WeatherBrush>) {
+    egui::Window::new("Weather Map")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.checkbox(&mut map.enabled, "Enabled")
+                .on_hover_text("Scale the coverage by a painted map over the ground");
+            ui.add_enabled_ui(map.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Center");
+                    ui.add(egui::DragValue::new(&mut map.center.x).prefix("x "));
+                    ui.add(egui::DragValue::new(&mut map.center.y).prefix("z "));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Half Size");
+                    ui.add(egui::DragValue::new(&mut map.half_extent.x).range(1.0..=10_000.0).prefix("x "));
+                    ui.add(egui::DragValue::new(&mut map.half_extent.y).range(1.0..=10_000.0).prefix("z "));
+                });
+
+                ui.separator();
+                if ui
+                    .checkbox(&mut brush.painting, "Paint")
+                    .on_hover_text("Drag in the viewport to paint; the camera ignores drags")
+                    .changed()
+                {
+                    brush.placing_holes = false;
+                }
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut brush.erase, false, "Add");
+                    ui.selectable_value(&mut brush.erase, true, "Erase");
+                });
+                ui.add(egui::Slider::new(&mut brush.radius, 0.5..=50.0).logarithmic(true).text("Radius"));
+                ui.add(egui::Slider::new(&mut brush.hardness, 0.0..=1.0).text("Hardness"));
+                ui.add(egui::Slider::new(&mut brush.strength, 0.1..=10.0).logarithmic(true).text("Strength"));
+                ui.add(egui::DragValue::new(&mut brush.altitude).speed(0.1).prefix("Plane height "))
+                    .on_hover_text("Set to the cloud layer's altitude so the brush lands under the cursor");
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(map.can_undo(), egui::Button::new("Undo Stroke")).clicked() {
+                        map.undo();
+                    }
+                    if ui.button("Clear").on_hover_text("Back to full coverage; the holes stay").clicked() {
+                        map.clear();
+                    }
+                });
+
+                ui.separator();
+                ui.label("Sky Holes");
+                if ui
+                    .checkbox(&mut brush.placing_holes, "Place Holes")
+                    .on_hover_text("Click the plane to add a hole of the brush size, drag to move, Ctrl+click to remove")
+                    .changed()
+                {
+                    brush.painting = false;
+                }
+                ui.checkbox(&mut map.holes_drift, "Drift With Wind");
+                let mut removed = None;
+                for (index, hole) in map.holes.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        let selected = brush.selected_hole == Some(index);
+                        if ui.selectable_label(selected, format!("#{}", index + 1)).clicked() {
+                            brush.selected_hole = Some(index);
+                        }
+                        ui.add(egui::DragValue::new(&mut hole.radius).range(0.1..=1000.0).prefix("r "));
+                        ui.add(egui::Slider::new(&mut hole.softness, 0.0..=1.0).text("Soft"));
+                        if ui.small_button("✕").clicked() {
+                            removed = Some(index);
+                        }
+                    });
+                }
+                if let Some(index) = removed {
+                    map.holes.remove(index);
+                    brush.selected_hole = None;
+                }
+            });
+        });
+}
@@ -0,0 +1,106 @@
+use bevy::{
+    prelude::*,
+    render::camera::RenderTarget,
+    window::{PrimaryWindow, WindowRef},
+};
+use bevy_egui::EguiContext;
+
+/// Keeps the editor working with more than one window open.
+///
+/// The settings UI belongs to the primary window: the egui panels only run
+/// while it is open, and the systems asking egui whether the pointer is over
+/// a panel ask the context of the window they look at. `OrbitCamera` input
+/// is only taken while the camera's own window has focus. With
+/// `--second-window`, a second window opens with a camera circling the
+/// default cloud; closing it despawns the camera.
+pub struct MultiWindowPlugin;
+
+impl Plugin for MultiWindowPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SecondWindow>()
+            .add_systems(Update, second_window_system);
+    }
+}
+
+/// Whether the window the settings UI is drawn in is open; a run condition
+/// for everything that draws into it.
+pub fn ui_window_open(windows: Query<(), (With<PrimaryWindow>, With<EguiContext>)>) -> bool {
+    !windows.is_empty()
+}
+
+/// The window a camera draws into, if it draws into one.
+pub fn camera_window(camera: &Camera, primary: Option<Entity>) -> Option<Entity> {
+    match &camera.target {
+        RenderTarget::Window(WindowRef::Primary) => primary,
+        RenderTarget::Window(WindowRef::Entity(window)) => Some(*window),
+        _ => None,
+    }
+}
+
+/// The `--second-window` demo.
+#[derive(Resource, Default)]
+pub struct SecondWindow {
+    pub enabled: bool,
+    spawned: bool,
+}
+
+impl SecondWindow {
+    pub fn from_args(args: &[String]) -> Self {
+        Self {
+            enabled: args.iter().any(|arg| arg == "--second-window"),
+            spawned: false,
+        }
+    }
+}
+
+/// The demo's camera, circling the default cloud.
+#[derive(Component)]
+struct SecondViewCamera {
+    window: Entity,
+}
+
+/// Center, radius, height above the center and angular speed in radians per
+/// second of the second view's circle.
+const ORBIT_CENTER: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+const ORBIT_RADIUS: f32 = 8.0;
+const ORBIT_HEIGHT: f32 = 2.5;
+const ORBIT_SPEED: f32 = 0.25;
+
+fn second_window_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut demo: ResMut<SecondWindow>,
+    windows: Query<(), With<Window>>,
+    mut cameras: Query<(Entity, &SecondViewCamera, &mut Transform)>,
+) {
+    if demo.enabled && !demo.spawned {
+        demo.spawned = true;
+        let window = commands
+            .spawn(Window {
+                title: "Clouds (second view)".into(),
+                resolution: (640.0, 480.0).into(),
+                ..default()
+            })
+            .id();
+        commands.spawn((
+            Camera3d::default(),
+            Camera {
+                target: RenderTarget::Window(WindowRef::Entity(window)),
+                ..default()
+            },
+            Transform::default(),
+            SecondViewCamera { window },
+        ));
+    }
+
+    let (sin, cos) = (time.elapsed_secs() * ORBIT_SPEED).sin_cos();
+    let position = ORBIT_CENTER + Vec3::new(cos * ORBIT_RADIUS, ORBIT_HEIGHT, sin * ORBIT_RADIUS);
+    for (entity, camera, mut transform) in &mut cameras {
+        // A camera left without its window would log an error every frame.
+        if !windows.contains(camera.window) {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        *transform = Transform::from_translation(position).looking_at(ORBIT_CENTER, Vec3::Y);
+    }
+}
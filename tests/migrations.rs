@@ -0,0 +1,140 @@
+use std::path::{Path, PathBuf};
+
+use bevy::math::{Vec2, Vec3};
+
+#[cfg(feature = "ui")]
+use bevy_clouds::{versioning::FORMAT_VERSION, CloudSession};
+use bevy_clouds::{
+    versioning::{from_ron, to_ron, Versioned},
+    CloudSettings,
+};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures").join(name)
+}
+
+fn load<T: Versioned>(name: &str) -> Result<(T, Vec<String>), String> {
+    let path = fixture(name);
+    let text = std::fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    from_ron(&text)
+}
+
+/// Compares through RON, which covers every saved field.
+fn assert_same(actual: &CloudSettings, expected: &CloudSettings) {
+    let actual = to_ron(actual).unwrap();
+    let expected = to_ron(expected).unwrap();
+    let diff: Vec<String> = actual
+        .lines()
+        .zip(expected.lines())
+        .filter(|(actual, expected)| actual != expected)
+        .map(|(actual, expected)| format!("got {}, expected {}", actual.trim(), expected.trim()))
+        .collect();
+    assert!(diff.is_empty(), "{}", diff.join("; "));
+}
+
+/// Format 0: a "Copy as RON" dump from before versioning.
+#[test]
+fn settings_v0_migrates() {
+    let (settings, warnings) = load::<CloudSettings>("settings_v0.ron").unwrap();
+    assert!(warnings.is_empty(), "unexpected warnings {:?}", warnings);
+    assert_same(
+        &settings,
+        &CloudSettings {
+            density_multiplier: 3.5,
+            threshold: 0.35,
+            absorption: 2.0,
+            steps: 48,
+            coverage: 0.8,
+            bounds_inset: 0.1,
+            contact_fade_distance: 0.0,
+            wind_upper: CloudSettings::defaults().wind,
+            ..CloudSettings::defaults()
+        },
+    );
+}
+
+#[test]
+fn settings_v1_loads() {
+    let (settings, warnings) = load::<CloudSettings>("settings_v1.ron").unwrap();
+    assert!(warnings.is_empty(), "unexpected warnings {:?}", warnings);
+    assert_same(
+        &settings,
+        &CloudSettings {
+            density_multiplier: 3.5,
+            threshold: 0.35,
+            steps: 48,
+            contact_fade_distance: 0.6,
+            wind_upper: CloudSettings::defaults().wind,
+            ..CloudSettings::defaults()
+        },
+    );
+}
+
+#[test]
+fn settings_v2_loads() {
+    let (settings, warnings) = load::<CloudSettings>("settings_v2.ron").unwrap();
+    assert!(warnings.is_empty(), "unexpected warnings {:?}", warnings);
+    assert_same(
+        &settings,
+        &CloudSettings {
+            steps: 48,
+            wind: Vec3::new(0.5, 0.0, 0.0),
+            wind_upper: Vec3::new(1.0, 0.0, 0.5),
+            wind_altitudes: Vec2::new(1.0, 6.0),
+            ..CloudSettings::defaults()
+        },
+    );
+}
+
+#[cfg(feature = "ui")]
+#[test]
+fn session_v0_migrates() {
+    let (session, warnings) = load::<CloudSession>("session_v0.ron").unwrap();
+    assert!(warnings.is_empty(), "unexpected warnings {:?}", warnings);
+    assert_eq!(session.version, FORMAT_VERSION);
+    assert_eq!(session.volumes.len(), 2);
+    assert_same(
+        &session.settings,
+        &CloudSettings {
+            threshold: 0.3,
+            steps: 24,
+            contact_fade_distance: 0.0,
+            wind_upper: CloudSettings::defaults().wind,
+            ..CloudSettings::defaults()
+        },
+    );
+}
+
+/// Fields no build ever had stand in for dropped ones.
+#[cfg(feature = "ui")]
+#[test]
+fn removed_fields_warn() {
+    let (session, warnings) = load::<CloudSession>("removed_field.ron").unwrap();
+    let expected = ["ignoring `exposure`", "ignoring `settings.cloud_gamma`"];
+    for expected in expected {
+        assert!(
+            warnings.iter().any(|warning| warning.starts_with(expected)),
+            "no warning about {}, got {:?}",
+            expected,
+            warnings
+        );
+    }
+    assert_eq!(warnings.len(), expected.len(), "unexpected warnings {:?}", warnings);
+    assert_same(
+        &session.settings,
+        &CloudSettings {
+            steps: 20,
+            contact_fade_distance: 0.0,
+            wind_upper: CloudSettings::defaults().wind,
+            ..CloudSettings::defaults()
+        },
+    );
+}
+
+#[test]
+fn future_format_is_refused() {
+    match load::<CloudSettings>("future_format.ron") {
+        Ok(_) => panic!("loaded a file from a newer format"),
+        Err(err) => assert!(err.contains("newer version"), "unclear error: {}", err),
+    }
+}